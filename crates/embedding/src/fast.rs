@@ -1,7 +1,15 @@
-use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use fastembed::{
+    EmbeddingModel, InitOptions, InitOptionsUserDefined, TextEmbedding, TokenizerFiles,
+    UserDefinedEmbeddingModel,
+};
 use ort::execution_providers::{CUDAExecutionProvider, CPUExecutionProvider};
+use rayon::prelude::*;
 use thiserror::Error;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
 
 /// Errors from fastembed-rs
 #[derive(Debug, Error)]
@@ -14,9 +22,13 @@ pub enum FastEmbedError {
     Embed(String),
 }
 
+/// Default number of texts embedded by one worker in `embed_batch`
+pub const DEFAULT_BATCH_CHUNK_SIZE: usize = 256;
+
 /// Client wrapper around fastembed TextEmbedding
 pub struct FastEmbedClient {
     inner: TextEmbedding,
+    chunk_size: usize,
 }
 
 impl FastEmbedClient {
@@ -51,7 +63,7 @@ impl FastEmbedClient {
         let inner = TextEmbedding::try_new(opts)
             .map_err(|e| FastEmbedError::Init(e.to_string()))?;
         
-        Ok(FastEmbedClient { inner })
+        Ok(FastEmbedClient { inner, chunk_size: DEFAULT_BATCH_CHUNK_SIZE })
     }
 
     /// Create a new client with CPU only
@@ -67,7 +79,59 @@ impl FastEmbedClient {
         opts = opts.with_show_download_progress(show_download_progress);
         let inner = TextEmbedding::try_new(opts)
             .map_err(|e| FastEmbedError::Init(e.to_string()))?;
-        Ok(FastEmbedClient { inner })
+        Ok(FastEmbedClient { inner, chunk_size: DEFAULT_BATCH_CHUNK_SIZE })
+    }
+
+    /// Create a client from a local ONNX model file and a tokenizer directory.
+    /// The tokenizer directory must contain `tokenizer.json`, `config.json`,
+    /// `special_tokens_map.json` and `tokenizer_config.json`; the embedding dimension
+    /// reported by `config.json` must match `dimension`.
+    pub fn new_from_local(
+        model_path: &Path,
+        tokenizer_path: &Path,
+        dimension: usize,
+        gpu_device_id: i32,
+    ) -> Result<Self, FastEmbedError> {
+        let onnx_file = read_local_file(model_path)?;
+        let tokenizer_files = TokenizerFiles {
+            tokenizer_file: read_local_file(&tokenizer_path.join("tokenizer.json"))?,
+            config_file: read_local_file(&tokenizer_path.join("config.json"))?,
+            special_tokens_map_file: read_local_file(&tokenizer_path.join("special_tokens_map.json"))?,
+            tokenizer_config_file: read_local_file(&tokenizer_path.join("tokenizer_config.json"))?,
+        };
+
+        // 모델을 올리기 전에 config.json의 출력 차원과 설정된 차원이 같은지 확인
+        let model_dimension = config_embedding_dimension(&tokenizer_files.config_file)?;
+        if model_dimension != dimension {
+            return Err(FastEmbedError::Init(format!(
+                "local model produces {}-dimensional embeddings, but {} was configured",
+                model_dimension,
+                dimension
+            )));
+        }
+
+        let mut providers = Vec::new();
+        if gpu_device_id >= 0 {
+            providers.push(
+                CUDAExecutionProvider::default()
+                    .with_device_id(gpu_device_id)
+                    .build(),
+            );
+        }
+        providers.push(CPUExecutionProvider::default().build());
+
+        let model = UserDefinedEmbeddingModel::new(onnx_file, tokenizer_files);
+        let opts = InitOptionsUserDefined::new().with_execution_providers(providers);
+        let inner = TextEmbedding::try_new_from_user_defined(model, opts)
+            .map_err(|e| FastEmbedError::Init(e.to_string()))?;
+
+        Ok(FastEmbedClient { inner, chunk_size: DEFAULT_BATCH_CHUNK_SIZE })
+    }
+
+    /// Set how many texts each worker embeds at once when a batch is split across threads
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
     }
 
     /// Embed a single piece of text
@@ -78,12 +142,133 @@ impl FastEmbedClient {
         embeddings.pop().ok_or_else(|| FastEmbedError::Embed("no embedding returned".to_string()))
     }
 
-    /// Embed multiple texts in batch
+    /// Embed multiple texts in batch.
+    /// Batches larger than the chunk size are split and embedded concurrently on the rayon pool.
     pub fn embed_batch(&mut self, texts: Vec<&str>) -> Result<Vec<Vec<f32>>, FastEmbedError> {
-        let batch_size = texts.len();
-        self.inner.embed(texts, Some(batch_size))
-            .map_err(|e| FastEmbedError::Embed(e.to_string()))
+        let inner = &self.inner;
+        embed_in_chunks(&texts, self.chunk_size, |chunk| {
+            inner.embed(chunk.to_vec(), Some(chunk.len()))
+                .map_err(|e| FastEmbedError::Embed(e.to_string()))
+        })
+    }
+}
+
+/// Identifies an initialized model so later calls with the same configuration can reuse it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ClientKey {
+    Hub {
+        model: String,
+        cache_dir: Option<PathBuf>,
+        gpu_device_id: Option<i32>, // None for CPU
+    },
+    Local {
+        model_path: PathBuf,
+        tokenizer_path: PathBuf,
+        dimension: usize,
+        gpu_device_id: Option<i32>, // None for CPU
+    },
+}
+
+/// Lazily initialized, process-wide map of shared clients.
+/// Initialization runs under the map lock so concurrent callers never load the same model twice;
+/// failed initializations are not cached and will be retried by the next caller.
+struct ClientCache<K, T> {
+    clients: Mutex<HashMap<K, Arc<Mutex<T>>>>,
+    inits: AtomicUsize,
+}
+
+impl<K: Eq + Hash, T> ClientCache<K, T> {
+    fn new() -> Self {
+        ClientCache {
+            clients: Mutex::new(HashMap::new()),
+            inits: AtomicUsize::new(0),
+        }
+    }
+
+    fn get_or_try_init<F>(&self, key: K, init: F) -> Result<Arc<Mutex<T>>, FastEmbedError>
+    where
+        F: FnOnce() -> Result<T, FastEmbedError>,
+    {
+        let mut clients = lock_ignoring_poison(&self.clients);
+        if let Some(client) = clients.get(&key) {
+            return Ok(Arc::clone(client));
+        }
+
+        let client = Arc::new(Mutex::new(init()?));
+        self.inits.fetch_add(1, Ordering::Relaxed);
+        clients.insert(key, Arc::clone(&client));
+        Ok(client)
+    }
+
+    fn init_count(&self) -> usize {
+        self.inits.load(Ordering::Relaxed)
+    }
+}
+
+fn client_cache() -> &'static ClientCache<ClientKey, FastEmbedClient> {
+    static CLIENTS: OnceLock<ClientCache<ClientKey, FastEmbedClient>> = OnceLock::new();
+    CLIENTS.get_or_init(ClientCache::new)
+}
+
+/// Return the shared client for `key`, building it with `init` on first use.
+/// Loading a model is expensive, so every `embed`/`embed_batch` call goes through this cache.
+pub fn cached_client<F>(key: ClientKey, init: F) -> Result<Arc<Mutex<FastEmbedClient>>, FastEmbedError>
+where
+    F: FnOnce() -> Result<FastEmbedClient, FastEmbedError>,
+{
+    client_cache().get_or_try_init(key, init)
+}
+
+/// Number of clients (models) initialized by [`cached_client`] in this process
+pub fn client_init_count() -> usize {
+    client_cache().init_count()
+}
+
+/// A panic while embedding leaves the model itself intact, so a poisoned lock is still usable
+pub fn lock_ignoring_poison<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 로컬 모델 파일을 읽고, 없으면 어떤 파일이 없는지 알려줍니다.
+fn read_local_file(path: &Path) -> Result<Vec<u8>, FastEmbedError> {
+    if !path.is_file() {
+        return Err(FastEmbedError::Init(format!("local model file not found: {}", path.display())));
+    }
+    std::fs::read(path)
+        .map_err(|e| FastEmbedError::Init(format!("failed to read {}: {}", path.display(), e)))
+}
+
+/// config.json에 기록된 모델 출력 차원 (pooling 결과가 hidden state 크기와 같다)
+fn config_embedding_dimension(config_file: &[u8]) -> Result<usize, FastEmbedError> {
+    let config: serde_json::Value = serde_json::from_slice(config_file)
+        .map_err(|e| FastEmbedError::Init(format!("invalid config.json: {}", e)))?;
+    ["hidden_size", "d_model", "dim"]
+        .iter()
+        .find_map(|key| config.get(*key).and_then(|value| value.as_u64()))
+        .map(|dimension| dimension as usize)
+        .ok_or_else(|| FastEmbedError::Init("config.json does not report the embedding dimension (hidden_size)".to_string()))
+}
+
+/// `texts`를 chunk_size 단위로 나눠 병렬로 임베딩하고, 입력 순서대로 결과를 이어 붙입니다.
+fn embed_in_chunks<F>(
+    texts: &[&str],
+    chunk_size: usize,
+    embed_chunk: F,
+) -> Result<Vec<Vec<f32>>, FastEmbedError>
+where
+    F: Fn(&[&str]) -> Result<Vec<Vec<f32>>, FastEmbedError> + Sync,
+{
+    if texts.len() <= chunk_size {
+        return embed_chunk(texts);
     }
+
+    // indexed parallel iterator의 collect는 chunk 순서를 보존한다
+    let chunks = texts
+        .par_chunks(chunk_size)
+        .map(&embed_chunk)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(chunks.into_iter().flatten().collect())
 }
 
 /// 문자열로부터 `EmbeddingModel` enum 을 찾아 반환합니다.
@@ -93,4 +278,168 @@ pub fn parse_embedding_model(model_name: &str) -> Result<EmbeddingModel, FastEmb
         .find(|info| info.model_code == model_name)
         .map(|info| info.model)
         .ok_or_else(|| FastEmbedError::UnsupportedModel(model_name.to_string()))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{embed_in_chunks, ClientCache, FastEmbedClient, FastEmbedError};
+
+    fn fake_embed(chunk: &[&str]) -> Result<Vec<Vec<f32>>, FastEmbedError> {
+        Ok(chunk
+            .iter()
+            .map(|t| vec![t.parse::<f32>().unwrap(), t.len() as f32])
+            .collect())
+    }
+
+    #[test]
+    fn test_embed_in_chunks_preserves_order() {
+        let owned: Vec<String> = (0..10_000).map(|i| i.to_string()).collect();
+        let texts: Vec<&str> = owned.iter().map(|s| s.as_str()).collect();
+
+        let single = fake_embed(&texts).unwrap();
+        let chunked = embed_in_chunks(&texts, 37, fake_embed).unwrap();
+
+        assert_eq!(chunked.len(), texts.len());
+        assert_eq!(chunked, single);
+        for (i, e) in chunked.iter().enumerate() {
+            assert_eq!(e[0], i as f32);
+        }
+    }
+
+    #[test]
+    fn test_embed_in_chunks_propagates_error() {
+        let texts = vec!["1", "2", "x", "4"];
+        let result = embed_in_chunks(&texts, 1, |chunk| {
+            if chunk[0] == "x" {
+                Err(FastEmbedError::Embed("bad input".to_string()))
+            } else {
+                fake_embed(chunk)
+            }
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_client_cache_reuses_instance() {
+        let cache: ClientCache<&str, Vec<f32>> = ClientCache::new();
+
+        let first = cache.get_or_try_init("model-a", || Ok(vec![1.0])).unwrap();
+        let second = cache.get_or_try_init("model-a", || panic!("must not re-initialize")).unwrap();
+        assert!(std::sync::Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.init_count(), 1);
+
+        // 다른 key는 별도 인스턴스
+        let other = cache.get_or_try_init("model-b", || Ok(vec![2.0])).unwrap();
+        assert!(!std::sync::Arc::ptr_eq(&first, &other));
+        assert_eq!(cache.init_count(), 2);
+    }
+
+    #[test]
+    fn test_client_cache_does_not_cache_failures() {
+        let cache: ClientCache<&str, Vec<f32>> = ClientCache::new();
+
+        assert!(cache
+            .get_or_try_init("model-a", || Err(FastEmbedError::Init("boom".to_string())))
+            .is_err());
+        assert_eq!(cache.init_count(), 0);
+
+        assert!(cache.get_or_try_init("model-a", || Ok(vec![1.0])).is_ok());
+        assert_eq!(cache.init_count(), 1);
+    }
+
+    #[test]
+    fn test_local_model_missing_files() {
+        let dir = std::env::temp_dir().join(format!("fastembed_local_missing_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let err = FastEmbedClient::new_from_local(&dir.join("model.onnx"), &dir, 384, -1)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("model.onnx"), "{}", err);
+
+        std::fs::write(dir.join("model.onnx"), b"not a model").unwrap();
+        let err = FastEmbedClient::new_from_local(&dir.join("model.onnx"), &dir, 384, -1)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("tokenizer.json"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_local_model_dimension_checked_on_construction() {
+        let dir = std::env::temp_dir().join(format!("fastembed_local_dimension_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("model.onnx"), b"not a model").unwrap();
+        std::fs::write(dir.join("tokenizer.json"), b"{}").unwrap();
+        std::fs::write(dir.join("special_tokens_map.json"), b"{}").unwrap();
+        std::fs::write(dir.join("tokenizer_config.json"), b"{}").unwrap();
+        std::fs::write(dir.join("config.json"), br#"{"hidden_size": 384}"#).unwrap();
+
+        // 모델을 올리기 전에 config.json 차원으로 거절
+        let err = FastEmbedClient::new_from_local(&dir.join("model.onnx"), &dir, 768, -1)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("384-dimensional"), "{}", err);
+
+        std::fs::write(dir.join("config.json"), br#"{"model_type": "bert"}"#).unwrap();
+        let err = FastEmbedClient::new_from_local(&dir.join("model.onnx"), &dir, 384, -1)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("hidden_size"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "local-model-tests")]
+    fn local_model_dir() -> std::path::PathBuf {
+        std::env::var("FASTEMBED_LOCAL_MODEL_DIR")
+            .expect("FASTEMBED_LOCAL_MODEL_DIR must point at a directory with model.onnx and tokenizer files")
+            .into()
+    }
+
+    #[cfg(feature = "local-model-tests")]
+    fn local_model_dimension() -> usize {
+        std::env::var("FASTEMBED_LOCAL_MODEL_DIM")
+            .ok()
+            .and_then(|d| d.parse().ok())
+            .unwrap_or(384)
+    }
+
+    /// `FASTEMBED_LOCAL_MODEL_DIR` 아래의 model.onnx + tokenizer 파일과 차원(`FASTEMBED_LOCAL_MODEL_DIM`)으로 검증
+    #[cfg(feature = "local-model-tests")]
+    #[test]
+    #[ignore = "needs FASTEMBED_LOCAL_MODEL_DIR pointing at a local ONNX model; run with --ignored"]
+    fn test_local_model_embedding() {
+        let dir = local_model_dir();
+        let dim = local_model_dimension();
+
+        let mut client = FastEmbedClient::new_from_local(&dir.join("model.onnx"), &dir, dim, -1).unwrap();
+        let embeddings = client.embed_batch(vec!["hello", "world"]).unwrap();
+        assert_eq!(embeddings.len(), 2);
+        assert!(embeddings.iter().all(|e| e.len() == dim));
+
+        assert!(FastEmbedClient::new_from_local(&dir.join("model.onnx"), &dir, dim + 1, -1).is_err());
+    }
+
+    /// 같은 로컬 모델로 두 번 임베딩하면 모델은 한 번만 로드되어야 함
+    #[cfg(feature = "local-model-tests")]
+    #[test]
+    #[ignore = "needs FASTEMBED_LOCAL_MODEL_DIR pointing at a local ONNX model; run with --ignored"]
+    fn test_local_model_reused_across_embeds() {
+        let dir = local_model_dir();
+        let backend = crate::BackendOptions::FastEmbedLocal {
+            model_path: dir.join("model.onnx"),
+            tokenizer_path: dir.clone(),
+            dimension: local_model_dimension(),
+            gpu_device_id: -1,
+        };
+
+        let before = super::client_init_count();
+        crate::embed("hello".to_string(), backend.clone()).unwrap();
+        crate::embed_batch(vec!["hello".to_string(), "world".to_string()], backend).unwrap();
+        assert_eq!(super::client_init_count(), before + 1);
+    }
+}
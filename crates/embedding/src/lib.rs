@@ -1,8 +1,10 @@
 pub mod openai;
 pub mod fast;
+pub mod voyage;
 
 use crate::openai::EmbeddingError as OpenAIError;
-use crate::fast::{FastEmbedError, parse_embedding_model};
+use crate::fast::{ClientKey, FastEmbedError, cached_client, lock_ignoring_poison, parse_embedding_model};
+use crate::voyage::{VoyageError, VoyageInputType, VoyageOptions};
 use fastembed::{EmbeddingModel, TextEmbedding};
 use thiserror::Error;
 use std::path::PathBuf;
@@ -16,6 +18,7 @@ pub enum EmbedError {
 }
 
 /// Available embedding backends with configuration
+#[derive(Clone)]
 pub enum BackendOptions {
     /// OpenAI embeddings: supply API base URL, key, and model name
     OpenAI { 
@@ -23,6 +26,13 @@ pub enum BackendOptions {
         api_key: String, 
         model: String 
     },
+    /// Voyage AI embeddings: supply API base URL, key, model name, and whether inputs are queries or documents
+    Voyage {
+        base_url: String,
+        api_key: String,
+        model: String,
+        input_type: Option<VoyageInputType>,
+    },
     /// FastEmbed embeddings: supply model variant, optional cache directory, download progress flag, and GPU device ID
     FastEmbed { 
         model: String, 
@@ -30,6 +40,43 @@ pub enum BackendOptions {
         show_download_progress: bool,
         gpu_device_id: i32,  // -1 for CPU, 0-3 for GPU
     },
+    /// FastEmbed with a user-provided ONNX model: supply the model file, tokenizer directory,
+    /// expected embedding dimension, and GPU device ID
+    FastEmbedLocal {
+        model_path: PathBuf,
+        tokenizer_path: PathBuf,
+        dimension: usize,
+        gpu_device_id: i32,  // -1 for CPU, 0-3 for GPU
+    },
+}
+
+/// Whether an input is a search query or a stored document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    Query,
+    Document,
+}
+
+/// Prefixes asymmetric models (E5, nomic-embed, ...) expect in front of each input,
+/// e.g. `query: ` for search terms and `passage: ` for stored table/column descriptions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputPrefixes {
+    pub query: Option<String>,
+    pub doc: Option<String>,
+}
+
+impl InputPrefixes {
+    /// Prepend the prefix for `kind` (unset or empty prefixes leave the input as is)
+    pub fn apply(&self, kind: InputKind, input: String) -> String {
+        let prefix = match kind {
+            InputKind::Query => self.query.as_deref(),
+            InputKind::Document => self.doc.as_deref(),
+        };
+        match prefix {
+            Some(p) if !p.is_empty() => format!("{p}{input}"),
+            _ => input,
+        }
+    }
 }
 
 /// Unified error type for both backends
@@ -39,6 +86,27 @@ pub enum EmbeddingError {
     OpenAI(#[from] OpenAIError),
     #[error(transparent)]
     FastEmbed(#[from] FastEmbedError),
+    #[error(transparent)]
+    Voyage(#[from] VoyageError),
+}
+
+/// Shared client for a user-provided local model, loaded on first use
+fn cached_local_client(
+    model_path: PathBuf,
+    tokenizer_path: PathBuf,
+    dimension: usize,
+    gpu_device_id: i32,
+) -> Result<std::sync::Arc<std::sync::Mutex<fast::FastEmbedClient>>, EmbeddingError> {
+    let key = ClientKey::Local {
+        model_path: model_path.clone(),
+        tokenizer_path: tokenizer_path.clone(),
+        dimension,
+        gpu_device_id: (gpu_device_id >= 0).then_some(gpu_device_id),
+    };
+    cached_client(key, || {
+        fast::FastEmbedClient::new_from_local(&model_path, &tokenizer_path, dimension, gpu_device_id)
+    })
+    .map_err(EmbeddingError::from)
 }
 
 /// Perform embedding for a single input using the specified backend
@@ -49,38 +117,78 @@ pub fn embed(input: String, backend: BackendOptions) -> Result<Vec<f32>, Embeddi
             let resp = openai::openai_embedding(input, model, opt)?;
             resp.try_pop_embedding().map_err(EmbeddingError::from)
         }
-        BackendOptions::FastEmbed { model, cache_dir, show_download_progress, gpu_device_id } => {
-            let model: EmbeddingModel = parse_embedding_model(&model)
-                .map_err(EmbeddingError::FastEmbed)?;
-            // [TODO] - it occurs corruped double free error when using GPU embedding currently
-            let mut client = if gpu_device_id >= 0 {
-                // GPU 사용
-                // fast::FastEmbedClient::new_with_gpu(
-                //     model, 
-                //     cache_dir, 
-                //     show_download_progress, 
-                //     gpu_device_id
-                // )?
-                // CPU 사용
-                fast::FastEmbedClient::new(
-                    model, 
-                    cache_dir, 
-                    show_download_progress
-                )?
-            } else {
+        BackendOptions::Voyage { base_url, api_key, model, input_type } => {
+            let opt = VoyageOptions { base_url, api_key };
+            let resp = voyage::voyage_embedding(vec![input], model, input_type, &opt)?;
+            let mut embeddings = resp.try_into_embeddings(1)?;
+            Ok(embeddings.remove(0))
+        }
+        BackendOptions::FastEmbed { model, cache_dir, show_download_progress, gpu_device_id: _ } => {
+            // [TODO] - it occurs corruped double free error when using GPU embedding currently,
+            // so single embeddings always run on the CPU client
+            let key = ClientKey::Hub {
+                model: model.clone(),
+                cache_dir: cache_dir.clone(),
+                gpu_device_id: None,
+            };
+            let client = cached_client(key, || {
+                let model: EmbeddingModel = parse_embedding_model(&model)?;
                 // CPU 사용
                 fast::FastEmbedClient::new(
                     model, 
                     cache_dir, 
                     show_download_progress
-                )?
-            };
-            
+                )
+            })?;
+
+            let mut client = lock_ignoring_poison(&client);
+            client.embed(&input).map_err(EmbeddingError::from)
+        }
+        BackendOptions::FastEmbedLocal { model_path, tokenizer_path, dimension, gpu_device_id: _ } => {
+            // single embeddings always run on the CPU client (see the FastEmbed arm above)
+            let client = cached_local_client(model_path, tokenizer_path, dimension, -1)?;
+            let mut client = lock_ignoring_poison(&client);
             client.embed(&input).map_err(EmbeddingError::from)
         }
     }
 }
 
+/// Perform embedding for a single input after prepending the prefix for `kind`
+pub fn embed_prefixed(
+    input: String,
+    kind: InputKind,
+    prefixes: &InputPrefixes,
+    backend: BackendOptions,
+) -> Result<Vec<f32>, EmbeddingError> {
+    embed(prefixes.apply(kind, input), backend)
+}
+
+/// Perform embedding for a single input and report the tokens it consumed.
+/// Local FastEmbed models are not billed per token, so they report zero usage.
+pub fn embed_with_usage(input: String, backend: BackendOptions) -> Result<(Vec<f32>, openai::Usage), EmbeddingError> {
+    match backend {
+        BackendOptions::OpenAI { base_url, api_key, model } => {
+            let opt = openai::OpenAIOptions { base_url, api_key };
+            let resp = openai::openai_embedding(input, model, opt)?;
+            resp.try_pop_embedding_with_usage().map_err(EmbeddingError::from)
+        }
+        BackendOptions::Voyage { base_url, api_key, model, input_type } => {
+            let opt = VoyageOptions { base_url, api_key };
+            let resp = voyage::voyage_embedding(vec![input], model, input_type, &opt)?;
+            // Voyage only reports total tokens; embeddings have no completion tokens
+            let usage = openai::Usage {
+                prompt_tokens: resp.usage.total_tokens,
+                total_tokens: resp.usage.total_tokens,
+            };
+            let mut embeddings = resp.try_into_embeddings(1)?;
+            Ok((embeddings.remove(0), usage))
+        }
+        backend @ (BackendOptions::FastEmbed { .. } | BackendOptions::FastEmbedLocal { .. }) => {
+            embed(input, backend).map(|embedding| (embedding, openai::Usage::default()))
+        }
+    }
+}
+
 /// Perform batch embedding using the specified backend
 pub fn embed_batch(inputs: Vec<String>, backend: BackendOptions) -> Result<Vec<Vec<f32>>, EmbeddingError> {
     match backend {
@@ -98,33 +206,204 @@ pub fn embed_batch(inputs: Vec<String>, backend: BackendOptions) -> Result<Vec<V
             }
             Ok(results)
         }
+        BackendOptions::Voyage { base_url, api_key, model, input_type } => {
+            let opt = VoyageOptions { base_url, api_key };
+            voyage::voyage_embedding_batch(inputs, model, input_type, &opt).map_err(EmbeddingError::from)
+        }
         BackendOptions::FastEmbed { model, cache_dir, show_download_progress, gpu_device_id } => {
-            let model: EmbeddingModel = parse_embedding_model(&model)
-                .map_err(EmbeddingError::FastEmbed)?;
-            
-            let mut client = if gpu_device_id >= 0 {
-                // GPU 사용
-                fast::FastEmbedClient::new_with_gpu(
-                    model, 
-                    cache_dir, 
-                    show_download_progress, 
-                    gpu_device_id
-                )?
-            } else {
-                // CPU 사용
-                fast::FastEmbedClient::new(
-                    model, 
-                    cache_dir, 
-                    show_download_progress
-                )?
+            let key = ClientKey::Hub {
+                model: model.clone(),
+                cache_dir: cache_dir.clone(),
+                gpu_device_id: (gpu_device_id >= 0).then_some(gpu_device_id),
             };
+            let client = cached_client(key, || {
+                let model: EmbeddingModel = parse_embedding_model(&model)?;
+                if gpu_device_id >= 0 {
+                    // GPU 사용
+                    fast::FastEmbedClient::new_with_gpu(
+                        model, 
+                        cache_dir, 
+                        show_download_progress, 
+                        gpu_device_id
+                    )
+                } else {
+                    // CPU 사용
+                    fast::FastEmbedClient::new(
+                        model, 
+                        cache_dir, 
+                        show_download_progress
+                    )
+                }
+            })?;
             
             let text_refs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+            let mut client = lock_ignoring_poison(&client);
+            client.embed_batch(text_refs).map_err(EmbeddingError::from)
+        }
+        BackendOptions::FastEmbedLocal { model_path, tokenizer_path, dimension, gpu_device_id } => {
+            let client = cached_local_client(model_path, tokenizer_path, dimension, gpu_device_id)?;
+            let text_refs: Vec<&str> = inputs.iter().map(|s| s.as_str()).collect();
+            let mut client = lock_ignoring_poison(&client);
             client.embed_batch(text_refs).map_err(EmbeddingError::from)
         }
     }
 }
 
+/// Lazily embeds inputs one chunk at a time, yielding each vector as soon as its chunk completes.
+/// Only the current chunk's embeddings are held in memory; after the first error the iterator ends.
+pub struct EmbedBatchIter<F> {
+    inputs: std::vec::IntoIter<String>,
+    chunk_size: usize,
+    embed_chunk: F,
+    pending: std::vec::IntoIter<Vec<f32>>,
+    failed: bool,
+}
+
+impl<F> EmbedBatchIter<F>
+where
+    F: FnMut(Vec<String>) -> Result<Vec<Vec<f32>>, EmbeddingError>,
+{
+    /// Iterate over `inputs` in chunks of `chunk_size`, embedding each chunk with `embed_chunk`
+    pub fn new(inputs: Vec<String>, chunk_size: usize, embed_chunk: F) -> Self {
+        EmbedBatchIter {
+            inputs: inputs.into_iter(),
+            chunk_size: chunk_size.max(1),
+            embed_chunk,
+            pending: Vec::new().into_iter(),
+            failed: false,
+        }
+    }
+}
+
+impl<F> Iterator for EmbedBatchIter<F>
+where
+    F: FnMut(Vec<String>) -> Result<Vec<Vec<f32>>, EmbeddingError>,
+{
+    type Item = Result<Vec<f32>, EmbeddingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(embedding) = self.pending.next() {
+                return Some(Ok(embedding));
+            }
+            if self.failed {
+                return None;
+            }
+
+            let chunk: Vec<String> = self.inputs.by_ref().take(self.chunk_size).collect();
+            if chunk.is_empty() {
+                return None;
+            }
+            match (self.embed_chunk)(chunk) {
+                Ok(embeddings) => self.pending = embeddings.into_iter(),
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Perform batch embedding as a stream: inputs are embedded `chunk_size` at a time
+/// (FastEmbed still splits each chunk across its worker pool) and yielded in input order
+pub fn embed_batch_iter(
+    inputs: Vec<String>,
+    backend: BackendOptions,
+    chunk_size: usize,
+) -> EmbedBatchIter<impl FnMut(Vec<String>) -> Result<Vec<Vec<f32>>, EmbeddingError>> {
+    EmbedBatchIter::new(inputs, chunk_size, move |chunk| embed_batch(chunk, backend.clone()))
+}
+
+/// Perform batch embedding, reporting failures per input instead of failing the whole batch.
+/// The output stays aligned with `inputs`. The batch is tried at once first; if it fails,
+/// each input is embedded on its own so only the offending inputs come back as errors.
+pub fn embed_batch_partial(inputs: Vec<String>, backend: BackendOptions) -> Vec<Result<Vec<f32>, EmbeddingError>> {
+    // OpenAI batch embedding is already sequential, so embed each input directly
+    if !matches!(backend, BackendOptions::OpenAI { .. }) {
+        if let Ok(embeddings) = embed_batch(inputs.clone(), backend.clone()) {
+            return embeddings.into_iter().map(Ok).collect();
+        }
+    }
+
+    inputs
+        .into_iter()
+        .map(|input| embed(input, backend.clone()))
+        .collect()
+}
+
+/// Stream `embed_batch_partial` over `inputs` `chunk_size` at a time, yielding one result per input
+/// in input order. Unlike `embed_batch_iter`, a failing input does not end the iteration.
+pub fn embed_batch_partial_iter(
+    inputs: Vec<String>,
+    backend: BackendOptions,
+    chunk_size: usize,
+) -> impl Iterator<Item = Result<Vec<f32>, EmbeddingError>> {
+    chunked_partial(inputs, chunk_size, move |chunk| embed_batch_partial(chunk, backend.clone()))
+}
+
+fn chunked_partial<T, F>(inputs: Vec<String>, chunk_size: usize, mut embed_chunk: F) -> impl Iterator<Item = T>
+where
+    F: FnMut(Vec<String>) -> Vec<T>,
+{
+    let chunk_size = chunk_size.max(1);
+    let mut inputs = inputs.into_iter();
+    std::iter::from_fn(move || {
+        let chunk: Vec<String> = inputs.by_ref().take(chunk_size).collect();
+        (!chunk.is_empty()).then(|| embed_chunk(chunk))
+    })
+    .flatten()
+}
+
+/// Perform embedding for a single input without blocking the async runtime on network I/O.
+/// FastEmbed runs locally and is CPU-bound, so it is executed inline.
+#[cfg(feature = "async")]
+pub async fn embed_async(input: String, backend: BackendOptions) -> Result<Vec<f32>, EmbeddingError> {
+    match backend {
+        BackendOptions::OpenAI { base_url, api_key, model } => {
+            let opt = openai::OpenAIOptions { base_url, api_key };
+            let resp = openai::openai_embedding_async(input, model, opt).await?;
+            resp.try_pop_embedding().map_err(EmbeddingError::from)
+        }
+        BackendOptions::Voyage { base_url, api_key, model, input_type } => {
+            let opt = VoyageOptions { base_url, api_key };
+            let resp = voyage::voyage_embedding_async(vec![input], model, input_type, &opt).await?;
+            let mut embeddings = resp.try_into_embeddings(1)?;
+            Ok(embeddings.remove(0))
+        }
+        backend @ (BackendOptions::FastEmbed { .. } | BackendOptions::FastEmbedLocal { .. }) => embed(input, backend),
+    }
+}
+
+/// Perform batch embedding without blocking the async runtime on network I/O
+#[cfg(feature = "async")]
+pub async fn embed_batch_async(inputs: Vec<String>, backend: BackendOptions) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+    match backend {
+        BackendOptions::OpenAI { base_url, api_key, model } => {
+            let mut results = Vec::with_capacity(inputs.len());
+            for input in inputs {
+                let opt = openai::OpenAIOptions {
+                    base_url: base_url.clone(),
+                    api_key: api_key.clone()
+                };
+                let resp = openai::openai_embedding_async(input, model.clone(), opt).await?;
+                results.push(resp.try_pop_embedding()?);
+            }
+            Ok(results)
+        }
+        BackendOptions::Voyage { base_url, api_key, model, input_type } => {
+            let opt = VoyageOptions { base_url, api_key };
+            let mut results = Vec::with_capacity(inputs.len());
+            for chunk in inputs.chunks(voyage::VOYAGE_MAX_BATCH_SIZE) {
+                let resp = voyage::voyage_embedding_async(chunk.to_vec(), model.clone(), input_type, &opt).await?;
+                results.extend(resp.try_into_embeddings(chunk.len())?);
+            }
+            Ok(results)
+        }
+        backend @ (BackendOptions::FastEmbed { .. } | BackendOptions::FastEmbedLocal { .. }) => embed_batch(inputs, backend),
+    }
+}
+
 /// 모델 이름(String)으로부터 (모델 코드, 차원) 정보를 반환합니다.
 pub fn get_model_info_by_name(model_name: String) -> Result<(String, usize), EmbedError> {
     if let Ok(model) = parse_embedding_model(&model_name) {
@@ -133,6 +412,10 @@ pub fn get_model_info_by_name(model_name: String) -> Result<(String, usize), Emb
         return Ok((info.model_code.clone(), info.dim));
     }
 
+    if let Some(dim) = voyage::voyage_model_dimension(&model_name) {
+        return Ok((model_name, dim));
+    }
+
     let openai_models = [
         ("text-embedding-ada-002", 1536),
         ("text-embedding-3-small", 1536),
@@ -144,4 +427,67 @@ pub fn get_model_info_by_name(model_name: String) -> Result<(String, usize), Emb
         .find(|(name, _)| *name == model_name)
         .map(|(name, dim)| (name.to_string(), *dim))
         .ok_or_else(|| EmbedError::UnsupportedModel(model_name))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunked_partial, EmbedBatchIter, EmbeddingError};
+    use crate::fast::FastEmbedError;
+    use std::cell::Cell;
+
+    fn fake_embed(chunk: Vec<String>) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        Ok(chunk.iter().map(|t| vec![t.parse::<f32>().unwrap()]).collect())
+    }
+
+    #[test]
+    fn test_embed_batch_iter_yields_in_order() {
+        let inputs: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+        let chunks_embedded = Cell::new(0);
+        let mut iter = EmbedBatchIter::new(inputs, 4, |chunk| {
+            chunks_embedded.set(chunks_embedded.get() + 1);
+            fake_embed(chunk)
+        });
+
+        // the first vector only needs the first chunk
+        assert_eq!(iter.next().unwrap().unwrap(), vec![0.0]);
+        assert_eq!(chunks_embedded.get(), 1);
+
+        let rest: Vec<Vec<f32>> = iter.map(|r| r.unwrap()).collect();
+        assert_eq!(rest.len(), 9);
+        for (i, embedding) in rest.iter().enumerate() {
+            assert_eq!(embedding[0], (i + 1) as f32);
+        }
+        assert_eq!(chunks_embedded.get(), 3);
+    }
+
+    #[test]
+    fn test_embed_batch_iter_stops_after_error() {
+        let inputs: Vec<String> = ["1", "2", "x", "4"].iter().map(|s| s.to_string()).collect();
+        let results: Vec<_> = EmbedBatchIter::new(inputs, 2, |chunk| {
+            if chunk.iter().any(|t| t == "x") {
+                Err(FastEmbedError::Embed("bad input".to_string()).into())
+            } else {
+                fake_embed(chunk)
+            }
+        })
+        .collect();
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok() && results[1].is_ok());
+        assert!(results[2].is_err());
+    }
+
+    #[test]
+    fn test_chunked_partial_keeps_going_after_error() {
+        let inputs: Vec<String> = ["1", "x", "3", "4", "5"].iter().map(|s| s.to_string()).collect();
+        let mut chunk_sizes = Vec::new();
+        let results: Vec<_> = chunked_partial(inputs, 2, |chunk| {
+            chunk_sizes.push(chunk.len());
+            chunk.iter().map(|t| t.parse::<f32>().map_err(|_| t.clone())).collect::<Vec<_>>()
+        })
+        .collect();
+
+        assert_eq!(chunk_sizes, vec![2, 2, 1]);
+        assert_eq!(results, vec![Ok(1.0), Err("x".to_string()), Ok(3.0), Ok(4.0), Ok(5.0)]);
+    }
+}
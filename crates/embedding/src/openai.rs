@@ -49,7 +49,8 @@ pub struct EmbeddingResponse {
     pub object: String,
     pub data: Vec<EmbeddingData>,
     pub model: String,
-    pub usage: Usage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
 }
 
 impl EmbeddingResponse {
@@ -61,9 +62,16 @@ impl EmbeddingResponse {
             }),
         }
     }
+
+    /// Pop the embedding together with the token usage reported by the service.
+    /// Services that omit `usage` report zero tokens.
+    pub fn try_pop_embedding_with_usage(mut self) -> Result<(Vec<f32>, Usage), EmbeddingError> {
+        let usage = self.usage.take().unwrap_or_default();
+        self.try_pop_embedding().map(|embedding| (embedding, usage))
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Usage {
     pub prompt_tokens: i32,
     pub total_tokens: i32,
@@ -106,6 +114,40 @@ pub fn openai_embedding(
     }
 }
 
+/// Non-blocking counterpart of [`openai_embedding`] built on reqwest's async client
+#[cfg(feature = "async")]
+pub async fn openai_embedding_async(
+    input: String,
+    model: String,
+    opt: OpenAIOptions,
+) -> Result<EmbeddingResponse, EmbeddingError> {
+    let url = format!("{}/embeddings", opt.base_url);
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| EmbeddingError {
+            hint: e.to_string(),
+        })?;
+
+    let request: EmbeddingRequest = EmbeddingRequest::new(model.to_string(), input, "float".to_string());
+    let resp = client
+        .post(url)
+        .header("Authorization", format!("Bearer {}", opt.api_key))
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| EmbeddingError {
+            hint: e.to_string(),
+        })?;
+
+    match resp.json::<EmbeddingResponse>().await {
+        Ok(c) => Ok(c),
+        Err(e) => Err(EmbeddingError {
+            hint: e.to_string(),
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::openai::EmbeddingData;
@@ -140,10 +182,10 @@ mod tests {
                 index: 0,
             }],
             model: "mock-model".to_string(),
-            usage: Usage {
+            usage: Some(Usage {
                 prompt_tokens: 0,
                 total_tokens: 0,
-            },
+            }),
         };
         let server = mock_server(resp);
 
@@ -164,10 +206,10 @@ mod tests {
             object: "mock-object".to_string(),
             data: vec![],
             model: "mock-model".to_string(),
-            usage: Usage {
+            usage: Some(Usage {
                 prompt_tokens: 0,
                 total_tokens: 0,
-            },
+            }),
         };
         let server = mock_server(resp);
 
@@ -182,6 +224,140 @@ mod tests {
         assert!(real_embedding.is_err());
     }
 
+    #[test]
+    fn test_openai_embedding_usage() {
+        let resp = EmbeddingResponse {
+            object: "mock-object".to_string(),
+            data: vec![EmbeddingData {
+                object: "mock-object".to_string(),
+                embedding: vec![1.0, 2.0],
+                index: 0,
+            }],
+            model: "mock-model".to_string(),
+            usage: Some(Usage {
+                prompt_tokens: 7,
+                total_tokens: 9,
+            }),
+        };
+        let server = mock_server(resp);
+
+        let backend = crate::BackendOptions::OpenAI {
+            base_url: server.url(""),
+            api_key: "fake-key".to_string(),
+            model: "mock-model".to_string(),
+        };
+
+        let (embedding, usage) = crate::embed_with_usage("mock-input".to_string(), backend).unwrap();
+        assert_eq!(embedding, vec![1.0, 2.0]);
+        assert_eq!(usage.prompt_tokens, 7);
+        assert_eq!(usage.total_tokens, 9);
+    }
+
+    #[test]
+    fn test_openai_embedding_missing_usage() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST).path("/embeddings");
+            then.status(200)
+                .header("content-type", "text/html; charset=UTF-8")
+                .body(r#"{"object":"list","data":[{"object":"embedding","embedding":[1.0],"index":0}],"model":"mock-model"}"#);
+        });
+
+        let opt = OpenAIOptions {
+            base_url: server.url(""),
+            api_key: "fake-key".to_string(),
+        };
+
+        let real_resp = openai_embedding("mock-input".to_string(), "mock-model".to_string(), opt).unwrap();
+        assert!(real_resp.usage.is_none());
+        let (_, usage) = real_resp.try_pop_embedding_with_usage().unwrap();
+        assert_eq!(usage, Usage::default());
+    }
+
+    #[test]
+    fn test_embed_batch_partial() {
+        let server = MockServer::start();
+        // 빈 입력은 서비스가 거부
+        let rejected = server.mock(|when, then| {
+            when.method(POST)
+                .path("/embeddings")
+                .json_body_partial(r#"{"input": ""}"#);
+            then.status(400).body("invalid input");
+        });
+        let resp = EmbeddingResponse {
+            object: "mock-object".to_string(),
+            data: vec![EmbeddingData {
+                object: "mock-object".to_string(),
+                embedding: vec![1.0, 2.0],
+                index: 0,
+            }],
+            model: "mock-model".to_string(),
+            usage: None,
+        };
+        let accepted = server.mock(|when, then| {
+            when.method(POST)
+                .path("/embeddings")
+                .json_body_partial(r#"{"input": "hello"}"#);
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(serde_json::to_string(&resp).unwrap());
+        });
+
+        let backend = crate::BackendOptions::OpenAI {
+            base_url: server.url(""),
+            api_key: "fake-key".to_string(),
+            model: "mock-model".to_string(),
+        };
+        let inputs = vec!["hello".to_string(), "".to_string(), "hello".to_string()];
+        let results = crate::embed_batch_partial(inputs, backend);
+
+        rejected.assert_hits(1);
+        accepted.assert_hits(2);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), &vec![1.0, 2.0]);
+        assert!(results[1].is_err());
+        assert_eq!(results[2].as_ref().unwrap(), &vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_embed_prefixed_prepends_prefix() {
+        let server = MockServer::start();
+        let resp = EmbeddingResponse {
+            object: "mock-object".to_string(),
+            data: vec![EmbeddingData {
+                object: "mock-object".to_string(),
+                embedding: vec![1.0, 2.0],
+                index: 0,
+            }],
+            model: "mock-model".to_string(),
+            usage: None,
+        };
+        let prefixed = server.mock(|when, then| {
+            when.method(POST)
+                .path("/embeddings")
+                .json_body_partial(r#"{"input": "query: customer name"}"#);
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(serde_json::to_string(&resp).unwrap());
+        });
+
+        let backend = crate::BackendOptions::OpenAI {
+            base_url: server.url(""),
+            api_key: "fake-key".to_string(),
+            model: "mock-model".to_string(),
+        };
+        let prefixes = crate::InputPrefixes {
+            query: Some("query: ".to_string()),
+            doc: Some("passage: ".to_string()),
+        };
+        let embedding = crate::embed_prefixed("customer name".to_string(), crate::InputKind::Query, &prefixes, backend);
+
+        prefixed.assert_hits(1);
+        assert_eq!(embedding.unwrap(), vec![1.0, 2.0]);
+        assert_eq!(prefixes.apply(crate::InputKind::Document, "orders".to_string()), "passage: orders");
+        assert_eq!(crate::InputPrefixes::default().apply(crate::InputKind::Query, "orders".to_string()), "orders");
+    }
+
     #[test]
     fn test_openai_embedding_error() {
         let server = MockServer::start();
@@ -201,4 +377,83 @@ mod tests {
         let real_resp = openai_embedding("mock-input".to_string(), "mock-model".to_string(), opt);
         assert!(real_resp.is_err());
     }
+
+    #[cfg(feature = "async")]
+    async fn mock_server_async(resp: EmbeddingResponse) -> MockServer {
+        let server = MockServer::start_async().await;
+        let data = serde_json::to_string(&resp).unwrap();
+        server
+            .mock_async(|when, then| {
+                when.method(POST).path("/embeddings");
+                then.status(200)
+                    .header("content-type", "text/html; charset=UTF-8")
+                    .body(data);
+            })
+            .await;
+        server
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_openai_embedding_async_successful() {
+        let embedding = vec![1.0, 2.0, 3.0];
+        let resp = EmbeddingResponse {
+            object: "mock-object".to_string(),
+            data: vec![EmbeddingData {
+                object: "mock-object".to_string(),
+                embedding: embedding.clone(),
+                index: 0,
+            }],
+            model: "mock-model".to_string(),
+            usage: Some(Usage {
+                prompt_tokens: 0,
+                total_tokens: 0,
+            }),
+        };
+        let server = mock_server_async(resp).await;
+
+        let opt = OpenAIOptions {
+            base_url: server.url(""),
+            api_key: "fake-key".to_string(),
+        };
+
+        let real_resp =
+            super::openai_embedding_async("mock-input".to_string(), "mock-model".to_string(), opt).await;
+        assert!(real_resp.is_ok());
+        assert_eq!(real_resp.unwrap().try_pop_embedding().unwrap(), embedding);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn test_embed_batch_async() {
+        let embedding = vec![0.5, 0.25];
+        let resp = EmbeddingResponse {
+            object: "mock-object".to_string(),
+            data: vec![EmbeddingData {
+                object: "mock-object".to_string(),
+                embedding: embedding.clone(),
+                index: 0,
+            }],
+            model: "mock-model".to_string(),
+            usage: Some(Usage {
+                prompt_tokens: 0,
+                total_tokens: 0,
+            }),
+        };
+        let server = mock_server_async(resp).await;
+
+        let backend = || crate::BackendOptions::OpenAI {
+            base_url: server.url(""),
+            api_key: "fake-key".to_string(),
+            model: "mock-model".to_string(),
+        };
+
+        let single = crate::embed_async("a".to_string(), backend()).await.unwrap();
+        assert_eq!(single, embedding);
+
+        let inputs = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let batch = crate::embed_batch_async(inputs, backend()).await.unwrap();
+        assert_eq!(batch.len(), 3);
+        assert!(batch.iter().all(|e| *e == embedding));
+    }
 }
@@ -0,0 +1,307 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use thiserror::Error;
+use reqwest::blocking::Client;
+use std::time::Duration;
+
+/// Maximum number of inputs Voyage accepts in a single request
+pub const VOYAGE_MAX_BATCH_SIZE: usize = 128;
+
+#[derive(Debug, Error)]
+#[error(
+    "\
+Error happens at voyage embedding.
+INFORMATION: hint = {hint}"
+)]
+pub struct VoyageError {
+    pub hint: String,
+}
+
+/// Voyage embeds queries and documents differently; `None` embeds the raw text without a prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VoyageInputType {
+    Query,
+    Document,
+}
+
+impl VoyageInputType {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "query" => Some(VoyageInputType::Query),
+            "document" => Some(VoyageInputType::Document),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct VoyageRequest {
+    pub input: Vec<String>,
+    pub model: String,
+    pub input_type: Option<VoyageInputType>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VoyageEmbeddingData {
+    pub object: String,
+    pub embedding: Vec<f32>,
+    pub index: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VoyageUsage {
+    pub total_tokens: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VoyageResponse {
+    pub object: String,
+    pub data: Vec<VoyageEmbeddingData>,
+    pub model: String,
+    pub usage: VoyageUsage,
+}
+
+impl VoyageResponse {
+    /// Return embeddings ordered by their input index
+    pub fn try_into_embeddings(mut self, expected: usize) -> Result<Vec<Vec<f32>>, VoyageError> {
+        if self.data.len() != expected {
+            return Err(VoyageError {
+                hint: format!(
+                    "expected {} embeddings from service, got {}",
+                    expected,
+                    self.data.len()
+                ),
+            });
+        }
+        self.data.sort_by_key(|d| d.index);
+        Ok(self.data.into_iter().map(|d| d.embedding).collect())
+    }
+}
+
+pub struct VoyageOptions {
+    pub base_url: String,
+    pub api_key: String,
+}
+
+pub fn voyage_embedding(
+    inputs: Vec<String>,
+    model: String,
+    input_type: Option<VoyageInputType>,
+    opt: &VoyageOptions,
+) -> Result<VoyageResponse, VoyageError> {
+    let url = format!("{}/embeddings", opt.base_url);
+    let client = Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| VoyageError {
+            hint: e.to_string(),
+        })?;
+
+    let request = VoyageRequest {
+        input: inputs,
+        model,
+        input_type,
+    };
+    let resp = client
+        .post(url)
+        .header("Authorization", format!("Bearer {}", opt.api_key))
+        .json(&request)
+        .send()
+        .map_err(|e| VoyageError {
+            hint: e.to_string(),
+        })?;
+
+    if !resp.status().is_success() {
+        return Err(VoyageError {
+            hint: format!("service returned {}", resp.status()),
+        });
+    }
+
+    match resp.json::<VoyageResponse>() {
+        Ok(c) => Ok(c),
+        Err(e) => Err(VoyageError {
+            hint: e.to_string(),
+        }),
+    }
+}
+
+/// Non-blocking counterpart of [`voyage_embedding`] built on reqwest's async client
+#[cfg(feature = "async")]
+pub async fn voyage_embedding_async(
+    inputs: Vec<String>,
+    model: String,
+    input_type: Option<VoyageInputType>,
+    opt: &VoyageOptions,
+) -> Result<VoyageResponse, VoyageError> {
+    let url = format!("{}/embeddings", opt.base_url);
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| VoyageError {
+            hint: e.to_string(),
+        })?;
+
+    let request = VoyageRequest {
+        input: inputs,
+        model,
+        input_type,
+    };
+    let resp = client
+        .post(url)
+        .header("Authorization", format!("Bearer {}", opt.api_key))
+        .json(&request)
+        .send()
+        .await
+        .map_err(|e| VoyageError {
+            hint: e.to_string(),
+        })?;
+
+    if !resp.status().is_success() {
+        return Err(VoyageError {
+            hint: format!("service returned {}", resp.status()),
+        });
+    }
+
+    match resp.json::<VoyageResponse>().await {
+        Ok(c) => Ok(c),
+        Err(e) => Err(VoyageError {
+            hint: e.to_string(),
+        }),
+    }
+}
+
+/// Embed all inputs, splitting them into requests of at most `VOYAGE_MAX_BATCH_SIZE`
+pub fn voyage_embedding_batch(
+    inputs: Vec<String>,
+    model: String,
+    input_type: Option<VoyageInputType>,
+    opt: &VoyageOptions,
+) -> Result<Vec<Vec<f32>>, VoyageError> {
+    let mut results = Vec::with_capacity(inputs.len());
+    for chunk in inputs.chunks(VOYAGE_MAX_BATCH_SIZE) {
+        let resp = voyage_embedding(chunk.to_vec(), model.clone(), input_type, opt)?;
+        results.extend(resp.try_into_embeddings(chunk.len())?);
+    }
+    Ok(results)
+}
+
+/// Known Voyage models and their default output dimensions
+pub fn voyage_model_dimension(model_name: &str) -> Option<usize> {
+    let voyage_models = [
+        ("voyage-3-large", 1024),
+        ("voyage-3.5", 1024),
+        ("voyage-3.5-lite", 1024),
+        ("voyage-3", 1024),
+        ("voyage-3-lite", 512),
+        ("voyage-code-3", 1024),
+        ("voyage-finance-2", 1024),
+        ("voyage-law-2", 1024),
+        ("voyage-multilingual-2", 1024),
+    ];
+
+    voyage_models
+        .iter()
+        .find(|(name, _)| *name == model_name)
+        .map(|(_, dim)| *dim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::Method::POST;
+    use httpmock::MockServer;
+
+    fn response(embeddings: Vec<Vec<f32>>) -> String {
+        // 순서 보존 확인을 위해 index 역순으로 응답
+        let data: Vec<VoyageEmbeddingData> = embeddings
+            .into_iter()
+            .enumerate()
+            .rev()
+            .map(|(index, embedding)| VoyageEmbeddingData {
+                object: "embedding".to_string(),
+                embedding,
+                index,
+            })
+            .collect();
+        serde_json::to_string(&VoyageResponse {
+            object: "list".to_string(),
+            data,
+            model: "voyage-3".to_string(),
+            usage: VoyageUsage { total_tokens: 4 },
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_voyage_query_embedding() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/embeddings")
+                .json_body_partial(r#"{"input_type": "query", "input": ["hello"]}"#);
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(response(vec![vec![1.0, 2.0]]));
+        });
+
+        let backend = crate::BackendOptions::Voyage {
+            base_url: server.url(""),
+            api_key: "fake-key".to_string(),
+            model: "voyage-3".to_string(),
+            input_type: Some(VoyageInputType::Query),
+        };
+
+        let embedding = crate::embed("hello".to_string(), backend).unwrap();
+        mock.assert();
+        assert_eq!(embedding, vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn test_voyage_document_batch_embedding() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/embeddings")
+                .json_body_partial(r#"{"input_type": "document"}"#);
+            then.status(200)
+                .header("content-type", "application/json")
+                .body(response(vec![vec![1.0], vec![2.0], vec![3.0]]));
+        });
+
+        let backend = crate::BackendOptions::Voyage {
+            base_url: server.url(""),
+            api_key: "fake-key".to_string(),
+            model: "voyage-3".to_string(),
+            input_type: Some(VoyageInputType::Document),
+        };
+
+        let inputs = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let embeddings = crate::embed_batch(inputs, backend).unwrap();
+        mock.assert();
+        assert_eq!(embeddings, vec![vec![1.0], vec![2.0], vec![3.0]]);
+    }
+
+    #[test]
+    fn test_voyage_error() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST).path("/embeddings");
+            then.status(401).body("unauthorized");
+        });
+
+        let opt = VoyageOptions {
+            base_url: server.url(""),
+            api_key: "fake-key".to_string(),
+        };
+
+        let resp = voyage_embedding(vec!["a".to_string()], "voyage-3".to_string(), None, &opt);
+        assert!(resp.is_err());
+    }
+
+    #[test]
+    fn test_voyage_model_dimension() {
+        assert_eq!(voyage_model_dimension("voyage-3-lite"), Some(512));
+        assert_eq!(voyage_model_dimension("voyage-unknown"), None);
+    }
+}
@@ -1,45 +1,80 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct SoftQLQuery {
     pub initial_table: String,
     pub operations: Vec<Operator>,
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SoftQLStatement {
+    Query(SoftQLQuery),
+    SetOp(SetOperation),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SetOperation {
+    pub kind: SetOpKind,
+    pub left: Box<SoftQLStatement>,
+    pub right: Box<SoftQLStatement>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SetOpKind {
+    Union,
+    UnionAll,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Operator {
     Join(JoinClause),
     Filter(PredicateExpr), // equivalent to `.where()`
-    Group(Expression),
+    Group(Vec<Expression>), // one entry per group key
+    /// Grouping implied by a non-aggregate column inside `aggregate(...)`
+    /// (only inferred when the query has no explicit `group(...)`).
+    InferredGroup(Expression),
     Having(PredicateExpr),
     Aggregate(Vec<Expression>),
     Project(Vec<Expression>),
     Order(Vec<Expression>),
-    Limit(String), // store as string first, you can parse to usize later
+    Limit(Expression),  // NumberLiteral or Placeholder
+    Offset(Expression), // NumberLiteral or Placeholder
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct JoinClause {
     pub table: String,
     pub predicate: Option<PredicateExpr>,
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum PredicateExpr {
     And(Box<PredicateExpr>, Box<PredicateExpr>),
     Or(Box<PredicateExpr>, Box<PredicateExpr>),
     Not(Box<PredicateExpr>),
     FuncCall(FunctionCall),
     BoolLiteral(bool),
+    NotIn(InListExpr),
+}
+
+/// `expr NOT IN (values...)`.
+///
+/// Follows SQL semantics: if any value in the list is NULL the predicate is
+/// never true (it evaluates to NULL for rows that match no other value), so
+/// `notIn(t.a, 1, null)` filters out every row.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InListExpr {
+    pub expr: Expression,
+    pub values: Vec<Expression>,
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct FunctionCall {
     pub name: String,
     pub args: Vec<Expression>,
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Expression {
     FunctionCall(FunctionCall),
     TableField(String, String),
@@ -47,5 +82,59 @@ pub enum Expression {
     NumberLiteral(String),
     BoolLiteral(bool),
     NullLiteral,
+    Placeholder(Placeholder), // `$1` / `:name` – bound later by the caller
+    BinaryOp(Box<Expression>, ArithmeticOp, Box<Expression>),
+    CaseWhen(CaseWhenExpr),
+    Wildcard,              // `*` – only valid inside project()
+    TableWildcard(String), // `table.*` – only valid inside project()
+    WildcardExcept(WildcardExcept), // `*` / `table.*` with `except(...)` – only valid inside project()
+}
+
+/// `*` or `table.*` minus the listed columns, e.g. `customers.* except(customers.ssn)`.
+///
+/// Binders expand the wildcard and drop every `(table, column)` in `excluded`;
+/// `table` is `None` for a bare `*`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WildcardExcept {
+    pub table: Option<String>,
+    pub excluded: Vec<(String, String)>,
+}
+
+/// Query parameter standing in for a literal, e.g. `limit($1)` or `offset(:n)`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Placeholder {
+    Positional(u32), // `$1`
+    Named(String),   // `:name`
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CaseWhenExpr {
+    pub branches: Vec<CaseBranch>,
+    pub else_result: Option<Box<Expression>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CaseBranch {
+    pub condition: PredicateExpr,
+    pub result: Expression,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ArithmeticOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl ArithmeticOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            ArithmeticOp::Add => "+",
+            ArithmeticOp::Sub => "-",
+            ArithmeticOp::Mul => "*",
+            ArithmeticOp::Div => "/",
+        }
+    }
 }
 
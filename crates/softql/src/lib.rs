@@ -3,7 +3,10 @@ pub mod ast;
 pub mod parser;
 
 // re-export for convenience
-pub use parser::static_parse_softql;
+pub use parser::{
+    static_parse_softql, static_parse_softql_statement, static_parse_softql_with_max_depth,
+    DEFAULT_MAX_NESTING_DEPTH,
+};
 
 #[cfg(test)]
 mod tests {
@@ -232,6 +235,24 @@ mod tests {
         assert_eq!(ast.operations.len(), 2); // group + having
     }
 
+    #[test]
+    fn test_group_multiple_columns() {
+        let ast = static_parse_softql(
+            r#"region.group(region.name, region.id).aggregate(region.name, region.id, count(region.id))"#,
+        )
+        .unwrap();
+        assert_eq!(ast.operations.len(), 2);
+        // group key 하나가 AST 항목 하나
+        let ast::Operator::Group(keys) = &ast.operations[0] else {
+            panic!("expected group, got {:?}", ast.operations[0]);
+        };
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys, &vec![field("region", "name"), field("region", "id")]);
+        assert!(!ast.operations.iter().any(|op| matches!(op, ast::Operator::InferredGroup(_))));
+
+        assert!(static_parse_softql("region.group(region.name,)").is_err());
+    }
+
     #[test]
     fn test_limit() {
         let ast = static_parse_softql("customers.limit(100)").unwrap();
@@ -284,6 +305,38 @@ mod tests {
         assert_eq!(ast.operations.len(), 1);
     }
 
+    #[test]
+    fn test_limit_positional_placeholder() {
+        let ast = static_parse_softql(r#"customers.order(customers.id).limit($1)"#).unwrap();
+        assert_eq!(
+            ast.operations[1],
+            ast::Operator::Limit(ast::Expression::Placeholder(ast::Placeholder::Positional(1)))
+        );
+
+        // 구체적인 정수는 그대로 숫자 리터럴
+        let ast = static_parse_softql("customers.limit(100)").unwrap();
+        assert_eq!(ast.operations[0], ast::Operator::Limit(ast::Expression::NumberLiteral("100".to_owned())));
+    }
+
+    #[test]
+    fn test_offset_named_placeholder() {
+        let ast = static_parse_softql(r#"customers.limit(10).offset(:n)"#).unwrap();
+        assert_eq!(ast.operations.len(), 2);
+        assert_eq!(
+            ast.operations[1],
+            ast::Operator::Offset(ast::Expression::Placeholder(ast::Placeholder::Named("n".to_owned())))
+        );
+        assert!(static_parse_softql("customers.offset($)").is_err());
+    }
+
+    #[test]
+    fn test_positional_placeholder_overflow_is_error() {
+        let err = static_parse_softql("customers.limit($99999999999)").unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+        assert!(static_parse_softql_statement("customers.limit($99999999999)").is_err());
+        assert!(static_parse_softql("customers.limit($4294967295)").is_ok());
+    }
+
     #[test]
     fn test_zero_arg_function_call() {
         // 인자가 없는 함수 random() --> function_args? 가 None 인 경우
@@ -300,4 +353,499 @@ mod tests {
         .unwrap();
         assert_eq!(ast.operations.len(), 1);
     }
+
+    // ────────────── 산술 연산 ──────────────
+    fn field(tbl: &str, col: &str) -> ast::Expression {
+        ast::Expression::TableField(tbl.to_owned(), col.to_owned())
+    }
+
+    fn binop(l: ast::Expression, op: ast::ArithmeticOp, r: ast::Expression) -> ast::Expression {
+        ast::Expression::BinaryOp(Box::new(l), op, Box::new(r))
+    }
+
+    #[test]
+    fn test_project_arithmetic_expression() {
+        let ast = static_parse_softql(
+            r#"customers.project(customers.price * customers.qty, customers.a + customers.b * 2)"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ast.operations,
+            vec![ast::Operator::Project(vec![
+                binop(field("customers", "price"), ast::ArithmeticOp::Mul, field("customers", "qty")),
+                // * 가 + 보다 먼저 묶임
+                binop(
+                    field("customers", "a"),
+                    ast::ArithmeticOp::Add,
+                    binop(
+                        field("customers", "b"),
+                        ast::ArithmeticOp::Mul,
+                        ast::Expression::NumberLiteral("2".to_owned()),
+                    ),
+                ),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_operand_in_predicate() {
+        let ast = static_parse_softql(
+            r#"customers.where(greater(customers.a + customers.b, 10))"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ast.operations,
+            vec![ast::Operator::Filter(ast::PredicateExpr::FuncCall(ast::FunctionCall {
+                name: "greater".to_owned(),
+                args: vec![
+                    binop(field("customers", "a"), ast::ArithmeticOp::Add, field("customers", "b")),
+                    ast::Expression::NumberLiteral("10".to_owned()),
+                ],
+            }))]
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_parentheses_and_associativity() {
+        let ast = static_parse_softql(
+            r#"customers.project((customers.a - customers.b) / sum(customers.c) - 1)"#,
+        )
+        .unwrap();
+        let expected = binop(
+            binop(
+                binop(field("customers", "a"), ast::ArithmeticOp::Sub, field("customers", "b")),
+                ast::ArithmeticOp::Div,
+                ast::Expression::FunctionCall(ast::FunctionCall {
+                    name: "sum".to_owned(),
+                    args: vec![field("customers", "c")],
+                }),
+            ),
+            ast::ArithmeticOp::Sub,
+            ast::Expression::NumberLiteral("1".to_owned()),
+        );
+        assert_eq!(ast.operations, vec![ast::Operator::Project(vec![expected])]);
+    }
+
+    // ────────────── WILDCARD ──────────────
+    #[test]
+    fn test_project_bare_wildcard() {
+        let ast = static_parse_softql("customers.project(*)").unwrap();
+        assert_eq!(ast.operations, vec![ast::Operator::Project(vec![ast::Expression::Wildcard])]);
+    }
+
+    #[test]
+    fn test_project_table_wildcard() {
+        let ast = static_parse_softql(
+            "customers.join(orders, equals(customers.id, orders.customer_id)).project(customers.*)",
+        )
+        .unwrap();
+        assert_eq!(
+            ast.operations[1],
+            ast::Operator::Project(vec![ast::Expression::TableWildcard("customers".to_owned())])
+        );
+    }
+
+    #[test]
+    fn test_project_wildcard_mixed_with_columns() {
+        let ast = static_parse_softql(
+            "customers.project(customers.*, orders.amount, *, count(orders.id))",
+        )
+        .unwrap();
+        let ast::Operator::Project(exprs) = &ast.operations[0] else {
+            panic!("expected project");
+        };
+        assert_eq!(exprs.len(), 4);
+        assert_eq!(exprs[0], ast::Expression::TableWildcard("customers".to_owned()));
+        assert_eq!(exprs[1], field("orders", "amount"));
+        assert_eq!(exprs[2], ast::Expression::Wildcard);
+        assert!(matches!(exprs[3], ast::Expression::FunctionCall(_)));
+        // wildcard 는 project 에서만 허용
+        assert!(static_parse_softql("customers.where(equals(customers.*, 1))").is_err());
+    }
+
+    #[test]
+    fn test_project_wildcard_except() {
+        let ast = static_parse_softql(
+            "customers.project(customers.* except(customers.ssn, customers.password_hash), *except(orders.note))",
+        )
+        .unwrap();
+        let ast::Operator::Project(exprs) = &ast.operations[0] else {
+            panic!("expected project");
+        };
+        assert_eq!(
+            exprs[0],
+            ast::Expression::WildcardExcept(ast::WildcardExcept {
+                table: Some("customers".to_owned()),
+                excluded: vec![
+                    ("customers".to_owned(), "ssn".to_owned()),
+                    ("customers".to_owned(), "password_hash".to_owned()),
+                ],
+            })
+        );
+        assert_eq!(
+            exprs[1],
+            ast::Expression::WildcardExcept(ast::WildcardExcept {
+                table: None,
+                excluded: vec![("orders".to_owned(), "note".to_owned())],
+            })
+        );
+    }
+
+    #[test]
+    fn test_project_wildcard_except_malformed() {
+        for input in [
+            "customers.project(customers.* except())",
+            "customers.project(customers.* except(ssn))",
+            "customers.project(customers.* except(customers.ssn,))",
+            "customers.project(customers.* except customers.ssn)",
+            "customers.project(customers.name except(customers.ssn))",
+        ] {
+            assert!(static_parse_softql(input).is_err(), "{}", input);
+        }
+    }
+
+    // ────────────── CASE WHEN ──────────────
+    fn equals_call(tbl: &str, col: &str, value: &str) -> ast::PredicateExpr {
+        ast::PredicateExpr::FuncCall(ast::FunctionCall {
+            name: "equals".to_owned(),
+            args: vec![field(tbl, col), ast::Expression::StringLiteral(value.to_owned())],
+        })
+    }
+
+    #[test]
+    fn test_case_when_two_branch() {
+        let ast = static_parse_softql(
+            r#"customers.project(customers.id, caseWhen(equals(customers.tier, 'gold'), 'vip', 'regular'))"#,
+        )
+        .unwrap();
+        assert_eq!(ast.operations.len(), 1);
+        let ast::Operator::Project(exprs) = &ast.operations[0] else {
+            panic!("expected project");
+        };
+        assert_eq!(exprs.len(), 2);
+        assert_eq!(
+            exprs[1],
+            ast::Expression::CaseWhen(ast::CaseWhenExpr {
+                branches: vec![ast::CaseBranch {
+                    condition: equals_call("customers", "tier", "gold"),
+                    result: ast::Expression::StringLiteral("vip".to_owned()),
+                }],
+                else_result: Some(Box::new(ast::Expression::StringLiteral("regular".to_owned()))),
+            })
+        );
+    }
+
+    #[test]
+    fn test_case_when_nested_in_aggregate() {
+        let ast = static_parse_softql(
+            r#"
+              customers
+                .group(customers.region)
+                .aggregate(sum(caseWhen(
+                    equals(customers.tier, 'gold'), caseWhen(greater(customers.amount, 100), customers.amount * 2, customers.amount),
+                    NOT equals(customers.tier, 'none'), customers.amount
+                )))
+            "#,
+        )
+        .unwrap();
+        assert_eq!(ast.operations.len(), 2);
+        let ast::Operator::Aggregate(exprs) = &ast.operations[1] else {
+            panic!("expected aggregate");
+        };
+        let ast::Expression::FunctionCall(sum) = &exprs[0] else {
+            panic!("expected sum()");
+        };
+        let ast::Expression::CaseWhen(outer) = &sum.args[0] else {
+            panic!("expected caseWhen inside sum()");
+        };
+        // 두 개의 분기, ELSE 없음
+        assert_eq!(outer.branches.len(), 2);
+        assert!(outer.else_result.is_none());
+        assert!(matches!(outer.branches[1].condition, ast::PredicateExpr::Not(_)));
+
+        let ast::Expression::CaseWhen(inner) = &outer.branches[0].result else {
+            panic!("expected nested caseWhen");
+        };
+        assert_eq!(inner.branches.len(), 1);
+        assert!(matches!(inner.branches[0].result, ast::Expression::BinaryOp(_, ast::ArithmeticOp::Mul, _)));
+        assert_eq!(inner.else_result.as_deref(), Some(&field("customers", "amount")));
+    }
+
+    // ────────────── aggregate → group 추론 ──────────────
+    #[test]
+    fn test_aggregate_infers_group_for_plain_column() {
+        let ast = static_parse_softql(
+            r#"customers.where(equals(customers.active, 'Y')).aggregate(customers.country, calcSum(customers.amount))"#,
+        )
+        .unwrap();
+        assert_eq!(ast.operations.len(), 3);
+        assert_eq!(ast.operations[1], ast::Operator::InferredGroup(field("customers", "country")));
+        assert!(matches!(&ast.operations[2], ast::Operator::Aggregate(exprs) if exprs.len() == 2));
+    }
+
+    #[test]
+    fn test_aggregate_without_aggregate_call_infers_nothing() {
+        let ast = static_parse_softql(r#"customers.aggregate(customers.country, upper(customers.name))"#).unwrap();
+        assert_eq!(ast.operations.len(), 1);
+
+        let ast = static_parse_softql(r#"customers.aggregate(count(customers.id))"#).unwrap();
+        assert_eq!(ast.operations.len(), 1);
+    }
+
+    #[test]
+    fn test_explicit_group_is_not_inferred() {
+        let ast = static_parse_softql(
+            r#"customers.group(customers.region).aggregate(customers.country, sum(customers.amount))"#,
+        )
+        .unwrap();
+        assert_eq!(ast.operations.len(), 2);
+        assert_eq!(ast.operations[0], ast::Operator::Group(vec![field("customers", "region")]));
+        assert!(!ast.operations.iter().any(|op| matches!(op, ast::Operator::InferredGroup(_))));
+    }
+
+    // ────────────── UNION / UNION ALL ──────────────
+    fn pipeline(stmt: &ast::SoftQLStatement) -> &ast::SoftQLQuery {
+        match stmt {
+            ast::SoftQLStatement::Query(q) => q,
+            other => panic!("expected a single pipeline, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_union() {
+        let stmt = static_parse_softql_statement(
+            r#"
+              union(
+                customers.where(equals(customers.city, 'Seoul')).project(customers.name),
+                suppliers.project(suppliers.name)
+              )
+            "#,
+        )
+        .unwrap();
+        let ast::SoftQLStatement::SetOp(op) = stmt else {
+            panic!("expected set operation");
+        };
+        assert_eq!(op.kind, ast::SetOpKind::Union);
+        assert_eq!(
+            *pipeline(&op.left),
+            static_parse_softql("customers.where(equals(customers.city, 'Seoul')).project(customers.name)").unwrap()
+        );
+        assert_eq!(pipeline(&op.right).initial_table, "suppliers");
+        assert_eq!(pipeline(&op.right).operations.len(), 1);
+    }
+
+    #[test]
+    fn test_union_all_nested() {
+        let stmt = static_parse_softql_statement(
+            r#"unionAll(a.project(a.id), union(b.project(b.id), c.project(c.id)))"#,
+        )
+        .unwrap();
+        let ast::SoftQLStatement::SetOp(op) = stmt else {
+            panic!("expected set operation");
+        };
+        assert_eq!(op.kind, ast::SetOpKind::UnionAll);
+        assert_eq!(pipeline(&op.left).initial_table, "a");
+        let ast::SoftQLStatement::SetOp(inner) = &*op.right else {
+            panic!("expected nested union");
+        };
+        assert_eq!(inner.kind, ast::SetOpKind::Union);
+        assert_eq!(pipeline(&inner.left).initial_table, "b");
+        assert_eq!(pipeline(&inner.right).initial_table, "c");
+    }
+
+    #[test]
+    fn test_statement_single_pipeline() {
+        // union 이 없으면 일반 파이프라인, 'union' 이라는 이름의 테이블도 허용
+        let stmt = static_parse_softql_statement("union.limit(5)").unwrap();
+        assert_eq!(pipeline(&stmt).initial_table, "union");
+        assert!(static_parse_softql_statement("union(a.project(a.id))").is_err());
+    }
+
+    // ────────────── 중첩 깊이 제한 ──────────────
+    #[test]
+    fn test_nesting_depth_exceeded_is_error() {
+        let deep_not = format!(
+            "customers.where({}equals(customers.a, 1){})",
+            "NOT (".repeat(5000),
+            ")".repeat(5000)
+        );
+        assert!(static_parse_softql(&deep_not).is_err());
+
+        let deep_call = format!("customers.project({}customers.a{})", "f(".repeat(5000), ")".repeat(5000));
+        assert!(static_parse_softql(&deep_call).is_err());
+
+        let chained_not = format!("customers.where({}true)", "NOT ".repeat(5000));
+        assert!(static_parse_softql_statement(&chained_not).is_err());
+    }
+
+    #[test]
+    fn test_nesting_depth_within_limit() {
+        let input = format!("customers.where({}equals(customers.a, 1){})", "NOT (".repeat(20), ")".repeat(20));
+        assert!(static_parse_softql(&input).is_ok());
+        assert!(static_parse_softql_with_max_depth(&input, 10).is_err());
+
+        // 형제 술어의 NOT 이나 문자열 안의 괄호는 깊이에 누적되지 않음
+        let siblings = (0..200)
+            .map(|i| format!("NOT equals(customers.a, '((({}')", i))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        assert!(static_parse_softql(&format!("customers.where({})", siblings)).is_ok());
+    }
+
+    // ────────────── 주석 ──────────────
+    #[test]
+    fn test_leading_and_trailing_comments() {
+        let plain = static_parse_softql("customers.where(equals(customers.id, 1)).limit(10)").unwrap();
+        let commented = static_parse_softql(
+            "# top-level comment\n// another one\ncustomers.where(equals(customers.id, 1)).limit(10) # trailing",
+        )
+        .unwrap();
+        assert_eq!(plain, commented);
+    }
+
+    #[test]
+    fn test_comments_between_operations() {
+        let plain = static_parse_softql(
+            "customers.join(orders, equals(customers.id, orders.customer_id)).project(customers.name)",
+        )
+        .unwrap();
+        let commented = static_parse_softql(
+            r#"
+              customers // 고객 테이블
+                # 주문과 조인
+                .join(orders, equals(customers.id, orders.customer_id))
+                // 이름만 출력
+                .project(customers.name)
+            "#,
+        )
+        .unwrap();
+        assert_eq!(plain, commented);
+    }
+
+    #[test]
+    fn test_inline_comments_in_arguments() {
+        let plain = static_parse_softql(
+            r#"customers.where(equals(customers.city, "a#b//c") AND greater(customers.age, 20))"#,
+        )
+        .unwrap();
+        let commented = static_parse_softql(
+            r#"
+              customers.where(
+                equals(
+                  customers.city, # 도시
+                  "a#b//c"        // 문자열 안의 주석 기호는 그대로
+                )
+                AND greater(customers.age, 20) // 나이 조건
+              )
+            "#,
+        )
+        .unwrap();
+        assert_eq!(plain, commented);
+    }
+
+    // ────────────── NOT + 술어 함수 ──────────────
+    fn negated_call(input: &str) -> ast::FunctionCall {
+        let mut ast = static_parse_softql(input).unwrap();
+        assert_eq!(ast.operations.len(), 1);
+        match ast.operations.remove(0) {
+            ast::Operator::Filter(ast::PredicateExpr::Not(inner)) => match *inner {
+                ast::PredicateExpr::FuncCall(call) => call,
+                other => panic!("expected function call under NOT, got {:?}", other),
+            },
+            other => panic!("expected negated filter, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_not_in() {
+        let call = negated_call(r#"customers.where(NOT in(customers.city, 'Seoul', 'Busan'))"#);
+        assert_eq!(call.name, "in");
+        assert_eq!(call.args.len(), 3);
+    }
+
+    #[test]
+    fn test_not_between() {
+        let call = negated_call(r#"customers.where(NOT between(customers.age, 20, 30))"#);
+        assert_eq!(call.name, "between");
+        assert_eq!(
+            call.args,
+            vec![
+                ast::Expression::TableField("customers".to_owned(), "age".to_owned()),
+                ast::Expression::NumberLiteral("20".to_owned()),
+                ast::Expression::NumberLiteral("30".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_like() {
+        let call = negated_call(r#"customers.where(NOT like(customers.name, 'A%'))"#);
+        assert_eq!(call.name, "like");
+        assert_eq!(call.args.len(), 2);
+    }
+
+    #[test]
+    fn test_not_binds_tighter_than_and() {
+        // NOT 은 바로 뒤의 술어에만 적용되어야 함
+        let ast = static_parse_softql(
+            r#"customers.where(NOT in(customers.id, 1, 2) AND like(customers.name, 'A%'))"#,
+        )
+        .unwrap();
+        match &ast.operations[0] {
+            ast::Operator::Filter(ast::PredicateExpr::And(lhs, rhs)) => {
+                assert!(matches!(**lhs, ast::PredicateExpr::Not(_)));
+                assert!(matches!(**rhs, ast::PredicateExpr::FuncCall(_)));
+            }
+            other => panic!("expected AND at top level, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_not_prefix_identifier_is_not_negation() {
+        // NOTHING(...) 은 NOT + HING(...) 이 아니라 하나의 함수 호출
+        let ast = static_parse_softql(r#"customers.where(NOTHING(customers.id))"#).unwrap();
+        match &ast.operations[0] {
+            ast::Operator::Filter(ast::PredicateExpr::FuncCall(call)) => assert_eq!(call.name, "NOTHING"),
+            other => panic!("expected plain function call, got {:?}", other),
+        }
+    }
+
+    fn not_in_list(input: &str) -> ast::InListExpr {
+        let mut ast = static_parse_softql(input).unwrap();
+        match ast.operations.remove(0) {
+            ast::Operator::Filter(ast::PredicateExpr::NotIn(in_list)) => in_list,
+            other => panic!("expected notIn filter, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_not_in_string_list() {
+        let in_list = not_in_list(r#"customers.where(notIn(customers.city, 'Seoul', "Busan"))"#);
+        assert_eq!(in_list.expr, field("customers", "city"));
+        assert_eq!(
+            in_list.values,
+            vec![
+                ast::Expression::StringLiteral("Seoul".to_owned()),
+                ast::Expression::StringLiteral("Busan".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_in_numeric_list() {
+        let in_list = not_in_list(r#"orders.where(notIn(orders.status_code, 1, 20, -3))"#);
+        assert_eq!(in_list.expr, field("orders", "status_code"));
+        assert_eq!(
+            in_list.values,
+            vec![
+                ast::Expression::NumberLiteral("1".to_owned()),
+                ast::Expression::NumberLiteral("20".to_owned()),
+                ast::Expression::NumberLiteral("-3".to_owned()),
+            ]
+        );
+
+        // notIn 과 NOT in 은 서로 다른 노드로 표현됨
+        let ast = static_parse_softql(r#"orders.where(NOT in(orders.status_code, 1))"#).unwrap();
+        assert!(matches!(ast.operations[0], ast::Operator::Filter(ast::PredicateExpr::Not(_))));
+    }
 }
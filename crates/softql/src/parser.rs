@@ -14,14 +14,188 @@ pub struct SoftQLParser;
 // ──────────────────────────────
 // public entry‐point
 // ──────────────────────────────
+/// Nesting depth (parentheses + chained `NOT`s) accepted by [`static_parse_softql`]
+/// and [`static_parse_softql_statement`]
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 128;
+
 pub fn static_parse_softql(input: &str) -> Result<SoftQLQuery, pest::error::Error<Rule>> {
+    static_parse_softql_with_max_depth(input, DEFAULT_MAX_NESTING_DEPTH).map_err(|e| *e)
+}
+
+/// Like [`static_parse_softql`], but rejects input nested deeper than `max_depth`
+/// with a parse error instead of recursing into it. The error is boxed to keep the `Result` small
+pub fn static_parse_softql_with_max_depth(
+    input: &str,
+    max_depth: usize,
+) -> Result<SoftQLQuery, Box<pest::error::Error<Rule>>> {
+    check_nesting_depth(input, max_depth)?;
     let mut pairs = SoftQLParser::parse(Rule::softql, input)?;
     let softql_pair = pairs
         .next()
         .expect("Expected top-level softql rule to yield one pair");
+    check_placeholders(&softql_pair)?;
     Ok(build_query(softql_pair))
 }
 
+/// Like [`static_parse_softql`], but also accepts `union(..)` / `unionAll(..)` over pipelines.
+/// The error is boxed to keep the `Result` small
+pub fn static_parse_softql_statement(input: &str) -> Result<SoftQLStatement, Box<pest::error::Error<Rule>>> {
+    check_nesting_depth(input, DEFAULT_MAX_NESTING_DEPTH)?;
+    let mut pairs = SoftQLParser::parse(Rule::softql_statement, input)?;
+    let stmt_pair = pairs
+        .next()
+        .expect("Expected top-level softql_statement rule to yield one pair")
+        .into_inner()
+        .next()
+        .expect("softql_statement must contain exactly one statement pair");
+    check_placeholders(&stmt_pair)?;
+    Ok(build_statement(stmt_pair))
+}
+
+// ──────────────────────────────
+// nesting depth guard
+// pest 와 build_* 모두 재귀 하강이므로, 파싱 전에 괄호 / 연속된 NOT 깊이를 미리 센다
+// (문자열 리터럴과 주석 안의 괄호는 세지 않음)
+// ──────────────────────────────
+fn check_nesting_depth(input: &str, max_depth: usize) -> Result<(), Box<pest::error::Error<Rule>>> {
+    // 괄호 레벨마다 아직 대상 술어가 끝나지 않은 NOT 개수
+    let mut pending_nots: Vec<usize> = vec![0];
+    let mut depth = 0usize;
+    let bytes = input.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            b'\'' | b'"' => {
+                let quote = bytes[i];
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += 1;
+                }
+                i += 1;
+                continue;
+            }
+            b'#' => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'(' => {
+                pending_nots.push(0);
+                depth += 1;
+            }
+            b')' => {
+                if pending_nots.len() > 1 {
+                    depth -= 1 + pending_nots.pop().unwrap_or(0);
+                }
+                // 닫힌 그룹이 바로 앞 NOT 들의 대상 술어
+                let level = pending_nots.last_mut().unwrap();
+                depth -= *level;
+                *level = 0;
+            }
+            c if c.is_ascii_alphanumeric() || c == b'_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                let level = pending_nots.last_mut().unwrap();
+                match &input[start..i] {
+                    "NOT" => {
+                        *level += 1;
+                        depth += 1;
+                    }
+                    // 새 술어가 시작되면 앞의 NOT 들은 이미 적용이 끝남
+                    "AND" | "OR" => {
+                        depth -= *level;
+                        *level = 0;
+                    }
+                    _ => {}
+                }
+                if depth > max_depth {
+                    return Err(nesting_depth_error(input, start, max_depth));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        if depth > max_depth {
+            return Err(nesting_depth_error(input, start, max_depth));
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+fn nesting_depth_error(input: &str, pos: usize, max_depth: usize) -> Box<pest::error::Error<Rule>> {
+    Box::new(pest::error::Error::new_from_pos(
+        pest::error::ErrorVariant::CustomError {
+            message: format!("nesting depth exceeds the maximum of {}", max_depth),
+        },
+        pest::Position::new(input, pos).expect("position must be within input"),
+    ))
+}
+
+// ──────────────────────────────
+// placeholder guard
+// grammar 는 `$` 뒤의 자릿수를 제한하지 않으므로, build_* 전에 u32 범위를 확인한다
+// ──────────────────────────────
+fn check_placeholders(pair: &Pair<Rule>) -> Result<(), Box<pest::error::Error<Rule>>> {
+    for inner in pair.clone().into_inner().flatten() {
+        if inner.as_rule() != Rule::placeholder {
+            continue;
+        }
+        if let Some(num) = inner.as_str().strip_prefix('$') {
+            if num.parse::<u32>().is_err() {
+                return Err(Box::new(pest::error::Error::new_from_span(
+                    pest::error::ErrorVariant::CustomError {
+                        message: format!("positional placeholder ${} is out of range", num),
+                    },
+                    inner.as_span(),
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+// ──────────────────────────────
+// statement      =  set_operation | query
+// set_operation  =  set_op_kind "(" statement "," statement ")"
+// ──────────────────────────────
+fn build_statement(pair: Pair<Rule>) -> SoftQLStatement {
+    debug_assert_eq!(pair.as_rule(), Rule::statement);
+    let inner = pair.into_inner().next().expect("statement must not be empty");
+    match inner.as_rule() {
+        Rule::set_operation => SoftQLStatement::SetOp(build_set_operation(inner)),
+        Rule::query => SoftQLStatement::Query(build_query_inner(inner)),
+        _ => unreachable!("unexpected statement child"),
+    }
+}
+
+fn build_set_operation(pair: Pair<Rule>) -> SetOperation {
+    let mut inner = pair.into_inner();
+    let kind = match inner.next().expect("set operation missing kind").as_str() {
+        "union" => SetOpKind::Union,
+        "unionAll" => SetOpKind::UnionAll,
+        other => unreachable!("unexpected set operation {}", other),
+    };
+    let left = build_statement(inner.next().expect("set operation missing left pipeline"));
+    let right = build_statement(inner.next().expect("set operation missing right pipeline"));
+
+    SetOperation {
+        kind,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
 // ──────────────────────────────
 // softql  =  SOI ~ query ~ EOI
 // query   =  identifier (“.” operator_call)*
@@ -53,6 +227,7 @@ fn build_query_inner(pair: Pair<Rule>) -> SoftQLQuery {
         // dotted “.” literals do NOT appear here – only operator_call
         operations.push(build_operator(op_pair));
     }
+    infer_aggregate_groups(&mut operations);
 
     SoftQLQuery {
         initial_table,
@@ -67,16 +242,84 @@ fn build_operator(pair: Pair<Rule>) -> Operator {
     match pair.as_rule() {
         Rule::join_call => Operator::Join(build_join_clause(pair)),
         Rule::where_call => Operator::Filter(build_where_or_having(pair)),
-        Rule::group_call => Operator::Group(build_group_clause(pair)),
+        Rule::group_call => Operator::Group(build_multi_expression_clause(pair)),
         Rule::having_call => Operator::Having(build_where_or_having(pair)),
         Rule::aggregate_call => Operator::Aggregate(build_multi_expression_clause(pair)),
-        Rule::project_call => Operator::Project(build_multi_expression_clause(pair)),
+        Rule::project_call => Operator::Project(build_project_clause(pair)),
         Rule::order_call => Operator::Order(build_multi_expression_clause(pair)),
         Rule::limit_call => Operator::Limit(build_limit_clause(pair)),
+        Rule::offset_call => Operator::Offset(build_limit_clause(pair)),
         _ => unreachable!("Unhandled operator rule"),
     }
 }
 
+// ──────────────────────────────
+// aggregate(region.name, sum(x))  →  group(region.name) 추론
+// explicit group(...) 이 하나라도 있으면 아무것도 추론하지 않음
+// ──────────────────────────────
+const AGGREGATE_FUNCTION_NAMES: [&str; 10] = [
+    "sum", "count", "avg", "average", "mean", "min", "max", "minimum", "maximum", "total",
+];
+
+fn is_aggregate_function_name(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    let base = lower.strip_prefix("calc").unwrap_or(&lower);
+    AGGREGATE_FUNCTION_NAMES.contains(&base)
+}
+
+fn contains_aggregate(expr: &Expression) -> bool {
+    match expr {
+        Expression::FunctionCall(f) => {
+            is_aggregate_function_name(&f.name) || f.args.iter().any(contains_aggregate)
+        }
+        Expression::BinaryOp(l, _, r) => contains_aggregate(l) || contains_aggregate(r),
+        Expression::CaseWhen(c) => {
+            c.branches.iter().any(|b| contains_aggregate(&b.result))
+                || c.else_result.as_deref().is_some_and(contains_aggregate)
+        }
+        _ => false,
+    }
+}
+
+fn references_column(expr: &Expression) -> bool {
+    match expr {
+        Expression::TableField(..) => true,
+        Expression::FunctionCall(f) => f.args.iter().any(references_column),
+        Expression::BinaryOp(l, _, r) => references_column(l) || references_column(r),
+        Expression::CaseWhen(c) => {
+            c.branches.iter().any(|b| references_column(&b.result))
+                || c.else_result.as_deref().is_some_and(references_column)
+        }
+        _ => false,
+    }
+}
+
+fn infer_aggregate_groups(operations: &mut Vec<Operator>) {
+    if operations.iter().any(|op| matches!(op, Operator::Group(_))) {
+        return;
+    }
+
+    let mut inferred = Vec::<(usize, Expression)>::new();
+    for (idx, op) in operations.iter().enumerate() {
+        let Operator::Aggregate(exprs) = op else {
+            continue;
+        };
+        if !exprs.iter().any(contains_aggregate) {
+            continue;
+        }
+        for e in exprs {
+            if !contains_aggregate(e) && references_column(e) && !inferred.iter().any(|(_, g)| g == e) {
+                inferred.push((idx, e.clone()));
+            }
+        }
+    }
+
+    // 뒤에서부터 끼워 넣어야 앞쪽 인덱스가 밀리지 않음 (aggregate 바로 앞에 group)
+    for (idx, e) in inferred.into_iter().rev() {
+        operations.insert(idx, Operator::InferredGroup(e));
+    }
+}
+
 // ──────────────────────────────
 // JOIN
 // join("(" ws* table_reference ("," predicate)? ws* ")")
@@ -107,36 +350,87 @@ fn build_where_or_having(pair: Pair<Rule>) -> PredicateExpr {
 }
 
 // ──────────────────────────────
-// GROUP      → single Expression
+// GROUP / AGG / PROJECT / ORDER  → Vec<Expression>
 // ──────────────────────────────
-fn build_group_clause(pair: Pair<Rule>) -> Expression {
-    let expr_pair = pair
+fn build_multi_expression_clause(pair: Pair<Rule>) -> Vec<Expression> {
+    let mex_pair = pair
         .into_inner()
         .next()
-        .expect("group requires one expression");
-    build_expression(expr_pair)
+        .expect("clause must contain multi_expressions");
+    build_multi_expressions(mex_pair)
 }
 
 // ──────────────────────────────
-// AGG / PROJECT / ORDER  → Vec<Expression>
+// PROJECT     → Vec<Expression> (with * / table.*)
 // ──────────────────────────────
-fn build_multi_expression_clause(pair: Pair<Rule>) -> Vec<Expression> {
-    let mex_pair = pair
+fn build_project_clause(pair: Pair<Rule>) -> Vec<Expression> {
+    let items_pair = pair
         .into_inner()
         .next()
-        .expect("clause must contain multi_expressions");
-    build_multi_expressions(mex_pair)
+        .expect("project must contain project_items");
+
+    items_pair
+        .into_inner()
+        .map(|item| match item.as_rule() {
+            Rule::wildcard => match item.into_inner().next() {
+                Some(except) => Expression::WildcardExcept(WildcardExcept {
+                    table: None,
+                    excluded: build_wildcard_except(except),
+                }),
+                None => Expression::Wildcard,
+            },
+            Rule::table_wildcard => {
+                let mut inner = item.into_inner();
+                let tbl = inner.next().unwrap().as_str().to_owned();
+                match inner.next() {
+                    Some(except) => Expression::WildcardExcept(WildcardExcept {
+                        table: Some(tbl),
+                        excluded: build_wildcard_except(except),
+                    }),
+                    None => Expression::TableWildcard(tbl),
+                }
+            }
+            Rule::expression => build_expression(item),
+            _ => unreachable!("unexpected project item"),
+        })
+        .collect()
+}
+
+// wildcard_except  =  "except" "(" table_field ("," table_field)* ")"
+fn build_wildcard_except(pair: Pair<Rule>) -> Vec<(String, String)> {
+    debug_assert_eq!(pair.as_rule(), Rule::wildcard_except);
+    pair.into_inner()
+        .map(|field| {
+            let mut idents = field.into_inner();
+            let tbl = idents.next().unwrap().as_str().to_owned();
+            let col = idents.next().unwrap().as_str().to_owned();
+            (tbl, col)
+        })
+        .collect()
 }
 
 // ──────────────────────────────
-// LIMIT       → String (number literal)
+// LIMIT / OFFSET  → NumberLiteral | Placeholder
 // ──────────────────────────────
-fn build_limit_clause(pair: Pair<Rule>) -> String {
-    pair.into_inner()
+fn build_limit_clause(pair: Pair<Rule>) -> Expression {
+    let inner = pair
+        .into_inner()
         .next()
-        .expect("limit requires number_literal")
-        .as_str()
-        .to_owned()
+        .expect("limit/offset requires number_literal or placeholder");
+    match inner.as_rule() {
+        Rule::number_literal => Expression::NumberLiteral(inner.as_str().to_owned()),
+        Rule::placeholder => Expression::Placeholder(build_placeholder(inner)),
+        _ => unreachable!("unexpected limit/offset child"),
+    }
+}
+
+// placeholder  =  "$" digits | ":" identifier
+fn build_placeholder(pair: Pair<Rule>) -> Placeholder {
+    let raw = pair.as_str();
+    match raw.strip_prefix('$') {
+        Some(num) => Placeholder::Positional(num.parse().expect("checked by check_placeholders")),
+        None => Placeholder::Named(raw[1..].to_owned()),
+    }
 }
 
 // ──────────────────────────────
@@ -193,12 +487,23 @@ fn build_unary_expr(pair: Pair<Rule>) -> PredicateExpr {
 fn build_condition(pair: Pair<Rule>) -> PredicateExpr {
     let inner = pair.into_inner().next().unwrap();
     match inner.as_rule() {
+        Rule::not_in => PredicateExpr::NotIn(build_not_in(inner)),
         Rule::function_call => PredicateExpr::FuncCall(build_function_call(inner)),
         Rule::boolean_literal => {
             let v = inner.as_str().eq_ignore_ascii_case("true");
             PredicateExpr::BoolLiteral(v)
         }
-        _ => unreachable!("condition expected function_call|boolean_literal"),
+        _ => unreachable!("condition expected not_in|function_call|boolean_literal"),
+    }
+}
+
+// not_in  =  "notIn" "(" expression ("," expression)+ ")"
+fn build_not_in(pair: Pair<Rule>) -> InListExpr {
+    let mut inner = pair.into_inner().filter(|p| p.as_rule() == Rule::expression);
+    let expr = build_expression(inner.next().expect("notIn requires an expression"));
+    InListExpr {
+        expr,
+        values: inner.map(build_expression).collect(),
     }
 }
 
@@ -212,10 +517,56 @@ fn build_multi_expressions(pair: Pair<Rule>) -> Vec<Expression> {
         .collect()
 }
 
+// expression  =  arith_term (add_op arith_term)*
 fn build_expression(pair: Pair<Rule>) -> Expression {
     debug_assert_eq!(pair.as_rule(), Rule::expression);
+    let mut inner = pair.into_inner();
+    let mut expr = build_arith_term(inner.next().unwrap());
+
+    while let Some(op_pair) = inner.next() {
+        let rhs = build_arith_term(inner.next().expect("operator must be followed by term"));
+        expr = Expression::BinaryOp(Box::new(expr), build_arith_op(op_pair), Box::new(rhs));
+    }
+    expr
+}
+
+// arith_term  =  arith_factor (mul_op arith_factor)*
+fn build_arith_term(pair: Pair<Rule>) -> Expression {
+    debug_assert_eq!(pair.as_rule(), Rule::arith_term);
+    let mut inner = pair.into_inner();
+    let mut expr = build_arith_factor(inner.next().unwrap());
+
+    while let Some(op_pair) = inner.next() {
+        let rhs = build_arith_factor(inner.next().expect("operator must be followed by factor"));
+        expr = Expression::BinaryOp(Box::new(expr), build_arith_op(op_pair), Box::new(rhs));
+    }
+    expr
+}
+
+// arith_factor  =  "(" expression ")" | primary
+fn build_arith_factor(pair: Pair<Rule>) -> Expression {
+    match pair.as_rule() {
+        Rule::expression => build_expression(pair),
+        Rule::primary => build_primary(pair),
+        _ => unreachable!("unexpected arith_factor child"),
+    }
+}
+
+fn build_arith_op(pair: Pair<Rule>) -> ArithmeticOp {
+    match pair.as_str() {
+        "+" => ArithmeticOp::Add,
+        "-" => ArithmeticOp::Sub,
+        "*" => ArithmeticOp::Mul,
+        "/" => ArithmeticOp::Div,
+        other => unreachable!("unexpected arithmetic operator {}", other),
+    }
+}
+
+fn build_primary(pair: Pair<Rule>) -> Expression {
+    debug_assert_eq!(pair.as_rule(), Rule::primary);
     let inner = pair.into_inner().next().unwrap();
     match inner.as_rule() {
+        Rule::case_when => Expression::CaseWhen(build_case_when(inner)),
         Rule::function_call => Expression::FunctionCall(build_function_call(inner)),
         Rule::table_field => {
             let mut idents = inner.into_inner();
@@ -229,10 +580,35 @@ fn build_expression(pair: Pair<Rule>) -> Expression {
             Expression::StringLiteral(unquoted.to_owned())
         }
         Rule::number_literal => Expression::NumberLiteral(inner.as_str().to_owned()),
+        Rule::placeholder => Expression::Placeholder(build_placeholder(inner)),
         Rule::boolean_literal => Expression::BoolLiteral(inner.as_str().eq_ignore_ascii_case("true")),
         Rule::null_literal => Expression::NullLiteral,
-        _ => unreachable!("unexpected expression child"),
+        _ => unreachable!("unexpected primary child"),
+    }
+}
+
+// ──────────────────────────────
+// CASE WHEN
+// caseWhen(cond, result (, cond, result)* (, else)?)
+// ──────────────────────────────
+fn build_case_when(pair: Pair<Rule>) -> CaseWhenExpr {
+    let mut branches = Vec::<CaseBranch>::new();
+    let mut else_result = None;
+
+    for child in pair.into_inner() {
+        match child.as_rule() {
+            Rule::case_branch => {
+                let mut inner = child.into_inner();
+                let condition = build_predicate(inner.next().expect("case branch missing condition"));
+                let result = build_expression(inner.next().expect("case branch missing result"));
+                branches.push(CaseBranch { condition, result });
+            }
+            Rule::expression => else_result = Some(Box::new(build_expression(child))),
+            _ => unreachable!("unexpected caseWhen child"),
+        }
     }
+
+    CaseWhenExpr { branches, else_result }
 }
 
 // ──────────────────────────────
@@ -1,7 +1,7 @@
 pub mod openai;
 pub mod prompt;
 
-use crate::openai::{ChatError, ChatRequest, ChatResponse};
+use crate::openai::{ChatError, ChatRequest, ChatResponse, ResponseFormat};
 use reqwest::blocking::Client;
 use std::time::Duration;
 
@@ -9,6 +9,32 @@ pub struct Text2SoftQLOptions {
     pub base_url: String,
     pub model_name: String,
     pub api_key: String,
+    /// `None` sends no stop sequences
+    pub stop: Option<Vec<String>>,
+    pub response_format: Option<ResponseFormat>,
+    /// Custom prompt with `{schema}`, `{context}` and `{query}` placeholders
+    /// `None` uses the built-in prompt
+    pub prompt_template: Option<String>,
+}
+
+/// Stop sequences used when none are configured
+pub fn default_stop_sequences() -> Vec<String> {
+    vec!["\n\n".to_string()]
+}
+
+/// Parse a comma-separated stop list, unescaping `\n` and `\t`
+/// An empty string means no stop sequences
+pub fn parse_stop_sequences(value: &str) -> Option<Vec<String>> {
+    let stop: Vec<String> = value
+        .split(',')
+        .filter(|s| !s.is_empty())
+        .map(|s| s.replace("\\n", "\n").replace("\\t", "\t"))
+        .collect();
+    if stop.is_empty() {
+        None
+    } else {
+        Some(stop)
+    }
 }
 
 pub fn text2softql(
@@ -17,6 +43,17 @@ pub fn text2softql(
     query: String,
     opt: Text2SoftQLOptions,
 ) -> Result<ChatResponse, ChatError> {
+    text2softql_with_prompt(schema, context, query, opt).map(|(resp, _)| resp)
+}
+
+/// Same as `text2softql`, but also returns the exact prompt sent to the model (for audit logging)
+/// In JSON mode the returned prompt includes the appended JSON response instruction
+pub fn text2softql_with_prompt(
+    schema: String,
+    context: String,
+    query: String,
+    opt: Text2SoftQLOptions,
+) -> Result<(ChatResponse, String), ChatError> {
     let url = format!("{}/chat/completions", opt.base_url);
     let client = Client::builder()
         .timeout(Duration::from_secs(30))
@@ -25,10 +62,8 @@ pub fn text2softql(
             hint: e.to_string(),
         })?;
 
-    let prompt = prompt::generate_text2softql_prompt(&schema, &context, &query);
-
-    let mut request = ChatRequest::new(opt.model_name, prompt);
-    request.stop = Some(vec!["\n\n".to_string()]); // 원하는 stop 조건 설정 가능
+    let request = prepare_request(&schema, &context, &query, &opt)?;
+    let prompt = request.messages[0].content.clone();
 
     let resp = client
         .post(url)
@@ -39,7 +74,105 @@ pub fn text2softql(
             hint: e.to_string(),
         })?;
 
-    resp.json::<ChatResponse>().map_err(|e| ChatError {
+    let resp = resp.json::<ChatResponse>().map_err(|e| ChatError {
         hint: e.to_string(),
-    })
+    })?;
+    Ok((resp, prompt))
+}
+
+fn prepare_request(schema: &str, context: &str, query: &str, opt: &Text2SoftQLOptions) -> Result<ChatRequest, ChatError> {
+    let prompt = prompt::generate_text2softql_prompt(schema, context, query, opt.prompt_template.as_deref())
+        .map_err(|e| ChatError {
+            hint: e.to_string(),
+        })?;
+    Ok(build_request(prompt, opt))
+}
+
+fn build_request(mut prompt: String, opt: &Text2SoftQLOptions) -> ChatRequest {
+    if opt.response_format == Some(ResponseFormat::JsonObject) {
+        prompt.push_str(&prompt::generate_json_response_prompt());
+    }
+
+    let mut request = ChatRequest::new(opt.model_name.clone(), prompt);
+    request.stop = opt.stop.clone();
+    request.response_format = opt.response_format;
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openai::{ChatChoice, ChatMessage};
+
+    fn options(stop: Option<Vec<String>>, response_format: Option<ResponseFormat>) -> Text2SoftQLOptions {
+        Text2SoftQLOptions {
+            base_url: "http://localhost".to_string(),
+            model_name: "gpt-4o".to_string(),
+            api_key: String::new(),
+            stop,
+            response_format,
+            prompt_template: None,
+        }
+    }
+
+    fn response(content: &str) -> ChatResponse {
+        ChatResponse {
+            choices: vec![ChatChoice {
+                message: ChatMessage {
+                    role: "assistant".to_string(),
+                    content: content.to_string(),
+                },
+            }],
+            usage: None,
+        }
+    }
+
+    #[test]
+    fn test_configured_stop_list_is_sent() {
+        let request = build_request("q".to_string(), &options(Some(default_stop_sequences()), None));
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["stop"], serde_json::json!(["\n\n"]));
+        assert!(body.get("response_format").is_none());
+
+        let stop = parse_stop_sequences(";,\\n---");
+        assert_eq!(stop, Some(vec![";".to_string(), "\n---".to_string()]));
+        let request = build_request("q".to_string(), &options(stop, None));
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["stop"], serde_json::json!([";", "\n---"]));
+
+        // 빈 문자열이면 stop을 보내지 않는다
+        let request = build_request("q".to_string(), &options(parse_stop_sequences(""), None));
+        assert!(serde_json::to_value(&request).unwrap().get("stop").is_none());
+    }
+
+    #[test]
+    fn test_json_mode_request_and_extraction() {
+        let request = build_request("q".to_string(), &options(None, Some(ResponseFormat::JsonObject)));
+        let body = serde_json::to_value(&request).unwrap();
+        assert_eq!(body["response_format"], serde_json::json!({ "type": "json_object" }));
+        assert!(request.messages[0].content.contains("JSON"));
+
+        // 여러 줄 SoftQL도 그대로 꺼낸다
+        let softql = "users\n.filter(age > 30)\n\n.map(name)";
+        let content = serde_json::json!({ "softql": softql }).to_string();
+        let extracted = response(&content).try_pop_softql_for(Some(ResponseFormat::JsonObject)).unwrap();
+        assert_eq!(extracted, softql);
+
+        // JSON mode가 아니면 content를 그대로 쓴다
+        assert_eq!(response(&content).try_pop_softql_for(None).unwrap(), content);
+        assert!(response("users.map(name)").try_pop_softql_for(Some(ResponseFormat::JsonObject)).is_err());
+    }
+
+    #[test]
+    fn test_sent_prompt_matches_generator() {
+        let schema = "CREATE TABLE users (name text, age int);";
+        let expected = prompt::generate_text2softql_prompt(schema, "age is in years", "users over 30", None).unwrap();
+        let request = prepare_request(schema, "age is in years", "users over 30", &options(None, None)).unwrap();
+        assert_eq!(request.messages[0].content, expected);
+
+        let mut opt = options(None, None);
+        opt.prompt_template = Some("{schema}|{context}|{query}".to_string());
+        let request = prepare_request(schema, "age is in years", "users over 30", &opt).unwrap();
+        assert_eq!(request.messages[0].content, format!("{schema}|age is in years|users over 30"));
+    }
 }
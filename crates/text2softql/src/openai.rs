@@ -22,6 +22,27 @@ pub struct ChatRequest {
     pub temperature: f32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+}
+
+/// `response_format` of the chat completion request
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    /// JSON mode: the model returns `{ "softql": "..." }`
+    JsonObject,
+}
+
+impl ResponseFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "text" => Some(ResponseFormat::Text),
+            "json_object" => Some(ResponseFormat::JsonObject),
+            _ => None,
+        }
+    }
 }
 
 impl ChatRequest {
@@ -35,6 +56,7 @@ impl ChatRequest {
             max_tokens: 500,
             temperature: 0.0,
             stop: None,
+            response_format: None,
         }
     }
 }
@@ -57,6 +79,11 @@ pub struct Usage {
     pub total_tokens: i32,
 }
 
+#[derive(Debug, Deserialize)]
+struct StructuredSoftQL {
+    softql: String,
+}
+
 impl ChatResponse {
     pub fn try_pop_softql(self) -> Result<String, ChatError> {
         self.choices
@@ -67,4 +94,17 @@ impl ChatResponse {
                 hint: "no response choices".to_string(),
             })
     }
+
+    /// Pop the SoftQL, unwrapping `{ "softql": "..." }` when JSON mode was requested
+    pub fn try_pop_softql_for(self, format: Option<ResponseFormat>) -> Result<String, ChatError> {
+        let content = self.try_pop_softql()?;
+        if format != Some(ResponseFormat::JsonObject) {
+            return Ok(content);
+        }
+        serde_json::from_str::<StructuredSoftQL>(&content)
+            .map(|s| s.softql)
+            .map_err(|e| ChatError {
+                hint: format!("invalid structured SoftQL response: {}", e),
+            })
+    }
 }
\ No newline at end of file
@@ -1,3 +1,12 @@
+use thiserror::Error;
+
+/// 사용자 정의 프롬프트 템플릿에 반드시 들어가야 하는 placeholder
+pub const PROMPT_TEMPLATE_PLACEHOLDERS: [&str; 3] = ["{schema}", "{context}", "{query}"];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("prompt template is missing the `{0}` placeholder")]
+pub struct PromptTemplateError(pub &'static str);
+
 /// 질문, SQL 방언, Knowledge 등을 바탕으로
 /// 주석(코멘트) 형태의 안내 문구를 만들어주는 함수
 pub fn generate_comment_prompt(context: &str, query: &str) -> String {
@@ -29,6 +38,13 @@ pub fn generate_instruction_prompt() -> String {
     "#.to_string();
 }
 
+/// JSON mode일 때 응답 형식을 지정하는 문구
+pub fn generate_json_response_prompt() -> String {
+    return r#"
+    Return your answer as a JSON object of the form {"softql": "<SoftQL code>"}.
+    "#.to_string();
+}
+
 /// few-shot 예시 프롬프트 (테이블 스키마 + 예시 질문/답변) 생성
 pub fn generate_few_shot_prompt() -> String {
     // singer, song 두 개의 테이블
@@ -113,18 +129,66 @@ pub fn generate_softql_explanation_prompt() -> String {
 }
 
 
+/// 템플릿에 필요한 placeholder가 모두 있는지 확인
+pub fn validate_prompt_template(template: &str) -> Result<(), PromptTemplateError> {
+    match PROMPT_TEMPLATE_PLACEHOLDERS.iter().find(|p| !template.contains(*p)) {
+        Some(missing) => Err(PromptTemplateError(missing)),
+        None => Ok(()),
+    }
+}
+
+/// 사용자 정의 템플릿의 `{schema}`, `{context}`, `{query}`를 한 번에 치환
+/// (치환된 값 안의 placeholder는 다시 치환하지 않는다)
+pub fn render_prompt_template(
+    template: &str,
+    schema: &str,
+    context: &str,
+    query: &str,
+) -> Result<String, PromptTemplateError> {
+    validate_prompt_template(template)?;
+
+    let mut rendered = String::with_capacity(template.len() + schema.len() + context.len() + query.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let tail = &rest[start..];
+        let value = [("{schema}", schema), ("{context}", context), ("{query}", query)]
+            .into_iter()
+            .find(|(placeholder, _)| tail.starts_with(placeholder));
+        match value {
+            Some((placeholder, value)) => {
+                rendered.push_str(value);
+                rest = &tail[placeholder.len()..];
+            }
+            None => {
+                rendered.push('{');
+                rest = &tail[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    Ok(rendered)
+}
+
+/// text2softql 프롬프트 생성
+/// `template`이 없으면 기본 프롬프트(설명 + few-shot + 지시사항)를 사용
 pub fn generate_text2softql_prompt(
     schema: &str,
     context: &str,
     query: &str,
-) -> String {
+    template: Option<&str>,
+) -> Result<String, PromptTemplateError> {
+    if let Some(template) = template {
+        return render_prompt_template(template, schema, context, query);
+    }
+
     let softql_explanation_prompt = generate_softql_explanation_prompt();
     let few_shot_prompt = generate_few_shot_prompt();
     let comment_prompt = generate_comment_prompt(context, query);
     let cot_prompt = generate_cot_prompt();
     let instruction_prompt = generate_instruction_prompt();
 
-    return format!(
+    Ok(format!(
         r#"{softql_explanation_prompt}
         {few_shot_prompt}
         {schema}
@@ -132,5 +196,35 @@ pub fn generate_text2softql_prompt(
         {cot_prompt}
         {instruction_prompt}
         "#
-    );
-}
\ No newline at end of file
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_template_substitution() {
+        let template = "Schema:\n{schema}\nHints: {context}\nQ: {query}\nReturn {\"softql\": ...}";
+        let prompt = generate_text2softql_prompt("CREATE TABLE t (a int);", "a is {query}", "count rows", Some(template)).unwrap();
+        assert_eq!(
+            prompt,
+            "Schema:\nCREATE TABLE t (a int);\nHints: a is {query}\nQ: count rows\nReturn {\"softql\": ...}"
+        );
+
+        // 템플릿이 없으면 기본 프롬프트
+        let prompt = generate_text2softql_prompt("CREATE TABLE t (a int);", "", "count rows", None).unwrap();
+        assert!(prompt.contains("SoftQL explanation"));
+        assert!(prompt.contains("count rows"));
+    }
+
+    #[test]
+    fn test_template_requires_all_placeholders() {
+        assert_eq!(
+            generate_text2softql_prompt("s", "c", "q", Some("{schema} {query}")),
+            Err(PromptTemplateError("{context}"))
+        );
+        assert_eq!(validate_prompt_template("{query}{context}"), Err(PromptTemplateError("{schema}")));
+        assert!(validate_prompt_template("{schema}{context}{query}").is_ok());
+    }
+}
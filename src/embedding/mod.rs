@@ -2,19 +2,28 @@ use crate::datatype::memory_vecf32::Vecf32Output;
 use crate::gucs::model::{
     openai_embedding_options,
     embedding_backend_options,
+    embedding_input_prefixes,
+    EMBEDDING_BATCH_CHUNK_SIZE,
 };
+use crate::safeql::SubTransactionGuard;
 use base::vector::*;
-use embedding::{embed, embed_batch, BackendOptions};
+use embedding::{embed, embed_batch_iter, embed_batch_partial_iter, embed_prefixed, BackendOptions, InputKind};
 use pgrx::error;
-use pgrx::iter::SetOfIterator;
+use pgrx::iter::{SetOfIterator, TableIterator};
+use pgrx::pg_sys::panic::CaughtError;
+use pgrx::{name, pg_sys, PgTryBuilder};
+use std::time::Instant;
 
 
 #[pgrx::pg_extern(volatile, strict, parallel_safe)]
 fn _vectors_text2vec(input: String) -> Vecf32Output {
     let backend = embedding_backend_options();
 
-    let embedding_vec = embed(
+    // 단건 embedding은 refinement search의 검색어이므로 query prefix를 붙인다
+    let embedding_vec = embed_prefixed(
         input,
+        InputKind::Query,
+        &embedding_input_prefixes(),
         backend
     ).unwrap_or_else(|e| error!("{}", e.to_string()));
 
@@ -26,17 +35,92 @@ fn _vectors_text2vec_array(
     inputs: Vec<String>
 ) -> SetOfIterator<'static, Vecf32Output> {
     let backend = embedding_backend_options();
+    let inputs = with_doc_prefix(inputs);
 
-    let embeddings = embed_batch(inputs, backend)
-        .unwrap_or_else(|e| error!("{}", e.to_string()));
-
+    // chunk 단위로 embed하면서 바로 반환하므로 전체 결과를 한꺼번에 들고 있지 않는다
     SetOfIterator::new(
-        embeddings
-            .into_iter()
+        embed_batch_iter(inputs, backend, batch_chunk_size())
+            .map(|result| result.unwrap_or_else(|e| error!("{}", e.to_string())))
             .map(|vec| Vecf32Output::new(VectBorrowed::new(&vec)))
     )
 }
 
+/// `_vectors_text2vec_array`와 같지만, 실패한 입력은 전체를 중단하지 않고 NULL로 반환
+#[pgrx::pg_extern(volatile, strict, parallel_safe)]
+fn _vectors_text2vec_array_partial(
+    inputs: Vec<String>
+) -> SetOfIterator<'static, Option<Vecf32Output>> {
+    let backend = embedding_backend_options();
+    let inputs = with_doc_prefix(inputs);
+
+    SetOfIterator::new(
+        embed_batch_partial_iter(inputs, backend, batch_chunk_size())
+            .enumerate()
+            .map(|(i, result)| match result {
+                Ok(vec) => Some(Vecf32Output::new(VectBorrowed::new(&vec))),
+                Err(e) => {
+                    pgrx::warning!("embedding failed for input {}: {}", i + 1, e);
+                    None
+                }
+            })
+    )
+}
+
+/// 배열 embedding은 pg_vector_* 카탈로그에 저장할 이름/설명이므로 doc prefix를 붙인다
+fn with_doc_prefix(inputs: Vec<String>) -> Vec<String> {
+    let prefixes = embedding_input_prefixes();
+    inputs.into_iter().map(|input| prefixes.apply(InputKind::Document, input)).collect()
+}
+
+fn batch_chunk_size() -> usize {
+    EMBEDDING_BATCH_CHUNK_SIZE.get().max(1) as usize
+}
+
+/// health check에서 embed하는 고정 문장
+const HEALTHCHECK_SENTINEL: &str = "SafeQL embedding backend health check";
+
+/// 설정된 embedding backend로 sentinel 문장을 embed해서 (ok, 차원, 지연 시간 ms, 에러) 반환
+/// 실패해도 error!로 중단하지 않고 에러 메시지를 돌려준다
+#[pgrx::pg_extern(volatile, parallel_safe)]
+fn _safeql_embedding_healthcheck() -> TableIterator<'static, (
+    name!(ok, bool),
+    name!(dim, Option<i32>),
+    name!(latency_ms, i32),
+    name!(error, Option<String>),
+)> {
+    TableIterator::once(embedding_healthcheck())
+}
+
+fn embedding_healthcheck() -> (bool, Option<i32>, i32, Option<String>) {
+    let started = Instant::now();
+    // GUC 설정 오류(bad_guc_literal)나 backend panic도 결과로 돌려준다
+    // ERROR를 잡은 뒤에도 트랜잭션을 계속 쓸 수 있도록 subtransaction 안에서 probe하고 항상 롤백한다
+    let result = unsafe {
+        let subxact = SubTransactionGuard::begin();
+        let old_context = subxact.old_context;
+
+        let result = PgTryBuilder::new(|| {
+            embed(HEALTHCHECK_SENTINEL.to_string(), embedding_backend_options()).map_err(|e| e.to_string())
+        })
+        .catch_others(|e| {
+            pg_sys::MemoryContextSwitchTo(old_context);
+            match e {
+                CaughtError::PostgresError(report) | CaughtError::ErrorReport(report) => Err(report.message().to_string()),
+                CaughtError::RustPanic { ereport, .. } => Err(ereport.message().to_string()),
+            }
+        })
+        .execute();
+
+        subxact.rollback();
+        result
+    };
+    let latency_ms = started.elapsed().as_millis().min(i32::MAX as u128) as i32;
+
+    match result {
+        Ok(vec) => (true, Some(vec.len() as i32), latency_ms, None),
+        Err(e) => (false, None, latency_ms, Some(e)),
+    }
+}
 
 #[pgrx::pg_extern(volatile, strict, parallel_safe)]
 fn _vectors_text2vec_openai(input: String, model: String) -> Vecf32Output {
@@ -54,4 +138,31 @@ fn _vectors_text2vec_openai(input: String, model: String) -> Vecf32Output {
     };
 
     Vecf32Output::new(VectBorrowed::new(&embedding_vec))
-}
\ No newline at end of file
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use super::*;
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_embedding_healthcheck() {
+        Spi::run("SET vectors.embedding_backend = 'fastembed'").unwrap();
+        Spi::run("SET vectors.embedding_model_name = 'Xenova/bge-base-en-v1.5'").unwrap();
+        let (ok, dim, latency_ms, error) = embedding_healthcheck();
+        assert!(ok, "{:?}", error);
+        assert_eq!(dim, Some(768));
+        assert!(latency_ms >= 0);
+
+        // 잘못된 backend 설정은 중단 없이 ok=false로 보고한다
+        Spi::run("SET vectors.embedding_backend = 'fastembed_local'").unwrap();
+        Spi::run("SET vectors.embedding_model_path = '/nonexistent/model.onnx'").unwrap();
+        let (ok, dim, _, error) = embedding_healthcheck();
+        assert!(!ok);
+        assert_eq!(dim, None);
+        assert!(error.is_some());
+        // 실패한 probe 뒤에도 트랜잭션은 그대로 쓸 수 있다
+        assert_eq!(Spi::get_one::<i32>("SELECT 1").unwrap(), Some(1));
+    }
+}
@@ -1,6 +1,9 @@
 use embedding::openai::OpenAIOptions;
-use embedding::BackendOptions;
-use text2softql::Text2SoftQLOptions;
+use embedding::{BackendOptions, InputPrefixes};
+use embedding::voyage::VoyageInputType;
+use text2softql::openai::ResponseFormat;
+use text2softql::prompt::validate_prompt_template;
+use text2softql::{default_stop_sequences, parse_stop_sequences, Text2SoftQLOptions};
 use pgrx::guc::{GucContext, GucFlags, GucRegistry, GucSetting};
 use std::ffi::CStr;
 use crate::error::*;
@@ -30,6 +33,31 @@ pub fn embedding_backend_options() -> BackendOptions {
                 gpu_device_id,  // GPU ID 추가
             }
         }
+        "fastembed_local" => {
+            BackendOptions::FastEmbedLocal {
+                model_path: parse(&EMBEDDING_MODEL_PATH, "vectors.embedding_model_path").into(),
+                tokenizer_path: parse(&EMBEDDING_TOKENIZER_PATH, "vectors.embedding_tokenizer_path").into(),
+                dimension: EMBEDDING_DIMENSION.get() as usize,
+                gpu_device_id: EMBEDDING_GPU_DEVICE_ID.get(),
+            }
+        }
+        "voyage" => {
+            let input_type = VOYAGE_INPUT_TYPE
+                .get()
+                .and_then(|s| s.to_str().ok())
+                .filter(|s| !s.is_empty())
+                .map(|s| {
+                    VoyageInputType::parse(s).unwrap_or_else(|| {
+                        bad_guc_literal("vectors.voyage_input_type", "should be `query`, `document` or empty")
+                    })
+                });
+            BackendOptions::Voyage {
+                base_url: parse(&VOYAGE_BASE_URL, "vectors.voyage_base_url"),
+                api_key: parse(&VOYAGE_API_KEY, "vectors.voyage_api_key"),
+                model: model_name,
+                input_type,
+            }
+        }
         _ => {
             BackendOptions::OpenAI {
                 base_url: parse(&OPENAI_BASE_URL, "vectors.openai_base_url"),
@@ -40,6 +68,21 @@ pub fn embedding_backend_options() -> BackendOptions {
     }
 }
 
+/// 비대칭 임베딩 모델용 입력 prefix (미설정이거나 빈 문자열이면 붙이지 않음)
+pub fn embedding_input_prefixes() -> InputPrefixes {
+    let prefix = |target: &'static GucSetting<Option<&'static CStr>>| {
+        target
+            .get()
+            .and_then(|s| s.to_str().ok())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+    };
+    InputPrefixes {
+        query: prefix(&EMBEDDING_QUERY_PREFIX),
+        doc: prefix(&EMBEDDING_DOC_PREFIX),
+    }
+}
+
 pub fn openai_embedding_options() -> OpenAIOptions {
     let base_url = parse(&OPENAI_BASE_URL, "vectors.openai_base_url");
     let api_key = parse(&OPENAI_API_KEY, "vectors.openai_api_key");
@@ -50,7 +93,30 @@ pub fn text2softql_options() -> Text2SoftQLOptions {
     let base_url = parse(&TEXT_TO_SOFTQL_MODEL_URL, "vectors.text2softql_model_url");
     let model_name = parse(&TEXT_TO_SOFTQL_MODEL_NAME, "vectors.text2softql_model_name");
     let api_key = parse(&OPENAI_API_KEY, "vectors.openai_api_key");
-    Text2SoftQLOptions { base_url, model_name, api_key }
+    let stop = match TEXT_TO_SOFTQL_STOP.get().and_then(|s| s.to_str().ok()) {
+        Some(s) => parse_stop_sequences(s),
+        None => Some(default_stop_sequences()),
+    };
+    let response_format = TEXT_TO_SOFTQL_RESPONSE_FORMAT
+        .get()
+        .and_then(|s| s.to_str().ok())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            ResponseFormat::parse(s).unwrap_or_else(|| {
+                bad_guc_literal("vectors.text2softql_response_format", "should be `text`, `json_object` or empty")
+            })
+        });
+    let prompt_template = TEXT_TO_SOFTQL_PROMPT_TEMPLATE
+        .get()
+        .and_then(|s| s.to_str().ok())
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            if let Err(e) = validate_prompt_template(s) {
+                bad_guc_literal("vectors.text2softql_prompt_template", &e.to_string())
+            }
+            s.to_string()
+        });
+    Text2SoftQLOptions { base_url, model_name, api_key, stop, response_format, prompt_template }
 }
 
 static OPENAI_API_KEY: GucSetting<Option<&'static CStr>> =
@@ -59,26 +125,67 @@ static OPENAI_API_KEY: GucSetting<Option<&'static CStr>> =
 static OPENAI_BASE_URL: GucSetting<Option<&'static CStr>> =
     GucSetting::<Option<&'static CStr>>::new(Some(c"https://api.openai.com/v1"));
 
+static VOYAGE_API_KEY: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+static VOYAGE_BASE_URL: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(Some(c"https://api.voyageai.com/v1"));
+
+static VOYAGE_INPUT_TYPE: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
 static EMBEDDING_BACKEND: GucSetting<Option<&'static CStr>> =
     GucSetting::<Option<&'static CStr>>::new(Some(c"fastembed"));
 
 static EMBEDDING_MODEL_NAME: GucSetting<Option<&'static CStr>> =
     GucSetting::<Option<&'static CStr>>::new(Some(c"Xenova/bge-base-en-v1.5"));
 
+static EMBEDDING_MODEL_PATH: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+static EMBEDDING_TOKENIZER_PATH: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+static EMBEDDING_QUERY_PREFIX: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+static EMBEDDING_DOC_PREFIX: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+static EMBEDDING_DIMENSION: GucSetting<i32> = GucSetting::<i32>::new(768);
+
 static EMBEDDING_GPU_DEVICE_ID: GucSetting<i32> =
     GucSetting::<i32>::new(0);  // -1 = CPU, 0~3 = GPU ID
 
 pub static VECTOR_EMBEDDING_BATCH_SIZE: GucSetting<i32> = GucSetting::<i32>::new(512);
 
+pub static EMBEDDING_BATCH_CHUNK_SIZE: GucSetting<i32> = GucSetting::<i32>::new(1024);
+
 pub static ENABLE_SEARCH_CACHE: GucSetting<bool> =
     GucSetting::<bool>::new(true);  // 기본값: true (캐시 활성화)
 
+pub static ENABLE_PERSISTENT_SEARCH_CACHE: GucSetting<bool> =
+    GucSetting::<bool>::new(false);  // 기본값: false (공유 메모리만 사용)
+
+pub static SEARCH_CACHE_NEGATIVE_TTL: GucSetting<i32> =
+    GucSetting::<i32>::new(300);  // 기본값: 5분 (후보가 없는 결과의 캐시 유지 시간, 초)
+
 static TEXT_TO_SOFTQL_MODEL_URL: GucSetting<Option<&'static CStr>> =
     GucSetting::<Option<&'static CStr>>::new(Some(c"https://api.openai.com/v1"));
 
 static TEXT_TO_SOFTQL_MODEL_NAME: GucSetting<Option<&'static CStr>> =
     GucSetting::<Option<&'static CStr>>::new(Some(c"gpt-4o"));
 
+// 미설정이면 기본 stop("\n\n"), 빈 문자열이면 stop 없음
+static TEXT_TO_SOFTQL_STOP: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+static TEXT_TO_SOFTQL_RESPONSE_FORMAT: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+static TEXT_TO_SOFTQL_PROMPT_TEMPLATE: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
 
 pub unsafe fn init() {
     GucRegistry::define_string_guc(
@@ -97,6 +204,30 @@ pub unsafe fn init() {
         GucContext::Userset,
         GucFlags::default(),
     );
+    GucRegistry::define_string_guc(
+        "vectors.voyage_api_key",
+        "The API key of Voyage AI.",
+        "",
+        &VOYAGE_API_KEY,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "vectors.voyage_base_url",
+        "The base url of Voyage AI.",
+        "",
+        &VOYAGE_BASE_URL,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "vectors.voyage_input_type",
+        "The Voyage AI input type (`query`, `document` or empty).",
+        "",
+        &VOYAGE_INPUT_TYPE,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
     GucRegistry::define_string_guc(
         "vectors.embedding_backend",
         "The model backend for embedding.",
@@ -113,6 +244,48 @@ pub unsafe fn init() {
         GucContext::Userset,
         GucFlags::default(),
     );
+    GucRegistry::define_string_guc(
+        "vectors.embedding_model_path",
+        "Path to a local ONNX model file (used by the `fastembed_local` backend).",
+        "",
+        &EMBEDDING_MODEL_PATH,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "vectors.embedding_tokenizer_path",
+        "Directory holding the tokenizer files of a local model (used by the `fastembed_local` backend).",
+        "Must contain tokenizer.json, config.json, special_tokens_map.json and tokenizer_config.json.",
+        &EMBEDDING_TOKENIZER_PATH,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "vectors.embedding_query_prefix",
+        "Prefix prepended to search terms before embedding (e.g. `query: `).",
+        "Used by _vectors_text2vec, which embeds refinement search terms. Unset or empty adds no prefix.",
+        &EMBEDDING_QUERY_PREFIX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "vectors.embedding_doc_prefix",
+        "Prefix prepended to stored names and descriptions before embedding (e.g. `passage: `).",
+        "Used by _vectors_text2vec_array, which embeds the pg_vector_* catalogs. Unset or empty adds no prefix.",
+        &EMBEDDING_DOC_PREFIX,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_int_guc(
+        "vectors.embedding_dimension",
+        "Expected embedding dimension of a local model (used by the `fastembed_local` backend).",
+        "",
+        &EMBEDDING_DIMENSION,
+        1,
+        65535,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
     GucRegistry::define_int_guc(
         "vectors.embedding_gpu_device_id",
         "GPU device ID for embedding (-1 for CPU, 0-3 for GPU).",
@@ -133,6 +306,16 @@ pub unsafe fn init() {
         GucContext::Userset,
         GucFlags::default(),
     );
+    GucRegistry::define_int_guc(
+        "vectors.embedding_batch_chunk_size",
+        "Maximum number of texts sent to the embedding backend in one call by text2vec_array",
+        "Larger arrays are split into chunks of this size, embedded one chunk at a time and concatenated in input order.",
+        &EMBEDDING_BATCH_CHUNK_SIZE,
+        1,          // min value
+        65536,      // max value
+        GucContext::Userset,
+        GucFlags::default(),
+    );
     GucRegistry::define_bool_guc(
         "vectors.enable_search_cache",
         "Enable or disable search caching.",
@@ -141,6 +324,24 @@ pub unsafe fn init() {
         GucContext::Userset,
         GucFlags::default(),
     );
+    GucRegistry::define_bool_guc(
+        "vectors.search_cache_persistent",
+        "Back the search cache with the pg_vector_search_cache table.",
+        "When enabled, cache entries are also upserted into pg_vector_search_cache so they survive restarts and are shared by every backend.",
+        &ENABLE_PERSISTENT_SEARCH_CACHE,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_int_guc(
+        "vectors.search_cache_negative_ttl",
+        "Seconds an empty (no candidate) search result stays cached.",
+        "Negative entries expire sooner than regular ones so that a later schema or embedding change is re-checked. 0 disables caching of empty results.",
+        &SEARCH_CACHE_NEGATIVE_TTL,
+        0,              // min value
+        7 * 24 * 3600,  // max value (일반 엔트리 정리 주기와 동일)
+        GucContext::Userset,
+        GucFlags::default(),
+    );
     GucRegistry::define_string_guc(
         "vectors.text2softql_model_url",
         "The url for the text2softql model.",
@@ -157,4 +358,28 @@ pub unsafe fn init() {
         GucContext::Userset,
         GucFlags::default(),
     );
+    GucRegistry::define_string_guc(
+        "vectors.text2softql_stop",
+        "Comma-separated stop sequences for text2softql.",
+        "`\\n` and `\\t` are unescaped. Unset uses a blank line as the stop sequence; an empty string sends none.",
+        &TEXT_TO_SOFTQL_STOP,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "vectors.text2softql_response_format",
+        "The response format for text2softql: `text` or `json_object`.",
+        "With `json_object` the model is asked for `{\"softql\": \"...\"}` and the SoftQL is extracted from it.",
+        &TEXT_TO_SOFTQL_RESPONSE_FORMAT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+    GucRegistry::define_string_guc(
+        "vectors.text2softql_prompt_template",
+        "A custom prompt template for text2softql.",
+        "Must contain the `{schema}`, `{context}` and `{query}` placeholders. Unset or empty uses the built-in prompt.",
+        &TEXT_TO_SOFTQL_PROMPT_TEMPLATE,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
 }
\ No newline at end of file
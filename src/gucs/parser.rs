@@ -1,6 +1,7 @@
 // guc.rs - GUC parameter 설정
 use pgrx::guc::{GucContext, GucFlags, GucRegistry, GucSetting};
 use pgrx::PostgresGucEnum;
+use std::ffi::CStr;
 
 #[derive(Debug, Clone, Copy, PostgresGucEnum)]
 #[allow(non_camel_case_types)]
@@ -11,13 +12,44 @@ pub enum BindMode {
 
 pub static BIND_MODE: GucSetting<BindMode> = GucSetting::<BindMode>::new(BindMode::soft);
 
+/// refined ColumnRef의 qualification 방식
+/// preserve: 원래 형태 유지, qualified: 테이블/alias를 붙임, unqualified: 컬럼명만 남김
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PostgresGucEnum)]
+#[allow(non_camel_case_types)]
+pub enum ColumnQualification {
+    preserve,
+    qualified,
+    unqualified,
+}
+
+pub static COLUMN_QUALIFICATION: GucSetting<ColumnQualification> =
+    GucSetting::<ColumnQualification>::new(ColumnQualification::preserve);
+
+/// refinement가 식별자를 비교할 때 쓰는 folding 방식
+/// lower: ASCII만 소문자로 (PostgreSQL unquoted 식별자 규칙), preserve: 그대로 비교 (quoted 식별자를 대소문자까지 구분), unicode_casefold: 유니코드 소문자로
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PostgresGucEnum)]
+#[allow(non_camel_case_types)]
+pub enum IdentifierFolding {
+    lower,
+    preserve,
+    unicode_casefold,
+}
+
+pub static IDENTIFIER_FOLDING: GucSetting<IdentifierFolding> =
+    GucSetting::<IdentifierFolding>::new(IdentifierFolding::lower);
+
 // SafeQL refinement 활성화 옵션 GUC 변수들
 pub static ENABLE_SAFEQL_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
+// 구조를 바꾸는 refinement(JOIN 추가, 빈 FROM에 테이블 추가, LATERAL 추가)를 막고 이름 교체만 허용
+pub static SAFEQL_CONSERVATIVE: GucSetting<bool> = GucSetting::<bool>::new(false);
+// analyze를 통과한 후보를 SPI로 실행해 실행 에러/빈 결과까지 검증할지 여부
+pub static ENABLE_EXECUTION_VALIDATION: GucSetting<bool> = GucSetting::<bool>::new(true);
 pub static ENABLE_TABLE_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
 pub static ENABLE_COLUMN_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
 pub static ENABLE_TABLE_FOR_COLUMN: GucSetting<bool> = GucSetting::<bool>::new(true);
 pub static ENABLE_COLUMN_TABLE_REFERENCE: GucSetting<bool> = GucSetting::<bool>::new(true);
 pub static ENABLE_JOIN_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
+pub static JOIN_REQUIRE_FK: GucSetting<bool> = GucSetting::<bool>::new(false);
 pub static ENABLE_OPERAND_COLUMN_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
 pub static ENABLE_OPERAND_TABLE_FOR_COLUMN_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
 pub static ENABLE_OPERAND_COLUMN_TABLE_REFERENCE_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
@@ -29,6 +61,15 @@ pub static ENABLE_ARGUMENT_TYPECAST_REFINEMENT: GucSetting<bool> = GucSetting::<
 pub static ENABLE_FUNCTION_NAME_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
 pub static ENABLE_COLUMN_AMBIGUITY_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
 pub static ENABLE_VALUE_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
+pub static ENABLE_NULLIF_GUARD_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
+pub static ENABLE_JSONB_OPERATOR_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
+pub static ENABLE_NULL_HANDLING_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(false);
+pub static ENABLE_SRF_LATERAL_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
+pub static ENABLE_WINDOW_OVER_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
+pub static ENABLE_FUNCTION_ARITY_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
+pub static ENABLE_ORDER_BY_TARGET_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
+pub static ENABLE_ORDINAL_POSITION_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
+pub static ENABLE_CASE_BRANCH_CAST_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
 
 // SafeQL search 최적화 옵션 GUC 변수들
 pub static ENABLE_TYPE_BASED_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::new(true);
@@ -36,11 +77,100 @@ pub static ENABLE_TYPE_BASED_REFINEMENT: GucSetting<bool> = GucSetting::<bool>::
 // SafeQL search limits
 pub static MAX_REFINEMENT_HOP: GucSetting<i32> = GucSetting::<i32>::new(5);
 pub static MAX_REFINEMENT_NUM: GucSetting<i32> = GucSetting::<i32>::new(300);
+// JOIN 추가 refinement를 멈추는 FROM절 테이블 수 (hop을 거듭하며 JOIN이 끝없이 커지는 것을 막는다)
+pub static MAX_FROM_TABLES: GucSetting<i32> = GucSetting::<i32>::new(6);
 pub static VALUE_REFINEMENT_SAMPLES: GucSetting<i32> = GucSetting::<i32>::new(1000000);
+pub static MAX_CANDIDATE_SQL_LEN: GucSetting<i32> = GucSetting::<i32>::new(0);
+pub static CANDIDATE_STATEMENT_TIMEOUT: GucSetting<i32> = GucSetting::<i32>::new(0);
+// refinement search 한 번에 허용하는 검색어 임베딩 계산 횟수 (0이면 무제한)
+pub static MAX_EMBEDDING_CALLS: GucSetting<i32> = GucSetting::<i32>::new(0);
+// refined 후보의 결과 행 수가 원본 FROM절 행 수와 크게 다를수록 priority를 뒤로 미루는 가중치 (0이면 끔)
+pub static CARDINALITY_CHANGE_PENALTY: GucSetting<f64> = GucSetting::<f64>::new(0.0);
+pub static ENABLE_SEARCH_SUMMARY_HINT: GucSetting<bool> = GucSetting::<bool>::new(false);
+pub static ENABLE_DEPARSE_ROUND_TRIP_CHECK: GucSetting<bool> = GucSetting::<bool>::new(false);
+pub static ENABLE_REFINEMENT_DIFF_NOTICE: GucSetting<bool> = GucSetting::<bool>::new(false);
+pub static ENABLE_REFINED_PLAN_NOTICE: GucSetting<bool> = GucSetting::<bool>::new(false);
+
+// value refinement 대상 비교 연산자 (쉼표 구분, 기본값 "=")
+pub static VALUE_REFINEMENT_OPERATORS: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(Some(c"="));
+
+// numeric value refinement에서 literal을 맞출 단위 (예: 1이면 정수, 10이면 10 단위, 0이면 numeric refinement 끔)
+pub static VALUE_REFINEMENT_NUMERIC_STEP: GucSetting<f64> = GucSetting::<f64>::new(0.0);
+
+/// value refinement에서 허용하는 비교 연산자 (가장 가까운 값으로 바꿔도 의미가 통하는 것들)
+pub const VALUE_REFINEMENT_SUPPORTED_OPERATORS: [&str; 6] = ["=", "<>", ">", "<", ">=", "<="];
+
+// refinement case 사용 목록과 시도 순서 (쉼표 구분 case 이름, 비어 있으면 모든 case를 기본 순서로)
+pub static REFINEMENT_CASE_ORDER: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+/// safeql.refinement_case_order에 쓸 수 있는 case 이름
+pub const REFINEMENT_CASES: [&str; 26] = [
+    "missing_from_join",
+    "table",
+    "column",
+    "table_for_column",
+    "column_table_reference",
+    "join",
+    "argument_column",
+    "argument_typecast",
+    "function_name",
+    "operand_column",
+    "operand_table_for_column",
+    "operand_column_table_reference",
+    "operand_typecast",
+    "column_ambiguity",
+    "argument_format",
+    "nullif_guard",
+    "value",
+    "jsonb_operator",
+    "join_add",
+    "null_handling",
+    "srf_lateral",
+    "window_over",
+    "function_arity",
+    "order_by_target",
+    "ordinal_position",
+    "case_branch_cast",
+];
+
+// SafeQL이 refinement를 시도할 에러 코드 목록 (쉼표 구분, 비어 있으면 모든 에러)
+pub static REFINEMENT_ENABLED_ERRORS: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+/// safeql.refinement_enabled_errors에 쓸 수 있는 에러 이름 (PostgreSQL condition name)
+pub const REFINEMENT_ERRORS: [&str; 11] = [
+    "undefined_table",
+    "undefined_column",
+    "undefined_function",
+    "ambiguous_column",
+    "grouping",
+    "invalid_column_reference",
+    "wrong_object_type",
+    "feature_not_supported",
+    "invalid_text_representation",
+    "division_by_zero",
+    "datatype_mismatch",
+];
+
+// SafeQL 후보 검색 스키마 범위 (비어 있으면 current_schemas(false))
+pub static REFINEMENT_SCHEMAS: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
+
+// 마지막으로 refinement된 SQL (SafeQL이 설정하고 클라이언트가 SHOW로 읽음)
+pub static LAST_REFINED_SQL: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(None);
 
 
 // SafeQL top k search 설정
 pub static TOP_K_EXPANSION: GucSetting<i32> = GucSetting::<i32>::new(3);
+// 최소 유사도 기준: embedding distance가 이 값보다 큰 후보는 버린다 (2.0이면 cosine distance 전체 허용)
+pub static MAX_CANDIDATE_DISTANCE: GucSetting<f64> = GucSetting::<f64>::new(2.0);
+// pg_stats(null_frac, n_distinct) 반영 가중치: NULL이 많거나 cardinality가 낮은 컬럼 후보를 뒤로 미룬다 (0이면 끔)
+pub static COLUMN_STATISTICS_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(0.0);
+// 테이블이 지정되지 않은 column refinement에서 FROM절에 이미 있는 테이블의 컬럼 후보 distance를 이만큼 줄인다 (0이면 끔)
+pub static SAME_TABLE_COLUMN_BONUS: GucSetting<f64> = GucSetting::<f64>::new(0.0);
 
 // SafeQL refinement priority 가중치 GUC 변수들
 pub static TABLE_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
@@ -54,7 +184,80 @@ pub static TYPECAST_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(
 pub static FUNCTION_NAME_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
 pub static COLUMN_AMBIGUITY_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(0.0);
 pub static VALUE_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static NULLIF_GUARD_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static JSONB_OPERATOR_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static NULL_HANDLING_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static SRF_LATERAL_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static WINDOW_OVER_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static FUNCTION_ARITY_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static ORDER_BY_TARGET_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static ORDINAL_POSITION_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+pub static CASE_BRANCH_CAST_REFINEMENT_WEIGHT: GucSetting<f64> = GucSetting::<f64>::new(1.0);
+
+
+/// safeql.refinement_schemas를 쉼표 기준으로 나눈 스키마 목록 (미설정 시 빈 목록)
+pub fn refinement_schemas() -> Vec<String> {
+    REFINEMENT_SCHEMAS
+        .get()
+        .and_then(|s| s.to_str().ok())
+        .map(|s| {
+            s.split(',')
+                .map(|schema| schema.trim())
+                .filter(|schema| !schema.is_empty())
+                .map(|schema| schema.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// safeql.value_refinement_operators를 파싱 (지원하지 않는 연산자는 무시, "!="는 "<>"로 취급)
+pub fn value_refinement_operators() -> Vec<String> {
+    VALUE_REFINEMENT_OPERATORS
+        .get()
+        .and_then(|s| s.to_str().ok())
+        .map(|s| {
+            s.split(',')
+                .map(|op| if op.trim() == "!=" { "<>" } else { op.trim() })
+                .filter(|op| VALUE_REFINEMENT_SUPPORTED_OPERATORS.contains(op))
+                .map(|op| op.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
 
+/// safeql.refinement_enabled_errors를 쉼표 기준으로 나눈 에러 이름 목록 (모르는 이름은 버린다, 미설정 시 None)
+pub fn refinement_enabled_errors() -> Option<Vec<String>> {
+    let setting = REFINEMENT_ENABLED_ERRORS.get()?;
+    let setting = setting.to_str().ok()?;
+    if setting.trim().is_empty() {
+        return None;
+    }
+
+    Some(
+        setting
+            .split(',')
+            .map(|error| error.trim().to_ascii_lowercase())
+            .filter(|error| REFINEMENT_ERRORS.contains(&error.as_str()))
+            .collect()
+    )
+}
+
+/// safeql.refinement_case_order를 쉼표 기준으로 나눈 case 목록 (모르는 이름과 중복은 버린다, 미설정 시 None)
+pub fn refinement_case_order() -> Option<Vec<String>> {
+    let setting = REFINEMENT_CASE_ORDER.get()?;
+    let setting = setting.to_str().ok()?;
+    if setting.trim().is_empty() {
+        return None;
+    }
+
+    let mut cases: Vec<String> = Vec::new();
+    for case in setting.split(',').map(|case| case.trim().to_ascii_lowercase()) {
+        if REFINEMENT_CASES.contains(&case.as_str()) && !cases.contains(&case) {
+            cases.push(case);
+        }
+    }
+    Some(cases)
+}
 
 pub unsafe fn init() {
     GucRegistry::define_enum_guc(
@@ -66,6 +269,24 @@ pub unsafe fn init() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_enum_guc(
+        "safeql.column_qualification",
+        "Qualification style for refined column references.",
+        "preserve keeps the original form, qualified prefixes the table or alias, unqualified drops the qualifier.",
+        &COLUMN_QUALIFICATION,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_enum_guc(
+        "safeql.identifier_folding",
+        "How SafeQL folds identifiers before comparing them: lower, preserve or unicode_casefold",
+        "lower folds ASCII letters only, like unquoted identifiers. preserve compares identifiers exactly, so only the quoted mixed-case identifier named in an error message is replaced. unicode_casefold also folds non-ASCII letters.",
+        &IDENTIFIER_FOLDING,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
     // SafeQL refinement 활성화 옵션 설정
     GucRegistry::define_bool_guc(
         "safeql.enable_refinement",
@@ -76,6 +297,24 @@ pub unsafe fn init() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_bool_guc(
+        "safeql.conservative",
+        "Restrict SafeQL to in-place renames",
+        "When enabled, refinements that add tables or joins to the FROM clause are never generated, so the row count of the original query shape is preserved. Default is false.",
+        &SAFEQL_CONSERVATIVE,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_execution_validation",
+        "Execute analyze-valid candidates to validate them",
+        "When disabled, the first candidate that passes parse analysis is returned without running it, so execution errors and empty results no longer trigger refinements. Default is true.",
+        &ENABLE_EXECUTION_VALIDATION,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
     GucRegistry::define_bool_guc(
         "safeql.enable_table_refinement",
         "Enable table name refinement (CASE 1: FROM Rel -> FROM Rel')",
@@ -121,6 +360,15 @@ pub unsafe fn init() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_bool_guc(
+        "safeql.join_require_fk",
+        "Restrict join refinement to declared PK-FK relationships",
+        "When enabled, tables sharing only a same-named column are not suggested as join candidates. Default is false.",
+        &JOIN_REQUIRE_FK,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
     GucRegistry::define_bool_guc(
         "safeql.enable_operand_column_refinement",
         "Enable operand replacement refinement (CASE 6: Expr -> Expr')",
@@ -230,6 +478,125 @@ pub unsafe fn init() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_string_guc(
+        "safeql.value_refinement_operators",
+        "Comma-separated comparison operators considered by value refinement",
+        "Supported operators are =, <>, !=, >, <, >= and <=. Others are ignored. Default is =.",
+        &VALUE_REFINEMENT_OPERATORS,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        "safeql.refinement_case_order",
+        "Comma-separated refinement cases to try, in order",
+        "When set, only the listed cases run (each still needs its enable_* setting) and earlier cases are preferred over later ones. Known cases: missing_from_join, table, column, table_for_column, column_table_reference, join, argument_column, argument_typecast, function_name, operand_column, operand_table_for_column, operand_column_table_reference, operand_typecast, column_ambiguity, argument_format, nullif_guard, value, jsonb_operator, join_add, null_handling, srf_lateral, window_over, function_arity, order_by_target, ordinal_position, case_branch_cast. Default is empty (all cases in the built-in order).",
+        &REFINEMENT_CASE_ORDER,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        "safeql.refinement_enabled_errors",
+        "Comma-separated error conditions SafeQL should try to refine",
+        "When set, only errors in the list are refined; each refinement case still needs its enable_* setting. Known errors: undefined_table, undefined_column, undefined_function, ambiguous_column, grouping, invalid_column_reference, wrong_object_type, feature_not_supported, invalid_text_representation, division_by_zero, datatype_mismatch. Default is empty (all errors).",
+        &REFINEMENT_ENABLED_ERRORS,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.value_refinement_numeric_step",
+        "Precision numeric literals are snapped to before value refinement looks up the nearest stored value",
+        "1 snaps to the nearest integer, 10 to the nearest multiple of ten, and so on. 0 disables numeric value refinement. Default is 0.",
+        &VALUE_REFINEMENT_NUMERIC_STEP,
+        0.0,
+        1.0e9,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_nullif_guard_refinement",
+        "Enable NULLIF guard refinement (CASE 12: a / b -> a / NULLIF(b, 0))",
+        "When disabled, division by zero errors during execution will not be refined. Default is true.",
+        &ENABLE_NULLIF_GUARD_REFINEMENT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_jsonb_operator_refinement",
+        "Enable jsonb operator refinement (CASE 13: data->'k' <-> data->>'k', misspelled keys)",
+        "When disabled, jsonb path operators and key literals will not be refined. Default is true.",
+        &ENABLE_JSONB_OPERATOR_REFINEMENT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_null_handling_refinement",
+        "Enable null handling refinement on empty results (CASE 14: col = x -> COALESCE(col, x) = x)",
+        "When enabled, equality predicates over nullable columns are loosened to also match NULLs. Default is false.",
+        &ENABLE_NULL_HANDLING_REFINEMENT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_srf_lateral_refinement",
+        "Enable set-returning function refinement (CASE 15: coalesce(srf(...)) -> coalesce(srf) FROM LATERAL srf(...) srf)",
+        "When disabled, set-returning functions used where a set is not allowed will not be moved into a LATERAL FROM item. Default is true.",
+        &ENABLE_SRF_LATERAL_REFINEMENT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_window_over_refinement",
+        "Enable missing OVER refinement (CASE 16: row_number() -> row_number() OVER ())",
+        "When disabled, window functions called without an OVER clause will not get a minimal OVER () appended. Default is true.",
+        &ENABLE_WINDOW_OVER_REFINEMENT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_function_arity_refinement",
+        "Enable function argument count refinement (CASE 17: substr(col) -> substr(col, 1))",
+        "When disabled, function calls with too many or too few arguments will not be adjusted to an existing overload. Default is true.",
+        &ENABLE_FUNCTION_ARITY_REFINEMENT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_order_by_target_refinement",
+        "Enable ORDER BY target refinement (CASE 18: ORDER BY sum(x) without GROUP BY -> GROUP BY the projected columns)",
+        "When disabled, ORDER BY expressions that are not valid for the select list will not be added to it, grouped, or replaced by their column. Default is true.",
+        &ENABLE_ORDER_BY_TARGET_REFINEMENT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_ordinal_position_refinement",
+        "Enable ordinal position refinement (CASE 19: ORDER BY 3 over two columns -> ORDER BY 2)",
+        "When disabled, ORDER BY/GROUP BY positions outside the select list will not be corrected to a projected position. Default is true.",
+        &ENABLE_ORDINAL_POSITION_REFINEMENT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_case_branch_cast_refinement",
+        "Enable CASE branch cast refinement (CASE 20: CASE WHEN ... THEN id ELSE name END -> THEN id::text)",
+        "When disabled, CASE branches whose result types cannot be matched will not be cast to a common type. Default is true.",
+        &ENABLE_CASE_BRANCH_CAST_REFINEMENT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
     // SafeQL search limits
     GucRegistry::define_int_guc(
         "safeql.max_refinement_hop",
@@ -253,6 +620,61 @@ pub unsafe fn init() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_int_guc(
+        "safeql.max_from_tables",
+        "Maximum number of FROM-clause tables before join-add refinements stop",
+        "Join refinements that add a table are skipped once the FROM clause already has this many tables, bounding join growth across hops. Default is 6.",
+        &MAX_FROM_TABLES,
+        1,      // min value
+        100,    // max value
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "safeql.max_candidate_sql_len",
+        "Maximum length of a refined candidate SQL (0 = unlimited)",
+        "Refinement candidates whose deparsed SQL is longer than this are dropped before they are queued. The original SQL is never dropped. Default is 0 (unlimited).",
+        &MAX_CANDIDATE_SQL_LEN,
+        0,      // min value
+        1000000,  // max value
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "safeql.candidate_statement_timeout",
+        "Statement timeout in milliseconds for each candidate execution test (0 = no limit)",
+        "Candidates that run longer than this are cancelled and treated as execution errors so the search moves on. An outer statement_timeout that expires sooner still applies. Default is 0.",
+        &CANDIDATE_STATEMENT_TIMEOUT,
+        0,      // min value
+        i32::MAX,  // max value
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_int_guc(
+        "safeql.max_embedding_calls",
+        "Maximum number of search-term embedding calls per refinement search (0 = unlimited)",
+        "Once a search has embedded this many terms, further similarity lookups only use cached results and otherwise return no candidates. Default is 0 (unlimited).",
+        &MAX_EMBEDDING_CALLS,
+        0,      // min value
+        1000000,  // max value
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.cardinality_change_penalty",
+        "Priority penalty for refined candidates whose row count differs wildly from the original FROM clause",
+        "After a refined SELECT executes successfully, its row count is compared with the row count of the original query's FROM and WHERE clauses. The candidate is re-queued with its priority raised by this weight times the log ratio of the two counts, so fixes that change the result size least are returned first. Candidates with aggregates, GROUP BY or HAVING are never penalized. When the original WHERE clause cannot run, only the FROM clause is counted, so more selective candidates (and ones with DISTINCT or LIMIT) are still penalized against less selective ones. Default is 0 (disabled).",
+        &CARDINALITY_CHANGE_PENALTY,
+        0.0,
+        100.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
     GucRegistry::define_int_guc(
         "safeql.value_refinement_samples",
         "Number of value samples to consider during value refinement",
@@ -264,6 +686,60 @@ pub unsafe fn init() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_bool_guc(
+        "safeql.enable_search_summary_hint",
+        "Append a search summary HINT when refinement gives up",
+        "When enabled, the re-raised analysis error carries a HINT with the number of candidates tried and why the search stopped. Default is false.",
+        &ENABLE_SEARCH_SUMMARY_HINT,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_deparse_round_trip_check",
+        "Check that deparsed refinement candidates parse back to the same tree (debugging aid)",
+        "When enabled, each candidate SQL is re-parsed before execution and a WARNING is logged if it does not match the candidate RawStmt. Default is false.",
+        &ENABLE_DEPARSE_ROUND_TRIP_CHECK,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_refinement_diff_notice",
+        "Emit a machine-readable diff NOTICE for refined SQL",
+        "When enabled, a successful refinement also emits a NOTICE with a JSON array of {kind, original, refined} token changes. Default is false.",
+        &ENABLE_REFINEMENT_DIFF_NOTICE,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_bool_guc(
+        "safeql.enable_refined_plan_notice",
+        "Emit the EXPLAIN plan of refined SQL as a NOTICE",
+        "When enabled, a successful refinement runs EXPLAIN (without ANALYZE) on the refined SQL in a rolled-back subtransaction and emits the plan as a NOTICE. Default is false.",
+        &ENABLE_REFINED_PLAN_NOTICE,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        "safeql.refinement_schemas",
+        "Comma-separated schemas searched for refinement candidates",
+        "When set, similarity lookups use these schemas instead of current_schemas(false). Default is empty (follow search_path).",
+        &REFINEMENT_SCHEMAS,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_string_guc(
+        "safeql.last_refined_sql",
+        "The SQL text of the last refined query in this session",
+        "Set by SafeQL when a refined candidate is accepted, and cleared when a query is used unchanged. Read it with SHOW safeql.last_refined_sql.",
+        &LAST_REFINED_SQL,
+        GucContext::Userset,
+        GucFlags::NOT_IN_SAMPLE,
+    );
+
     // SafeQL top k search 설정
     GucRegistry::define_int_guc(
         "safeql.top_k_expansion",
@@ -276,6 +752,39 @@ pub unsafe fn init() {
         GucFlags::default(),
     );
 
+    GucRegistry::define_float_guc(
+        "safeql.max_candidate_distance",
+        "Maximum embedding distance for table/column refinement candidates",
+        "Acts as a minimum similarity threshold: candidates farther than this distance are dropped before priority computation. Default is 2.0 (no filtering).",
+        &MAX_CANDIDATE_DISTANCE,
+        0.0,
+        2.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.column_statistics_weight",
+        "Weight of pg_stats selectivity (null_frac, n_distinct) in operand and value refinement ranking",
+        "Mostly-NULL or low-cardinality columns get their candidate distance raised by up to this amount. Default is 0 (disabled).",
+        &COLUMN_STATISTICS_WEIGHT,
+        0.0,
+        10.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.same_table_column_bonus",
+        "Distance bonus for column refinement candidates from tables already in FROM",
+        "When the missing column has no table qualifier, candidates whose table is already in the FROM clause have their distance lowered by this amount, so they are tried before closer-named columns of unrelated tables. Default is 0 (disabled).",
+        &SAME_TABLE_COLUMN_BONUS,
+        0.0,
+        2.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
     // SafeQL refinement priority 가중치 설정
     GucRegistry::define_float_guc(
         "safeql.table_refinement_weight",
@@ -397,4 +906,103 @@ pub unsafe fn init() {
         GucContext::Userset,
         GucFlags::default(),
     );
+
+    GucRegistry::define_float_guc(
+        "safeql.nullif_guard_refinement_weight",
+        "Weight multiplier for NULLIF guard refinement priority (CASE 12: a / b -> a / NULLIF(b, 0))",
+        "Higher values make NULLIF guard refinements less preferred. Default is 1.0.",
+        &NULLIF_GUARD_REFINEMENT_WEIGHT,
+        0.0,
+        10.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.jsonb_operator_refinement_weight",
+        "Weight multiplier for jsonb operator refinement priority (CASE 13: data->'k' <-> data->>'k', misspelled keys)",
+        "Higher values make jsonb operator refinements less preferred. Default is 1.0.",
+        &JSONB_OPERATOR_REFINEMENT_WEIGHT,
+        0.0,
+        10.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.null_handling_refinement_weight",
+        "Weight multiplier for null handling refinement priority (CASE 14: col = x -> COALESCE(col, x) = x)",
+        "Higher values make null handling refinements less preferred. Default is 1.0.",
+        &NULL_HANDLING_REFINEMENT_WEIGHT,
+        0.0,
+        10.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.srf_lateral_refinement_weight",
+        "Weight multiplier for set-returning function refinement priority (CASE 15: move srf(...) into LATERAL FROM item)",
+        "Higher values make set-returning function refinements less preferred. Default is 1.0.",
+        &SRF_LATERAL_REFINEMENT_WEIGHT,
+        0.0,
+        10.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.window_over_refinement_weight",
+        "Weight multiplier for missing OVER refinement priority (CASE 16: append OVER () to a window function call)",
+        "Higher values make missing OVER refinements less preferred. Default is 1.0.",
+        &WINDOW_OVER_REFINEMENT_WEIGHT,
+        0.0,
+        10.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.function_arity_refinement_weight",
+        "Weight multiplier for function argument count refinement priority (CASE 17: drop or add a function argument)",
+        "Higher values make function argument count refinements less preferred. Default is 1.0.",
+        &FUNCTION_ARITY_REFINEMENT_WEIGHT,
+        0.0,
+        10.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.order_by_target_refinement_weight",
+        "Weight multiplier for ORDER BY target refinement priority (CASE 18: make an ORDER BY expression valid for the select list)",
+        "Higher values make ORDER BY target refinements less preferred. Default is 1.0.",
+        &ORDER_BY_TARGET_REFINEMENT_WEIGHT,
+        0.0,
+        10.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.ordinal_position_refinement_weight",
+        "Weight multiplier for ordinal position refinement priority (CASE 19: correct an ORDER BY/GROUP BY position)",
+        "Higher values make ordinal position refinements less preferred. Default is 1.0.",
+        &ORDINAL_POSITION_REFINEMENT_WEIGHT,
+        0.0,
+        10.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
+
+    GucRegistry::define_float_guc(
+        "safeql.case_branch_cast_refinement_weight",
+        "Weight multiplier for CASE branch cast refinement priority (CASE 20: cast mismatched CASE branches)",
+        "Higher values make CASE branch cast refinements less preferred. Default is 1.0.",
+        &CASE_BRANCH_CAST_REFINEMENT_WEIGHT,
+        0.0,
+        10.0,
+        GucContext::Userset,
+        GucFlags::default(),
+    );
 }
\ No newline at end of file
@@ -3,11 +3,12 @@ use pgrx::{memcx, pg_sys, FromDatum};
 use pgrx::list::List;
 use pgrx::error;
 use pgrx::nodes::node_to_string;
-use pgrx::prelude::pg_extern;
+use pgrx::prelude::{pg_extern, name, TableIterator};
+use pgrx::JsonB;
 use pgrx::pg_sys::parse_analyze_fixedparams;
 use crate::utils::{catalog, schema};
 use crate::gucs::model::text2softql_options;
-use crate::safeql::{analyze_with_refinement, perform_refinement_search};
+use crate::safeql::{analyze_with_refinement, perform_refinement_search, perform_refinement_search_with_steps, diagnose_refinement, rank_refinement_candidates, refinement_metrics, warm_similarity_cache, would_refine};
 use crate::softql::{parse_softql, deparse_raw_stmt};
 use text2softql::text2softql;
 
@@ -52,6 +53,21 @@ fn softql_to_sql(query_string: &str) -> String {
     return deparse_raw_stmt(ast);
 }
 
+/// SoftQL을 파싱하여 AST를 JSON으로 반환 (파싱 실패 시 NOTICE 후 NULL)
+#[pg_extern(create_or_replace)]
+pub fn _softql_parse(query_string: &str) -> Option<JsonB> {
+    match softql::static_parse_softql(query_string) {
+        Ok(ast) => match serde_json::to_value(&ast) {
+            Ok(value) => Some(JsonB(value)),
+            Err(e) => error!("failed to serialize SoftQL AST: {}", e),
+        },
+        Err(e) => {
+            pgrx::notice!("SoftQL parse error:\n{}", e);
+            None
+        }
+    }
+}
+
 /// SafeQL refinement를 수행하고 refined SQL string을 반환
 #[pg_extern(create_or_replace)]
 pub fn safeql_to_sql(sql: &str) -> String {
@@ -68,6 +84,99 @@ pub fn safeql_to_sql(sql: &str) -> String {
     deparse_raw_stmt(refined_raw)
 }
 
+/// SafeQL refinement 결과를 실행하지 않고 JSONB로 반환 ({ refined, sql, original, steps })
+#[pg_extern(create_or_replace)]
+pub fn _safeql_refine(sql: &str) -> JsonB {
+    let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+    let (refined_raw, steps) = perform_refinement_search_with_steps(sql, pstate);
+    unsafe { pg_sys::free_parsestate(pstate) };
+
+    let refined_sql = if steps > 0 { deparse_raw_stmt(refined_raw) } else { sql.to_string() };
+
+    JsonB(serde_json::json!({
+        "refined": steps > 0,
+        "sql": refined_sql,
+        "original": sql,
+        "steps": steps,
+    }))
+}
+
+/// refinement search 없이 analyze 에러를 분류하고 시도될 refinement case 목록을 반환
+#[pg_extern(create_or_replace)]
+pub fn _safeql_diagnose(sql: &str) -> TableIterator<'static, (
+    name!(error_class, String),
+    name!(error_code, Option<String>),
+    name!(missing_object, Option<String>),
+    name!(message, Option<String>),
+    name!(candidate_cases, Vec<String>),
+)> {
+    let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+    let diagnosis = diagnose_refinement(sql, pstate);
+    unsafe { pg_sys::free_parsestate(pstate) };
+
+    TableIterator::once((
+        diagnosis.error_class.as_str().to_string(),
+        diagnosis.error_code,
+        diagnosis.missing_object,
+        diagnosis.message,
+        diagnosis.candidate_cases.iter().map(|c| c.to_string()).collect(),
+    ))
+}
+
+/// 쿼리가 지금 analyze에 실패하고 그 에러에 시도할 refinement case가 있는지만 확인 (재작성/실행 없음, health check용)
+#[pg_extern(create_or_replace)]
+pub fn _safeql_would_refine(sql: &str) -> bool {
+    let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+    let result = would_refine(sql, pstate);
+    unsafe { pg_sys::free_parsestate(pstate) };
+    result
+}
+
+/// 이미 받은 에러 메시지에 대해 refinement 후보를 priority 순으로 반환 (에러를 다시 발생시키지 않음)
+/// sqlstate를 넘기지 않으면 쿼리를 한 번 analyze해 에러 코드를 얻는다
+#[pg_extern(create_or_replace)]
+pub fn _safeql_rank_candidates(
+    sql: &str,
+    error_message: &str,
+    cursor_pos: Option<i32>,
+    sqlstate: default!(Option<&str>, "NULL"),
+) -> TableIterator<'static, (
+    name!(deparsed_sql, String),
+    name!(priority, i32),
+)> {
+    TableIterator::new(rank_refinement_candidates(sql, error_message, cursor_pos, sqlstate))
+}
+
+/// 검색어 목록의 table/column similarity 결과를 미리 계산해 캐시에 저장 (스키마 로드 후 실행)
+#[pg_extern(create_or_replace)]
+pub fn _safeql_warm_cache(
+    terms: Vec<String>,
+) -> TableIterator<'static, (
+    name!(term, String),
+    name!(table_candidates, i32),
+    name!(column_candidates, i32),
+)> {
+    TableIterator::new(warm_similarity_cache(&terms))
+}
+
+/// 모든 backend를 합친 refinement 통계 (서버 시작 이후 누적)
+#[pg_extern(create_or_replace)]
+pub fn _safeql_metrics() -> TableIterator<'static, (
+    name!(queries_refined, i64),
+    name!(queries_unchanged, i64),
+    name!(avg_candidates_tried, f64),
+    name!(execution_success_rate, f64),
+)> {
+    let metrics = refinement_metrics();
+
+    TableIterator::once((
+        metrics.queries_refined,
+        metrics.queries_unchanged,
+        metrics.avg_candidates_tried,
+        metrics.execution_success_rate,
+    ))
+}
+
 #[pgrx::pg_guard]
 pub unsafe extern "C" fn convert_chat_walker(
     _node: *mut pg_sys::Node, 
@@ -243,11 +352,13 @@ fn convert_chat_to_subquery(
             format!("Chat function schema: {}, context: {}, prompt: {}", schema, context, prompt)
         );
 
-        let resp = match text2softql(schema, context, prompt, text2softql_options()) {
+        let options = text2softql_options();
+        let response_format = options.response_format;
+        let resp = match text2softql(schema, context, prompt, options) {
             Ok(r) => r,
             Err(e) => error!("{}", e.to_string()),
         };
-        let softql = match resp.try_pop_softql() {
+        let softql = match resp.try_pop_softql_for(response_format) {
             Ok(softql) => softql,
             Err(e) => error!("{}", e.to_string()),
         };
@@ -651,4 +762,59 @@ fn expr_get_const_str(
 
     let constvalue = unsafe { (*expr).constvalue };
     return unsafe { <String as FromDatum>::from_datum(constvalue, false).unwrap() };
-}
\ No newline at end of file
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use pgrx::prelude::*;
+    use pgrx::JsonB;
+
+    #[pg_test]
+    fn test_softql_parse_returns_json() {
+        let json = Spi::get_one::<JsonB>(
+            "SELECT _softql_parse('customers.where(greater(customers.amount, 100)).project(customers.name).limit(10)')",
+        )
+        .unwrap()
+        .expect("valid SoftQL should produce JSON");
+
+        let value = json.0;
+        assert_eq!(value["initial_table"], "customers");
+        let ops = value["operations"].as_array().unwrap();
+        assert_eq!(ops.len(), 3);
+        assert_eq!(ops[0]["Filter"]["FuncCall"]["name"], "greater");
+        assert_eq!(ops[1]["Project"][0]["TableField"][1], "name");
+        assert_eq!(ops[2]["Limit"]["NumberLiteral"], "10");
+    }
+
+    #[pg_test]
+    fn test_safeql_refine_returns_json() {
+        Spi::run("CREATE TABLE refine_json_items (id int, name text)").unwrap();
+        Spi::run("INSERT INTO refine_json_items VALUES (1, 'a')").unwrap();
+
+        let json = Spi::get_one::<JsonB>("SELECT _safeql_refine('SELECT name, row_number() FROM refine_json_items')")
+            .unwrap()
+            .unwrap();
+        let value = json.0;
+        assert_eq!(value["refined"], true);
+        assert_eq!(value["sql"], "SELECT name, row_number() OVER () FROM refine_json_items");
+        assert_eq!(value["original"], "SELECT name, row_number() FROM refine_json_items");
+        assert_eq!(value["steps"], 1);
+
+        // 이미 유효한 쿼리는 그대로 돌아온다
+        let json = Spi::get_one::<JsonB>("SELECT _safeql_refine('SELECT id FROM refine_json_items')")
+            .unwrap()
+            .unwrap();
+        let value = json.0;
+        assert_eq!(value["refined"], false);
+        assert_eq!(value["sql"], "SELECT id FROM refine_json_items");
+        assert_eq!(value["original"], "SELECT id FROM refine_json_items");
+        assert_eq!(value["steps"], 0);
+    }
+
+    #[pg_test]
+    fn test_softql_parse_error_returns_null() {
+        let json = Spi::get_one::<JsonB>("SELECT _softql_parse('customers.where(')").unwrap();
+        assert!(json.is_none());
+    }
+}
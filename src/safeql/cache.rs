@@ -2,7 +2,9 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
 use std::ffi::CString;
-use crate::gucs::model::ENABLE_SEARCH_CACHE;
+use pgrx::{pg_sys, IntoDatum, PgOid, Spi};
+use crate::gucs::model::{ENABLE_PERSISTENT_SEARCH_CACHE, ENABLE_SEARCH_CACHE, SEARCH_CACHE_NEGATIVE_TTL};
+use crate::gucs::parser::refinement_schemas;
 
 /// 캐시 엔트리 구조체 (C-compatible)
 #[repr(C, align(8))]
@@ -13,6 +15,7 @@ struct CacheEntry {
     created_at: u64,
     last_accessed: u64,
     data_len: usize,
+    negative: bool,     // 후보가 없는 결과("[]") - SEARCH_CACHE_NEGATIVE_TTL이 지나면 miss
     // 가변 길이 데이터: key_str + query_type_str + data_str이 뒤에 따라옴
 }
 
@@ -30,6 +33,7 @@ struct CacheHeader {
 const CACHE_MAGIC: u32 = 0x56454354; // "VECT"
 const MAX_CACHE_ENTRIES: u32 = 100000;
 const CACHE_CLEANUP_INTERVAL: u64 = 3600; // 1시간마다 정리
+const CACHE_ENTRY_TTL: u64 = 7 * 24 * 3600; // 7일 지난 엔트리는 만료
 const ENTRY_SLOT_SIZE: usize = 7200; // 각 엔트리 슬롯의 고정 크기
 const MAX_DATA_SIZE: usize = ENTRY_SLOT_SIZE - size_of::<CacheEntry>() - 128; // 안전 마진
 
@@ -169,6 +173,10 @@ impl SharedMemoryCache {
                 
                 let entry_key_hash = (*entry).key_hash;
                 if entry_key_hash == key_hash {
+                    if (*entry).negative && negative_entry_expired((*entry).created_at) {
+                        return None;
+                    }
+
                     // 데이터 크기 검증
                     let data_len = (*entry).data_len;
                     if data_len > MAX_DATA_SIZE {
@@ -227,10 +235,13 @@ impl SharedMemoryCache {
             let query_type_hash = Self::hash_string(query_type);
             let entries_start = Self::get_entries_start(header);
             
-            // 새 엔트리 추가
+            // 같은 키가 이미 있으면 그 슬롯을 덮어쓰고, 없으면 새 엔트리 추가
             let current_entries = (*header).current_entries;
-            let entry_offset = current_entries as usize * ENTRY_SLOT_SIZE;
-            let entry = entries_start.add(entry_offset) as *mut CacheEntry;
+            let existing_index = (0..current_entries as usize).find(|&i| {
+                (*(entries_start.add(i * ENTRY_SLOT_SIZE) as *mut CacheEntry)).key_hash == key_hash
+            });
+            let entry_index = existing_index.unwrap_or(current_entries as usize);
+            let entry = entries_start.add(entry_index * ENTRY_SLOT_SIZE) as *mut CacheEntry;
             
             let now = current_timestamp();
             (*entry).key_hash = key_hash;
@@ -238,6 +249,7 @@ impl SharedMemoryCache {
             (*entry).created_at = now;
             (*entry).last_accessed = now;
             (*entry).data_len = data.len();
+            (*entry).negative = is_negative_result(data);
             
             // 데이터 복사 - 크기 재검증
             if data.len() > MAX_DATA_SIZE {
@@ -247,7 +259,9 @@ impl SharedMemoryCache {
             let data_ptr = (entry as *mut u8).add(size_of::<CacheEntry>());
             std::ptr::copy_nonoverlapping(data.as_ptr(), data_ptr, data.len());
             
-            (*header).current_entries += 1;
+            if existing_index.is_none() {
+                (*header).current_entries += 1;
+            }
             
             // let new_count = (*header).current_entries;
             // pgrx::notice!("Cached entry added: key_hash={:x}, size={} bytes, total_entries={}", 
@@ -264,7 +278,7 @@ impl SharedMemoryCache {
         }
         
         let now = current_timestamp();
-        let cutoff_time = now.saturating_sub(7 * 24 * 3600); // 7일 이전
+        let cutoff_time = now.saturating_sub(CACHE_ENTRY_TTL);
         
         let entries_start = unsafe { Self::get_entries_start(header) };
         let mut write_index = 0;
@@ -456,19 +470,130 @@ pub fn generate_cache_key(query_type: &str, params: &[&str]) -> String {
     for param in params {
         param.hash(&mut hasher);
     }
+    // 스키마 범위가 다르면 같은 파라미터라도 결과가 다르다
+    refinement_schemas().hash(&mut hasher);
+    // role마다 보이는 테이블이 다르고, 다른 role이 저장한 결과를 쓰지 않도록 분리한다
+    unsafe { pg_sys::GetUserId() }.as_u32().hash(&mut hasher);
     format!("{}_{:016x}", query_type, hasher.finish())
 }
 
+/// constraint를 바꿀 수 있는 DDL이 실행될 때마다 바뀌는 token (event trigger가 sequence를 증가시킨다)
+/// FK로 찾는 join 경로 캐시 키에만 넣어 constraint 변경 시 그 캐시만 무효화한다
+/// nextval 전에는 last_value가 시작값(1)이므로 is_called로 첫 증가와 구분한다
+pub fn constraint_generation_token() -> String {
+    Spi::get_one::<i64>(
+        "SELECT CASE WHEN is_called THEN last_value ELSE 0 END FROM vectors.pg_vector_constraint_generation",
+    )
+        .ok()
+        .flatten()
+        .unwrap_or(0)
+        .to_string()
+}
+
+/// 후보가 하나도 없는 결과인지 확인 (negative cache entry)
+fn is_negative_result(data: &str) -> bool {
+    data.trim() == "[]"
+}
+
+/// negative 엔트리가 SEARCH_CACHE_NEGATIVE_TTL보다 오래됐는지 확인 (0이면 항상 만료)
+fn negative_entry_expired(created_at: u64) -> bool {
+    let ttl = SEARCH_CACHE_NEGATIVE_TTL.get().max(0) as u64;
+    current_timestamp().saturating_sub(created_at) >= ttl
+}
+
 /// 캐시에서 결과를 가져오는 함수
+/// 공유 메모리에 없으면 (설정된 경우) pg_vector_search_cache 테이블을 조회하고, 찾으면 공유 메모리에도 올린다
+/// 빈 결과("[]")는 SEARCH_CACHE_NEGATIVE_TTL 동안만 hit로 취급한다
 pub fn get_cached_result(cache_key: &str) -> Option<String> {
-    SHARED_CACHE.get(cache_key)
+    if let Some(result) = SHARED_CACHE.get(cache_key) {
+        return Some(result);
+    }
+
+    let (query_type, data) = get_persisted_result(cache_key)?;
+    // negative 엔트리를 다시 올리면 created_at이 갱신되어 TTL이 늘어나므로 테이블에만 둔다
+    if !is_negative_result(&data) {
+        let _ = SHARED_CACHE.set(cache_key, &query_type, &data);
+    }
+    Some(data)
 }
 
 /// 결과를 캐시에 저장하는 함수
 pub fn store_cached_result(cache_key: &str, query_type: &str, result_json: &str) -> Result<(), &'static str> {
+    persist_result(cache_key, query_type, result_json);
     SHARED_CACHE.set(cache_key, query_type, result_json)
 }
 
+fn persistent_cache_enabled() -> bool {
+    ENABLE_SEARCH_CACHE.get() && ENABLE_PERSISTENT_SEARCH_CACHE.get()
+}
+
+/// pg_vector_search_cache 테이블에서 (query_type, data) 조회
+/// expires_at이 지난 행은 miss로 보고, 지우는 것은 persist_result에 맡긴다
+/// row level security로 현재 role이 저장한 행만 보인다
+fn get_persisted_result(cache_key: &str) -> Option<(String, String)> {
+    if !persistent_cache_enabled() {
+        return None;
+    }
+
+    let sql = r#"
+        SELECT query_type, data FROM pg_vector_search_cache
+        WHERE cache_key = $1
+          AND expires_at > now()
+    "#;
+    Spi::connect(|client| -> Result<Option<(String, String)>, pgrx::spi::Error> {
+        let args = vec![(PgOid::from(pg_sys::TEXTOID), cache_key.into_datum())];
+        let rows = client.select(sql, Some(1), Some(args))?;
+        for row in rows {
+            if let (Ok(Some(query_type)), Ok(Some(data))) =
+                (row["query_type"].value::<String>(), row["data"].value::<String>())
+            {
+                return Ok(Some((query_type, data)));
+            }
+        }
+
+        Ok(None)
+    }).ok().flatten()
+}
+
+/// pg_vector_search_cache 테이블에 upsert
+/// 여러 백엔드가 같은 키를 동시에 저장해도 ON CONFLICT로 충돌 없이 마지막 값이 남는다
+/// expires_at은 CACHE_ENTRY_TTL(빈 결과는 SEARCH_CACHE_NEGATIVE_TTL) 뒤로 잡고, 저장하면서 만료된 행을 지운다
+/// 읽기 전용 트랜잭션(핫 스탠바이 포함)에서는 저장하지 않는다
+fn persist_result(cache_key: &str, query_type: &str, data: &str) {
+    if !persistent_cache_enabled() || unsafe { pg_sys::XactReadOnly } {
+        return;
+    }
+
+    if let Err(e) = Spi::run("DELETE FROM pg_vector_search_cache WHERE expires_at <= now()") {
+        pgrx::log!("Failed to purge expired cache entries: {}", e);
+    }
+
+    let ttl = if is_negative_result(data) {
+        SEARCH_CACHE_NEGATIVE_TTL.get().max(0) as u64
+    } else {
+        CACHE_ENTRY_TTL
+    };
+    let sql = r#"
+        INSERT INTO pg_vector_search_cache (cache_key, query_type, data, expires_at)
+        VALUES ($1, $2, $3, now() + make_interval(secs => $4))
+        ON CONFLICT (role_name, cache_key) DO UPDATE
+        SET query_type = EXCLUDED.query_type,
+            data = EXCLUDED.data,
+            created_at = now(),
+            last_accessed = now(),
+            expires_at = EXCLUDED.expires_at
+    "#;
+    let args = vec![
+        (PgOid::from(pg_sys::TEXTOID), cache_key.into_datum()),
+        (PgOid::from(pg_sys::TEXTOID), query_type.into_datum()),
+        (PgOid::from(pg_sys::TEXTOID), data.into_datum()),
+        (PgOid::from(pg_sys::FLOAT8OID), (ttl as f64).into_datum()),
+    ];
+    if let Err(e) = Spi::run_with_args(sql, Some(args)) {
+        pgrx::log!("Failed to persist cache entry {}: {}", cache_key, e);
+    }
+}
+
 /// 캐시 통계 조회
 pub fn _get_cache_stats() -> Vec<(String, i64, Option<f64>)> {
     SHARED_CACHE._get_stats()
@@ -606,4 +731,121 @@ fn initialize_shared_cache() {
             current_entries
         );
     }
-}
\ No newline at end of file
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use super::*;
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_persistent_cache_serves_entries_missing_from_shared_memory() {
+        Spi::run("SET vectors.search_cache_persistent = on").unwrap();
+
+        let cache_key = generate_cache_key("table_similarity", &["persisted", "3"]);
+        store_cached_result(&cache_key, "table_similarity", r#"[["public.orders",0.1]]"#).unwrap();
+        assert_eq!(
+            Spi::get_one_with_args::<String>(
+                "SELECT data FROM pg_vector_search_cache WHERE cache_key = $1",
+                vec![(PgOid::from(pg_sys::TEXTOID), cache_key.clone().into_datum())],
+            )
+            .unwrap()
+            .as_deref(),
+            Some(r#"[["public.orders",0.1]]"#)
+        );
+
+        // 다른 백엔드가 저장해 공유 메모리에는 없는 항목도 테이블에서 읽어온다
+        let other_key = generate_cache_key("table_similarity", &["persisted_elsewhere", "3"]);
+        Spi::run_with_args(
+            "INSERT INTO pg_vector_search_cache (cache_key, query_type, data) VALUES ($1, 'table_similarity', $2)",
+            Some(vec![
+                (PgOid::from(pg_sys::TEXTOID), other_key.clone().into_datum()),
+                (PgOid::from(pg_sys::TEXTOID), r#"[["public.orders",0.2]]"#.into_datum()),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(get_cached_result(&other_key).as_deref(), Some(r#"[["public.orders",0.2]]"#));
+
+        // 같은 키를 다시 저장하면 upsert
+        store_cached_result(&cache_key, "table_similarity", "[]").unwrap();
+        assert_eq!(
+            Spi::get_one_with_args::<i64>(
+                "SELECT count(*) FROM pg_vector_search_cache WHERE cache_key = $1",
+                vec![(PgOid::from(pg_sys::TEXTOID), cache_key.clone().into_datum())],
+            )
+            .unwrap(),
+            Some(1)
+        );
+    }
+
+    #[pg_test]
+    fn test_persistent_cache_expires_old_rows() {
+        Spi::run("SET vectors.search_cache_persistent = on").unwrap();
+
+        let cache_key = generate_cache_key("table_similarity", &["persisted_old", "3"]);
+        Spi::run_with_args(
+            "INSERT INTO pg_vector_search_cache (cache_key, query_type, data, expires_at)
+             VALUES ($1, 'table_similarity', '[[\"public.orders\",0.1]]', now() - interval '1 second')",
+            Some(vec![(PgOid::from(pg_sys::TEXTOID), cache_key.clone().into_datum())]),
+        )
+        .unwrap();
+        let count = || {
+            Spi::get_one_with_args::<i64>(
+                "SELECT count(*) FROM pg_vector_search_cache WHERE cache_key = $1",
+                vec![(PgOid::from(pg_sys::TEXTOID), cache_key.clone().into_datum())],
+            )
+            .unwrap()
+        };
+
+        // 만료된 행은 miss지만 읽기만으로는 지우지 않는다
+        assert_eq!(get_cached_result(&cache_key), None);
+        assert_eq!(count(), Some(1));
+
+        // 다음 저장에서 만료된 행을 정리한다
+        let other_key = generate_cache_key("table_similarity", &["persisted_new", "3"]);
+        store_cached_result(&other_key, "table_similarity", r#"[["public.orders",0.2]]"#).unwrap();
+        assert_eq!(count(), Some(0));
+    }
+
+    #[pg_test]
+    fn test_persistent_cache_rows_are_private_to_role() {
+        Spi::run("SET vectors.search_cache_persistent = on").unwrap();
+        // superuser는 row level security를 우회하므로 일반 role 둘로 확인한다
+        Spi::run("CREATE ROLE sc_role_a").unwrap();
+        Spi::run("CREATE ROLE sc_role_b").unwrap();
+        Spi::run("GRANT USAGE ON SCHEMA vectors TO sc_role_a, sc_role_b").unwrap();
+
+        let cache_key = generate_cache_key("table_similarity", &["role_private", "3"]);
+        let orders = r#"[["public.orders",0.1]]"#;
+        let invoices = r#"[["public.invoices",0.2]]"#;
+
+        Spi::run("SET ROLE sc_role_a").unwrap();
+        persist_result(&cache_key, "table_similarity", orders);
+        let seen_by_a = get_persisted_result(&cache_key);
+
+        // role B는 A의 행을 읽지 못하고, 같은 키로 저장하면 자기 행이 따로 생긴다
+        Spi::run("SET ROLE sc_role_b").unwrap();
+        let seen_by_b = get_persisted_result(&cache_key);
+        persist_result(&cache_key, "table_similarity", invoices);
+        let stored_by_b = get_persisted_result(&cache_key);
+
+        Spi::run("SET ROLE sc_role_a").unwrap();
+        let seen_by_a_after_b = get_persisted_result(&cache_key);
+        Spi::run("RESET ROLE").unwrap();
+
+        let entry = |data: &str| Some(("table_similarity".to_string(), data.to_string()));
+        assert_eq!(seen_by_a, entry(orders));
+        assert_eq!(seen_by_b, None);
+        assert_eq!(stored_by_b, entry(invoices));
+        assert_eq!(seen_by_a_after_b, entry(orders));
+        assert_eq!(
+            Spi::get_one_with_args::<i64>(
+                "SELECT count(*) FROM pg_vector_search_cache WHERE cache_key = $1",
+                vec![(PgOid::from(pg_sys::TEXTOID), cache_key.clone().into_datum())],
+            )
+            .unwrap(),
+            Some(2)
+        );
+    }
+}
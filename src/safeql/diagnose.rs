@@ -0,0 +1,403 @@
+use pgrx::prelude::*;
+
+use super::search::{
+    try_analyze_raw_once,
+    rawstmt_from_sql,
+    is_operator_type_error,
+    is_set_returning_context_error,
+    is_missing_over_error,
+    is_order_by_target_error,
+    extract_ordinal_position,
+    extract_case_branch_types,
+    extract_missing_relation,
+    extract_missing_from_clause_entry,
+    extract_missing_column,
+    extract_missing_function,
+    extract_ambiguous_column,
+    load_case_order,
+    case_enabled,
+    error_refinement_enabled,
+    join_refinement_enabled,
+    srf_lateral_refinement_enabled,
+    AnalyzeOutcome,
+};
+use crate::gucs::parser::{
+    ENABLE_SAFEQL_REFINEMENT,
+    ENABLE_TABLE_REFINEMENT,
+    ENABLE_COLUMN_REFINEMENT,
+    ENABLE_TABLE_FOR_COLUMN,
+    ENABLE_COLUMN_TABLE_REFERENCE,
+    ENABLE_OPERAND_COLUMN_REFINEMENT,
+    ENABLE_OPERAND_TABLE_FOR_COLUMN_REFINEMENT,
+    ENABLE_OPERAND_COLUMN_TABLE_REFERENCE_REFINEMENT,
+    ENABLE_OPERAND_TYPECAST_REFINEMENT,
+    ENABLE_ARGUMENT_COLUMN_REFINEMENT,
+    ENABLE_ARGUMENT_TYPECAST_REFINEMENT,
+    ENABLE_FUNCTION_NAME_REFINEMENT,
+    ENABLE_COLUMN_AMBIGUITY_REFINEMENT,
+    ENABLE_JSONB_OPERATOR_REFINEMENT,
+    ENABLE_WINDOW_OVER_REFINEMENT,
+    ENABLE_FUNCTION_ARITY_REFINEMENT,
+    ENABLE_ORDER_BY_TARGET_REFINEMENT,
+    ENABLE_ORDINAL_POSITION_REFINEMENT,
+    ENABLE_CASE_BRANCH_CAST_REFINEMENT,
+};
+
+/// analyze 에러 분류
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    None,
+    Table,
+    Column,
+    Function,
+    Operator,
+    Ambiguity,
+    Grouping,
+    Other,
+}
+
+impl ErrorClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorClass::None => "none",
+            ErrorClass::Table => "table",
+            ErrorClass::Column => "column",
+            ErrorClass::Function => "function",
+            ErrorClass::Operator => "operator",
+            ErrorClass::Ambiguity => "ambiguity",
+            ErrorClass::Grouping => "grouping",
+            ErrorClass::Other => "other",
+        }
+    }
+}
+
+/// 한 번의 analyze 결과로 만든 진단 정보
+#[derive(Debug, Clone)]
+pub struct RefinementDiagnosis {
+    pub error_class: ErrorClass,
+    pub error_code: Option<String>,
+    pub missing_object: Option<String>,
+    pub message: Option<String>,
+    pub candidate_cases: Vec<&'static str>,
+}
+
+/// 전체 search 없이 analyze를 한 번만 수행하고, 어떤 refinement case가 시도될지 보고
+pub fn diagnose_refinement(sql: &str, pstate: *mut pg_sys::ParseState) -> RefinementDiagnosis {
+    let raw = rawstmt_from_sql(sql).unwrap_or_else(|e| {
+        pgrx::error!("SQL parse error: {}", e);
+    });
+
+    let (code, message) = match try_analyze_raw_once(sql, raw, pstate) {
+        AnalyzeOutcome::Success(_) => {
+            return RefinementDiagnosis {
+                error_class: ErrorClass::None,
+                error_code: None,
+                missing_object: None,
+                message: None,
+                candidate_cases: Vec::new(),
+            };
+        }
+        AnalyzeOutcome::Failure { code, message, .. } => (code, message),
+    };
+
+    let (error_class, missing_object, candidate_cases) = classify_error(code, &message);
+
+    RefinementDiagnosis {
+        error_class,
+        error_code: code.map(|c| format!("{:?}", c)),
+        missing_object,
+        message: Some(message),
+        candidate_cases: if ENABLE_SAFEQL_REFINEMENT.get() { candidate_cases } else { Vec::new() },
+    }
+}
+
+/// analyze를 한 번만 수행해 지금 쿼리가 실패하고, 그 에러에 시도할 refinement case가 있는지 반환
+/// 후보 생성, 재작성, 실행은 하지 않는다
+pub fn would_refine(sql: &str, pstate: *mut pg_sys::ParseState) -> bool {
+    if !ENABLE_SAFEQL_REFINEMENT.get() {
+        return false;
+    }
+
+    !diagnose_refinement(sql, pstate).candidate_cases.is_empty()
+}
+
+/// 에러 코드와 메시지로 분류, 누락된 객체, 시도될 case 목록을 결정
+/// (push_analyze_error_refinements의 match arm과 같은 순서/조건을 따르고,
+/// case 순서/에러 코드 목록/safeql.conservative 판단은 search와 같은 helper를 쓴다)
+fn classify_error(
+    code: Option<PgSqlErrorCode>,
+    message: &str,
+) -> (ErrorClass, Option<String>, Vec<&'static str>) {
+    load_case_order();
+    let mut cases = Vec::new();
+
+    let (error_class, missing_object) = match code {
+        Some(PgSqlErrorCode::ERRCODE_UNDEFINED_TABLE) => {
+            if case_enabled("missing_from_join", join_refinement_enabled())
+                && extract_missing_from_clause_entry(message).is_some()
+            {
+                cases.push("missing_from_join_refinement");
+            }
+            let missing_rel = extract_missing_relation(message);
+            if case_enabled("table", ENABLE_TABLE_REFINEMENT.get()) && missing_rel.is_some() {
+                cases.push("table_refinement");
+            }
+            (ErrorClass::Table, missing_rel)
+        }
+        Some(PgSqlErrorCode::ERRCODE_UNDEFINED_COLUMN) => match extract_missing_column(message) {
+            Some((table_name, missing_col)) => {
+                if case_enabled("column", ENABLE_COLUMN_REFINEMENT.get()) {
+                    cases.push("column_refinement");
+                }
+                if case_enabled("table_for_column", ENABLE_TABLE_FOR_COLUMN.get()) {
+                    cases.push("table_for_column");
+                }
+                if case_enabled("column_table_reference", ENABLE_COLUMN_TABLE_REFERENCE.get()) && table_name.is_some() {
+                    cases.push("column_table_reference");
+                }
+                if case_enabled("join", join_refinement_enabled()) {
+                    cases.push("join_refinement");
+                }
+                let missing_object = match table_name {
+                    Some(table) => format!("{}.{}", table, missing_col),
+                    None => missing_col,
+                };
+                (ErrorClass::Column, Some(missing_object))
+            }
+            None => (ErrorClass::Column, None),
+        },
+        Some(PgSqlErrorCode::ERRCODE_UNDEFINED_FUNCTION) => {
+            if let Some((function_name, arg_types)) = extract_missing_function(message) {
+                if case_enabled("argument_column", ENABLE_ARGUMENT_COLUMN_REFINEMENT.get()) {
+                    cases.push("argument_column_refinement");
+                }
+                if case_enabled("argument_typecast", ENABLE_ARGUMENT_TYPECAST_REFINEMENT.get()) {
+                    cases.push("argument_typecast_refinement");
+                }
+                if case_enabled("function_name", ENABLE_FUNCTION_NAME_REFINEMENT.get()) {
+                    cases.push("function_name_refinement");
+                }
+                if case_enabled("function_arity", ENABLE_FUNCTION_ARITY_REFINEMENT.get()) {
+                    cases.push("function_arity_refinement");
+                }
+                if case_enabled("jsonb_operator", ENABLE_JSONB_OPERATOR_REFINEMENT.get())
+                    && arg_types.iter().any(|t| t == "jsonb")
+                {
+                    cases.push("jsonb_operator_refinement");
+                }
+                let missing_object = format!("{}({})", function_name, arg_types.join(", "));
+                (ErrorClass::Function, Some(missing_object))
+            } else if is_operator_type_error(message) {
+                if case_enabled("operand_column", ENABLE_OPERAND_COLUMN_REFINEMENT.get()) {
+                    cases.push("operand_column_refinement");
+                }
+                if case_enabled("operand_table_for_column", ENABLE_OPERAND_TABLE_FOR_COLUMN_REFINEMENT.get()) {
+                    cases.push("operand_table_for_column_refinement");
+                }
+                if case_enabled("operand_column_table_reference", ENABLE_OPERAND_COLUMN_TABLE_REFERENCE_REFINEMENT.get()) {
+                    cases.push("operand_column_table_reference_refinement");
+                }
+                if case_enabled("operand_typecast", ENABLE_OPERAND_TYPECAST_REFINEMENT.get()) {
+                    cases.push("operand_typecast_refinement");
+                }
+                if case_enabled("jsonb_operator", ENABLE_JSONB_OPERATOR_REFINEMENT.get()) && message.contains("jsonb") {
+                    cases.push("jsonb_operator_refinement");
+                }
+                (ErrorClass::Operator, None)
+            } else {
+                (ErrorClass::Other, None)
+            }
+        }
+        Some(PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED) if is_set_returning_context_error(message) => {
+            if srf_lateral_refinement_enabled() {
+                cases.push("srf_lateral_refinement");
+            }
+            (ErrorClass::Function, None)
+        }
+        Some(PgSqlErrorCode::ERRCODE_WRONG_OBJECT_TYPE) if is_missing_over_error(message) => {
+            if case_enabled("window_over", ENABLE_WINDOW_OVER_REFINEMENT.get()) {
+                cases.push("window_over_refinement");
+            }
+            (ErrorClass::Function, None)
+        }
+        Some(PgSqlErrorCode::ERRCODE_AMBIGUOUS_COLUMN) => {
+            let ambiguous_col = extract_ambiguous_column(message);
+            if case_enabled("column_ambiguity", ENABLE_COLUMN_AMBIGUITY_REFINEMENT.get()) && ambiguous_col.is_some() {
+                cases.push("column_ambiguity_refinement");
+            }
+            (ErrorClass::Ambiguity, ambiguous_col)
+        }
+        Some(PgSqlErrorCode::ERRCODE_INVALID_COLUMN_REFERENCE) if extract_ordinal_position(message).is_some() => {
+            if case_enabled("ordinal_position", ENABLE_ORDINAL_POSITION_REFINEMENT.get()) {
+                cases.push("ordinal_position_refinement");
+            }
+            (ErrorClass::Column, None)
+        }
+        Some(PgSqlErrorCode::ERRCODE_DATATYPE_MISMATCH) if extract_case_branch_types(message).is_some() => {
+            if case_enabled("case_branch_cast", ENABLE_CASE_BRANCH_CAST_REFINEMENT.get()) {
+                cases.push("case_branch_cast_refinement");
+            }
+            (ErrorClass::Other, None)
+        }
+        Some(PgSqlErrorCode::ERRCODE_INVALID_COLUMN_REFERENCE | PgSqlErrorCode::ERRCODE_GROUPING_ERROR)
+            if is_order_by_target_error(message) =>
+        {
+            if case_enabled("order_by_target", ENABLE_ORDER_BY_TARGET_REFINEMENT.get()) {
+                cases.push("order_by_target_refinement");
+            }
+            (ErrorClass::Grouping, None)
+        }
+        Some(PgSqlErrorCode::ERRCODE_GROUPING_ERROR) => (ErrorClass::Grouping, None),
+        _ => (ErrorClass::Other, None),
+    };
+
+    // safeql.refinement_enabled_errors에 없는 에러는 search가 어떤 case도 시도하지 않는다
+    if !error_refinement_enabled(code) {
+        cases.clear();
+    }
+
+    (error_class, missing_object, cases)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use super::*;
+
+    fn diagnose(sql: &str) -> RefinementDiagnosis {
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let diagnosis = diagnose_refinement(sql, pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+        diagnosis
+    }
+
+    fn setup_tables() {
+        Spi::run("CREATE TABLE diag_customers (id int, name text, city text)").unwrap();
+        Spi::run("CREATE TABLE diag_orders (id int, customer_id int, amount numeric)").unwrap();
+    }
+
+    #[pg_test]
+    fn test_diagnose_valid_query() {
+        setup_tables();
+        let d = diagnose("SELECT name FROM diag_customers");
+        assert_eq!(d.error_class, ErrorClass::None);
+        assert!(d.candidate_cases.is_empty());
+    }
+
+    #[pg_test]
+    fn test_would_refine() {
+        setup_tables();
+        let check = |sql: &str| {
+            let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+            let result = would_refine(sql, pstate);
+            unsafe { pg_sys::free_parsestate(pstate) };
+            result
+        };
+
+        assert!(!check("SELECT name FROM diag_customers"));
+        assert!(check("SELECT nme FROM diag_customers"));
+        assert!(check("SELECT name FROM diag_customer"));
+
+        Spi::run("SET safeql.enable_refinement = off").unwrap();
+        assert!(!check("SELECT nme FROM diag_customers"));
+    }
+
+    #[pg_test]
+    fn test_would_refine_conservative_join_add() {
+        setup_tables();
+        let check = |sql: &str| {
+            let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+            let result = would_refine(sql, pstate);
+            unsafe { pg_sys::free_parsestate(pstate) };
+            result
+        };
+        // JOIN 추가로만 고칠 수 있는 에러
+        let join_add_sql = "SELECT diag_orders.amount FROM diag_customers";
+        Spi::run("SET safeql.enable_table_refinement = off").unwrap();
+        assert!(check(join_add_sql));
+
+        Spi::run("SET safeql.conservative = on").unwrap();
+        assert!(!check(join_add_sql));
+        // rename으로 고칠 수 있는 에러는 그대로 true
+        assert!(check("SELECT nme FROM diag_customers"));
+
+        Spi::run("RESET safeql.conservative").unwrap();
+        Spi::run("SET safeql.refinement_enabled_errors = 'undefined_table'").unwrap();
+        assert!(!check("SELECT nme FROM diag_customers"));
+    }
+
+    #[pg_test]
+    fn test_diagnose_follows_search_gating() {
+        setup_tables();
+        // missing FROM-clause entry에 JOIN 추가 case만 남긴다
+        let join_add_sql = "SELECT diag_orders.amount FROM diag_customers";
+        Spi::run("SET safeql.enable_table_refinement = off").unwrap();
+        assert_eq!(diagnose(join_add_sql).candidate_cases, vec!["missing_from_join_refinement"]);
+
+        // conservative면 search가 JOIN을 추가하지 않는다
+        Spi::run("SET safeql.conservative = on").unwrap();
+        let d = diagnose(join_add_sql);
+        assert_eq!(d.error_class, ErrorClass::Table);
+        assert!(d.candidate_cases.is_empty());
+        Spi::run("RESET safeql.conservative").unwrap();
+
+        // case 순서 목록에 없는 case는 보고하지 않는다
+        Spi::run("SET safeql.refinement_case_order = 'table_for_column'").unwrap();
+        assert_eq!(diagnose("SELECT nme FROM diag_customers").candidate_cases, vec!["table_for_column"]);
+        Spi::run("RESET safeql.refinement_case_order").unwrap();
+
+        // safeql.refinement_enabled_errors에 없는 에러도 마찬가지
+        Spi::run("SET safeql.refinement_enabled_errors = 'undefined_table'").unwrap();
+        let d = diagnose("SELECT nme FROM diag_customers");
+        assert_eq!(d.error_class, ErrorClass::Column);
+        assert!(d.candidate_cases.is_empty());
+    }
+
+    #[pg_test]
+    fn test_diagnose_undefined_table() {
+        setup_tables();
+        let d = diagnose("SELECT name FROM diag_customer");
+        assert_eq!(d.error_class, ErrorClass::Table);
+        assert_eq!(d.missing_object.as_deref(), Some("diag_customer"));
+        assert!(d.candidate_cases.contains(&"table_refinement"));
+    }
+
+    #[pg_test]
+    fn test_diagnose_undefined_column() {
+        setup_tables();
+        let d = diagnose("SELECT c.nme FROM diag_customers c");
+        assert_eq!(d.error_class, ErrorClass::Column);
+        assert_eq!(d.missing_object.as_deref(), Some("c.nme"));
+        assert!(d.candidate_cases.contains(&"column_refinement"));
+        assert!(d.candidate_cases.contains(&"column_table_reference"));
+    }
+
+    #[pg_test]
+    fn test_diagnose_undefined_function() {
+        setup_tables();
+        let d = diagnose("SELECT sumx(amount) FROM diag_orders");
+        assert_eq!(d.error_class, ErrorClass::Function);
+        assert_eq!(d.missing_object.as_deref(), Some("sumx(numeric)"));
+        assert!(d.candidate_cases.contains(&"function_name_refinement"));
+    }
+
+    #[pg_test]
+    fn test_diagnose_operator_mismatch() {
+        setup_tables();
+        let d = diagnose("SELECT id FROM diag_customers WHERE name > 10");
+        assert_eq!(d.error_class, ErrorClass::Operator);
+        assert!(d.candidate_cases.contains(&"operand_column_refinement"));
+    }
+
+    #[pg_test]
+    fn test_diagnose_ambiguous_and_grouping() {
+        setup_tables();
+        let d = diagnose("SELECT id FROM diag_customers, diag_orders");
+        assert_eq!(d.error_class, ErrorClass::Ambiguity);
+        assert_eq!(d.missing_object.as_deref(), Some("id"));
+
+        let d = diagnose("SELECT city, count(*), name FROM diag_customers GROUP BY city");
+        assert_eq!(d.error_class, ErrorClass::Grouping);
+        assert!(d.candidate_cases.is_empty());
+    }
+}
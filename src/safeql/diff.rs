@@ -0,0 +1,231 @@
+use std::ffi::CStr;
+use std::os::raw::c_void;
+
+use pgrx::list::List;
+use pgrx::{memcx, pg_sys};
+use serde::Serialize;
+
+use super::refine::safe_raw_expression_tree_walker;
+
+/// 원본과 refined SQL 사이의 토큰 단위 변경 하나
+/// original/refined가 비어 있으면 토큰이 추가/삭제된 것
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RefinementChange {
+    pub kind: &'static str,
+    pub original: String,
+    pub refined: String,
+}
+
+/// 두 RawStmt의 식별자/연산자/literal 토큰을 종류별로 비교해 바뀐 것만 반환
+pub fn diff_raw_stmts(
+    orig: *mut pg_sys::RawStmt,
+    refined: *mut pg_sys::RawStmt,
+) -> Vec<RefinementChange> {
+    let orig_tokens = unsafe { collect_tokens(orig) };
+    let refined_tokens = unsafe { collect_tokens(refined) };
+
+    let mut changes = Vec::new();
+    for kind in ["table", "column", "function", "operator", "value"] {
+        let before: Vec<&str> = orig_tokens.iter().filter(|(k, _)| *k == kind).map(|(_, t)| t.as_str()).collect();
+        let after: Vec<&str> = refined_tokens.iter().filter(|(k, _)| *k == kind).map(|(_, t)| t.as_str()).collect();
+        changes.extend(diff_token_sequence(kind, &before, &after));
+    }
+    changes
+}
+
+/// 변경 목록을 NOTICE로 내보낼 JSON 문자열로 변환
+pub fn changes_to_json(changes: &[RefinementChange]) -> String {
+    serde_json::to_string(changes).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// LCS로 공통 토큰을 맞춘 뒤, 사이에 남은 삭제/추가 토큰을 순서대로 짝지어 변경으로 만든다
+fn diff_token_sequence(kind: &'static str, before: &[&str], after: &[&str]) -> Vec<RefinementChange> {
+    let (n, m) = (before.len(), after.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if before[i] == after[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let mut removed: Vec<&str> = Vec::new();
+    let mut added: Vec<&str> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < n || j < m {
+        if i < n && j < m && before[i] == after[j] {
+            flush_changes(kind, &mut removed, &mut added, &mut changes);
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            added.push(after[j]);
+            j += 1;
+        } else {
+            removed.push(before[i]);
+            i += 1;
+        }
+    }
+    flush_changes(kind, &mut removed, &mut added, &mut changes);
+
+    changes
+}
+
+fn flush_changes(
+    kind: &'static str,
+    removed: &mut Vec<&str>,
+    added: &mut Vec<&str>,
+    changes: &mut Vec<RefinementChange>,
+) {
+    for idx in 0..removed.len().max(added.len()) {
+        changes.push(RefinementChange {
+            kind,
+            original: removed.get(idx).unwrap_or(&"").to_string(),
+            refined: added.get(idx).unwrap_or(&"").to_string(),
+        });
+    }
+    removed.clear();
+    added.clear();
+}
+
+/// AST를 순회하며 (종류, 토큰) 목록을 등장 순서대로 수집
+unsafe fn collect_tokens(raw: *mut pg_sys::RawStmt) -> Vec<(&'static str, String)> {
+    let mut tokens = Vec::new();
+    let ctx_ptr = &mut tokens as *mut Vec<(&'static str, String)> as *mut c_void;
+
+    unsafe {
+        if !raw.is_null() && !(*raw).stmt.is_null() {
+            safe_raw_expression_tree_walker((*raw).stmt, Some(collect_tokens_walker), ctx_ptr);
+        }
+    }
+
+    tokens
+}
+
+unsafe extern "C" fn collect_tokens_walker(node: *mut pg_sys::Node, ctx: *mut c_void) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let tokens = &mut *(ctx as *mut Vec<(&'static str, String)>);
+
+        match (*node).type_ {
+            pg_sys::NodeTag::T_RangeVar => {
+                let range_var = node as *mut pg_sys::RangeVar;
+                if !(*range_var).relname.is_null() {
+                    let relname = CStr::from_ptr((*range_var).relname).to_string_lossy().into_owned();
+                    tokens.push(("table", relname));
+                }
+            }
+            pg_sys::NodeTag::T_ColumnRef => {
+                if let Some(name) = dotted_string_list((*(node as *mut pg_sys::ColumnRef)).fields) {
+                    tokens.push(("column", name));
+                }
+            }
+            pg_sys::NodeTag::T_FuncCall => {
+                if let Some(name) = dotted_string_list((*(node as *mut pg_sys::FuncCall)).funcname) {
+                    tokens.push(("function", name));
+                }
+            }
+            pg_sys::NodeTag::T_A_Expr => {
+                let a_expr = node as *mut pg_sys::A_Expr;
+                if (*a_expr).kind == pg_sys::A_Expr_Kind::AEXPR_OP {
+                    if let Some(name) = dotted_string_list((*a_expr).name) {
+                        tokens.push(("operator", name));
+                    }
+                }
+            }
+            pg_sys::NodeTag::T_A_Const => {
+                let a_const = node as *mut pg_sys::A_Const;
+                if !(*a_const).isnull {
+                    let value_node = &(*a_const).val.node as *const pg_sys::Node;
+                    match (*value_node).type_ {
+                        pg_sys::NodeTag::T_String => {
+                            let sval = (*a_const).val.sval.sval;
+                            if !sval.is_null() {
+                                tokens.push(("value", CStr::from_ptr(sval).to_string_lossy().into_owned()));
+                            }
+                        }
+                        pg_sys::NodeTag::T_Integer => {
+                            tokens.push(("value", (*a_const).val.ival.ival.to_string()));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        safe_raw_expression_tree_walker(node, Some(collect_tokens_walker), ctx)
+    }
+}
+
+/// String 노드 리스트를 "a.b" 형태로 연결 (A_Star는 "*")
+unsafe fn dotted_string_list(list: *mut pg_sys::List) -> Option<String> {
+    if list.is_null() {
+        return None;
+    }
+
+    unsafe {
+        memcx::current_context(|mcx| {
+            let items = List::<*mut c_void>::downcast_ptr_in_memcx(list, mcx)?;
+            let mut parts = Vec::new();
+            for item in items.iter() {
+                let item_node = *item as *mut pg_sys::Node;
+                match (*item_node).type_ {
+                    pg_sys::NodeTag::T_String => {
+                        parts.push(CStr::from_ptr((*(item_node as *mut pg_sys::String)).sval).to_string_lossy().into_owned());
+                    }
+                    pg_sys::NodeTag::T_A_Star => parts.push("*".to_string()),
+                    _ => return None,
+                }
+            }
+            Some(parts.join("."))
+        })
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use super::*;
+    use pgrx::prelude::*;
+    use crate::safeql::search::rawstmt_from_sql;
+
+    fn diff_sql(orig: &str, refined: &str) -> Vec<RefinementChange> {
+        let orig_raw = rawstmt_from_sql(orig).unwrap();
+        let refined_raw = rawstmt_from_sql(refined).unwrap();
+        diff_raw_stmts(orig_raw, refined_raw)
+    }
+
+    #[pg_test]
+    fn test_diff_single_column_rename() {
+        let changes = diff_sql(
+            "SELECT c.nme FROM customers c WHERE c.city = 'Seoul'",
+            "SELECT c.name FROM customers c WHERE c.city = 'Seoul'",
+        );
+        assert_eq!(
+            changes,
+            vec![RefinementChange { kind: "column", original: "c.nme".to_string(), refined: "c.name".to_string() }]
+        );
+        assert_eq!(
+            changes_to_json(&changes),
+            r#"[{"kind":"column","original":"c.nme","refined":"c.name"}]"#
+        );
+    }
+
+    #[pg_test]
+    fn test_diff_added_table() {
+        let changes = diff_sql(
+            "SELECT y.v FROM x",
+            "SELECT y.v FROM x, y WHERE x.id = y.x_id",
+        );
+        assert!(changes.contains(&RefinementChange { kind: "table", original: String::new(), refined: "y".to_string() }));
+        assert!(changes.iter().all(|c| c.original.is_empty()), "{:?}", changes);
+    }
+}
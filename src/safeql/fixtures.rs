@@ -0,0 +1,47 @@
+use pgrx::prelude::*;
+use serde::Serialize;
+
+use super::cache::store_cached_result;
+
+/// 테스트용 pg_vector_* 카탈로그를 비운 채로 다시 만든다.
+/// 스키마는 load_vector_tables/load_vector_fields/load_vector_values와 같고,
+/// 임베딩 차원만 정하지 않는다.
+pub(crate) fn reset_vector_catalogs() {
+    Spi::run("DROP TABLE IF EXISTS pg_vector_tables, pg_vector_fields, pg_vector_values").unwrap();
+    Spi::run(
+        "CREATE TABLE pg_vector_tables (
+            schemaname TEXT,
+            tablename  TEXT,
+            model      TEXT,
+            embedding  vector
+        )",
+    )
+    .unwrap();
+    Spi::run(
+        "CREATE TABLE pg_vector_fields (
+            schemaname TEXT,
+            tablename  TEXT,
+            fieldname  TEXT,
+            fieldtype  TEXT,
+            model      TEXT,
+            embedding  vector
+        )",
+    )
+    .unwrap();
+    Spi::run(
+        "CREATE TABLE pg_vector_values (
+            schemaname TEXT,
+            tablename  TEXT,
+            columnname TEXT,
+            value      TEXT,
+            model      TEXT,
+            embedding  vector
+        )",
+    )
+    .unwrap();
+}
+
+/// 임베딩 모델 없이 similarity lookup 결과를 캐시에 미리 넣어둔다.
+pub(crate) fn seed_cached_result<T: Serialize>(cache_key: &str, query_type: &str, result: &T) {
+    store_cached_result(cache_key, query_type, &serde_json::to_string(result).unwrap()).unwrap();
+}
@@ -0,0 +1,141 @@
+use std::ffi::CString;
+use std::sync::atomic::{AtomicPtr, AtomicU64, Ordering};
+
+/// 공유 메모리에 두는 refinement 통계 카운터
+/// 모든 backend가 같은 카운터를 올리므로 서버 시작 이후 누적값이다
+#[repr(C)]
+struct MetricsCounters {
+    queries_refined: AtomicU64,
+    queries_unchanged: AtomicU64,
+    candidates_tried: AtomicU64,
+    executions_attempted: AtomicU64,
+    executions_succeeded: AtomicU64,
+}
+
+/// shmem_startup_hook에서 붙인 카운터 위치 (postmaster에서 설정되어 fork된 backend로 이어진다)
+static COUNTERS: AtomicPtr<MetricsCounters> = AtomicPtr::new(std::ptr::null_mut());
+
+/// `_safeql_metrics()`로 보여줄 refinement 통계
+#[derive(Debug, Clone, Copy)]
+pub struct RefinementMetrics {
+    pub queries_refined: i64,
+    pub queries_unchanged: i64,
+    pub avg_candidates_tried: f64,
+    pub execution_success_rate: f64,
+}
+
+/// 공유 메모리 카운터 (shared_preload_libraries로 로드되지 않았으면 None)
+fn counters() -> Option<&'static MetricsCounters> {
+    let counters = COUNTERS.load(Ordering::Acquire);
+    // 공유 메모리는 서버가 끝날 때까지 유지된다
+    unsafe { counters.as_ref() }
+}
+
+pub fn refinement_metrics() -> RefinementMetrics {
+    let load = |counter: fn(&MetricsCounters) -> &AtomicU64| {
+        counters().map_or(0, |c| counter(c).load(Ordering::Relaxed))
+    };
+    let refined = load(|c| &c.queries_refined);
+    let unchanged = load(|c| &c.queries_unchanged);
+    let candidates = load(|c| &c.candidates_tried);
+    let attempted = load(|c| &c.executions_attempted);
+    let succeeded = load(|c| &c.executions_succeeded);
+
+    let searches = refined + unchanged;
+    RefinementMetrics {
+        queries_refined: refined as i64,
+        queries_unchanged: unchanged as i64,
+        avg_candidates_tried: if searches > 0 { candidates as f64 / searches as f64 } else { 0.0 },
+        execution_success_rate: if attempted > 0 { succeeded as f64 / attempted as f64 } else { 0.0 },
+    }
+}
+
+/// search 한 번이 끝났을 때 통계 반영 (refined: 원본과 다른 후보가 채택됨)
+pub(super) fn record_search_result(refined: bool, candidates_tried: i32) {
+    let Some(counters) = counters() else { return };
+    if refined {
+        counters.queries_refined.fetch_add(1, Ordering::Relaxed);
+    } else {
+        counters.queries_unchanged.fetch_add(1, Ordering::Relaxed);
+    }
+    counters.candidates_tried.fetch_add(candidates_tried.max(0) as u64, Ordering::Relaxed);
+}
+
+pub(super) fn record_execution_attempt() {
+    if let Some(counters) = counters() {
+        counters.executions_attempted.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub(super) fn record_execution_success() {
+    if let Some(counters) = counters() {
+        counters.executions_succeeded.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+pub(super) fn executions_attempted() -> u64 {
+    counters().map_or(0, |c| c.executions_attempted.load(Ordering::Relaxed))
+}
+
+/// 초기화 함수
+pub unsafe fn init() {
+    register_shmem_hooks();
+}
+
+fn register_shmem_hooks() {
+    static mut PREV_SHMEM_REQUEST_HOOK: Option<unsafe extern "C" fn()> = None;
+    static mut PREV_SHMEM_STARTUP_HOOK: Option<unsafe extern "C" fn()> = None;
+
+    unsafe extern "C" fn shmem_request_hook() {
+        unsafe {
+            if let Some(prev_hook) = PREV_SHMEM_REQUEST_HOOK {
+                prev_hook();
+            }
+            pgrx::pg_sys::RequestAddinShmemSpace(size_of::<MetricsCounters>());
+        }
+    }
+
+    unsafe extern "C" fn shmem_startup_hook() {
+        unsafe {
+            if let Some(prev_hook) = PREV_SHMEM_STARTUP_HOOK {
+                prev_hook();
+            }
+        }
+
+        initialize_shared_counters();
+    }
+
+    unsafe {
+        PREV_SHMEM_REQUEST_HOOK = pgrx::pg_sys::shmem_request_hook;
+        pgrx::pg_sys::shmem_request_hook = Some(shmem_request_hook);
+
+        PREV_SHMEM_STARTUP_HOOK = pgrx::pg_sys::shmem_startup_hook;
+        pgrx::pg_sys::shmem_startup_hook = Some(shmem_startup_hook);
+    }
+}
+
+/// 공유 카운터 초기화
+fn initialize_shared_counters() {
+    let shmem_name = CString::new("pg_vector_refinement_metrics").unwrap();
+    let mut found = false;
+    let shmem = unsafe {
+        pgrx::pg_sys::ShmemInitStruct(
+            shmem_name.as_ptr(),
+            size_of::<MetricsCounters>(),
+            &mut found as *mut bool
+        )
+    } as *mut MetricsCounters;
+
+    if shmem.is_null() {
+        return;
+    }
+
+    // 처음 만든 경우 모든 카운터를 0으로 (AtomicU64는 0 바이트가 곧 0)
+    if !found {
+        unsafe {
+            std::ptr::write_bytes(shmem, 0, 1);
+        }
+    }
+    COUNTERS.store(shmem, Ordering::Release);
+}
@@ -1,13 +1,23 @@
 mod cache;
+mod diagnose;
+mod diff;
+#[cfg(any(test, feature = "pg_test"))]
+mod fixtures;
+mod metrics;
 mod refine;
 mod search;
 mod score;
 mod utils;
 
-pub use search::{analyze_with_refinement, perform_refinement_search};
+pub use search::{analyze_with_refinement, perform_refinement_search, perform_refinement_search_with_steps, rank_refinement_candidates};
+pub use metrics::refinement_metrics;
+pub use diagnose::{diagnose_refinement, would_refine};
+pub use score::warm_similarity_cache;
+pub(crate) use search::SubTransactionGuard;
 
 pub unsafe fn init() {
     unsafe {
         cache::init();    
+        metrics::init();
     }
 }
\ No newline at end of file
@@ -7,15 +7,25 @@ use pgrx::list::List;
 use super::score::{
     list_tables_by_similarity, 
     list_columns_by_similarity, 
+    list_columns_by_similarity_with_type,
     find_all_joinable_tables,
     find_joinable_tables_for_column, 
+    verify_join_right_column,
+    group_join_conditions,
     find_compatible_columns_for_operator,
     get_typecast_refinements_for_operator,
     find_compatible_arguments_for_function,
     get_typecast_refinements_for_function,
-    find_similar_functions,
+    find_similar_functions_with_aggregates,
+    is_aggregate_function,
     find_tables_with_exact_column,
-    find_similar_values_for_literal,
+    rank_tables_by_column_type,
+    find_similar_values_for_literals,
+    find_nearest_numeric_value,
+    weighted_column_statistics_penalty,
+    snap_to_step,
+    edit_distance,
+    find_similar_jsonb_keys,
     JoinCondition,
     OperandPosition,
     ArgumentPosition,
@@ -24,6 +34,15 @@ use super::score::{
 };
 use super::utils::copy_node;
 use crate::gucs::parser::{
+    ColumnQualification,
+    IdentifierFolding,
+    IDENTIFIER_FOLDING,
+    COLUMN_QUALIFICATION,
+    value_refinement_operators,
+    MAX_FROM_TABLES,
+    SAFEQL_CONSERVATIVE,
+    VALUE_REFINEMENT_NUMERIC_STEP,
+    SAME_TABLE_COLUMN_BONUS,
     TABLE_REFINEMENT_WEIGHT,
     COLUMN_REFINEMENT_WEIGHT,
     TABLE_FOR_COLUMN_WEIGHT,
@@ -35,6 +54,15 @@ use crate::gucs::parser::{
     FUNCTION_NAME_REFINEMENT_WEIGHT,
     COLUMN_AMBIGUITY_REFINEMENT_WEIGHT,
     VALUE_REFINEMENT_WEIGHT,
+    NULLIF_GUARD_REFINEMENT_WEIGHT,
+    JSONB_OPERATOR_REFINEMENT_WEIGHT,
+    NULL_HANDLING_REFINEMENT_WEIGHT,
+    SRF_LATERAL_REFINEMENT_WEIGHT,
+    WINDOW_OVER_REFINEMENT_WEIGHT,
+    FUNCTION_ARITY_REFINEMENT_WEIGHT,
+    ORDER_BY_TARGET_REFINEMENT_WEIGHT,
+    ORDINAL_POSITION_REFINEMENT_WEIGHT,
+    CASE_BRANCH_CAST_REFINEMENT_WEIGHT,
 };
 
 /* ------------------------------------------------
@@ -44,6 +72,18 @@ use crate::gucs::parser::{
 pub struct ColumnOperand {
     pub table_name: Option<String>,
     pub column_name: String,
+    /// 추출 시점에 알아낸 컬럼 타입 (typecast refinement에서 다시 조회하지 않도록)
+    pub resolved_type: Option<String>,
+}
+
+/* ------------------------------------------------
+리터럴 오퍼랜드 정보 (반대편 컬럼 타입으로 캐스팅할 때 사용)
+------------------------------------------------ */
+#[derive(Debug, Clone)]
+pub struct LiteralOperand {
+    pub position: OperandPosition,
+    pub expr_location: i32,
+    pub column_type: String,
 }
 
 /* ------------------------------------------------
@@ -79,7 +119,7 @@ pub fn generate_table_refinements_for_all_from_tables_raw(
             };
             
             // 자기 자신은 스킵
-            if candidate_table.to_ascii_lowercase() == target_table.to_ascii_lowercase() {
+            if identifiers_match(&candidate_table, target_table) {
                 continue;
             }
             
@@ -118,9 +158,9 @@ pub fn generate_table_refinements_raw(
     let existing_tables = extract_all_tables_from_raw(orig);
     let existing_table_names: std::collections::HashSet<String> = existing_tables.iter()
         .flat_map(|t| {
-            let mut names = vec![t.table_name.to_ascii_lowercase()];
+            let mut names = vec![fold_identifier(&t.table_name)];
             if let Some(ref alias) = t.alias {
-                names.push(alias.to_ascii_lowercase());
+                names.push(fold_identifier(alias));
             }
             names
         })
@@ -143,7 +183,7 @@ pub fn generate_table_refinements_raw(
         };
         
         // 현재 FROM절에 이미 있는 테이블이면 스킵
-        if existing_table_names.contains(&candidate_table.to_ascii_lowercase()) {
+        if existing_table_names.contains(&fold_identifier(&candidate_table)) {
             continue;
         }
 
@@ -151,6 +191,11 @@ pub fn generate_table_refinements_raw(
         let additional_priority = ((distance * 100.0) * weight) as i32;
         let cumulative_priority = base_priority + additional_priority;
         
+        // missing_rel이 alias면 FROM에 테이블을 추가해야 하므로 safeql.conservative에서는 만들지 않는다
+        if missing_rel != target_table_name && SAFEQL_CONSERVATIVE.get() {
+            continue;
+        }
+
         if let Some((_schema, rel)) = split_schema_rel(&fq) {
             let cloned = unsafe { copy_node(orig) };
             unsafe {
@@ -223,6 +268,14 @@ pub fn generate_column_refinements_raw(
     missing_col: &str,
     base_priority: i32
 ) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    // 함수 항목(generate_series 등)의 컬럼을 가리키면 카탈로그가 아니라 함수 컬럼 이름에서 찾는다
+    if let Some((qualifier, columns)) = function_column_scope(orig, table_name) {
+        return generate_function_column_refinements_raw(orig, table_name, missing_col, qualifier.as_deref(), &columns, base_priority);
+    }
+
+    // WHERE절에서 리터럴과 비교되고 있으면 그 타입으로 후보를 보정
+    let expected_type = unsafe { infer_missing_column_type(orig, missing_col) };
+
     // table_name이 있으면 해당 테이블에서만, 없으면 전체에서 검색
     let candidates = if let Some(table) = table_name {
         // 특정 테이블이 지정된 경우: 실제 테이블명으로 변환 후 해당 테이블에서만 검색
@@ -230,15 +283,18 @@ pub fn generate_column_refinements_raw(
             find_actual_table_name(orig, table)
                 .unwrap_or_else(|| table.to_string())
         };
-        list_columns_by_similarity(Some(&actual_table_name), missing_col, false)
+        list_columns_by_similarity_with_type(Some(&actual_table_name), missing_col, false, expected_type.as_deref())
     } else {
         // 테이블이 지정되지 않은 경우: 모든 테이블에서 검색
-        list_columns_by_similarity(None, missing_col, false)
+        list_columns_by_similarity_with_type(None, missing_col, false, expected_type.as_deref())
     };
     
     let mut out = Vec::with_capacity(candidates.len());
+    // 테이블이 지정되지 않았으면 FROM절에 이미 있는 테이블의 컬럼을 먼저 시도 (safeql.same_table_column_bonus)
+    let same_table_bonus = SAME_TABLE_COLUMN_BONUS.get() as f32;
+    let from_tables = extract_all_tables_from_raw(orig);
     
-    for (col_name, candidate_table_name, distance) in candidates {
+    for (col_name, candidate_table_name, mut distance) in candidates {
         // table_name이 지정된 경우 해당 테이블의 컬럼만 사용
         if let Some(specified_table) = table_name {
             let actual_table_name = unsafe { 
@@ -247,29 +303,192 @@ pub fn generate_column_refinements_raw(
             };
             
             // 후보 컬럼이 지정된 테이블에서 온 것이 아니면 스킵
-            if candidate_table_name.to_ascii_lowercase() != actual_table_name.to_ascii_lowercase() {
+            if !identifiers_match(&candidate_table_name, &actual_table_name) {
                 continue;
             }
         }
         
+        let from_table = from_tables
+            .iter()
+            .find(|t| identifiers_match(&t.table_name, &candidate_table_name));
+        if table_name.is_none() && from_table.is_some() {
+            distance = (distance - same_table_bonus).max(0.0);
+        }
+        
         // GUC 가중치를 적용하여 priority 계산
         let weight = COLUMN_REFINEMENT_WEIGHT.get() as f32;
         let additional_priority = ((distance * 100.0) * weight) as i32;
         let cumulative_priority = base_priority + additional_priority;
         
+        // qualified 모드에서 unqualified reference에 붙일 이름 (FROM절에 있는 후보 테이블의 alias 또는 이름)
+        let qualifier = table_name
+            .map(|t| t.to_string())
+            .or_else(|| from_table.map(|t| t.get_reference_name().to_string()));
+
         let cloned = unsafe { copy_node(orig) };
-        unsafe { replace_column_ref_inplace(cloned, missing_col, &col_name, table_name); }
+        unsafe { replace_column_ref_with_qualifier(cloned, missing_col, &col_name, table_name, qualifier.as_deref()); }
         out.push((cumulative_priority, cloned));
     }
     
     out
 }
 
+/// 누락된 컬럼이 FROM절 함수 항목의 컬럼이어야 하는 경우 (qualifier, 후보 컬럼들) 반환
+/// - qualifier가 함수의 alias/이름이면 그 함수의 컬럼
+/// - unqualified인데 FROM절에 테이블 없이 함수만 있으면 모든 함수의 컬럼
+fn function_column_scope(orig: *mut pg_sys::RawStmt, table_name: Option<&str>) -> Option<(Option<String>, Vec<String>)> {
+    let from_items = extract_from_items_from_raw(orig);
+    if from_items.functions.is_empty() {
+        return None;
+    }
+
+    match table_name {
+        Some(table) => from_items.functions.iter()
+            .find(|f| identifiers_match(f.get_reference_name(), table))
+            .map(|f| (Some(f.get_reference_name().to_string()), f.columns.clone())),
+        None if from_items.tables.is_empty() => {
+            let qualifier = (from_items.functions.len() == 1)
+                .then(|| from_items.functions[0].get_reference_name().to_string());
+            Some((qualifier, from_items.functions.into_iter().flat_map(|f| f.columns).collect()))
+        }
+        None => None,
+    }
+}
+
+/// 함수 컬럼 이름 중 edit distance가 가까운 것으로 교체하는 후보 생성 (임베딩 없이)
+fn generate_function_column_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    table_name: Option<&str>,
+    missing_col: &str,
+    qualifier: Option<&str>,
+    columns: &[String],
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let weight = COLUMN_REFINEMENT_WEIGHT.get() as f32;
+    let missing = fold_identifier(missing_col);
+    let max_distance = (missing.chars().count() / 3).max(1);
+
+    let mut out = Vec::new();
+    for column in columns {
+        let distance = edit_distance(&missing, &fold_identifier(column));
+        if distance == 0 || distance > max_distance {
+            continue;
+        }
+        let normalized = distance as f32 / missing.chars().count().max(1) as f32;
+        let cumulative_priority = base_priority + ((normalized * 100.0) * weight) as i32;
+
+        let cloned = unsafe { copy_node(orig) };
+        unsafe { replace_column_ref_with_qualifier(cloned, missing_col, column, table_name, qualifier); }
+        out.push((cumulative_priority, cloned));
+    }
+    out
+}
+
+/// 누락된 컬럼이 WHERE절에서 리터럴과 비교되는 경우 리터럴 타입으로 원래 컬럼 타입을 추정
+unsafe fn infer_missing_column_type(raw: *mut pg_sys::RawStmt, missing_col: &str) -> Option<String> {
+    unsafe {
+        for expr_node in find_all_where_expressions(raw) {
+            let a_expr = expr_node as *mut pg_sys::A_Expr;
+            let (lexpr, rexpr) = ((*a_expr).lexpr, (*a_expr).rexpr);
+
+            let literal = if column_ref_name(lexpr).is_some_and(|c| identifiers_match(&c, missing_col)) {
+                rexpr
+            } else if column_ref_name(rexpr).is_some_and(|c| identifiers_match(&c, missing_col)) {
+                lexpr
+            } else {
+                continue;
+            };
+
+            if let Some(literal_type) = literal_type_name(literal) {
+                return Some(literal_type);
+            }
+        }
+    }
+    None
+}
+
+/// ColumnRef의 마지막 필드(컬럼명)
+unsafe fn column_ref_name(node: *mut pg_sys::Node) -> Option<String> {
+    unsafe {
+        if !is_column_ref(node) {
+            return None;
+        }
+        let col_ref = node as *mut pg_sys::ColumnRef;
+
+        memcx::current_context(|mcx| {
+            let fields = List::<*mut c_void>::downcast_ptr_in_memcx((*col_ref).fields, mcx)?;
+            let last_field = *fields.get(fields.len().checked_sub(1)?)? as *mut pg_sys::Node;
+            if (*last_field).type_ != pg_sys::NodeTag::T_String {
+                return None;
+            }
+            let str_node = last_field as *mut pg_sys::String;
+            Some(CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned())
+        })
+    }
+}
+
+/// 리터럴(또는 리터럴 캐스트)의 타입 이름. 타입이 정해지지 않은 문자열 리터럴은 None
+pub(super) unsafe fn literal_type_name(node: *mut pg_sys::Node) -> Option<String> {
+    if node.is_null() {
+        return None;
+    }
+
+    unsafe {
+        match (*node).type_ {
+            pg_sys::NodeTag::T_A_Const => {
+                let a_const = node as *mut pg_sys::A_Const;
+                if (*a_const).isnull {
+                    return None;
+                }
+                match (*a_const).val.node.type_ {
+                    pg_sys::NodeTag::T_Integer => Some("integer".to_string()),
+                    pg_sys::NodeTag::T_Float => Some("numeric".to_string()),
+                    pg_sys::NodeTag::T_Boolean => Some("boolean".to_string()),
+                    _ => None,
+                }
+            }
+            pg_sys::NodeTag::T_TypeCast => {
+                let type_name = (*(node as *mut pg_sys::TypeCast)).typeName;
+                if type_name.is_null() {
+                    return None;
+                }
+                memcx::current_context(|mcx| {
+                    let names = List::<*mut c_void>::downcast_ptr_in_memcx((*type_name).names, mcx)?;
+                    let last_name = *names.get(names.len().checked_sub(1)?)? as *mut pg_sys::Node;
+                    if (*last_name).type_ != pg_sys::NodeTag::T_String {
+                        return None;
+                    }
+                    let str_node = last_name as *mut pg_sys::String;
+                    Some(CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned())
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// safeql.identifier_folding에 따라 식별자를 비교용으로 정규화
+pub(crate) fn fold_identifier(name: &str) -> String {
+    match IDENTIFIER_FOLDING.get() {
+        IdentifierFolding::lower => name.to_ascii_lowercase(),
+        IdentifierFolding::preserve => name.to_string(),
+        IdentifierFolding::unicode_casefold => name.to_lowercase(),
+    }
+}
+
+/// 두 식별자를 같은 방식으로 folding한 뒤 비교
+pub(crate) fn identifiers_match(a: &str, b: &str) -> bool {
+    fold_identifier(a) == fold_identifier(b)
+}
+
 #[derive(Debug)]
 struct ReplaceColumnCtx {
     missing: String,
     new_col: CString,
     table_filter: Option<String>, // 특정 테이블의 컬럼만 교체
+    unqualified_only: bool,       // table_filter가 없을 때 unqualified reference만 교체
+    qualification: ColumnQualification,
+    qualifier: Option<String>,    // qualified 모드에서 붙일 테이블/alias
     replaced_any: bool,
 }
 
@@ -278,17 +497,52 @@ unsafe fn replace_column_ref_inplace(
     missing: &str,
     new_col: &str,
     table_filter: Option<&str>,
+) {
+    unsafe { replace_column_ref_with_qualifier(raw, missing, new_col, table_filter, table_filter) }
+}
+
+/// replace_column_ref_inplace + safeql.column_qualification 적용
+/// qualifier가 없으면 qualified 모드에서도 unqualified reference는 그대로 둔다
+unsafe fn replace_column_ref_with_qualifier(
+    raw: *mut pg_sys::RawStmt,
+    missing: &str,
+    new_col: &str,
+    table_filter: Option<&str>,
+    qualifier: Option<&str>,
 ) {
     let mut ctx = ReplaceColumnCtx {
-        missing: missing.to_ascii_lowercase(),
+        missing: missing.to_string(),
         new_col: CString::new(new_col).unwrap(),
         table_filter: table_filter.map(|s| s.to_string()),
+        unqualified_only: false,
+        qualification: COLUMN_QUALIFICATION.get(),
+        qualifier: qualifier.map(|s| s.to_string()),
         replaced_any: false,
     };
     let ctx_ptr = &mut ctx as *mut ReplaceColumnCtx as *mut c_void;
 
     unsafe {
+        // ORDER BY의 `missing`이 같은 이름의 출력 alias를 가리키면 컬럼 교체 대상에서 뺀다
+        let order_by_refers_to_alias = has_output_alias(raw, missing);
+        let detached_sort_clause = if order_by_refers_to_alias { detach_sort_clause(raw) } else { None };
+
         safe_raw_expression_tree_walker((*raw).stmt, Some(raw_replace_column_walker), ctx_ptr);
+
+        if let Some((select_stmt, sort_clause)) = detached_sort_clause {
+            (*select_stmt).sortClause = sort_clause;
+        }
+
+        // qualified reference만 바꾼 경우 GROUP BY/ORDER BY/HAVING의 unqualified reference가 남을 수 있다
+        // (table_filter가 없으면 walker가 이미 바꿨으므로 아래 sync는 아무것도 바꾸지 않는다)
+        if ctx.replaced_any {
+            sync_group_by_column_refs(raw, missing, new_col);
+            sync_order_by_and_having_column_refs(raw, missing, new_col, !order_by_refers_to_alias);
+        }
+
+        // INSERT 대상 컬럼 목록은 ColumnRef가 아니라 ResTarget.name이라 walker가 바꾸지 못한다
+        if replace_insert_target_column(raw, missing, new_col, table_filter) {
+            ctx.replaced_any = true;
+        }
     }
     
     // if ctx.replaced_any {
@@ -340,11 +594,11 @@ unsafe extern "C" fn raw_replace_column_walker(node: *mut pg_sys::Node, ctx: *mu
                                     let cur_col = CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned();
 
                                     // 조건: (컬럼명이 맞고) + (table_filter 없거나, 일치하는 경우만)
-                                    let column_match = cur_col.to_ascii_lowercase() == ctx.missing;
+                                    let column_match = identifiers_match(&cur_col, &ctx.missing);
                                     let table_match = match (&ctx.table_filter, &table_name) {
-                                        (Some(filter), Some(tbl)) => tbl.to_ascii_lowercase() == filter.to_ascii_lowercase(),
+                                        (Some(filter), Some(tbl)) => identifiers_match(tbl, filter),
                                         (Some(_), None) => false, // 필터가 있는데 테이블명이 없는 경우 -> qualified reference가 아님
-                                        (None, _) => true,        // 필터 없으면 무조건 허용 (unqualified reference)
+                                        (None, _) => !(ctx.unqualified_only && field_count > 1), // 필터 없으면 허용 (unqualified_only면 unqualified만)
                                     };
 
                                     if column_match && table_match {
@@ -375,6 +629,7 @@ unsafe extern "C" fn raw_replace_column_walker(node: *mut pg_sys::Node, ctx: *mu
                 });
                 
                 if should_stop {
+                    apply_column_qualification(col_ref, ctx.qualification, ctx.qualifier.as_deref());
                     return false;
                 }
             }
@@ -386,6 +641,198 @@ unsafe extern "C" fn raw_replace_column_walker(node: *mut pg_sys::Node, ctx: *mu
     }
 }
 
+/// 교체된 ColumnRef의 fields를 qualification 방식에 맞게 조정
+unsafe fn apply_column_qualification(
+    col_ref: *mut pg_sys::ColumnRef,
+    qualification: ColumnQualification,
+    qualifier: Option<&str>,
+) {
+    unsafe {
+        let fields = memcx::current_context(|mcx| {
+            List::<*mut c_void>::downcast_ptr_in_memcx((*col_ref).fields, mcx)
+                .map(|fields| fields.iter().map(|f| *f as *mut pg_sys::Node).collect::<Vec<_>>())
+        });
+        let Some(column_node) = fields.as_ref().and_then(|f| f.last().copied()) else {
+            return;
+        };
+
+        match (qualification, qualifier) {
+            (ColumnQualification::unqualified, _) if fields.as_ref().is_some_and(|f| f.len() > 1) => {
+                (*col_ref).fields = create_single_item_list(column_node);
+            }
+            (ColumnQualification::qualified, Some(qualifier)) if fields.as_ref().is_some_and(|f| f.len() == 1) => {
+                (*col_ref).fields = pg_sys::list_make2_impl(
+                    pg_sys::NodeTag::T_List,
+                    pg_sys::ListCell { ptr_value: create_string_node(qualifier) as *mut c_void },
+                    pg_sys::ListCell { ptr_value: column_node as *mut c_void },
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// INSERT INTO rel (col, ...)의 대상 컬럼 이름 교체
+/// table_filter가 있으면 INSERT 대상 테이블명 또는 alias와 일치할 때만 교체
+unsafe fn replace_insert_target_column(
+    raw: *mut pg_sys::RawStmt,
+    missing: &str,
+    new_col: &str,
+    table_filter: Option<&str>,
+) -> bool {
+    unsafe {
+        let stmt = (*raw).stmt;
+        if stmt.is_null() || (*stmt).type_ != pg_sys::NodeTag::T_InsertStmt {
+            return false;
+        }
+        let insert_stmt = stmt as *mut pg_sys::InsertStmt;
+        let relation = (*insert_stmt).relation;
+        if relation.is_null() || (*insert_stmt).cols.is_null() {
+            return false;
+        }
+
+        if let Some(filter) = table_filter {
+            let relname = CStr::from_ptr((*relation).relname).to_string_lossy();
+            let alias_match = !(*relation).alias.is_null()
+                && identifiers_match(&CStr::from_ptr((*(*relation).alias).aliasname).to_string_lossy(), filter);
+            if !identifiers_match(&relname, filter) && !alias_match {
+                return false;
+            }
+        }
+
+        let new_col = CString::new(new_col).unwrap();
+        memcx::current_context(|mcx| {
+            let Some(cols) = List::<*mut c_void>::downcast_ptr_in_memcx((*insert_stmt).cols, mcx) else {
+                return false;
+            };
+
+            let mut replaced = false;
+            for col_ptr in cols.iter() {
+                let res_target = *col_ptr as *mut pg_sys::ResTarget;
+                if res_target.is_null() || (*res_target).name.is_null() {
+                    continue;
+                }
+                let name = CStr::from_ptr((*res_target).name).to_string_lossy();
+                if identifiers_match(&name, missing) {
+                    (*res_target).name = pg_sys::pstrdup(new_col.as_ptr());
+                    replaced = true;
+                }
+            }
+            replaced
+        })
+    }
+}
+
+/// 컬럼을 바꾼 뒤 GROUP BY에 남은 옛 이름의 unqualified reference도 같이 바꿔서
+/// 컬럼 refinement가 새 grouping 에러를 만들지 않게 한다
+unsafe fn sync_group_by_column_refs(raw: *mut pg_sys::RawStmt, old_col: &str, new_col: &str) {
+    if identifiers_match(old_col, new_col) {
+        return;
+    }
+
+    unsafe {
+        let Some(select_stmt) = find_select_stmt(raw) else {
+            return;
+        };
+        if (*select_stmt).groupClause.is_null() {
+            return;
+        }
+
+        let mut ctx = ReplaceColumnCtx {
+            missing: old_col.to_string(),
+            new_col: CString::new(new_col).unwrap(),
+            table_filter: None,
+            unqualified_only: true,
+            qualification: ColumnQualification::preserve,
+            qualifier: None,
+            replaced_any: false,
+        };
+        let ctx_ptr = &mut ctx as *mut ReplaceColumnCtx as *mut c_void;
+
+        safe_raw_expression_tree_walker(
+            (*select_stmt).groupClause as *mut pg_sys::Node,
+            Some(raw_replace_column_walker),
+            ctx_ptr
+        );
+    }
+}
+
+/// SELECT 목록에 `name`과 같은 명시적 출력 alias(ResTarget.name)가 있는지 확인
+unsafe fn has_output_alias(raw: *mut pg_sys::RawStmt, name: &str) -> bool {
+    unsafe {
+        let Some(select_stmt) = find_select_stmt(raw) else {
+            return false;
+        };
+        if (*select_stmt).targetList.is_null() {
+            return false;
+        }
+
+        memcx::current_context(|mcx| {
+            let Some(targets) = List::<*mut c_void>::downcast_ptr_in_memcx((*select_stmt).targetList, mcx) else {
+                return false;
+            };
+            targets.iter().any(|target_ptr| {
+                let res_target = *target_ptr as *mut pg_sys::ResTarget;
+                !res_target.is_null()
+                    && !(*res_target).name.is_null()
+                    && identifiers_match(&CStr::from_ptr((*res_target).name).to_string_lossy(), name)
+            })
+        })
+    }
+}
+
+/// walker가 ORDER BY를 건드리지 않도록 sortClause를 잠시 떼어낸다 (호출자가 다시 붙인다)
+unsafe fn detach_sort_clause(raw: *mut pg_sys::RawStmt) -> Option<(*mut pg_sys::SelectStmt, *mut pg_sys::List)> {
+    unsafe {
+        let select_stmt = find_select_stmt(raw)?;
+        let sort_clause = (*select_stmt).sortClause;
+        (*select_stmt).sortClause = std::ptr::null_mut();
+        Some((select_stmt, sort_clause))
+    }
+}
+
+/// 컬럼을 바꾼 뒤 ORDER BY(출력 컬럼 이름으로 참조하는 경우)와 HAVING에 남은 옛 이름의
+/// unqualified reference도 같이 바꾼다. ORDER BY가 같은 이름의 출력 alias를 가리키면 그대로 둔다
+unsafe fn sync_order_by_and_having_column_refs(
+    raw: *mut pg_sys::RawStmt,
+    old_col: &str,
+    new_col: &str,
+    include_order_by: bool,
+) {
+    if identifiers_match(old_col, new_col) {
+        return;
+    }
+
+    unsafe {
+        let Some(select_stmt) = find_select_stmt(raw) else {
+            return;
+        };
+
+        let mut ctx = ReplaceColumnCtx {
+            missing: old_col.to_string(),
+            new_col: CString::new(new_col).unwrap(),
+            table_filter: None,
+            unqualified_only: true,
+            qualification: ColumnQualification::preserve,
+            qualifier: None,
+            replaced_any: false,
+        };
+        let ctx_ptr = &mut ctx as *mut ReplaceColumnCtx as *mut c_void;
+
+        if include_order_by && !(*select_stmt).sortClause.is_null() {
+            safe_raw_expression_tree_walker(
+                (*select_stmt).sortClause as *mut pg_sys::Node,
+                Some(raw_replace_column_walker),
+                ctx_ptr
+            );
+        }
+        if !(*select_stmt).havingClause.is_null() {
+            // havingClause 자체가 ColumnRef일 수도 있으므로 직접 walker를 호출
+            raw_replace_column_walker((*select_stmt).havingClause, ctx_ptr);
+        }
+    }
+}
+
 /* ------------------------------------------------
 CASE 3) FROM Rel -> FROM Rel' - 칼럼이 참조하는 테이블 refinement 수행
 ------------------------------------------------ */
@@ -398,7 +845,7 @@ pub fn generate_table_refinements_for_column_error_raw(
     // 현재 FROM절의 모든 테이블명 추출 (중복 방지용)
     let existing_tables = extract_all_tables_from_raw(orig);
     let existing_table_names: std::collections::HashSet<String> = existing_tables.iter()
-        .map(|t| t.table_name.to_ascii_lowercase())
+        .map(|t| fold_identifier(&t.table_name))
         .collect();
 
     // 먼저 old_table_name이 alias인지 실제 테이블명인지 확인
@@ -418,8 +865,8 @@ pub fn generate_table_refinements_for_column_error_raw(
         };
         
         // 현재 FROM절에 이미 있는 테이블이면 스킵 (단, 교체 대상인 테이블은 제외)
-        if existing_table_names.contains(&candidate_table.to_ascii_lowercase()) && 
-           candidate_table.to_ascii_lowercase() != target_table.to_ascii_lowercase() {
+        if existing_table_names.contains(&fold_identifier(&candidate_table)) && 
+           !identifiers_match(&candidate_table, target_table) {
             continue;
         }
 
@@ -447,7 +894,7 @@ pub fn generate_table_refinements_for_column_error_raw(
 /// old_table_name이 실제로 어떤 테이블을 가리키는지 찾기
 unsafe fn find_actual_table_name(raw: *mut pg_sys::RawStmt, reference_name: &str) -> Option<String> {
     let mut result = None;
-    let reference_lower = reference_name.to_ascii_lowercase();
+    let reference_lower = fold_identifier(reference_name);
     
     unsafe {
         safe_raw_expression_tree_walker(
@@ -478,7 +925,7 @@ unsafe extern "C" fn find_table_walker(node: *mut pg_sys::Node, ctx: *mut c_void
             };
 
             // 1) 실제 테이블명과 매치되는 경우
-            if table_name.to_ascii_lowercase() == *reference_name {
+            if fold_identifier(&table_name) == *reference_name {
                 **result = Some(table_name);
                 return false; // 찾았으므로 중단
             }
@@ -488,7 +935,7 @@ unsafe extern "C" fn find_table_walker(node: *mut pg_sys::Node, ctx: *mut c_void
                 let alias = (*rv).alias as *mut pg_sys::Alias;
                 if !(*alias).aliasname.is_null() {
                     let alias_name = CStr::from_ptr((*alias).aliasname).to_string_lossy().into_owned();
-                    if alias_name.to_ascii_lowercase() == *reference_name {
+                    if fold_identifier(&alias_name) == *reference_name {
                         **result = Some(table_name); // 실제 테이블명 반환
                         return false; // 찾았으므로 중단
                     }
@@ -520,19 +967,50 @@ impl TableInfo {
     }
 }
 
-/// FROM절에서 모든 테이블명과 alias 추출
-pub fn extract_all_tables_from_raw(raw: *mut pg_sys::RawStmt) -> Vec<TableInfo> {
-    let mut tables = Vec::new();
-    let tables_ptr = &mut tables as *mut Vec<TableInfo> as *mut c_void;
-    
+/// FROM절의 함수 항목 (generate_series(1, 10) g(n), unnest(arr) u 등)
+#[derive(Debug, Clone)]
+pub struct FunctionRangeInfo {
+    pub function_name: String,
+    pub alias: Option<String>,
+    pub columns: Vec<String>, // 함수가 만드는 컬럼 이름 (column alias가 없으면 alias 또는 함수명)
+}
+
+impl FunctionRangeInfo {
+    /// 참조할 때 사용할 이름 반환 (alias가 있으면 alias, 없으면 함수명)
+    pub fn get_reference_name(&self) -> &str {
+        self.alias.as_ref().unwrap_or(&self.function_name)
+    }
+}
+
+/// extract_tables_with_alias_walker가 모으는 FROM절 항목
+#[derive(Default)]
+struct FromItemsCtx {
+    tables: Vec<TableInfo>,
+    functions: Vec<FunctionRangeInfo>,
+}
+
+fn extract_from_items_from_raw(raw: *mut pg_sys::RawStmt) -> FromItemsCtx {
+    let mut ctx = FromItemsCtx::default();
+    let ctx_ptr = &mut ctx as *mut FromItemsCtx as *mut c_void;
+
     unsafe {
-        safe_raw_expression_tree_walker((*raw).stmt, Some(extract_tables_with_alias_walker), tables_ptr);
+        safe_raw_expression_tree_walker((*raw).stmt, Some(extract_tables_with_alias_walker), ctx_ptr);
     }
-    
-    tables
+
+    ctx
+}
+
+/// FROM절에서 모든 테이블명과 alias 추출
+pub fn extract_all_tables_from_raw(raw: *mut pg_sys::RawStmt) -> Vec<TableInfo> {
+    extract_from_items_from_raw(raw).tables
+}
+
+/// FROM절에서 모든 함수 항목과 그 컬럼 이름 추출
+pub fn extract_all_function_ranges_from_raw(raw: *mut pg_sys::RawStmt) -> Vec<FunctionRangeInfo> {
+    extract_from_items_from_raw(raw).functions
 }
 
-/// 테이블과 alias를 함께 추출하는 walker 함수
+/// 테이블과 alias를 함께 추출하는 walker 함수 (함수 항목은 따로 모은다)
 unsafe extern "C" fn extract_tables_with_alias_walker(
     node: *mut pg_sys::Node,
     context: *mut c_void,
@@ -541,7 +1019,7 @@ unsafe extern "C" fn extract_tables_with_alias_walker(
         return false;
     }
 
-    let tables = context as *mut Vec<TableInfo>;
+    let ctx = context as *mut FromItemsCtx;
 
     unsafe {
         match (*node).type_ {
@@ -560,7 +1038,12 @@ unsafe extern "C" fn extract_tables_with_alias_walker(
                     Some(alias_name)
                 };
                 
-                (*tables).push(TableInfo::new(table_name, alias));
+                (*ctx).tables.push(TableInfo::new(table_name, alias));
+            }
+            pg_sys::NodeTag::T_RangeFunction => {
+                if let Some(function) = function_range_info(node as *mut pg_sys::RangeFunction) {
+                    (*ctx).functions.push(function);
+                }
             }
             _ => {}
         }
@@ -569,6 +1052,59 @@ unsafe extern "C" fn extract_tables_with_alias_walker(
     }
 }
 
+/// RangeFunction에서 함수명, alias, 컬럼 이름 추출
+/// 컬럼 이름은 alias의 column 목록 > 컬럼 정의 목록 > alias > 함수명 순으로 정한다
+unsafe fn function_range_info(range_function: *mut pg_sys::RangeFunction) -> Option<FunctionRangeInfo> {
+    unsafe {
+        memcx::current_context(|mcx| {
+            // functions는 (FuncCall, coldeflist) 쌍의 리스트
+            let functions = List::<*mut c_void>::downcast_ptr_in_memcx((*range_function).functions, mcx)?;
+            let pair = List::<*mut c_void>::downcast_ptr_in_memcx(*functions.get(0)? as *mut pg_sys::List, mcx)?;
+            let func_node = *pair.get(0)? as *mut pg_sys::Node;
+            if func_node.is_null() || (*func_node).type_ != pg_sys::NodeTag::T_FuncCall {
+                return None;
+            }
+            let func_names = List::<*mut c_void>::downcast_ptr_in_memcx((*(func_node as *mut pg_sys::FuncCall)).funcname, mcx)?;
+            let function_name = string_node_value(*func_names.get(func_names.len() - 1)? as *mut pg_sys::Node)?;
+
+            let alias_node = (*range_function).alias;
+            let alias = (!alias_node.is_null())
+                .then(|| CStr::from_ptr((*alias_node).aliasname).to_string_lossy().into_owned());
+
+            let mut columns: Vec<String> = Vec::new();
+            if !alias_node.is_null() {
+                if let Some(colnames) = List::<*mut c_void>::downcast_ptr_in_memcx((*alias_node).colnames, mcx) {
+                    columns.extend(colnames.iter().filter_map(|c| string_node_value(*c as *mut pg_sys::Node)));
+                }
+            }
+            if columns.is_empty() {
+                if let Some(coldefs) = List::<*mut c_void>::downcast_ptr_in_memcx((*range_function).coldeflist, mcx) {
+                    columns.extend(coldefs.iter().filter_map(|c| {
+                        let column_def = *c as *mut pg_sys::ColumnDef;
+                        (!column_def.is_null() && !(*column_def).colname.is_null())
+                            .then(|| CStr::from_ptr((*column_def).colname).to_string_lossy().into_owned())
+                    }));
+                }
+            }
+            if columns.is_empty() {
+                columns.push(alias.clone().unwrap_or_else(|| function_name.clone()));
+            }
+
+            Some(FunctionRangeInfo { function_name, alias, columns })
+        })
+    }
+}
+
+/// String 노드의 값
+unsafe fn string_node_value(node: *mut pg_sys::Node) -> Option<String> {
+    unsafe {
+        if node.is_null() || (*node).type_ != pg_sys::NodeTag::T_String {
+            return None;
+        }
+        Some(CStr::from_ptr((*(node as *mut pg_sys::String)).sval).to_string_lossy().into_owned())
+    }
+}
+
 /// 테이블명이 특정되지 않은 경우 FROM절의 모든 테이블에 대해 refinement 생성
 pub fn generate_table_refinements_for_all_tables_raw(
     orig: *mut pg_sys::RawStmt,
@@ -577,7 +1113,7 @@ pub fn generate_table_refinements_for_all_tables_raw(
     // FROM절의 모든 테이블 추출
     let from_tables = extract_all_tables_from_raw(orig);
     let existing_table_names: std::collections::HashSet<String> = from_tables.iter()
-        .map(|t| t.table_name.to_ascii_lowercase())
+        .map(|t| fold_identifier(&t.table_name))
         .collect();
     
     let mut out = Vec::new();
@@ -596,8 +1132,8 @@ pub fn generate_table_refinements_for_all_tables_raw(
             };
             
             // 현재 FROM절에 이미 있는 테이블이면 스킵 (단, 교체 대상인 테이블은 제외)
-            if existing_table_names.contains(&candidate_table.to_ascii_lowercase()) && 
-               candidate_table.to_ascii_lowercase() != table_info.table_name.to_ascii_lowercase() {
+            if existing_table_names.contains(&fold_identifier(&candidate_table)) && 
+               !identifiers_match(&candidate_table, &table_info.table_name) {
                 continue;
             }
 
@@ -643,9 +1179,9 @@ unsafe fn replace_table_intelligently(
     };
 
     let mut ctx = IntelligentTableReplaceCtx {
-        old_reference: old_reference.to_ascii_lowercase(),
+        old_reference: fold_identifier(old_reference),
         new_table: CString::new(new_table).unwrap(),
-        actual_old_table: actual_old_table.to_ascii_lowercase(),
+        actual_old_table: fold_identifier(&actual_old_table),
         replaced_any: false,
     };
     let ctx_ptr = &mut ctx as *mut IntelligentTableReplaceCtx as *mut c_void;
@@ -680,7 +1216,7 @@ unsafe extern "C" fn intelligent_table_replace_walker(node: *mut pg_sys::Node, c
                 };
                 
                 // 실제 테이블명이 일치하면 교체
-                if cur_rel.to_ascii_lowercase() == ctx.actual_old_table {
+                if fold_identifier(&cur_rel) == ctx.actual_old_table {
                     (*rv).relname = pg_sys::pstrdup(ctx.new_table.as_ptr());
                     ctx.replaced_any = true;
                     
@@ -707,7 +1243,7 @@ unsafe extern "C" fn intelligent_table_replace_walker(node: *mut pg_sys::Node, c
                                         let qualifier = CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned();
                                         
                                         // qualifier가 old_reference와 일치하면 교체
-                                        if qualifier.to_ascii_lowercase() == ctx.old_reference {
+                                        if fold_identifier(&qualifier) == ctx.old_reference {
                                             // old_reference가 실제 테이블명이었다면 새 테이블명으로 교체
                                             // old_reference가 alias였다면 alias는 그대로 유지
                                             let new_qualifier = if ctx.old_reference == ctx.actual_old_table {
@@ -768,8 +1304,8 @@ pub fn generate_column_table_reference_refinements_raw(
         let reference_name = table_info.get_reference_name();
         
         // 현재 테이블과 동일한 경우 스킵
-        if reference_name.to_ascii_lowercase() == current_table.to_ascii_lowercase() ||
-           table_info.table_name.to_ascii_lowercase() == current_table.to_ascii_lowercase() {
+        if identifiers_match(reference_name, current_table) ||
+           identifiers_match(&table_info.table_name, current_table) {
             continue;
         }
         
@@ -819,9 +1355,9 @@ unsafe fn replace_column_table_reference(
     new_column: &str,
 ) {
     let mut ctx = ColumnTableReferenceCtx {
-        old_table: old_table.to_ascii_lowercase(),
+        old_table: fold_identifier(old_table),
         new_table: new_table.to_string(),
-        old_column: old_column.to_ascii_lowercase(),
+        old_column: fold_identifier(old_column),
         new_column: new_column.to_string(),
         replaced_any: false,
     };
@@ -867,7 +1403,7 @@ unsafe extern "C" fn column_table_reference_walker(node: *mut pg_sys::Node, ctx:
                                     let str_node = first_field as *mut pg_sys::String;
                                     let table_name = CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned();
                                     
-                                    if table_name.to_ascii_lowercase() == ctx.old_table {
+                                    if fold_identifier(&table_name) == ctx.old_table {
                                         table_match = true;
                                     }
                                 }
@@ -881,7 +1417,7 @@ unsafe extern "C" fn column_table_reference_walker(node: *mut pg_sys::Node, ctx:
                                     let str_node = last_field as *mut pg_sys::String;
                                     let column_name = CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned();
                                     
-                                    if column_name.to_ascii_lowercase() == ctx.old_column {
+                                    if fold_identifier(&column_name) == ctx.old_column {
                                         column_match = true;
                                     }
                                 }
@@ -949,9 +1485,14 @@ pub fn generate_one_hop_join_refinements_for_all_tables_raw(
     if existing_table_names.is_empty() {
         return out;
     }
+
+    // FROM절이 이미 safeql.max_from_tables개면 더 JOIN하지 않는다
+    if existing_table_names.len() >= MAX_FROM_TABLES.get() as usize {
+        return out;
+    }
     
     let existing_table_set: std::collections::HashSet<String> = existing_table_names.iter()
-        .map(|t| t.to_ascii_lowercase())
+        .map(|t| fold_identifier(t))
         .collect();
     
     let weight = JOIN_REFINEMENT_WEIGHT.get() as f32;
@@ -961,23 +1502,22 @@ pub fn generate_one_hop_join_refinements_for_all_tables_raw(
     
     for (join_table, _distance, join_conditions) in joinable_tables {
         // 이미 FROM절에 있는 테이블이면 스킵
-        if existing_table_set.contains(&join_table.to_ascii_lowercase()) {
+        if existing_table_set.contains(&fold_identifier(&join_table)) {
             continue;
         }
         
-        // 각 JOIN 조건에 대해 refinement 생성
-        for join_condition in join_conditions {
+        // 각 FK 제약조건(복합 FK면 모든 컬럼 쌍)에 대해 refinement 생성
+        for join_group in group_join_conditions(&join_conditions) {
+            let Some(join_group) = verify_join_condition_group(&join_group) else {
+                continue;
+            };
             let additional_priority = ((1.0 * 100.0) * weight) as i32;
             let cumulative_priority = base_priority + additional_priority;
             
             let cloned = unsafe { copy_node(orig) };
             unsafe {
-                // FROM절의 테이블 정보를 고려하여 JOIN 조건 조정
-                let existing_tables = extract_all_tables_from_raw(cloned);
-                let adjusted_condition = adjust_join_condition_for_existing_alias(&join_condition, &existing_tables);
-                
-                // 테이블 추가 및 WHERE 조건 추가
-                add_table_and_where_condition(cloned, &join_table, &adjusted_condition);
+                // 테이블 추가 및 WHERE 조건 추가 (기존 테이블 alias는 내부에서 반영)
+                add_table_and_where_condition(cloned, &join_table, &join_group);
             }
             out.push((cumulative_priority, cloned));
             
@@ -991,62 +1531,147 @@ pub fn generate_one_hop_join_refinements_for_all_tables_raw(
     out
 }
 
-pub fn generate_join_refinements_raw(
+/* ------------------------------------------------
+CASE 5-1) missing FROM-clause entry for table "S" - S를 rename하지 않고 JOIN으로 추가
+------------------------------------------------ */
+pub fn generate_missing_from_entry_refinements_raw(
     orig: *mut pg_sys::RawStmt,
-    missing_col: &str,
+    missing_ref: &str,
     base_priority: i32
 ) -> Vec<(i32, *mut pg_sys::RawStmt)> {
     let mut out = Vec::new();
-    
-    // 현재 FROM절의 모든 테이블 추출
+
     let from_tables = extract_all_tables_from_raw(orig);
     let existing_table_names: Vec<String> = from_tables.iter()
         .map(|t| t.table_name.clone())
         .collect();
-    
-    let weight = JOIN_REFINEMENT_WEIGHT.get() as f32; // 가중치 미리 가져오기
-    
+
     if existing_table_names.is_empty() {
-        // FROM절이 비어있는 경우: 테이블을 직접 추가
-        let column_candidates = list_columns_by_similarity(None, missing_col, false);
-        
-        for (_col_name, table_name, distance) in column_candidates {
-            // GUC 가중치를 적용하여 priority 계산
-            let additional_priority = (((distance + 1.0) * 100.0) * weight) as i32;
-            let cumulative_priority = base_priority + additional_priority;
-            
-            let cloned = unsafe { copy_node(orig) };
-            unsafe {
-                add_table_to_empty_from_clause(cloned, &table_name);
-            }
-            out.push((cumulative_priority, cloned));
-        }
-        
         return out;
     }
-    
-    // PK-FK 관계로 JOIN 가능한 테이블들 찾기 (기존 테이블들과 중복되지 않는 것만)
-    let joinable_tables = find_joinable_tables_for_column(&existing_table_names, missing_col);
+
+    let weight = JOIN_REFINEMENT_WEIGHT.get() as f32;
+
+    // 참조된 이름이 실제 테이블이고 FROM절 테이블과 PK-FK(또는 같은 이름 컬럼)로 연결되면 그 조건으로 JOIN
+    let joinable_tables = find_all_joinable_tables(&existing_table_names);
+    let Some((join_table, _distance, join_conditions)) = joinable_tables.into_iter()
+        .find(|(table, _, _)| identifiers_match(table, missing_ref)) else {
+        return out;
+    };
+
+    for join_group in group_join_conditions(&join_conditions) {
+        let Some(join_group) = verify_join_condition_group(&join_group) else {
+            continue;
+        };
+        let cloned = unsafe { copy_node(orig) };
+        unsafe {
+            add_referenced_table_with_condition(cloned, &join_table, missing_ref, Some(&join_group));
+        }
+        out.push((base_priority, cloned));
+    }
+
+    // WHERE절에 이미 JOIN 조건이 있을 수 있으므로 조건 없이 추가하는 후보도 둔다
+    let cloned = unsafe { copy_node(orig) };
+    unsafe {
+        add_referenced_table_with_condition(cloned, &join_table, missing_ref, None);
+    }
+    out.push((base_priority + ((1.0 * 100.0) * weight) as i32, cloned));
+
+    out
+}
+
+/// FROM절에 쿼리가 참조한 이름 그대로 테이블 추가 (+ JOIN 조건)
+unsafe fn add_referenced_table_with_condition(
+    raw: *mut pg_sys::RawStmt,
+    table_name: &str,
+    reference_name: &str,
+    join_conditions: Option<&[JoinCondition]>,
+) {
+    unsafe {
+        if let Some(select_stmt) = find_select_stmt(raw) {
+            let existing_tables = extract_all_tables_from_raw(raw);
+
+            let table_cstr = CString::new(table_name).unwrap();
+            add_table_to_from_list((*select_stmt).fromClause, create_range_var(&table_cstr) as *mut pg_sys::Node);
+
+            for condition in join_conditions.unwrap_or_default() {
+                let mut adjusted_condition = adjust_join_condition_for_existing_alias(condition, &existing_tables);
+                adjusted_condition.right_table = reference_name.to_string();
+                add_condition_to_where_clause(select_stmt, create_join_condition_expr(&adjusted_condition));
+            }
+        }
+    }
+}
+
+pub fn generate_join_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    missing_col: &str,
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let mut out = Vec::new();
+    
+    // 현재 FROM절의 모든 테이블 추출
+    let from_tables = extract_all_tables_from_raw(orig);
+    let existing_table_names: Vec<String> = from_tables.iter()
+        .map(|t| t.table_name.clone())
+        .collect();
+    
+    let weight = JOIN_REFINEMENT_WEIGHT.get() as f32; // 가중치 미리 가져오기
+    
+    if existing_table_names.is_empty() {
+        // FROM절에 함수 항목만 있으면 비어 있는 것이 아니므로 FROM절을 덮어쓰지 않는다
+        if !extract_all_function_ranges_from_raw(orig).is_empty() {
+            return out;
+        }
+
+        // FROM절이 비어있는 경우: 테이블을 직접 추가
+        let column_candidates = list_columns_by_similarity(None, missing_col, false);
+        
+        for (_col_name, table_name, distance) in column_candidates {
+            // GUC 가중치를 적용하여 priority 계산
+            let additional_priority = (((distance + 1.0) * 100.0) * weight) as i32;
+            let cumulative_priority = base_priority + additional_priority;
+            
+            let cloned = unsafe { copy_node(orig) };
+            unsafe {
+                add_table_to_empty_from_clause(cloned, &table_name);
+            }
+            out.push((cumulative_priority, cloned));
+        }
+        
+        return out;
+    }
+
+    // FROM절이 이미 safeql.max_from_tables개면 더 JOIN하지 않는다
+    if existing_table_names.len() >= MAX_FROM_TABLES.get() as usize {
+        return out;
+    }
+    
+    // PK-FK 관계로 JOIN 가능한 테이블들 찾기 (기존 테이블들과 중복되지 않는 것만)
+    let joinable_tables = find_joinable_tables_for_column(&existing_table_names, missing_col);
     let existing_table_set: std::collections::HashSet<String> = existing_table_names.iter()
-        .map(|t| t.to_ascii_lowercase())
+        .map(|t| fold_identifier(t))
         .collect();
     
     for (join_table, column_distance, join_conditions) in joinable_tables {
         // 이미 FROM절에 있는 테이블이면 스킵
-        if existing_table_set.contains(&join_table.to_ascii_lowercase()) {
+        if existing_table_set.contains(&fold_identifier(&join_table)) {
             // pgrx::notice!("SafeQL: Skipping JOIN with table '{}' - already exists in FROM clause", join_table);
             continue;
         }
         
-        // 각 JOIN 조건에 대해 별도의 refinement 생성
-        for join_condition in join_conditions {
+        // 각 FK 제약조건에 대해 별도의 refinement 생성 (오른쪽 컬럼이 실제로 없으면 보정하거나 건너뜀)
+        for join_group in group_join_conditions(&join_conditions) {
+            let Some(join_group) = verify_join_condition_group(&join_group) else {
+                continue;
+            };
             // GUC 가중치를 적용하여 priority 계산
             let additional_priority = (((column_distance + 1.0) * 100.0) * weight) as i32;
             let cumulative_priority = base_priority + additional_priority;
             
             let cloned = unsafe { copy_node(orig) };
             unsafe {
-                add_table_and_where_condition(cloned, &join_table, &join_condition);
+                add_table_and_where_condition(cloned, &join_table, &join_group);
             }            
             out.push((cumulative_priority, cloned));
             // pgrx::notice!("SafeQL added JOIN with table {} on condition {:?} (weight: {})", 
@@ -1057,28 +1682,44 @@ pub fn generate_join_refinements_raw(
     out
 }
 
+/// 복합 FK의 모든 컬럼 쌍을 검증 (하나라도 보정할 수 없으면 그 제약조건은 쓰지 않음)
+fn verify_join_condition_group(join_conditions: &[JoinCondition]) -> Option<Vec<JoinCondition>> {
+    join_conditions.iter().map(verify_join_right_column).collect()
+}
+
 /// FROM절에 테이블 추가 + WHERE절에 JOIN 조건 추가
+/// 복합 FK면 모든 컬럼 쌍의 동등 조건을 AND로 추가
 unsafe fn add_table_and_where_condition(
     raw: *mut pg_sys::RawStmt,
     table_name: &str,
-    join_condition: &JoinCondition,
+    join_conditions: &[JoinCondition],
 ) {
     unsafe {
         if let Some(select_stmt) = find_select_stmt(raw) {
             // 1. 현재 FROM절의 테이블과 alias 정보 추출
             let existing_tables = extract_all_tables_from_raw(raw);
             
-            // 2. JoinCondition의 left_table을 alias를 고려해서 수정
-            let adjusted_condition = adjust_join_condition_for_existing_alias(join_condition, &existing_tables);
-            
-            // 3. FROM절에 새 테이블 추가 (alias 없이)
+            // 2. FROM절에 새 테이블 추가 (쿼리가 alias를 쓰거나 이름이 겹치면 생성한 alias 사용)
             let table_cstr = CString::new(table_name).unwrap();
-            let range_var = create_range_var(&table_cstr);
+            let added_alias = generate_alias_for_added_table(table_name, &existing_tables);
+            let range_var = match &added_alias {
+                Some(alias) => {
+                    let alias_cstr = CString::new(alias.as_str()).unwrap();
+                    create_range_var_with_alias(&table_cstr, Some(&alias_cstr))
+                }
+                None => create_range_var(&table_cstr),
+            };
             add_table_to_from_list((*select_stmt).fromClause, range_var as *mut pg_sys::Node);
             
-            // 4. WHERE절에 조정된 JOIN 조건 추가
-            let join_cond_expr = create_join_condition_expr(&adjusted_condition);
-            add_condition_to_where_clause(select_stmt, join_cond_expr);
+            // 3. JoinCondition의 left_table을 alias를 고려해서 수정한 뒤 WHERE절에 추가
+            for join_condition in join_conditions {
+                let mut adjusted_condition = adjust_join_condition_for_existing_alias(join_condition, &existing_tables);
+                if let Some(alias) = &added_alias {
+                    adjusted_condition.right_table = alias.clone();
+                }
+                let join_cond_expr = create_join_condition_expr(&adjusted_condition);
+                add_condition_to_where_clause(select_stmt, join_cond_expr);
+            }
         }
     }
 }
@@ -1092,7 +1733,7 @@ fn adjust_join_condition_for_existing_alias(
     
     // left_table이 기존 테이블 중 하나이고 alias가 있으면 alias로 변경
     for table_info in existing_tables {
-        if table_info.table_name.to_ascii_lowercase() == condition.left_table.to_ascii_lowercase() {
+        if identifiers_match(&table_info.table_name, &condition.left_table) {
             if let Some(ref alias) = table_info.alias {
                 adjusted.left_table = alias.clone();
                 // pgrx::notice!("SafeQL: Adjusted left table reference from {} to {} (using existing alias)", 
@@ -1107,6 +1748,35 @@ fn adjust_join_condition_for_existing_alias(
     adjusted
 }
 
+/// 새로 추가할 테이블의 alias 생성
+/// 기존 테이블 중 alias를 쓰는 것이 있거나, 새 테이블명이 기존 참조 이름과 겹칠 때만 alias를 만든다.
+/// 이름의 '_' 단위 첫 글자를 이어 붙이고 (order_items -> oi), 겹치면 숫자를 붙인다 (oi2, oi3, ...).
+fn generate_alias_for_added_table(table_name: &str, existing_tables: &[TableInfo]) -> Option<String> {
+    let used_names: std::collections::HashSet<String> = existing_tables.iter()
+        .flat_map(|t| [Some(fold_identifier(&t.table_name)), t.alias.as_ref().map(|a| fold_identifier(a))])
+        .flatten()
+        .collect();
+
+    let uses_alias = existing_tables.iter().any(|t| t.alias.is_some());
+    let collides = used_names.contains(&fold_identifier(table_name));
+    if !uses_alias && !collides {
+        return None;
+    }
+
+    let base: String = table_name
+        .split('_')
+        .filter_map(|part| part.chars().find(|c| c.is_ascii_alphabetic()))
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    let base = if base.is_empty() { "t".to_string() } else { base };
+
+    if !used_names.contains(&base) {
+        return Some(base);
+    }
+    (2..).map(|n| format!("{}{}", base, n))
+        .find(|candidate| !used_names.contains(candidate))
+}
+
 /// 빈 FROM절에 테이블만 추가 (WHERE 조건 없음)
 unsafe fn add_table_to_empty_from_clause(
     raw: *mut pg_sys::RawStmt,
@@ -1217,7 +1887,7 @@ pub fn generate_operand_column_refinements_raw(
 /// FROM절에서 테이블의 참조명(alias가 있으면 alias, 없으면 테이블명) 찾기
 fn find_table_reference_name(from_tables: &[TableInfo], table_name: &str) -> String {
     for table_info in from_tables {
-        if table_info.table_name.to_ascii_lowercase() == table_name.to_ascii_lowercase() {
+        if identifiers_match(&table_info.table_name, table_name) {
             return table_info.get_reference_name().to_string();
         }
     }
@@ -1241,6 +1911,10 @@ unsafe fn replace_column_in_all_operands(
 
     unsafe {
         safe_raw_expression_tree_walker((*raw).stmt, Some(all_operand_replace_walker), ctx_ptr);
+
+        if ctx.replaced_count > 0 && old_operand.table_name.is_some() {
+            sync_group_by_column_refs(raw, &old_operand.column_name, new_column);
+        }
     }
     
     // if ctx.replaced_count > 0 {
@@ -1292,8 +1966,8 @@ unsafe extern "C" fn all_operand_replace_walker(node: *mut pg_sys::Node, ctx: *m
                                     let column_name = CStr::from_ptr((*last_str).sval).to_string_lossy().into_owned();
                                     
                                     if let Some(ref old_table) = ctx.old_operand.table_name {
-                                        if table_name.to_ascii_lowercase() == old_table.to_ascii_lowercase() &&
-                                           column_name.to_ascii_lowercase() == ctx.old_operand.column_name.to_ascii_lowercase() {
+                                        if identifiers_match(&table_name, old_table) &&
+                                           identifiers_match(&column_name, &ctx.old_operand.column_name) {
                                             return true;
                                         }
                                     }
@@ -1307,7 +1981,7 @@ unsafe extern "C" fn all_operand_replace_walker(node: *mut pg_sys::Node, ctx: *m
                                     let str_node = field as *mut pg_sys::String;
                                     let column_name = CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned();
                                     
-                                    if column_name.to_ascii_lowercase() == ctx.old_operand.column_name.to_ascii_lowercase() {
+                                    if identifiers_match(&column_name, &ctx.old_operand.column_name) {
                                         return true;
                                     }
                                 }
@@ -1354,14 +2028,27 @@ pub fn generate_operand_typecast_refinements_raw(
     orig: *mut pg_sys::RawStmt,
     left_operand: &Option<ColumnOperand>,
     right_operand: &Option<ColumnOperand>,
+    literal_operand: Option<&LiteralOperand>,
     operator_info: &OperatorInfo,
     base_priority: i32
 ) -> Vec<(i32, *mut pg_sys::RawStmt)> {
     let mut out = Vec::new();
     let typecast_weight = TYPECAST_REFINEMENT_WEIGHT.get() as f32;
 
-    let current_left_type = operator_info.left_type.as_deref();
-    let current_right_type = operator_info.right_type.as_deref();
+    // 리터럴 쪽을 컬럼 타입으로 캐스팅 - 컬럼을 캐스팅하는 것보다 우선
+    if let Some(literal) = literal_operand {
+        let cloned = unsafe { copy_node(orig) };
+        if unsafe { apply_typecast_to_literal_operand(cloned, literal) } {
+            let additional_priority = ((0.5 * 100.0) * typecast_weight) as i32;
+            out.push((base_priority + additional_priority, cloned));
+        }
+    }
+
+    // 오퍼레이터 정보에 타입이 없으면 오퍼랜드 추출 시 알아둔 타입을 쓴다
+    let current_left_type = operator_info.left_type.as_deref()
+        .or_else(|| left_operand.as_ref().and_then(|op| op.resolved_type.as_deref()));
+    let current_right_type = operator_info.right_type.as_deref()
+        .or_else(|| right_operand.as_ref().and_then(|op| op.resolved_type.as_deref()));
     
     let typecast_refinements = get_typecast_refinements_for_operator(
         &operator_info,
@@ -1423,6 +2110,59 @@ unsafe fn apply_typecast_to_all_operands(
     }
 }
 
+/// expr_location의 A_Expr에서 리터럴 쪽 오퍼랜드를 column_type으로 캐스팅
+unsafe fn apply_typecast_to_literal_operand(
+    raw: *mut pg_sys::RawStmt,
+    literal: &LiteralOperand,
+) -> bool {
+    let mut ctx = LiteralTypecastCtx {
+        literal: literal.clone(),
+        replaced: false,
+    };
+    let ctx_ptr = &mut ctx as *mut LiteralTypecastCtx as *mut c_void;
+
+    unsafe {
+        safe_raw_expression_tree_walker((*raw).stmt, Some(literal_typecast_walker), ctx_ptr);
+    }
+
+    ctx.replaced
+}
+
+#[derive(Debug)]
+struct LiteralTypecastCtx {
+    literal: LiteralOperand,
+    replaced: bool,
+}
+
+unsafe extern "C" fn literal_typecast_walker(node: *mut pg_sys::Node, ctx: *mut c_void) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let ctx_ref = &mut *(ctx as *mut LiteralTypecastCtx);
+
+        if (*node).type_ == pg_sys::NodeTag::T_A_Expr {
+            let a_expr = node as *mut pg_sys::A_Expr;
+
+            if (*a_expr).location == ctx_ref.literal.expr_location {
+                let slot = match ctx_ref.literal.position {
+                    OperandPosition::Left => &mut (*a_expr).lexpr,
+                    OperandPosition::Right => &mut (*a_expr).rexpr,
+                };
+
+                if !slot.is_null() {
+                    *slot = create_typecast_node(*slot, &ctx_ref.literal.column_type);
+                    ctx_ref.replaced = true;
+                    return true;
+                }
+            }
+        }
+
+        safe_raw_expression_tree_walker(node, Some(literal_typecast_walker), ctx)
+    }
+}
+
 #[derive(Debug)]
 struct AllOperandTypecastCtx {
     operand: Option<ColumnOperand>,
@@ -1439,13 +2179,21 @@ unsafe extern "C" fn all_operand_typecast_walker(node: *mut pg_sys::Node, ctx: *
     unsafe {
         let ctx = &mut *(ctx as *mut AllOperandTypecastCtx);
 
-        if (*node).type_ == pg_sys::NodeTag::T_A_Expr {
+        let slot: *mut *mut pg_sys::Node = if (*node).type_ == pg_sys::NodeTag::T_A_Expr {
             let a_expr = node as *mut pg_sys::A_Expr;
-            
-            let target_operand_node = match ctx.cast_position {
-                OperandPosition::Left => (*a_expr).lexpr,
-                OperandPosition::Right => (*a_expr).rexpr,
-            };
+            match ctx.cast_position {
+                OperandPosition::Left => std::ptr::addr_of_mut!((*a_expr).lexpr),
+                OperandPosition::Right => std::ptr::addr_of_mut!((*a_expr).rexpr),
+            }
+        } else if let (Some(sublink), OperandPosition::Left) = (any_all_sublink(node), &ctx.cast_position) {
+            // ANY/ALL 서브쿼리는 스칼라 쪽(testexpr)만 캐스팅 대상
+            std::ptr::addr_of_mut!((*sublink).testexpr)
+        } else {
+            std::ptr::null_mut()
+        };
+
+        if !slot.is_null() {
+            let target_operand_node = *slot;
             
             if !target_operand_node.is_null() && 
                (*target_operand_node).type_ != pg_sys::NodeTag::T_TypeCast {
@@ -1473,8 +2221,8 @@ unsafe extern "C" fn all_operand_typecast_walker(node: *mut pg_sys::Node, ctx: *
                                                 let column_name = CStr::from_ptr((*last_str).sval).to_string_lossy();
                                                 
                                                 if let Some(ref op_table) = operand.table_name {
-                                                    return table_name.to_ascii_lowercase() == op_table.to_ascii_lowercase() &&
-                                                           column_name.to_ascii_lowercase() == operand.column_name.to_ascii_lowercase();
+                                                    return identifiers_match(&table_name, op_table) &&
+                                                           identifiers_match(&column_name, &operand.column_name);
                                                 }
                                             }
                                         }
@@ -1484,7 +2232,7 @@ unsafe extern "C" fn all_operand_typecast_walker(node: *mut pg_sys::Node, ctx: *
                                             if (*field).type_ == pg_sys::NodeTag::T_String {
                                                 let str_node = field as *mut pg_sys::String;
                                                 let column_name = CStr::from_ptr((*str_node).sval).to_string_lossy();
-                                                return column_name.to_ascii_lowercase() == operand.column_name.to_ascii_lowercase();
+                                                return identifiers_match(&column_name, &operand.column_name);
                                             }
                                         }
                                     }
@@ -1493,13 +2241,7 @@ unsafe extern "C" fn all_operand_typecast_walker(node: *mut pg_sys::Node, ctx: *
                             });
                             
                             if matches {
-                                let typecast_node = create_typecast_node(target_operand_node, &ctx.target_type);
-                                
-                                match ctx.cast_position {
-                                    OperandPosition::Left => (*a_expr).lexpr = typecast_node,
-                                    OperandPosition::Right => (*a_expr).rexpr = typecast_node,
-                                }
-                                
+                                *slot = create_typecast_node(target_operand_node, &ctx.target_type);
                                 ctx.replaced_count += 1;
                             }
                         }
@@ -1544,15 +2286,30 @@ pub unsafe fn find_all_where_expressions(raw: *mut pg_sys::RawStmt) -> Vec<*mut
     let mut expressions = Vec::new();
     
     unsafe {
-        if let Some(select_stmt) = find_select_stmt(raw) {
-            if !(*select_stmt).whereClause.is_null() {
-                collect_a_exprs((*select_stmt).whereClause, &mut expressions);
-            }
+        if let Some(where_clause) = find_where_clause(raw) {
+            collect_a_exprs(where_clause, &mut expressions);
         }
     }
     expressions
 }
 
+/// SELECT/UPDATE/DELETE의 WHERE절 (UPDATE/DELETE도 SELECT와 같은 walker로 refinement)
+unsafe fn find_where_clause(raw: *mut pg_sys::RawStmt) -> Option<*mut pg_sys::Node> {
+    unsafe {
+        if raw.is_null() || (*raw).stmt.is_null() {
+            return None;
+        }
+        let stmt = (*raw).stmt;
+        let where_clause = match (*stmt).type_ {
+            pg_sys::NodeTag::T_SelectStmt => (*(stmt as *mut pg_sys::SelectStmt)).whereClause,
+            pg_sys::NodeTag::T_UpdateStmt => (*(stmt as *mut pg_sys::UpdateStmt)).whereClause,
+            pg_sys::NodeTag::T_DeleteStmt => (*(stmt as *mut pg_sys::DeleteStmt)).whereClause,
+            _ => return None,
+        };
+        (!where_clause.is_null()).then_some(where_clause)
+    }
+}
+
 unsafe fn collect_a_exprs(node: *mut pg_sys::Node, expressions: &mut Vec<*mut pg_sys::Node>) {
     if node.is_null() {
         return;
@@ -1574,6 +2331,33 @@ unsafe extern "C" fn collect_a_exprs_walker(node: *mut pg_sys::Node, ctx: *mut c
     false
 }
 
+/// `x op ANY/ALL (ARRAY[...])` 비교인지 확인 - 오른쪽은 배열이므로 스칼라인 왼쪽만 refinement 대상
+pub unsafe fn is_any_all_expr(node: *mut pg_sys::Node) -> bool {
+    unsafe {
+        !node.is_null()
+            && (*node).type_ == pg_sys::NodeTag::T_A_Expr
+            && matches!(
+                (*(node as *mut pg_sys::A_Expr)).kind,
+                pg_sys::A_Expr_Kind::AEXPR_OP_ANY | pg_sys::A_Expr_Kind::AEXPR_OP_ALL
+            )
+    }
+}
+
+/// `x op ANY/ALL (SELECT ...)` 형태의 SubLink이면 반환 (testexpr이 스칼라 쪽)
+pub unsafe fn any_all_sublink(node: *mut pg_sys::Node) -> Option<*mut pg_sys::SubLink> {
+    unsafe {
+        if node.is_null() || (*node).type_ != pg_sys::NodeTag::T_SubLink {
+            return None;
+        }
+        let sublink = node as *mut pg_sys::SubLink;
+        let is_any_all = matches!(
+            (*sublink).subLinkType,
+            pg_sys::SubLinkType::ANY_SUBLINK | pg_sys::SubLinkType::ALL_SUBLINK
+        );
+        (is_any_all && !(*sublink).testexpr.is_null()).then_some(sublink)
+    }
+}
+
 // pub unsafe fn extract_columns_from_where_clause(raw: *mut pg_sys::RawStmt) -> Vec<String> {
 //     let mut columns = std::collections::HashSet::new();
     
@@ -1625,18 +2409,19 @@ unsafe extern "C" fn collect_a_exprs_walker(node: *mut pg_sys::Node, ctx: *mut c
 
 pub unsafe fn extract_operator_info_from_expr(expr_node: *mut pg_sys::Node) -> OperatorInfo {
     unsafe {
-        if expr_node.is_null() || (*expr_node).type_ != pg_sys::NodeTag::T_A_Expr {
-            return OperatorInfo {
-                operator_name: String::new(),
-                left_type: None,
-                right_type: None,
-            };
-        }
-        
-        let a_expr = expr_node as *mut pg_sys::A_Expr;
-        let operator_name = if !(*a_expr).name.is_null() {
+        let op_name_list = if expr_node.is_null() {
+            std::ptr::null_mut()
+        } else if (*expr_node).type_ == pg_sys::NodeTag::T_A_Expr {
+            (*(expr_node as *mut pg_sys::A_Expr)).name
+        } else if let Some(sublink) = any_all_sublink(expr_node) {
+            (*sublink).operName
+        } else {
+            std::ptr::null_mut()
+        };
+
+        let operator_name = if !op_name_list.is_null() {
             memcx::current_context(|mcx| {
-                if let Some(op_names) = List::<*mut c_void>::downcast_ptr_in_memcx((*a_expr).name, mcx) {
+                if let Some(op_names) = List::<*mut c_void>::downcast_ptr_in_memcx(op_name_list, mcx) {
                     if let Some(op_name_ptr) = op_names.get(0) {
                         let op_name_node = *op_name_ptr as *mut pg_sys::Node;
                         if (*op_name_node).type_ == pg_sys::NodeTag::T_String {
@@ -1738,8 +2523,8 @@ unsafe fn replace_function_argument_at_position(
     let mut ctx = FunctionArgumentReplaceCtx {
         error_pos,
         arg_index,
-        old_table: old_table.map(|s| s.to_ascii_lowercase()),
-        old_column: old_column.to_ascii_lowercase(),
+        old_table: old_table.map(|s| fold_identifier(s)),
+        old_column: fold_identifier(old_column),
         new_table: new_table.to_string(),
         new_column: new_column.to_string(),
         replaced_any: false,
@@ -1814,7 +2599,7 @@ unsafe extern "C" fn function_argument_replace_walker(node: *mut pg_sys::Node, c
                                                             let table_name = CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned();
                                                             
                                                             if let Some(ref old_table) = ctx.old_table {
-                                                                if table_name.to_ascii_lowercase() == *old_table {
+                                                                if fold_identifier(&table_name) == *old_table {
                                                                     table_match = true;
                                                                 }
                                                             }
@@ -1829,7 +2614,7 @@ unsafe extern "C" fn function_argument_replace_walker(node: *mut pg_sys::Node, c
                                                             let str_node = last_field as *mut pg_sys::String;
                                                             let column_name = CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned();
                                                             
-                                                            if column_name.to_ascii_lowercase() == ctx.old_column {
+                                                            if fold_identifier(&column_name) == ctx.old_column {
                                                                 column_match = true;
                                                             }
                                                         }
@@ -1846,7 +2631,7 @@ unsafe extern "C" fn function_argument_replace_walker(node: *mut pg_sys::Node, c
                                                             let str_node = field as *mut pg_sys::String;
                                                             let column_name = CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned();
                                                             
-                                                            if column_name.to_ascii_lowercase() == ctx.old_column && ctx.old_table.is_none() {
+                                                            if fold_identifier(&column_name) == ctx.old_column && ctx.old_table.is_none() {
                                                                 column_match = true;
                                                             }
                                                         }
@@ -2074,12 +2859,25 @@ pub fn generate_function_name_refinements_raw(
     error_pos: i32,
     function_name: &str,
     arg_types: &[String],
+    schema: Option<&str>,
+    aggregate_only: bool,
     base_priority: i32
 ) -> Vec<(i32, *mut pg_sys::RawStmt)> {
     let mut out = Vec::new();
     let function_name_weight = FUNCTION_NAME_REFINEMENT_WEIGHT.get() as f32;
     
-    let similar_functions = find_similar_functions(function_name, arg_types.len());
+    // schema로 한정된 호출은 그 스키마 안에서 먼저 찾는다 (한정자를 떼면 다른 함수로 resolve될 수 있다)
+    // 그 스키마에 후보가 없으면 search_path 후보로 바꾸고 한정자를 뗀다
+    let mut similar_functions = find_similar_functions_with_aggregates(function_name, arg_types.len(), schema);
+    let mut keep_schema = true;
+    if schema.is_some() && similar_functions.is_empty() {
+        similar_functions = find_similar_functions_with_aggregates(function_name, arg_types.len(), None);
+        keep_schema = false;
+    }
+    // HAVING절 안의 호출이면 집계 함수 후보만 남긴다
+    if aggregate_only {
+        similar_functions.retain(|(name, _, _, _)| is_aggregate_function(name));
+    }
     
     for (similar_func_name, _similar_arg_types, _return_type, distance) in &similar_functions {
         let additional_priority = ((distance * 100.0) * function_name_weight) as i32;
@@ -2091,7 +2889,8 @@ pub fn generate_function_name_refinements_raw(
                 cloned,
                 error_pos,
                 function_name,
-                similar_func_name
+                similar_func_name,
+                keep_schema
             );
         }
         out.push((cumulative_priority, cloned));
@@ -2103,6 +2902,59 @@ pub fn generate_function_name_refinements_raw(
     out
 }
 
+/// call_pos(location + 1)의 함수 호출이 HAVING절 안에 있는지 확인 (set operation의 각 SELECT 포함)
+pub unsafe fn is_call_in_having_clause(raw: *mut pg_sys::RawStmt, call_pos: i32) -> bool {
+    unsafe {
+        let stmt = (*raw).stmt;
+        !stmt.is_null()
+            && (*stmt).type_ == pg_sys::NodeTag::T_SelectStmt
+            && having_contains_call(stmt as *mut pg_sys::SelectStmt, call_pos)
+    }
+}
+
+unsafe fn having_contains_call(select: *mut pg_sys::SelectStmt, call_pos: i32) -> bool {
+    if select.is_null() {
+        return false;
+    }
+
+    unsafe {
+        if !(*select).havingClause.is_null() {
+            let mut ctx = HavingCallCtx { call_pos, found: false };
+            // HAVING절 자체가 함수 호출일 수 있으므로 루트부터 walker에 넘긴다
+            having_call_walker((*select).havingClause, &mut ctx as *mut HavingCallCtx as *mut c_void);
+            if ctx.found {
+                return true;
+            }
+        }
+
+        having_contains_call((*select).larg, call_pos) || having_contains_call((*select).rarg, call_pos)
+    }
+}
+
+struct HavingCallCtx {
+    call_pos: i32,
+    found: bool,
+}
+
+unsafe extern "C" fn having_call_walker(node: *mut pg_sys::Node, ctx: *mut c_void) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let ctx_ref = &mut *(ctx as *mut HavingCallCtx);
+
+        if (*node).type_ == pg_sys::NodeTag::T_FuncCall
+            && (*(node as *mut pg_sys::FuncCall)).location + 1 == ctx_ref.call_pos
+        {
+            ctx_ref.found = true;
+            return true;
+        }
+
+        safe_raw_expression_tree_walker(node, Some(having_call_walker), ctx)
+    }
+}
+
 // function_typecast_walker 함수 뒤에 추가
 /// 특정 위치의 함수명 교체
 unsafe fn replace_function_name_at_position(
@@ -2110,11 +2962,13 @@ unsafe fn replace_function_name_at_position(
     error_pos: i32,
     old_function_name: &str,
     new_function_name: &str,
+    keep_schema: bool,
 ) {
     let mut ctx = FunctionNameReplaceCtx {
         error_pos,
-        old_function_name: old_function_name.to_ascii_lowercase(),
+        old_function_name: fold_identifier(old_function_name),
         new_function_name: new_function_name.to_string(),
+        keep_schema,
         replaced_any: false,
     };
     let ctx_ptr = &mut ctx as *mut FunctionNameReplaceCtx as *mut c_void;
@@ -2134,6 +2988,8 @@ struct FunctionNameReplaceCtx {
     error_pos: i32,
     old_function_name: String,
     new_function_name: String,
+    // false이면 schema 한정자를 떼고 이름만 남긴다
+    keep_schema: bool,
     replaced_any: bool,
 }
 
@@ -2162,9 +3018,12 @@ unsafe extern "C" fn function_name_replace_walker(node: *mut pg_sys::Node, ctx:
                                         .to_string_lossy()
                                         .into_owned();
                                     
-                                    if current_name.to_ascii_lowercase() == ctx.old_function_name {
+                                    if fold_identifier(&current_name) == ctx.old_function_name {
                                         let new_name_cstr = CString::new(ctx.new_function_name.as_str()).unwrap();
                                         (*str_node).sval = pg_sys::pstrdup(new_name_cstr.as_ptr());
+                                        if !ctx.keep_schema && func_names.len() > 1 {
+                                            (*func_call).funcname = pg_sys::lappend(std::ptr::null_mut(), str_node as *mut c_void);
+                                        }
                                         ctx.replaced_any = true;
                                         should_stop = true;
                                     }
@@ -2389,75 +3248,1284 @@ fn strftime_to_datepart(format_str: &str) -> Option<String> {
 }
 
 /* ------------------------------------------------
-CASE 9) Column Reference Ambiguous - qualified reference로 변경
+CASE 12) Division By Zero - divisor를 NULLIF(divisor, 0)로 감싸기
 ------------------------------------------------ */
-/// Column ambiguity 에러에 대한 refinement 생성
-/// FROM절의 테이블들 중에서 해당 컬럼을 가진 테이블을 찾아 qualified reference로 변경
-pub fn generate_column_ambiguity_refinements_raw(
+/// 실행 중 division_by_zero 에러에 대한 refinement 생성
+/// 나눗셈(/, %) A_Expr마다 divisor를 NULLIF로 감싼 후보를 하나씩 만든다
+pub fn generate_nullif_guard_refinements_raw(
     orig: *mut pg_sys::RawStmt,
-    ambiguous_col: &str,
     base_priority: i32
 ) -> Vec<(i32, *mut pg_sys::RawStmt)> {
     let mut out = Vec::new();
-    
-    // FROM절의 모든 테이블과 alias 추출
-    let from_tables = extract_all_tables_from_raw(orig);
-    if from_tables.is_empty() {
-        return out;
-    }
-    
-    let weight = COLUMN_AMBIGUITY_REFINEMENT_WEIGHT.get() as f32;
-    
-    // 모든 테이블명 추출 (실제 테이블명만, alias는 제외)
-    let table_names: Vec<String> = from_tables.iter()
-        .map(|t| t.table_name.clone())
-        .collect();
-    
-    // 한 번에 해당 컬럼을 가진 테이블들 찾기
-    let matching_table_names = find_tables_with_exact_column(&table_names, ambiguous_col);
-    
-    if matching_table_names.is_empty() {
-        // pgrx::notice!("SafeQL: No tables found with exact column '{}' in FROM clause", ambiguous_col);
-        return out;
-    }
-    
-    // 매치된 테이블들에 대해서만 refinement 생성
-    for table_info in from_tables.iter() {
-        // 실제 테이블명이 매치된 목록에 있는지 확인
-        if matching_table_names.contains(&table_info.table_name) {
-            // distance는 1.0으로 고정
-            let additional_priority = ((1.0 * 100.0) * weight) as i32;
-            let cumulative_priority = base_priority + additional_priority;
-            
-            let cloned = unsafe { copy_node(orig) };
-            
-            // 참조할 때 사용할 이름 결정 (alias가 있으면 alias, 없으면 테이블명)
-            let reference_name = table_info.get_reference_name();
-            
-            unsafe { 
-                qualify_ambiguous_column_reference(cloned, ambiguous_col, reference_name, ambiguous_col);
-            }
+    let weight = NULLIF_GUARD_REFINEMENT_WEIGHT.get() as f32;
+
+    unsafe {
+        let division_count = find_division_expressions(orig).len();
+
+        for idx in 0..division_count {
+            // clone 후 같은 순서로 다시 찾아서 idx번째 나눗셈만 수정
+            let cloned = copy_node(orig);
+            let divisions = find_division_expressions(cloned);
+            let Some(&a_expr) = divisions.get(idx) else {
+                continue;
+            };
+
+            (*a_expr).rexpr = create_nullif_zero_expr((*a_expr).rexpr);
+
+            let cumulative_priority = base_priority + ((1.0 * 100.0) * weight) as i32;
             out.push((cumulative_priority, cloned));
-            
-            // pgrx::notice!("SafeQL: Generated ambiguity refinement {} -> {}.{} (using {})", 
-            //     ambiguous_col, reference_name, ambiguous_col, 
-            //     if table_info.alias.is_some() { "alias" } else { "table name" });
+            // pgrx::notice!("SafeQL: Generated NULLIF guard refinement for division #{}", idx);
         }
     }
-    
-    // pgrx::notice!("SafeQL: Generated {} column ambiguity refinements for '{}' from {} matching tables", 
-    //     out.len(), ambiguous_col, matching_table_names.len());
-    
+
     out
 }
 
-#[derive(Debug)]
-struct AmbiguousColumnQualifyCtx {
-    ambiguous_col: String,
-    table_qualifier: String,
-    new_column_name: String,
-    replaced_any: bool,
-}
+/// AST에서 아직 NULLIF로 보호되지 않은 나눗셈 A_Expr 찾기
+unsafe fn find_division_expressions(
+    raw: *mut pg_sys::RawStmt,
+) -> Vec<*mut pg_sys::A_Expr> {
+    let mut divisions = Vec::new();
+    let ctx_ptr = &mut divisions as *mut Vec<*mut pg_sys::A_Expr> as *mut c_void;
+
+    unsafe {
+        safe_raw_expression_tree_walker(
+            (*raw).stmt,
+            Some(find_division_expressions_walker),
+            ctx_ptr
+        );
+    }
+
+    divisions
+}
+
+unsafe extern "C" fn find_division_expressions_walker(
+    node: *mut pg_sys::Node,
+    ctx: *mut c_void
+) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let divisions = &mut *(ctx as *mut Vec<*mut pg_sys::A_Expr>);
+
+        if (*node).type_ == pg_sys::NodeTag::T_A_Expr {
+            let a_expr = node as *mut pg_sys::A_Expr;
+
+            if (*a_expr).kind == pg_sys::A_Expr_Kind::AEXPR_OP
+                && !(*a_expr).name.is_null()
+                && !(*a_expr).rexpr.is_null()
+                && !is_nullif_expr((*a_expr).rexpr)
+            {
+                let is_division = memcx::current_context(|mcx| {
+                    let Some(op_names) = List::<*mut c_void>::downcast_ptr_in_memcx((*a_expr).name, mcx) else {
+                        return false;
+                    };
+                    let Some(op_name_ptr) = op_names.get(0) else {
+                        return false;
+                    };
+                    let op_name_node = *op_name_ptr as *mut pg_sys::Node;
+                    if (*op_name_node).type_ != pg_sys::NodeTag::T_String {
+                        return false;
+                    }
+                    let op_name = CStr::from_ptr((*(op_name_node as *mut pg_sys::String)).sval).to_string_lossy();
+                    op_name == "/" || op_name == "%"
+                });
+
+                if is_division {
+                    divisions.push(a_expr);
+                }
+            }
+        }
+
+        safe_raw_expression_tree_walker(node, Some(find_division_expressions_walker), ctx)
+    }
+}
+
+/// 이미 NULLIF(...)인 표현식인지 확인
+unsafe fn is_nullif_expr(node: *mut pg_sys::Node) -> bool {
+    unsafe {
+        (*node).type_ == pg_sys::NodeTag::T_A_Expr
+            && (*(node as *mut pg_sys::A_Expr)).kind == pg_sys::A_Expr_Kind::AEXPR_NULLIF
+    }
+}
+
+/// NULLIF(expr, 0) 생성 - NULLIF는 "=" 연산자 이름을 가진 AEXPR_NULLIF
+unsafe fn create_nullif_zero_expr(divisor: *mut pg_sys::Node) -> *mut pg_sys::Node {
+    unsafe {
+        let a_const = pg_sys::palloc0(size_of::<pg_sys::A_Const>()) as *mut pg_sys::A_Const;
+        (*a_const).type_ = pg_sys::NodeTag::T_A_Const;
+        (*a_const).val.ival = *pg_sys::makeInteger(0);
+        (*a_const).location = -1;
+
+        let expr = create_equality_expr(divisor, a_const as *mut pg_sys::Node);
+        (*(expr as *mut pg_sys::A_Expr)).kind = pg_sys::A_Expr_Kind::AEXPR_NULLIF;
+        expr
+    }
+}
+
+/* ------------------------------------------------
+CASE 13) JSONB Path Operator - `->`/`->>` 교체 및 key literal 보정
+------------------------------------------------ */
+/// jsonb 연산자 타입 에러에 대한 refinement 생성
+/// `->`/`->>` A_Expr마다 연산자를 반대쪽으로 바꾼 후보를 하나씩 만든다 (jsonb <-> text)
+pub fn generate_jsonb_operator_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let mut out = Vec::new();
+    let weight = JSONB_OPERATOR_REFINEMENT_WEIGHT.get() as f32;
+
+    unsafe {
+        let arrow_count = find_jsonb_arrow_expressions(orig).len();
+
+        for idx in 0..arrow_count {
+            // clone 후 같은 순서로 다시 찾아서 idx번째 연산자만 수정
+            let cloned = copy_node(orig);
+            let arrows = find_jsonb_arrow_expressions(cloned);
+            let Some(&(a_expr, ref op_name)) = arrows.get(idx) else {
+                continue;
+            };
+
+            let swapped = if op_name == "->" { "->>" } else { "->" };
+            let c_op = CString::new(swapped).expect("CString conversion failed");
+            (*a_expr).name = pg_sys::lappend(
+                std::ptr::null_mut(),
+                pg_sys::makeString(pg_sys::pstrdup(c_op.as_ptr())) as *mut c_void
+            );
+
+            let cumulative_priority = base_priority + ((1.0 * 100.0) * weight) as i32;
+            out.push((cumulative_priority, cloned));
+            // pgrx::notice!("SafeQL: Generated jsonb operator refinement {} -> {} for #{}", op_name, swapped, idx);
+        }
+    }
+
+    out
+}
+
+/// 빈 결과에 대한 jsonb key refinement 생성
+/// `col->'key'`/`col->>'key'`의 key가 컬럼에 없으면 실제 key 중 가장 비슷한 것으로 바꾼 후보를 만든다
+pub fn generate_jsonb_key_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let mut out = Vec::new();
+    let weight = JSONB_OPERATOR_REFINEMENT_WEIGHT.get() as f32;
+
+    unsafe {
+        let arrows = find_jsonb_arrow_expressions(orig);
+
+        for (idx, (a_expr, _)) in arrows.into_iter().enumerate() {
+            if !is_column_ref((*a_expr).lexpr) || !is_literal_value((*a_expr).rexpr) {
+                continue;
+            }
+            let Some((table_name, column_name)) = extract_table_column_from_ref((*a_expr).lexpr, orig) else {
+                continue;
+            };
+            let Some(key) = extract_literal_string_value((*a_expr).rexpr) else {
+                continue;
+            };
+
+            for (similar_key, distance) in find_similar_jsonb_keys(&table_name, &column_name, &key) {
+                let cloned = copy_node(orig);
+                let Some(&(cloned_expr, _)) = find_jsonb_arrow_expressions(cloned).get(idx) else {
+                    continue;
+                };
+                (*cloned_expr).rexpr = create_string_literal(&similar_key);
+
+                let cumulative_priority = base_priority + ((distance * 100.0) * weight) as i32;
+                out.push((cumulative_priority, cloned));
+                // pgrx::notice!("SafeQL: Generated jsonb key refinement {}.{}: '{}' -> '{}'",
+                //     table_name, column_name, key, similar_key);
+            }
+        }
+    }
+
+    out
+}
+
+/// AST에서 jsonb path 연산자(`->`, `->>`) A_Expr와 연산자 이름 찾기
+unsafe fn find_jsonb_arrow_expressions(
+    raw: *mut pg_sys::RawStmt,
+) -> Vec<(*mut pg_sys::A_Expr, String)> {
+    let mut arrows = Vec::new();
+    let ctx_ptr = &mut arrows as *mut Vec<(*mut pg_sys::A_Expr, String)> as *mut c_void;
+
+    unsafe {
+        safe_raw_expression_tree_walker(
+            (*raw).stmt,
+            Some(find_jsonb_arrow_expressions_walker),
+            ctx_ptr
+        );
+    }
+
+    arrows
+}
+
+unsafe extern "C" fn find_jsonb_arrow_expressions_walker(
+    node: *mut pg_sys::Node,
+    ctx: *mut c_void
+) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let arrows = &mut *(ctx as *mut Vec<(*mut pg_sys::A_Expr, String)>);
+
+        if (*node).type_ == pg_sys::NodeTag::T_A_Expr {
+            let a_expr = node as *mut pg_sys::A_Expr;
+
+            if (*a_expr).kind == pg_sys::A_Expr_Kind::AEXPR_OP && !(*a_expr).name.is_null() {
+                let op_name = memcx::current_context(|mcx| {
+                    let op_names = List::<*mut c_void>::downcast_ptr_in_memcx((*a_expr).name, mcx)?;
+                    // 스키마 한정 연산자(OPERATOR(pg_catalog.->))는 건드리지 않는다
+                    if op_names.len() != 1 {
+                        return None;
+                    }
+                    let op_name_node = *op_names.get(0)? as *mut pg_sys::Node;
+                    if (*op_name_node).type_ != pg_sys::NodeTag::T_String {
+                        return None;
+                    }
+                    Some(CStr::from_ptr((*(op_name_node as *mut pg_sys::String)).sval).to_string_lossy().into_owned())
+                });
+
+                if let Some(op_name) = op_name {
+                    if op_name == "->" || op_name == "->>" {
+                        arrows.push((a_expr, op_name));
+                    }
+                }
+            }
+        }
+
+        safe_raw_expression_tree_walker(node, Some(find_jsonb_arrow_expressions_walker), ctx)
+    }
+}
+
+/* ------------------------------------------------
+CASE 14) Null-aware Comparison - 빈 결과일 때 nullable 컬럼 비교 완화
+------------------------------------------------ */
+/// 빈 결과에 대한 NULL 처리 refinement 생성
+/// WHERE절의 `col = x`마다 하나씩:
+/// - x가 NULL literal이나 컬럼이면 `col IS NOT DISTINCT FROM x`
+/// - x가 literal이면 `COALESCE(col, x) = x` (= `col = x OR col IS NULL`)
+pub fn generate_null_handling_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let mut out = Vec::new();
+    let weight = NULL_HANDLING_REFINEMENT_WEIGHT.get() as f32;
+
+    unsafe {
+        let comparisons = find_all_where_expressions(orig);
+
+        for (idx, comparison) in comparisons.into_iter().enumerate() {
+            if !is_nullable_equality(comparison as *mut pg_sys::A_Expr) {
+                continue;
+            }
+
+            // clone 후 같은 순서로 다시 찾아서 idx번째 비교만 수정
+            let cloned = copy_node(orig);
+            let Some(&node) = find_all_where_expressions(cloned).get(idx) else {
+                continue;
+            };
+            if !loosen_equality_for_nulls(node as *mut pg_sys::A_Expr) {
+                continue;
+            }
+
+            let cumulative_priority = base_priority + ((1.0 * 100.0) * weight) as i32;
+            out.push((cumulative_priority, cloned));
+            // pgrx::notice!("SafeQL: Generated null handling refinement for comparison #{}", idx);
+        }
+    }
+
+    out
+}
+
+/// 한쪽이 컬럼인 `=` 비교인지 확인 (스키마 한정 연산자 제외)
+unsafe fn is_nullable_equality(a_expr: *mut pg_sys::A_Expr) -> bool {
+    unsafe {
+        if (*a_expr).kind != pg_sys::A_Expr_Kind::AEXPR_OP || (*a_expr).name.is_null() {
+            return false;
+        }
+        if !is_column_ref((*a_expr).lexpr) && !is_column_ref((*a_expr).rexpr) {
+            return false;
+        }
+
+        memcx::current_context(|mcx| {
+            let Some(op_names) = List::<*mut c_void>::downcast_ptr_in_memcx((*a_expr).name, mcx) else {
+                return false;
+            };
+            if op_names.len() != 1 {
+                return false;
+            }
+            let Some(op_name_ptr) = op_names.get(0) else {
+                return false;
+            };
+            let op_name_node = *op_name_ptr as *mut pg_sys::Node;
+            (*op_name_node).type_ == pg_sys::NodeTag::T_String
+                && CStr::from_ptr((*(op_name_node as *mut pg_sys::String)).sval).to_bytes() == b"="
+        })
+    }
+}
+
+/// `col = x`를 NULL을 허용하는 비교로 바꾼다. 바꿀 수 없는 형태면 false
+unsafe fn loosen_equality_for_nulls(a_expr: *mut pg_sys::A_Expr) -> bool {
+    unsafe {
+        let (column_side, other_side) = if is_column_ref((*a_expr).lexpr) {
+            (&mut (*a_expr).lexpr, (*a_expr).rexpr)
+        } else {
+            (&mut (*a_expr).rexpr, (*a_expr).lexpr)
+        };
+
+        if is_null_literal(other_side) || is_column_ref(other_side) {
+            // 연산자 이름 "="은 그대로 두고 kind만 바꾸면 IS NOT DISTINCT FROM
+            (*a_expr).kind = pg_sys::A_Expr_Kind::AEXPR_NOT_DISTINCT;
+            return true;
+        }
+
+        if is_literal_value(other_side) {
+            *column_side = create_coalesce_expr(*column_side, copy_node(other_side));
+            return true;
+        }
+
+        false
+    }
+}
+
+unsafe fn is_null_literal(node: *mut pg_sys::Node) -> bool {
+    unsafe { is_literal_value(node) && (*(node as *mut pg_sys::A_Const)).isnull }
+}
+
+/// COALESCE(expr, fallback) 생성
+unsafe fn create_coalesce_expr(expr: *mut pg_sys::Node, fallback: *mut pg_sys::Node) -> *mut pg_sys::Node {
+    unsafe {
+        let coalesce = pg_sys::palloc0(size_of::<pg_sys::CoalesceExpr>()) as *mut pg_sys::CoalesceExpr;
+        (*coalesce).xpr.type_ = pg_sys::NodeTag::T_CoalesceExpr;
+        let expr_cell = pg_sys::ListCell { ptr_value: expr as *mut c_void };
+        let fallback_cell = pg_sys::ListCell { ptr_value: fallback as *mut c_void };
+        (*coalesce).args = pg_sys::list_make2_impl(pg_sys::NodeTag::T_List, expr_cell, fallback_cell);
+        (*coalesce).location = -1;
+
+        coalesce as *mut pg_sys::Node
+    }
+}
+
+/* ------------------------------------------------
+CASE 15) Set-returning Function - 허용되지 않는 위치의 SRF를 LATERAL FROM item으로 옮기기
+------------------------------------------------ */
+/// set-returning 함수가 CASE/COALESCE/집계 인자 등 set을 받을 수 없는 위치에서 호출된 경우의 refinement 생성
+/// call_positions(FuncCall location + 1)의 호출마다 `LATERAL fn(...) srf`를 FROM절에 추가하고 원래 자리는 `srf`로 바꾼다
+pub fn generate_srf_lateral_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    call_positions: &[i32],
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let mut out = Vec::new();
+    let weight = SRF_LATERAL_REFINEMENT_WEIGHT.get() as f32;
+
+    unsafe {
+        for &call_pos in call_positions {
+            let cloned = copy_node(orig);
+            let Some(select_stmt) = find_select_stmt(cloned) else {
+                continue;
+            };
+            let Some(func_call) = find_function_call_at(cloned, call_pos) else {
+                continue;
+            };
+
+            let alias = generate_srf_alias(cloned);
+            if !replace_child_node(cloned, func_call, create_unqualified_column_ref(&alias)) {
+                continue;
+            }
+            let range_function = create_lateral_range_function(func_call, &alias);
+            (*select_stmt).fromClause = pg_sys::lappend((*select_stmt).fromClause, range_function as *mut c_void);
+
+            let cumulative_priority = base_priority + ((1.0 * 100.0) * weight) as i32;
+            out.push((cumulative_priority, cloned));
+            // pgrx::notice!("SafeQL: Generated LATERAL refinement for set-returning call at {}", call_pos);
+        }
+    }
+
+    out
+}
+
+/// location + 1이 call_pos인 FuncCall 찾기
+unsafe fn find_function_call_at(raw: *mut pg_sys::RawStmt, call_pos: i32) -> Option<*mut pg_sys::Node> {
+    let mut ctx: (i32, Option<*mut pg_sys::Node>) = (call_pos, None);
+
+    unsafe {
+        safe_raw_expression_tree_walker(
+            (*raw).stmt,
+            Some(find_function_call_at_walker),
+            &mut ctx as *mut (i32, Option<*mut pg_sys::Node>) as *mut c_void
+        );
+    }
+
+    ctx.1
+}
+
+unsafe extern "C" fn find_function_call_at_walker(node: *mut pg_sys::Node, ctx: *mut c_void) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let (call_pos, found) = &mut *(ctx as *mut (i32, Option<*mut pg_sys::Node>));
+
+        if (*node).type_ == pg_sys::NodeTag::T_FuncCall
+            && (*(node as *mut pg_sys::FuncCall)).location + 1 == *call_pos
+        {
+            *found = Some(node);
+            return true; // 찾았으므로 중단
+        }
+
+        safe_raw_expression_tree_walker(node, Some(find_function_call_at_walker), ctx)
+    }
+}
+
+/// FROM절의 테이블명/alias와 겹치지 않는 srf alias 생성 (srf, srf2, ...)
+fn generate_srf_alias(raw: *mut pg_sys::RawStmt) -> String {
+    let used_names: std::collections::HashSet<String> = extract_all_tables_from_raw(raw).iter()
+        .flat_map(|t| [Some(fold_identifier(&t.table_name)), t.alias.as_ref().map(|a| fold_identifier(a))])
+        .flatten()
+        .collect();
+
+    if !used_names.contains("srf") {
+        return "srf".to_string();
+    }
+    (2..).map(|n| format!("srf{}", n))
+        .find(|candidate| !used_names.contains(candidate))
+        .unwrap()
+}
+
+/// LATERAL fn(...) AS alias 형태의 RangeFunction 생성
+unsafe fn create_lateral_range_function(func_call: *mut pg_sys::Node, alias: &str) -> *mut pg_sys::RangeFunction {
+    unsafe {
+        let range_function = pg_sys::palloc0(size_of::<pg_sys::RangeFunction>()) as *mut pg_sys::RangeFunction;
+        (*range_function).type_ = pg_sys::NodeTag::T_RangeFunction;
+        (*range_function).lateral = true;
+
+        // functions는 (함수 호출, column definition list) 쌍의 리스트
+        let function_cell = pg_sys::ListCell { ptr_value: func_call as *mut c_void };
+        let coldeflist_cell = pg_sys::ListCell { ptr_value: std::ptr::null_mut() };
+        let function_item = pg_sys::list_make2_impl(pg_sys::NodeTag::T_List, function_cell, coldeflist_cell);
+        (*range_function).functions = create_single_item_list(function_item as *mut pg_sys::Node);
+
+        let alias_cstr = CString::new(alias).unwrap();
+        (*range_function).alias = pg_sys::makeAlias(alias_cstr.as_ptr(), std::ptr::null_mut());
+
+        range_function
+    }
+}
+
+/// 컬럼 참조 생성 (column)
+unsafe fn create_unqualified_column_ref(column_name: &str) -> *mut pg_sys::Node {
+    unsafe {
+        let col_ref = pg_sys::palloc0(size_of::<pg_sys::ColumnRef>()) as *mut pg_sys::ColumnRef;
+        (*col_ref).type_ = pg_sys::NodeTag::T_ColumnRef;
+        (*col_ref).fields = create_single_item_list(create_string_node(column_name));
+        (*col_ref).location = -1;
+
+        col_ref as *mut pg_sys::Node
+    }
+}
+
+/// AST에서 target 노드를 가리키는 부모의 포인터를 replacement로 교체
+unsafe fn replace_child_node(
+    raw: *mut pg_sys::RawStmt,
+    target: *mut pg_sys::Node,
+    replacement: *mut pg_sys::Node,
+) -> bool {
+    let mut ctx: (*mut pg_sys::Node, *mut pg_sys::Node, bool) = (target, replacement, false);
+
+    unsafe {
+        // walker는 루트의 자식부터 방문하므로 루트 SelectStmt의 슬롯은 직접 확인
+        if replace_node_slots((*raw).stmt, target, replacement) {
+            return true;
+        }
+        safe_raw_expression_tree_walker(
+            (*raw).stmt,
+            Some(replace_child_node_walker),
+            &mut ctx as *mut (*mut pg_sys::Node, *mut pg_sys::Node, bool) as *mut c_void
+        );
+    }
+
+    ctx.2
+}
+
+unsafe extern "C" fn replace_child_node_walker(node: *mut pg_sys::Node, ctx: *mut c_void) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let (target, replacement, replaced) = &mut *(ctx as *mut (*mut pg_sys::Node, *mut pg_sys::Node, bool));
+
+        if replace_node_slots(node, *target, *replacement) {
+            *replaced = true;
+            return true; // 교체 완료, 더 이상 탐색 불필요
+        }
+
+        safe_raw_expression_tree_walker(node, Some(replace_child_node_walker), ctx)
+    }
+}
+
+/// parent의 표현식 슬롯 중 target을 가리키는 것을 replacement로 교체
+unsafe fn replace_node_slots(
+    parent: *mut pg_sys::Node,
+    target: *mut pg_sys::Node,
+    replacement: *mut pg_sys::Node,
+) -> bool {
+    unsafe {
+        if parent.is_null() {
+            return false;
+        }
+
+        match (*parent).type_ {
+            pg_sys::NodeTag::T_SelectStmt => {
+                let select_stmt = parent as *mut pg_sys::SelectStmt;
+                replace_node_slot(&mut (*select_stmt).whereClause, target, replacement)
+                    || replace_node_slot(&mut (*select_stmt).havingClause, target, replacement)
+            }
+            pg_sys::NodeTag::T_ResTarget => {
+                replace_node_slot(&mut (*(parent as *mut pg_sys::ResTarget)).val, target, replacement)
+            }
+            pg_sys::NodeTag::T_A_Expr => {
+                let a_expr = parent as *mut pg_sys::A_Expr;
+                replace_node_slot(&mut (*a_expr).lexpr, target, replacement)
+                    || replace_node_slot(&mut (*a_expr).rexpr, target, replacement)
+            }
+            pg_sys::NodeTag::T_CaseExpr => {
+                let case_expr = parent as *mut pg_sys::CaseExpr;
+                replace_node_slot(&mut (*case_expr).arg, target, replacement)
+                    || replace_node_slot(&mut (*case_expr).defresult, target, replacement)
+            }
+            pg_sys::NodeTag::T_CaseWhen => {
+                let case_when = parent as *mut pg_sys::CaseWhen;
+                replace_node_slot(&mut (*case_when).expr, target, replacement)
+                    || replace_node_slot(&mut (*case_when).result, target, replacement)
+            }
+            pg_sys::NodeTag::T_TypeCast => {
+                replace_node_slot(&mut (*(parent as *mut pg_sys::TypeCast)).arg, target, replacement)
+            }
+            pg_sys::NodeTag::T_NullTest => {
+                replace_node_slot(&mut (*(parent as *mut pg_sys::NullTest)).arg, target, replacement)
+            }
+            pg_sys::NodeTag::T_BoolExpr => {
+                replace_node_in_list((*(parent as *mut pg_sys::BoolExpr)).args, target, replacement)
+            }
+            pg_sys::NodeTag::T_FuncCall => {
+                replace_node_in_list((*(parent as *mut pg_sys::FuncCall)).args, target, replacement)
+            }
+            pg_sys::NodeTag::T_CoalesceExpr => {
+                replace_node_in_list((*(parent as *mut pg_sys::CoalesceExpr)).args, target, replacement)
+            }
+            pg_sys::NodeTag::T_MinMaxExpr => {
+                replace_node_in_list((*(parent as *mut pg_sys::MinMaxExpr)).args, target, replacement)
+            }
+            pg_sys::NodeTag::T_WindowDef => {
+                // OVER (PARTITION BY ... ORDER BY ...), ORDER BY 항목은 SortBy가 감싼다
+                replace_node_in_list((*(parent as *mut pg_sys::WindowDef)).partitionClause, target, replacement)
+            }
+            pg_sys::NodeTag::T_SortBy => {
+                replace_node_slot(&mut (*(parent as *mut pg_sys::SortBy)).node, target, replacement)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 포인터 슬롯 하나가 target이면 replacement로 교체 (Expr* 슬롯도 같은 방식)
+fn replace_node_slot<T>(slot: &mut *mut T, target: *mut pg_sys::Node, replacement: *mut pg_sys::Node) -> bool {
+    if *slot as *mut pg_sys::Node == target {
+        *slot = replacement as *mut T;
+        return true;
+    }
+    false
+}
+
+/// List의 원소 중 target을 replacement로 교체
+unsafe fn replace_node_in_list(list: *mut pg_sys::List, target: *mut pg_sys::Node, replacement: *mut pg_sys::Node) -> bool {
+    unsafe {
+        if list.is_null() || (*list).elements.is_null() {
+            return false;
+        }
+        for idx in 0..(*list).length as usize {
+            let cell_ptr = (*list).elements.add(idx);
+            if (*cell_ptr).ptr_value == target as *mut c_void {
+                (*cell_ptr).ptr_value = replacement as *mut c_void;
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/* ------------------------------------------------
+CASE 16) Missing OVER - OVER 없이 호출한 window 함수에 `OVER ()` 붙이기
+------------------------------------------------ */
+/// "window function X requires an OVER clause" 에러 위치(FuncCall location + 1)의 호출에 빈 window를 붙인다
+pub fn generate_window_over_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    call_pos: i32,
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let mut out = Vec::new();
+    let weight = WINDOW_OVER_REFINEMENT_WEIGHT.get() as f32;
+
+    unsafe {
+        let cloned = copy_node(orig);
+        let Some(func_call) = find_function_call_at(cloned, call_pos) else {
+            return out;
+        };
+        let func_call = func_call as *mut pg_sys::FuncCall;
+        if !(*func_call).over.is_null() {
+            return out;
+        }
+
+        (*func_call).over = create_empty_window_def();
+
+        let cumulative_priority = base_priority + ((1.0 * 100.0) * weight) as i32;
+        out.push((cumulative_priority, cloned));
+    }
+
+    out
+}
+
+/// partition/order/frame이 모두 기본값인 `OVER ()` WindowDef 생성
+unsafe fn create_empty_window_def() -> *mut pg_sys::WindowDef {
+    unsafe {
+        let window_def = pg_sys::palloc0(size_of::<pg_sys::WindowDef>()) as *mut pg_sys::WindowDef;
+        (*window_def).type_ = pg_sys::NodeTag::T_WindowDef;
+        (*window_def).frameOptions = pg_sys::FRAMEOPTION_DEFAULTS as i32;
+        (*window_def).location = -1;
+        window_def
+    }
+}
+
+/* ------------------------------------------------
+CASE 17) Function Arity - 인자 개수가 맞지 않는 함수 호출을 존재하는 overload에 맞추기
+------------------------------------------------ */
+/// 에러 위치(FuncCall location + 1)의 호출에서 남는 인자를 빼거나 모자란 인자를 기본값으로 채운다
+/// overloads는 같은 이름 함수들의 인자 타입 목록 (find_function_overload_arg_types)
+pub fn generate_function_arity_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    call_pos: i32,
+    overloads: &[Vec<String>],
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let mut out = Vec::new();
+    let weight = FUNCTION_ARITY_REFINEMENT_WEIGHT.get() as f32;
+
+    unsafe {
+        let Some(func_call) = find_function_call_at(orig, call_pos) else {
+            return out;
+        };
+        let func_call = func_call as *mut pg_sys::FuncCall;
+        if (*func_call).agg_star || (*func_call).func_variadic {
+            return out;
+        }
+        let arg_count = list_length((*func_call).args);
+
+        let mut seen_arities = std::collections::HashSet::new();
+        for arg_types in overloads {
+            let target_count = arg_types.len();
+            if target_count == arg_count || !seen_arities.insert(target_count) {
+                continue;
+            }
+            let diff = target_count.abs_diff(arg_count);
+            let arity_priority = base_priority + ((0.5 * diff as f32 * 100.0) * weight) as i32;
+
+            if target_count < arg_count {
+                // 하나만 남으면 어느 인자든 뺄 수 있고, 그 이상이면 뒤쪽 인자들을 뺀다
+                let drop_sets: Vec<Vec<usize>> = if diff == 1 {
+                    (0..arg_count).rev().map(|idx| vec![idx]).collect()
+                } else {
+                    vec![(target_count..arg_count).collect()]
+                };
+                for (rank, drop_set) in drop_sets.iter().enumerate() {
+                    let cloned = copy_node(orig);
+                    let Some(cloned_call) = find_function_call_at(cloned, call_pos) else {
+                        continue;
+                    };
+                    let cloned_call = cloned_call as *mut pg_sys::FuncCall;
+                    (*cloned_call).args = rebuild_args_without((*cloned_call).args, drop_set);
+                    out.push((arity_priority + rank as i32, cloned));
+                }
+            } else {
+                let cloned = copy_node(orig);
+                let Some(cloned_call) = find_function_call_at(cloned, call_pos) else {
+                    continue;
+                };
+                let cloned_call = cloned_call as *mut pg_sys::FuncCall;
+                for arg_type in &arg_types[arg_count..] {
+                    (*cloned_call).args = pg_sys::lappend(
+                        (*cloned_call).args,
+                        create_default_argument(arg_type) as *mut c_void
+                    );
+                }
+                out.push((arity_priority, cloned));
+            }
+            // pgrx::notice!("SafeQL: Generated arity refinement {} -> {} args at {}", arg_count, target_count, call_pos);
+        }
+    }
+
+    out
+}
+
+unsafe fn list_length(list: *mut pg_sys::List) -> usize {
+    unsafe {
+        if list.is_null() { 0 } else { (*list).length as usize }
+    }
+}
+
+/// drop_set 위치의 원소를 뺀 새 인자 List
+unsafe fn rebuild_args_without(args: *mut pg_sys::List, drop_set: &[usize]) -> *mut pg_sys::List {
+    unsafe {
+        let mut rebuilt: *mut pg_sys::List = std::ptr::null_mut();
+        for idx in 0..list_length(args) {
+            if drop_set.contains(&idx) {
+                continue;
+            }
+            let cell_ptr = (*args).elements.add(idx);
+            rebuilt = pg_sys::lappend(rebuilt, (*cell_ptr).ptr_value);
+        }
+        rebuilt
+    }
+}
+
+/// 모자란 인자를 채울 타입별 기본값 (정수 1, 실수 0, 문자열 '', 그 외 NULL::type)
+unsafe fn create_default_argument(type_name: &str) -> *mut pg_sys::Node {
+    unsafe {
+        match type_name {
+            "int2" | "int4" | "int8" => create_numeric_literal("1"),
+            "numeric" | "float4" | "float8" => create_numeric_literal("0"),
+            "text" | "varchar" | "bpchar" | "name" => create_string_literal(""),
+            _ => create_typecast_node(create_null_literal(), type_name),
+        }
+    }
+}
+
+unsafe fn create_null_literal() -> *mut pg_sys::Node {
+    unsafe {
+        let a_const =
+            pg_sys::palloc0(size_of::<pg_sys::A_Const>()) as *mut pg_sys::A_Const;
+        (*a_const).type_ = pg_sys::NodeTag::T_A_Const;
+        (*a_const).isnull = true;
+        (*a_const).location = -1;
+
+        a_const as *mut pg_sys::Node
+    }
+}
+
+/* ------------------------------------------------
+CASE 18) ORDER BY Target - select list와 맞지 않는 ORDER BY 식을 유효하게 만들기
+------------------------------------------------ */
+/// ORDER BY 식이 select list와 맞지 않아 analyze가 실패한 경우의 refinement 생성
+/// - SELECT DISTINCT: select list에 없는 ORDER BY 식을 select list에 추가
+/// - GROUP BY 없이 ORDER BY에만 집계가 있으면: select list의 식들로 GROUP BY 추가
+/// - fallback: ORDER BY의 인자 하나짜리 집계 호출을 그 인자 컬럼으로 교체
+pub fn generate_order_by_target_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let mut out = Vec::new();
+    let weight = ORDER_BY_TARGET_REFINEMENT_WEIGHT.get() as f32;
+    let sort_target_priority = base_priority + ((0.5 * 100.0) * weight) as i32;
+    let fallback_priority = base_priority + ((1.0 * 100.0) * weight) as i32;
+
+    unsafe {
+        let Some(select_stmt) = find_select_stmt(orig) else {
+            return out;
+        };
+        let sort_exprs = list_nodes((*select_stmt).sortClause)
+            .into_iter()
+            .map(|sort_by| (*(sort_by as *mut pg_sys::SortBy)).node)
+            .collect::<Vec<_>>();
+        if sort_exprs.is_empty() {
+            return out;
+        }
+        let target_vals = target_list_values(select_stmt);
+
+        if !(*select_stmt).distinctClause.is_null() {
+            // DISTINCT는 ORDER BY 식이 select list에 있어야 한다
+            let cloned = copy_node(orig);
+            if let Some(cloned_select) = find_select_stmt(cloned) {
+                for sort_expr in &sort_exprs {
+                    let projected = target_vals.iter()
+                        .any(|target_val| pg_sys::equal(*target_val as *const c_void, *sort_expr as *const c_void));
+                    if !projected {
+                        (*cloned_select).targetList = pg_sys::lappend(
+                            (*cloned_select).targetList,
+                            create_res_target(copy_node(*sort_expr)) as *mut c_void
+                        );
+                    }
+                }
+                out.push((sort_target_priority, cloned));
+            }
+        } else if (*select_stmt).groupClause.is_null()
+            && sort_exprs.iter().any(|sort_expr| contains_aggregate_call(*sort_expr))
+            && !target_vals.iter().any(|target_val| contains_aggregate_call(*target_val) || is_star_column_ref(*target_val))
+        {
+            // ORDER BY의 집계만 있으면 select list 식들로 묶어야 집계 정렬이 유효하다
+            let cloned = copy_node(orig);
+            if let Some(cloned_select) = find_select_stmt(cloned) {
+                for target_val in target_list_values(cloned_select) {
+                    (*cloned_select).groupClause = pg_sys::lappend(
+                        (*cloned_select).groupClause,
+                        copy_node(target_val) as *mut c_void
+                    );
+                }
+                out.push((sort_target_priority, cloned));
+            }
+        }
+
+        // fallback: sum(amount) -> amount
+        let cloned = copy_node(orig);
+        if let Some(cloned_select) = find_select_stmt(cloned) {
+            let mut replaced_any = false;
+            for sort_by in list_nodes((*cloned_select).sortClause) {
+                let sort_by = sort_by as *mut pg_sys::SortBy;
+                let sort_expr = (*sort_by).node;
+                if !is_aggregate_call(sort_expr) {
+                    continue;
+                }
+                let args = list_nodes((*(sort_expr as *mut pg_sys::FuncCall)).args);
+                if let [arg] = args.as_slice() {
+                    (*sort_by).node = *arg;
+                    replaced_any = true;
+                }
+            }
+            if replaced_any {
+                out.push((fallback_priority, cloned));
+            }
+        }
+    }
+
+    out
+}
+
+/// List의 원소들을 Node 포인터 Vec로
+unsafe fn list_nodes(list: *mut pg_sys::List) -> Vec<*mut pg_sys::Node> {
+    unsafe {
+        (0..list_length(list))
+            .map(|idx| (*(*list).elements.add(idx)).ptr_value as *mut pg_sys::Node)
+            .collect()
+    }
+}
+
+/// select list의 ResTarget 값들
+unsafe fn target_list_values(select_stmt: *mut pg_sys::SelectStmt) -> Vec<*mut pg_sys::Node> {
+    unsafe {
+        list_nodes((*select_stmt).targetList)
+            .into_iter()
+            .map(|res_target| (*(res_target as *mut pg_sys::ResTarget)).val)
+            .collect()
+    }
+}
+
+/// 이름 없는 select list 항목 생성
+unsafe fn create_res_target(val: *mut pg_sys::Node) -> *mut pg_sys::ResTarget {
+    unsafe {
+        let res_target = pg_sys::palloc0(size_of::<pg_sys::ResTarget>()) as *mut pg_sys::ResTarget;
+        (*res_target).type_ = pg_sys::NodeTag::T_ResTarget;
+        (*res_target).val = val;
+        (*res_target).location = -1;
+        res_target
+    }
+}
+
+/// `*` 또는 `t.*` 컬럼 참조인지 확인
+unsafe fn is_star_column_ref(node: *mut pg_sys::Node) -> bool {
+    unsafe {
+        is_column_ref(node)
+            && list_nodes((*(node as *mut pg_sys::ColumnRef)).fields)
+                .last()
+                .is_some_and(|field| (**field).type_ == pg_sys::NodeTag::T_A_Star)
+    }
+}
+
+/// OVER 없는 집계 함수 호출인지 확인 (count(*) 포함)
+unsafe fn is_aggregate_call(node: *mut pg_sys::Node) -> bool {
+    unsafe {
+        if node.is_null() || (*node).type_ != pg_sys::NodeTag::T_FuncCall {
+            return false;
+        }
+        let func_call = node as *mut pg_sys::FuncCall;
+        if !(*func_call).over.is_null() {
+            return false;
+        }
+        if (*func_call).agg_star {
+            return true;
+        }
+        let names = list_nodes((*func_call).funcname);
+        names.last()
+            .and_then(|name| string_node_value(*name))
+            .is_some_and(|name| is_aggregate_function(&name))
+    }
+}
+
+/// 최상위 SELECT가 GROUP BY/HAVING을 쓰거나 select list에 집계 함수가 있는지 확인
+pub fn is_aggregate_select(raw: *mut pg_sys::RawStmt) -> bool {
+    unsafe {
+        let Some(select_stmt) = find_select_stmt(raw) else {
+            return false;
+        };
+        !(*select_stmt).groupClause.is_null()
+            || !(*select_stmt).havingClause.is_null()
+            || contains_aggregate_call((*select_stmt).targetList as *mut pg_sys::Node)
+    }
+}
+
+/// 식 안에 집계 함수 호출이 있는지 확인
+unsafe fn contains_aggregate_call(node: *mut pg_sys::Node) -> bool {
+    let mut found = false;
+    unsafe {
+        contains_aggregate_call_walker(node, &mut found as *mut bool as *mut c_void);
+    }
+    found
+}
+
+unsafe extern "C" fn contains_aggregate_call_walker(node: *mut pg_sys::Node, ctx: *mut c_void) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        if is_aggregate_call(node) {
+            *(ctx as *mut bool) = true;
+            return true;
+        }
+
+        safe_raw_expression_tree_walker(node, Some(contains_aggregate_call_walker), ctx)
+    }
+}
+
+/* ------------------------------------------------
+CASE 19) Ordinal Position - select list 밖의 ORDER BY/GROUP BY 위치 번호 보정
+------------------------------------------------ */
+/// "ORDER BY position N is not in select list" 에러의 위치 번호를 select list 안의 번호로 바꾼다
+/// 가장 가까운 마지막 위치(clamp)가 먼저, 멀어질수록 priority가 뒤로 밀린다
+pub fn generate_ordinal_position_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    position: i32,
+    in_group_by: bool,
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let mut out = Vec::new();
+    let weight = ORDINAL_POSITION_REFINEMENT_WEIGHT.get() as f32;
+
+    unsafe {
+        let Some(select_stmt) = find_select_stmt(orig) else {
+            return out;
+        };
+        let target_count = list_length((*select_stmt).targetList) as i32;
+
+        for valid_position in (1..=target_count.min(position - 1)).rev() {
+            let cloned = copy_node(orig);
+            let Some(cloned_select) = find_select_stmt(cloned) else {
+                continue;
+            };
+
+            let clause = if in_group_by { (*cloned_select).groupClause } else { (*cloned_select).sortClause };
+            let mut replaced_any = false;
+            for idx in 0..list_length(clause) {
+                let cell_ptr = (*clause).elements.add(idx);
+                // GROUP BY 항목은 리스트 원소 자체, ORDER BY 항목은 SortBy가 감싼다
+                let slot = if in_group_by {
+                    &mut (*cell_ptr).ptr_value as *mut *mut c_void as *mut *mut pg_sys::Node
+                } else {
+                    &mut (*((*cell_ptr).ptr_value as *mut pg_sys::SortBy)).node as *mut *mut pg_sys::Node
+                };
+                if extract_literal_numeric_value(*slot) == Some(position as f64) {
+                    *slot = create_numeric_literal(&valid_position.to_string());
+                    replaced_any = true;
+                }
+            }
+
+            if replaced_any {
+                let distance = (position - valid_position) as f32;
+                let cumulative_priority = base_priority + ((0.5 * distance * 100.0) * weight) as i32;
+                out.push((cumulative_priority, cloned));
+            }
+        }
+    }
+
+    out
+}
+
+/* ------------------------------------------------
+CASE 20) CASE Branch Cast - 타입이 맞지 않는 CASE 분기를 cast로 통일
+------------------------------------------------ */
+/// "CASE types X and Y cannot be matched" 에러의 refinement 생성
+/// 에러 위치(error_pos, 1-based)의 분기가 Y 타입이고, 먼저 본 분기 중 하나가 X 타입
+/// - 에러 위치의 분기를 X로 cast
+/// - 나머지 분기를 모두 Y로 cast
+/// cast하는 분기가 적을수록(소수 쪽 분기) 먼저 시도
+pub fn generate_case_branch_cast_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    error_pos: i32,
+    common_type: &str,
+    branch_type: &str,
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let mut out = Vec::new();
+    let weight = CASE_BRANCH_CAST_REFINEMENT_WEIGHT.get() as f32;
+
+    for cast_error_branch in [true, false] {
+        unsafe {
+            let cloned = copy_node(orig);
+            let Some(branches) = find_case_branches_at(cloned, error_pos) else {
+                return out;
+            };
+            let Some(error_idx) = branches.iter()
+                .position(|slot| pg_sys::exprLocation(*(*slot) as *const pg_sys::Node) + 1 == error_pos)
+            else {
+                return out;
+            };
+
+            let (slots, target_type): (Vec<_>, &str) = if cast_error_branch {
+                (vec![branches[error_idx]], common_type)
+            } else {
+                let others = branches.iter().enumerate()
+                    .filter(|(idx, _)| *idx != error_idx)
+                    .map(|(_, slot)| *slot)
+                    .collect();
+                (others, branch_type)
+            };
+            if slots.is_empty() {
+                continue;
+            }
+
+            for slot in &slots {
+                *(*slot) = create_typecast_node_from_type_string(*(*slot) as *mut pg_sys::Node, target_type) as *mut pg_sys::Expr;
+            }
+
+            let cast_fraction = slots.len() as f32 / branches.len() as f32;
+            let cumulative_priority = base_priority + ((cast_fraction * 100.0) * weight) as i32;
+            out.push((cumulative_priority, cloned));
+        }
+    }
+
+    out
+}
+
+/// 결과 분기 중 하나가 error_pos(location + 1)에 있는 CASE 식의 분기 slot들 (THEN 결과들, ELSE 순)
+unsafe fn find_case_branches_at(raw: *mut pg_sys::RawStmt, error_pos: i32) -> Option<Vec<*mut *mut pg_sys::Expr>> {
+    let mut ctx: (i32, Option<Vec<*mut *mut pg_sys::Expr>>) = (error_pos, None);
+
+    unsafe {
+        safe_raw_expression_tree_walker(
+            (*raw).stmt,
+            Some(find_case_branches_at_walker),
+            &mut ctx as *mut (i32, Option<Vec<*mut *mut pg_sys::Expr>>) as *mut c_void
+        );
+    }
+
+    ctx.1
+}
+
+unsafe extern "C" fn find_case_branches_at_walker(node: *mut pg_sys::Node, ctx: *mut c_void) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let (error_pos, found) = &mut *(ctx as *mut (i32, Option<Vec<*mut *mut pg_sys::Expr>>));
+
+        if (*node).type_ == pg_sys::NodeTag::T_CaseExpr {
+            let case_expr = node as *mut pg_sys::CaseExpr;
+            let mut branches = Vec::new();
+            for idx in 0..list_length((*case_expr).args) {
+                let case_when = (*(*(*case_expr).args).elements.add(idx)).ptr_value as *mut pg_sys::CaseWhen;
+                branches.push(&mut (*case_when).result as *mut *mut pg_sys::Expr);
+            }
+            if !(*case_expr).defresult.is_null() {
+                branches.push(&mut (*case_expr).defresult as *mut *mut pg_sys::Expr);
+            }
+
+            if branches.iter().any(|slot| pg_sys::exprLocation(*(*slot) as *const pg_sys::Node) + 1 == *error_pos) {
+                *found = Some(branches);
+                return true; // 찾았으므로 중단
+            }
+        }
+
+        safe_raw_expression_tree_walker(node, Some(find_case_branches_at_walker), ctx)
+    }
+}
+
+/// "character varying"처럼 여러 단어인 타입 이름도 그대로 파싱해 TypeCast 노드 생성
+unsafe fn create_typecast_node_from_type_string(operand_node: *mut pg_sys::Node, type_string: &str) -> *mut pg_sys::Node {
+    unsafe {
+        let type_cstr = CString::new(type_string).unwrap();
+        let typecast = pg_sys::palloc0(size_of::<pg_sys::TypeCast>()) as *mut pg_sys::TypeCast;
+        (*typecast).type_ = pg_sys::NodeTag::T_TypeCast;
+        (*typecast).arg = operand_node;
+        (*typecast).typeName = pg_sys::typeStringToTypeName(type_cstr.as_ptr(), std::ptr::null_mut());
+        (*typecast).location = -1;
+
+        typecast as *mut pg_sys::Node
+    }
+}
+
+/* ------------------------------------------------
+CASE 9) Column Reference Ambiguous - qualified reference로 변경
+------------------------------------------------ */
+/// Column ambiguity 에러에 대한 refinement 생성
+/// FROM절의 테이블들 중에서 해당 컬럼을 가진 테이블을 찾아 qualified reference로 변경
+pub fn generate_column_ambiguity_refinements_raw(
+    orig: *mut pg_sys::RawStmt,
+    ambiguous_col: &str,
+    base_priority: i32
+) -> Vec<(i32, *mut pg_sys::RawStmt)> {
+    let mut out = Vec::new();
+    
+    // FROM절의 모든 테이블과 alias 추출
+    let from_tables = extract_all_tables_from_raw(orig);
+    if from_tables.is_empty() {
+        return out;
+    }
+    
+    let weight = COLUMN_AMBIGUITY_REFINEMENT_WEIGHT.get() as f32;
+    
+    // 모든 테이블명 추출 (실제 테이블명만, alias는 제외)
+    let table_names: Vec<String> = from_tables.iter()
+        .map(|t| t.table_name.clone())
+        .collect();
+    
+    // 한 번에 해당 컬럼을 가진 테이블들 찾기
+    let matching_table_names = find_tables_with_exact_column(&table_names, ambiguous_col);
+    
+    if matching_table_names.is_empty() {
+        // pgrx::notice!("SafeQL: No tables found with exact column '{}' in FROM clause", ambiguous_col);
+        return out;
+    }
+    
+    // 주변 연산자 문맥에서 기대하는 타입이 있으면, 그 타입의 컬럼을 가진 테이블부터 시도
+    let ranked_table_names = match unsafe { find_ambiguous_column_context_type(orig, ambiguous_col) } {
+        Some(expected_type) => rank_tables_by_column_type(&matching_table_names, ambiguous_col, &expected_type),
+        None => matching_table_names,
+    };
+
+    // 매치된 테이블들에 대해서만 refinement 생성
+    // 같은 priority의 순서는 SQL 사전순으로 정해지므로, 타입 순위(rank)를 priority에 1씩 더해 유지한다
+    for (rank, table_info) in ranked_table_names.iter()
+        .flat_map(|name| from_tables.iter().filter(move |t| &t.table_name == name))
+        .enumerate()
+    {
+        // distance는 1.0으로 고정
+        let additional_priority = ((1.0 * 100.0) * weight) as i32;
+        let cumulative_priority = base_priority + additional_priority + rank as i32;
+        
+        let cloned = unsafe { copy_node(orig) };
+        
+        // 참조할 때 사용할 이름 결정 (alias가 있으면 alias, 없으면 테이블명)
+        let reference_name = table_info.get_reference_name();
+        
+        unsafe { 
+            qualify_ambiguous_column_reference(cloned, ambiguous_col, reference_name, ambiguous_col);
+        }
+        out.push((cumulative_priority, cloned));
+        
+        // pgrx::notice!("SafeQL: Generated ambiguity refinement {} -> {}.{} (using {})", 
+        //     ambiguous_col, reference_name, ambiguous_col, 
+        //     if table_info.alias.is_some() { "alias" } else { "table name" });
+    }
+    
+    // pgrx::notice!("SafeQL: Generated {} column ambiguity refinements for '{}' from {} matching tables", 
+    //     out.len(), ambiguous_col, matching_table_names.len());
+    
+    out
+}
+
+#[derive(Debug)]
+struct AmbiguousContextTypeCtx {
+    ambiguous_col: String,
+    expected_type: Option<String>,
+}
+
+/// ambiguous 컬럼이 연산자의 한쪽 operand로 쓰일 때, 반대쪽 literal/cast가 기대하는 타입 찾기
+unsafe fn find_ambiguous_column_context_type(
+    raw: *mut pg_sys::RawStmt,
+    ambiguous_col: &str,
+) -> Option<String> {
+    let mut ctx = AmbiguousContextTypeCtx {
+        ambiguous_col: fold_identifier(ambiguous_col),
+        expected_type: None,
+    };
+    let ctx_ptr = &mut ctx as *mut AmbiguousContextTypeCtx as *mut c_void;
+
+    unsafe {
+        safe_raw_expression_tree_walker((*raw).stmt, Some(ambiguous_context_type_walker), ctx_ptr);
+    }
+
+    ctx.expected_type
+}
+
+unsafe extern "C" fn ambiguous_context_type_walker(node: *mut pg_sys::Node, ctx: *mut c_void) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let ctx_ref = &mut *(ctx as *mut AmbiguousContextTypeCtx);
+
+        if (*node).type_ == pg_sys::NodeTag::T_A_Expr {
+            let a_expr = node as *mut pg_sys::A_Expr;
+
+            if (*a_expr).kind == pg_sys::A_Expr_Kind::AEXPR_OP {
+                let other = if is_unqualified_column((*a_expr).lexpr, &ctx_ref.ambiguous_col) {
+                    (*a_expr).rexpr
+                } else if is_unqualified_column((*a_expr).rexpr, &ctx_ref.ambiguous_col) {
+                    (*a_expr).lexpr
+                } else {
+                    std::ptr::null_mut()
+                };
+
+                if let Some(expected_type) = literal_expression_type(other) {
+                    ctx_ref.expected_type = Some(expected_type);
+                    return true;
+                }
+            }
+        }
+
+        safe_raw_expression_tree_walker(node, Some(ambiguous_context_type_walker), ctx)
+    }
+}
+
+/// 한정자 없는 ColumnRef가 주어진 컬럼명인지 확인
+unsafe fn is_unqualified_column(node: *mut pg_sys::Node, column: &str) -> bool {
+    unsafe {
+        if !is_column_ref(node) {
+            return false;
+        }
+        let col_ref = node as *mut pg_sys::ColumnRef;
+        let field_count = memcx::current_context(|mcx| {
+            List::<*mut c_void>::downcast_ptr_in_memcx((*col_ref).fields, mcx).map(|fields| fields.len())
+        });
+        field_count == Some(1)
+            && column_ref_name(node).is_some_and(|name| identifiers_match(&name, column))
+    }
+}
+
+/// literal_type_name과 같지만, 타입이 정해지지 않은 문자열 리터럴은 text로 본다
+unsafe fn literal_expression_type(node: *mut pg_sys::Node) -> Option<String> {
+    unsafe {
+        literal_type_name(node).or_else(|| {
+            extract_literal_string_value(node).map(|_| "text".to_string())
+        })
+    }
+}
+
+#[derive(Debug)]
+struct AmbiguousColumnQualifyCtx {
+    ambiguous_col: String,
+    table_qualifier: String,
+    new_column_name: String,
+    replaced_any: bool,
+}
 
 /// ambiguous unqualified column reference를 qualified reference로 변경
 unsafe fn qualify_ambiguous_column_reference(
@@ -2467,7 +4535,7 @@ unsafe fn qualify_ambiguous_column_reference(
     new_column_name: &str,
 ) {
     let mut ctx = AmbiguousColumnQualifyCtx {
-        ambiguous_col: ambiguous_col.to_ascii_lowercase(),
+        ambiguous_col: fold_identifier(ambiguous_col),
         table_qualifier: table_qualifier.to_string(),
         new_column_name: new_column_name.to_string(),
         replaced_any: false,
@@ -2511,7 +4579,7 @@ unsafe extern "C" fn ambiguous_column_qualify_walker(node: *mut pg_sys::Node, ct
                                     let column_name = CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned();
                                     
                                     // ambiguous column과 일치하면 qualified reference로 변경
-                                    if column_name.to_ascii_lowercase() == ctx.ambiguous_col {
+                                    if fold_identifier(&column_name) == ctx.ambiguous_col {
                                         // 새로운 qualified fields 리스트 생성
                                         let table_str = create_string_node(&ctx.table_qualifier);
                                         let column_str = create_string_node(&ctx.new_column_name);
@@ -2567,40 +4635,79 @@ pub fn generate_value_refinements_raw(
 
     let cloned = unsafe { copy_node(orig) };
     
-    // SQL에서 모든 equality expression들을 찾기 (R.a = 'literal' 형태)
+    // SQL에서 모든 비교 expression들을 찾기 (R.a = 'literal' 형태, 연산자는 safeql.value_refinement_operators)
     let equality_expressions = unsafe { find_all_equality_expressions(cloned) };
     
     if equality_expressions.is_empty() {
         return out;
     }
     
+    // (table, column, literal)을 모아 한 번의 쿼리로 가장 가까운 값을 찾는다
+    // 추출 단계에서 찾은 literal 쪽 operand를 함께 기억해 교체 시 그 자리만 바꾼다
+    let mut target_exprs = Vec::new();
+    let mut targets = Vec::new();
+    for eq_expr in &equality_expressions {
+        if let Some((literal_position, target)) = unsafe { value_refinement_target(*eq_expr, cloned) } {
+            target_exprs.push((*eq_expr, literal_position));
+            targets.push(target);
+        }
+    }
+
     // 모든 expression에 대한 refinement 정보를 수집
     let mut all_refinements = Vec::new();
     let mut total_distance = 0.0f32;
     // let mut refinement_count = 0;
     
-    for eq_expr in &equality_expressions {
-        unsafe {
-            if let Some((similar_value, table_name, column_name, distance)) = generate_value_refinements_for_expression(*eq_expr, cloned) {
-                all_refinements.push((*eq_expr, similar_value, table_name, column_name, distance));
-                total_distance += distance;
-                // refinement_count += 1;
+    for (((eq_expr, literal_position), (table_name, column_name, _)), similar_values) in target_exprs.iter()
+        .zip(&targets)
+        .zip(find_similar_values_for_literals(&targets))
+    {
+        // 가장 가까운 값 하나만 사용
+        if let Some((similar_value, _, _, distance)) = similar_values.into_iter().next() {
+            all_refinements.push((*eq_expr, literal_position.clone(), similar_value, table_name.clone(), column_name.clone(), distance));
+            // NULL이 많거나 cardinality가 낮은 컬럼의 refinement는 뒤로 (safeql.column_statistics_weight)
+            total_distance += distance + weighted_column_statistics_penalty(table_name, column_name);
+            // refinement_count += 1;
+        }
+    }
+
+    // numeric literal은 safeql.value_refinement_numeric_step 단위로 맞춘 뒤 저장된 값 중 가장 가까운 값으로 교체
+    let numeric_step = VALUE_REFINEMENT_NUMERIC_STEP.get();
+    let mut numeric_refinements = Vec::new();
+    if numeric_step > 0.0 {
+        for eq_expr in &equality_expressions {
+            let Some((literal_position, (table_name, column_name, literal))) = (unsafe { numeric_value_refinement_target(*eq_expr, cloned) }) else {
+                continue;
+            };
+            let snapped = snap_to_step(literal, numeric_step);
+            if let Some(nearest) = find_nearest_numeric_value(&table_name, &column_name, snapped) {
+                if let Ok(nearest_num) = nearest.parse::<f64>() {
+                    if nearest_num != literal {
+                        // 원래 값 대비 상대 거리 (최대 1.0)
+                        total_distance += ((nearest_num - literal).abs() / literal.abs().max(1.0)).min(1.0) as f32;
+                        total_distance += weighted_column_statistics_penalty(&table_name, &column_name);
+                        numeric_refinements.push((*eq_expr, literal_position, nearest));
+                    }
+                }
             }
         }
     }
     
     // refinement할 것이 있으면 모두 적용한 새로운 쿼리 생성
-    if !all_refinements.is_empty() {
+    if !all_refinements.is_empty() || !numeric_refinements.is_empty() {
         let additional_priority = ((total_distance * 100.0) * weight) as i32;
         let cumulative_priority = base_priority + additional_priority;
         
         unsafe {
             // 모든 refinement를 한번에 적용
-            for (expr_node, new_value, _table_name, _column_name, _distance) in &all_refinements {
-                replace_literal_value_in_expression(cloned, *expr_node, new_value);
+            for (expr_node, literal_position, new_value, _table_name, _column_name, _distance) in &all_refinements {
+                replace_literal_value_in_expression(cloned, *expr_node, literal_position.clone(), new_value);
                 // pgrx::notice!("SafeQL: Refined value for {}.{}: -> '{}' (distance: {})", 
                 //     table_name, column_name, new_value, distance);
             }
+            for (expr_node, literal_position, new_value) in &numeric_refinements {
+                replace_numeric_literal_in_expression(cloned, *expr_node, literal_position.clone(), new_value);
+            }
             
             out.push((cumulative_priority, cloned));
             // pgrx::notice!("SafeQL: Generated value refinement with {} changes (total distance: {})", 
@@ -2614,15 +4721,30 @@ pub fn generate_value_refinements_raw(
 
 
 /// Value refinement를 위한 helper 함수들
+/// safeql.value_refinement_operators에 포함된 비교 연산자의 expression을 모두 찾는다
 unsafe fn find_all_equality_expressions(raw: *mut pg_sys::RawStmt) -> Vec<*mut pg_sys::Node> {
-    let mut expressions = Vec::new();
-    let expressions_ptr = &mut expressions as *mut Vec<*mut pg_sys::Node> as *mut c_void;
+    let mut ctx = ValueComparisonCtx {
+        operators: value_refinement_operators(),
+        expressions: Vec::new(),
+    };
+    if ctx.operators.is_empty() {
+        return ctx.expressions;
+    }
     
     unsafe {
-        safe_raw_expression_tree_walker((*raw).stmt, Some(find_equality_expressions_walker), expressions_ptr);
+        safe_raw_expression_tree_walker(
+            (*raw).stmt,
+            Some(find_equality_expressions_walker),
+            &mut ctx as *mut ValueComparisonCtx as *mut c_void,
+        );
     }
     
-    expressions
+    ctx.expressions
+}
+
+struct ValueComparisonCtx {
+    operators: Vec<String>,
+    expressions: Vec<*mut pg_sys::Node>,
 }
 
 unsafe extern "C" fn find_equality_expressions_walker(
@@ -2634,7 +4756,7 @@ unsafe extern "C" fn find_equality_expressions_walker(
     }
     
     unsafe {
-        let expressions = ctx as *mut Vec<*mut pg_sys::Node>;
+        let ctx = &mut *(ctx as *mut ValueComparisonCtx);
         
         if (*node).type_ == pg_sys::NodeTag::T_A_Expr {
             let a_expr = node as *mut pg_sys::A_Expr;
@@ -2648,8 +4770,8 @@ unsafe extern "C" fn find_equality_expressions_walker(
                                 let str_node = op_name_node as *mut pg_sys::String;
                                 let op_name = CStr::from_ptr((*str_node).sval).to_string_lossy();
                                 
-                                if op_name == "=" {
-                                    (*expressions).push(node);
+                                if ctx.operators.iter().any(|op| *op == op_name) {
+                                    ctx.expressions.push(node);
                                 }
                             }
                         }
@@ -2658,14 +4780,16 @@ unsafe extern "C" fn find_equality_expressions_walker(
             }
         }
         
-        safe_raw_expression_tree_walker(node, Some(find_equality_expressions_walker), ctx)
+        safe_raw_expression_tree_walker(node, Some(find_equality_expressions_walker), ctx as *mut ValueComparisonCtx as *mut c_void)
     }
 }
 
-unsafe fn generate_value_refinements_for_expression(
+/// `col = 'literal'` 또는 `'literal' = col` 형태의 expression에서 값 검색 대상 (table, column, literal) 추출
+/// literal이 놓인 쪽(OperandPosition)을 함께 반환한다
+unsafe fn value_refinement_target(
     expr_node: *mut pg_sys::Node,
     orig: *mut pg_sys::RawStmt
-) -> Option<(String, String, String, f32)> {
+) -> Option<(OperandPosition, (String, String, String))> {
     if expr_node.is_null() {
         return None;
     }
@@ -2674,20 +4798,11 @@ unsafe fn generate_value_refinements_for_expression(
         if (*expr_node).type_ == pg_sys::NodeTag::T_A_Expr {
             let a_expr = expr_node as *mut pg_sys::A_Expr;
             
-            let (column_ref, literal_value) = if is_column_ref((*a_expr).lexpr) && is_literal_value((*a_expr).rexpr) {
-                ((*a_expr).lexpr, (*a_expr).rexpr)
-            } else if is_literal_value((*a_expr).lexpr) && is_column_ref((*a_expr).rexpr) {
-                ((*a_expr).rexpr, (*a_expr).lexpr)
-            } else {
-                return None;
-            };
+            let (literal_position, column_ref, literal_value) = literal_comparison_operands(a_expr)?;
             
             if let Some((table_name, column_name)) = extract_table_column_from_ref(column_ref, orig) {
                 if let Some(literal_str) = extract_literal_string_value(literal_value) {
-                    // find_similar_values_for_literal가 하나만 리턴한다고 가정
-                    if let Some((similar_value, _, _, distance)) = find_similar_values_for_literal(&table_name, &column_name, &literal_str).first() {
-                        return Some((similar_value.clone(), table_name, column_name, *distance));
-                    }
+                    return Some((literal_position, (table_name, column_name, literal_str)));
                 }
             }
         }
@@ -2696,6 +4811,45 @@ unsafe fn generate_value_refinements_for_expression(
     None
 }
 
+/// `col = 101` 형태의 expression에서 numeric value refinement 대상 (table, column, literal) 추출
+/// literal이 놓인 쪽(OperandPosition)을 함께 반환한다
+unsafe fn numeric_value_refinement_target(
+    expr_node: *mut pg_sys::Node,
+    orig: *mut pg_sys::RawStmt
+) -> Option<(OperandPosition, (String, String, f64))> {
+    if expr_node.is_null() {
+        return None;
+    }
+
+    unsafe {
+        if (*expr_node).type_ != pg_sys::NodeTag::T_A_Expr {
+            return None;
+        }
+        let a_expr = expr_node as *mut pg_sys::A_Expr;
+
+        let (literal_position, column_ref, literal_value) = literal_comparison_operands(a_expr)?;
+
+        let literal = extract_literal_numeric_value(literal_value)?;
+        let (table_name, column_name) = extract_table_column_from_ref(column_ref, orig)?;
+        Some((literal_position, (table_name, column_name, literal)))
+    }
+}
+
+/// column과 literal을 비교하는 A_Expr에서 (literal 위치, column operand, literal operand)를 찾는다
+unsafe fn literal_comparison_operands(
+    a_expr: *mut pg_sys::A_Expr
+) -> Option<(OperandPosition, *mut pg_sys::Node, *mut pg_sys::Node)> {
+    unsafe {
+        if is_column_ref((*a_expr).lexpr) && is_literal_value((*a_expr).rexpr) {
+            Some((OperandPosition::Right, (*a_expr).lexpr, (*a_expr).rexpr))
+        } else if is_literal_value((*a_expr).lexpr) && is_column_ref((*a_expr).rexpr) {
+            Some((OperandPosition::Left, (*a_expr).rexpr, (*a_expr).lexpr))
+        } else {
+            None
+        }
+    }
+}
+
 unsafe fn is_column_ref(node: *mut pg_sys::Node) -> bool {
     unsafe {
         !node.is_null() && (*node).type_ == pg_sys::NodeTag::T_ColumnRef
@@ -2746,14 +4900,14 @@ unsafe fn extract_table_column_from_ref(
                             }
                         }
                     } else if field_count == 1 {
-                        // unqualified reference - FROM절에서 테이블 추정
+                        // unqualified reference - reference를 감싸는 가장 안쪽 SELECT(EXISTS 서브쿼리 등)의 FROM절에서 테이블 추정
                         if let Some(field_ptr) = fields.get(0) {
                             let field = *field_ptr as *mut pg_sys::Node;
                             if (*field).type_ == pg_sys::NodeTag::T_String {
                                 let str_node = field as *mut pg_sys::String;
                                 let column_name = CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned();
                                 
-                                let from_tables = extract_all_tables_from_raw(orig);
+                                let from_tables = enclosing_from_tables(orig, column_ref);
                                 if let Some(first_table) = from_tables.first() {
                                     return Some((first_table.table_name.clone(), column_name));
                                 }
@@ -2769,6 +4923,71 @@ unsafe fn extract_table_column_from_ref(
     None
 }
 
+/// node를 감싸는 가장 안쪽 SelectStmt의 FROM절 테이블 (찾지 못하거나 FROM절이 비어 있으면 전체 FROM절 테이블)
+fn enclosing_from_tables(orig: *mut pg_sys::RawStmt, node: *mut pg_sys::Node) -> Vec<TableInfo> {
+    // walker는 최상위 문장 자체는 방문하지 않으므로 미리 넣어둔다
+    let mut ctx = EnclosingSelectCtx {
+        target: node,
+        selects: unsafe { find_select_stmt(orig) }.into_iter().collect(),
+        found: None,
+    };
+
+    unsafe {
+        safe_raw_expression_tree_walker(
+            (*orig).stmt,
+            Some(enclosing_select_walker),
+            &mut ctx as *mut EnclosingSelectCtx as *mut c_void,
+        );
+    }
+
+    let Some(select_stmt) = ctx.found else {
+        return extract_all_tables_from_raw(orig);
+    };
+
+    let mut from_items = FromItemsCtx::default();
+    unsafe {
+        safe_raw_expression_tree_walker(
+            (*select_stmt).fromClause as *mut pg_sys::Node,
+            Some(extract_tables_with_alias_walker),
+            &mut from_items as *mut FromItemsCtx as *mut c_void,
+        );
+    }
+    if from_items.tables.is_empty() {
+        return extract_all_tables_from_raw(orig);
+    }
+    from_items.tables
+}
+
+struct EnclosingSelectCtx {
+    target: *mut pg_sys::Node,
+    // 현재 walker 위치를 감싸는 SelectStmt들 (바깥 -> 안쪽)
+    selects: Vec<*mut pg_sys::SelectStmt>,
+    found: Option<*mut pg_sys::SelectStmt>,
+}
+
+unsafe extern "C" fn enclosing_select_walker(node: *mut pg_sys::Node, ctx: *mut c_void) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let ctx_ref = &mut *(ctx as *mut EnclosingSelectCtx);
+        if node == ctx_ref.target {
+            ctx_ref.found = ctx_ref.selects.last().copied();
+            return true;
+        }
+
+        if (*node).type_ == pg_sys::NodeTag::T_SelectStmt {
+            ctx_ref.selects.push(node as *mut pg_sys::SelectStmt);
+            let done = safe_raw_expression_tree_walker(node, Some(enclosing_select_walker), ctx);
+            ctx_ref.selects.pop();
+            return done;
+        }
+
+        safe_raw_expression_tree_walker(node, Some(enclosing_select_walker), ctx)
+    }
+}
+
 unsafe fn extract_literal_string_value(literal_node: *mut pg_sys::Node) -> Option<String> {
     if literal_node.is_null() {
         return None;
@@ -2789,14 +5008,44 @@ unsafe fn extract_literal_string_value(literal_node: *mut pg_sys::Node) -> Optio
     None
 }
 
+/// A_Const의 Integer/Float 값을 f64로 읽는다
+unsafe fn extract_literal_numeric_value(literal_node: *mut pg_sys::Node) -> Option<f64> {
+    if literal_node.is_null() {
+        return None;
+    }
+
+    unsafe {
+        if (*literal_node).type_ == pg_sys::NodeTag::T_A_Const {
+            let a_const = literal_node as *mut pg_sys::A_Const;
+            let value_node = &(*a_const).val.node as *const pg_sys::Node as *mut pg_sys::Node;
+
+            match (*value_node).type_ {
+                pg_sys::NodeTag::T_Integer => return Some((*a_const).val.ival.ival as f64),
+                pg_sys::NodeTag::T_Float => {
+                    let fval = (*a_const).val.fval.fval;
+                    if !fval.is_null() {
+                        return CStr::from_ptr(fval).to_str().ok()?.parse::<f64>().ok();
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    None
+}
+
 unsafe fn replace_literal_value_in_expression(
     raw: *mut pg_sys::RawStmt,
     target_expr: *mut pg_sys::Node,
+    literal_position: OperandPosition,
     new_value: &str
 ) {
     let mut ctx = ValueReplaceCtx {
         target_expr,
+        literal_position,
         new_value: new_value.to_string(),
+        numeric: false,
         replaced_any: false,
     };
     let ctx_ptr = &mut ctx as *mut ValueReplaceCtx as *mut c_void;
@@ -2810,10 +5059,37 @@ unsafe fn replace_literal_value_in_expression(
     // }
 }
 
+unsafe fn replace_numeric_literal_in_expression(
+    raw: *mut pg_sys::RawStmt,
+    target_expr: *mut pg_sys::Node,
+    literal_position: OperandPosition,
+    new_value: &str
+) {
+    let mut ctx = ValueReplaceCtx {
+        target_expr,
+        literal_position,
+        new_value: new_value.to_string(),
+        numeric: true,
+        replaced_any: false,
+    };
+
+    unsafe {
+        safe_raw_expression_tree_walker(
+            (*raw).stmt,
+            Some(value_replace_walker),
+            &mut ctx as *mut ValueReplaceCtx as *mut c_void,
+        );
+    }
+}
+
 #[derive(Debug)]
 struct ValueReplaceCtx {
     target_expr: *mut pg_sys::Node,
+    // 추출 단계에서 literal로 식별된 operand 위치
+    literal_position: OperandPosition,
     new_value: String,
+    // true이면 string 대신 numeric literal로 교체
+    numeric: bool,
     replaced_any: bool,
 }
 
@@ -2827,15 +5103,17 @@ unsafe extern "C" fn value_replace_walker(node: *mut pg_sys::Node, ctx: *mut c_v
 
         if node == ctx_ref.target_expr && (*node).type_ == pg_sys::NodeTag::T_A_Expr {
             let a_expr = node as *mut pg_sys::A_Expr;
+            let operand = match ctx_ref.literal_position {
+                OperandPosition::Left => &mut (*a_expr).lexpr,
+                OperandPosition::Right => &mut (*a_expr).rexpr,
+            };
             
-            if is_literal_value((*a_expr).rexpr) {
-                let new_literal = create_string_literal(&ctx_ref.new_value);
-                (*a_expr).rexpr = new_literal;
-                ctx_ref.replaced_any = true;
-                return false;
-            } else if is_literal_value((*a_expr).lexpr) {
-                let new_literal = create_string_literal(&ctx_ref.new_value);
-                (*a_expr).lexpr = new_literal;
+            if is_literal_value(*operand) {
+                *operand = if ctx_ref.numeric {
+                    create_numeric_literal(&ctx_ref.new_value)
+                } else {
+                    create_string_literal(&ctx_ref.new_value)
+                };
                 ctx_ref.replaced_any = true;
                 return false;
             }
@@ -2845,6 +5123,26 @@ unsafe extern "C" fn value_replace_walker(node: *mut pg_sys::Node, ctx: *mut c_v
     }
 }
 
+/// 정수로 표현되면 Integer, 아니면 Float A_Const를 만든다
+unsafe fn create_numeric_literal(value: &str) -> *mut pg_sys::Node {
+    unsafe {
+        let a_const =
+            pg_sys::palloc0(size_of::<pg_sys::A_Const>()) as *mut pg_sys::A_Const;
+        (*a_const).type_ = pg_sys::NodeTag::T_A_Const;
+
+        if let Ok(ival) = value.parse::<i32>() {
+            (*a_const).val.ival = *pg_sys::makeInteger(ival);
+        } else {
+            let c_str = CString::new(value).expect("CString conversion failed");
+            (*a_const).val.fval = *pg_sys::makeFloat(pg_sys::pstrdup(c_str.as_ptr()));
+        }
+
+        (*a_const).location = -1;
+
+        a_const as *mut pg_sys::Node
+    }
+}
+
 unsafe fn create_string_literal(value: &str) -> *mut pg_sys::Node {
     unsafe {
         let a_const =
@@ -3069,4 +5367,673 @@ fn split_schema_rel(fq: &str) -> Option<(Option<String>, String)> {
         let schema = parts.join("."); // 다중 스키마 경로는 일반적이진 않지만 안전하게 join
         Some((Some(schema), rel))
     }
-}
\ No newline at end of file
+}
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use super::*;
+    use pgrx::prelude::*;
+    use crate::safeql::fixtures::{reset_vector_catalogs, seed_cached_result};
+    use crate::safeql::search::rawstmt_from_sql;
+    use crate::softql::deparse_raw_stmt;
+
+    /// SQL을 파싱해 walker 하나만 적용한 뒤 다시 deparse
+    fn apply_walker(sql: &str, walker: impl FnOnce(*mut pg_sys::RawStmt)) -> String {
+        let raw = rawstmt_from_sql(sql).unwrap();
+        walker(raw);
+        deparse_raw_stmt(raw)
+    }
+
+    #[test]
+    fn test_generate_alias_for_added_table() {
+        let aliased = vec![TableInfo::new("customers".into(), Some("c".into()))];
+        assert_eq!(generate_alias_for_added_table("order_items", &aliased).as_deref(), Some("oi"));
+        assert_eq!(generate_alias_for_added_table("cities", &aliased).as_deref(), Some("c2"));
+
+        // alias를 쓰지 않는 쿼리는 이름이 겹칠 때만 alias 생성
+        let plain = vec![TableInfo::new("customers".into(), None)];
+        assert_eq!(generate_alias_for_added_table("orders", &plain), None);
+        assert_eq!(generate_alias_for_added_table("customers", &plain).as_deref(), Some("c"));
+    }
+
+    #[pg_test]
+    fn test_join_add_uses_generated_alias() {
+        let raw = rawstmt_from_sql("SELECT c.name FROM customers c WHERE c.city = 'Seoul'").unwrap();
+        let condition = JoinCondition {
+            left_table: "customers".into(),
+            left_column: "id".into(),
+            right_table: "orders".into(),
+            right_column: "customer_id".into(),
+            constraint_name: None,
+        };
+
+        unsafe { add_table_and_where_condition(raw, "orders", &[condition]) };
+        let sql = deparse_raw_stmt(raw);
+
+        assert!(sql.contains("orders o"), "{}", sql);
+        assert!(sql.contains("c.id = o.customer_id"), "{}", sql);
+    }
+
+    #[pg_test]
+    fn test_replace_column_ref_in_window_definition() {
+        let sql = apply_walker(
+            "SELECT rank() OVER (PARTITION BY regon ORDER BY regon DESC) FROM sales",
+            |raw| unsafe { replace_column_ref_inplace(raw, "regon", "region", None) },
+        );
+        assert_eq!(sql, "SELECT rank() OVER (PARTITION BY region ORDER BY region DESC) FROM sales");
+
+        // WINDOW절에 이름 붙여 정의한 경우도 교체
+        let sql = apply_walker(
+            "SELECT sum(s.amount) OVER w FROM sales s WINDOW w AS (PARTITION BY s.regon)",
+            |raw| unsafe { replace_column_ref_inplace(raw, "regon", "region", Some("s")) },
+        );
+        assert_eq!(sql, "SELECT sum(s.amount) OVER w FROM sales s WINDOW w AS (PARTITION BY s.region)");
+    }
+
+    #[pg_test]
+    fn test_column_qualification_preserve() {
+        let sql = apply_walker("SELECT c.nme, nme FROM customers c", |raw| unsafe {
+            replace_column_ref_with_qualifier(raw, "nme", "name", None, Some("c"))
+        });
+        assert_eq!(sql, "SELECT c.name, name FROM customers c");
+    }
+
+    #[pg_test]
+    fn test_column_qualification_qualified() {
+        Spi::run("SET safeql.column_qualification = 'qualified'").unwrap();
+        let sql = apply_walker("SELECT c.nme, nme FROM customers c", |raw| unsafe {
+            replace_column_ref_with_qualifier(raw, "nme", "name", None, Some("c"))
+        });
+        assert_eq!(sql, "SELECT c.name, c.name FROM customers c");
+
+        // 붙일 qualifier를 모르면 그대로 둔다
+        let sql = apply_walker("SELECT nme FROM customers c", |raw| unsafe {
+            replace_column_ref_with_qualifier(raw, "nme", "name", None, None)
+        });
+        assert_eq!(sql, "SELECT name FROM customers c");
+    }
+
+    #[pg_test]
+    fn test_column_qualification_unqualified() {
+        Spi::run("SET safeql.column_qualification = 'unqualified'").unwrap();
+        let sql = apply_walker("SELECT c.nme, nme FROM customers c", |raw| unsafe {
+            replace_column_ref_with_qualifier(raw, "nme", "name", None, Some("c"))
+        });
+        assert_eq!(sql, "SELECT name, name FROM customers c");
+    }
+
+    #[pg_test]
+    fn test_join_add_repairs_renamed_fk_column() {
+        use crate::gucs::parser::TOP_K_EXPANSION;
+        use crate::safeql::cache::generate_cache_key;
+
+        Spi::run("CREATE TABLE rj_customers (id int PRIMARY KEY, name text)").unwrap();
+        Spi::run("CREATE TABLE rj_orders (order_id int, customer_id int REFERENCES rj_customers(id), amount int)").unwrap();
+        Spi::run("INSERT INTO rj_customers VALUES (1, 'kim')").unwrap();
+        Spi::run("INSERT INTO rj_orders VALUES (10, 1, 500)").unwrap();
+        reset_vector_catalogs();
+
+        // rename 이전에 캐시된 JOIN 후보: 오른쪽 컬럼이 예전 이름(customer_id)
+        let joinable_key = generate_cache_key("joinable_tables", &["rj_customers", "amount", "false"]);
+        let joinable = vec![(
+            "rj_orders".to_string(),
+            0.1f32,
+            vec![JoinCondition {
+                left_table: "rj_customers".into(),
+                left_column: "id".into(),
+                right_table: "rj_orders".into(),
+                right_column: "customer_id".into(),
+                constraint_name: None,
+            }],
+        )];
+        seed_cached_result(&joinable_key, "joinable_tables", &joinable);
+        Spi::run("ALTER TABLE rj_orders RENAME COLUMN customer_id TO cust_id").unwrap();
+
+        let column_key = generate_cache_key(
+            "column_similarity",
+            &["rj_orders", "customer_id", "true", &TOP_K_EXPANSION.get().to_string()],
+        );
+        let similar = vec![("cust_id".to_string(), "rj_orders".to_string(), 0.2f32)];
+        seed_cached_result(&column_key, "column_similarity", &similar);
+
+        let raw = rawstmt_from_sql("SELECT c.name FROM rj_customers c WHERE amount > 10").unwrap();
+        let refinements = generate_join_refinements_raw(raw, "amount", 0);
+        assert_eq!(refinements.len(), 1);
+
+        let sql = deparse_raw_stmt(refinements[0].1);
+        assert!(sql.contains("c.id = ro.cust_id"), "{}", sql);
+        assert_eq!(Spi::get_one::<String>(&sql).unwrap().as_deref(), Some("kim"));
+    }
+
+    #[pg_test]
+    fn test_one_hop_join_pairs_composite_fk_columns() {
+        Spi::run("CREATE TABLE cf_shipments (region text, shipment_no int, carrier text, PRIMARY KEY (region, shipment_no))").unwrap();
+        Spi::run(
+            "CREATE TABLE cf_packages (
+                pkg_id int PRIMARY KEY, ship_region text, ship_no int, weight int,
+                FOREIGN KEY (ship_region, ship_no) REFERENCES cf_shipments (region, shipment_no)
+            )",
+        )
+        .unwrap();
+        Spi::run("INSERT INTO cf_shipments VALUES ('kr', 1, 'cj'), ('kr', 2, 'hanjin'), ('us', 1, 'ups')").unwrap();
+        Spi::run("INSERT INTO cf_packages VALUES (100, 'us', 1, 7)").unwrap();
+        Spi::run("SET safeql.join_require_fk = on").unwrap();
+
+        // 두 컬럼 쌍이 하나의 JOIN 조건 목록으로 묶이고, 위치가 다른 컬럼끼리는 짝지어지지 않는다
+        let joinable = find_all_joinable_tables(&["cf_shipments".to_string()]);
+        let (_, _, conditions) = joinable.iter().find(|(t, _, _)| t == "cf_packages").unwrap();
+        let mut pairs: Vec<(&str, &str)> = conditions.iter()
+            .map(|c| (c.left_column.as_str(), c.right_column.as_str()))
+            .collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![("region", "ship_region"), ("shipment_no", "ship_no")]);
+        assert_eq!(group_join_conditions(conditions).len(), 1);
+
+        let raw = rawstmt_from_sql("SELECT carrier FROM cf_shipments WHERE weight > 5").unwrap();
+        let refinements = generate_one_hop_join_refinements_for_all_tables_raw(raw, 0);
+        assert_eq!(refinements.len(), 1);
+
+        let sql = deparse_raw_stmt(refinements[0].1);
+        assert!(sql.contains("cf_shipments.region = cf_packages.ship_region"), "{}", sql);
+        assert!(sql.contains("cf_shipments.shipment_no = cf_packages.ship_no"), "{}", sql);
+        assert_eq!(Spi::get_one::<String>(&sql).unwrap().as_deref(), Some("ups"));
+    }
+
+    #[pg_test]
+    fn test_schema_qualified_function_name_refinement() {
+        use crate::safeql::cache::generate_cache_key;
+        use crate::safeql::search::{extract_missing_function, extract_missing_function_schema};
+
+        Spi::run("CREATE SCHEMA myschema").unwrap();
+        Spi::run("CREATE FUNCTION myschema.func(x int) RETURNS int LANGUAGE sql AS 'SELECT x * 2'").unwrap();
+
+        let message = "function myschema.funk(integer) does not exist";
+        let (function_name, arg_types) = extract_missing_function(message).unwrap();
+        assert_eq!(function_name, "funk");
+        let schema = extract_missing_function_schema(message);
+        assert_eq!(schema.as_deref(), Some("myschema"));
+        assert_eq!(extract_missing_function_schema("function funk(integer) does not exist"), None);
+
+        // 임베딩 없이 myschema 안의 후보를 캐시에 넣어둔다
+        let top_k = crate::gucs::parser::TOP_K_EXPANSION.get().to_string();
+        let key = generate_cache_key("similar_functions", &["funk", "1", &top_k, "myschema"]);
+        let similar = vec![("func".to_string(), vec!["integer".to_string()], "integer".to_string(), 0.1f32)];
+        seed_cached_result(&key, "similar_functions", &similar);
+
+        let raw = rawstmt_from_sql("SELECT myschema.funk(21)").unwrap();
+        let refinements = generate_function_name_refinements_raw(raw, 8, &function_name, &arg_types, schema.as_deref(), false, 0);
+        assert_eq!(refinements.len(), 1);
+        let sql = deparse_raw_stmt(refinements[0].1);
+        assert_eq!(sql, "SELECT myschema.func(21)");
+        assert_eq!(Spi::get_one::<i32>(&sql).unwrap(), Some(42));
+    }
+
+    #[pg_test]
+    fn test_having_function_name_refinement_prefers_aggregates() {
+        use crate::safeql::cache::generate_cache_key;
+
+        Spi::run("CREATE TABLE hv_items (id int, grp text)").unwrap();
+
+        // 이름만 보면 스칼라 함수 cot가 가장 가깝다고 가정
+        let top_k = crate::gucs::parser::TOP_K_EXPANSION.get().to_string();
+        let key = generate_cache_key("similar_functions", &["cnt", "1", &top_k]);
+        let similar = vec![("cot".to_string(), vec!["double precision".to_string()], "double precision".to_string(), 0.05f32)];
+        seed_cached_result(&key, "similar_functions", &similar);
+
+        let sql = "SELECT grp, cnt(id) FROM hv_items GROUP BY grp HAVING cnt(id) > 10";
+        let raw = rawstmt_from_sql(sql).unwrap();
+        let select_pos = sql.find("cnt").unwrap() as i32 + 1;
+        let having_pos = sql.rfind("cnt").unwrap() as i32 + 1;
+        assert!(!unsafe { is_call_in_having_clause(raw, select_pos) });
+        assert!(unsafe { is_call_in_having_clause(raw, having_pos) });
+
+        let arg_types = vec!["integer".to_string()];
+        // SELECT 목록에서는 스칼라 함수도 후보로 남는다
+        let refinements = generate_function_name_refinements_raw(raw, select_pos, "cnt", &arg_types, None, false, 0);
+        assert_eq!(refinements.len(), 2);
+
+        // HAVING절에서는 집계 함수만 남는다
+        let refinements = generate_function_name_refinements_raw(raw, having_pos, "cnt", &arg_types, None, true, 0);
+        assert_eq!(refinements.len(), 1);
+        let refined = deparse_raw_stmt(refinements[0].1);
+        assert_eq!(refined, "SELECT grp, cnt(id) FROM hv_items GROUP BY grp HAVING count(id) > 10");
+    }
+
+    #[pg_test]
+    fn test_window_over_refinement() {
+        let sql = "SELECT g, row_number() FROM generate_series(1, 3) g";
+        let raw = rawstmt_from_sql(sql).unwrap();
+        let call_pos = sql.find("row_number").unwrap() as i32 + 1;
+
+        let refinements = generate_window_over_refinements_raw(raw, call_pos, 0);
+        assert_eq!(refinements.len(), 1);
+        let refined = deparse_raw_stmt(refinements[0].1);
+        assert_eq!(refined, "SELECT g, row_number() OVER () FROM generate_series(1, 3) g");
+        assert_eq!(Spi::get_one::<i64>(&format!("SELECT max(row_number) FROM ({}) s", refined)).unwrap(), Some(3));
+
+        // 이미 OVER가 있거나 위치가 맞지 않으면 후보가 없다
+        assert!(generate_window_over_refinements_raw(refinements[0].1, call_pos, 0).is_empty());
+        assert!(generate_window_over_refinements_raw(raw, 1, 0).is_empty());
+    }
+
+    #[pg_test]
+    fn test_max_from_tables_stops_join_add() {
+        Spi::run("CREATE TABLE mf_customers (id int PRIMARY KEY, name text)").unwrap();
+        Spi::run("CREATE TABLE mf_orders (id int PRIMARY KEY, cust_id int REFERENCES mf_customers (id), total int)")
+            .unwrap();
+        Spi::run("CREATE TABLE mf_items (id int PRIMARY KEY, order_id int REFERENCES mf_orders (id), sku text)")
+            .unwrap();
+        Spi::run("SET safeql.join_require_fk = on").unwrap();
+
+        let raw = rawstmt_from_sql("SELECT total FROM mf_orders o, mf_customers c WHERE o.cust_id = c.id").unwrap();
+        assert!(!generate_one_hop_join_refinements_for_all_tables_raw(raw, 0).is_empty());
+
+        // FROM절에 이미 최대 개수의 테이블이 있으면 JOIN 추가 후보를 만들지 않는다
+        Spi::run("SET safeql.max_from_tables = 2").unwrap();
+        assert!(generate_one_hop_join_refinements_for_all_tables_raw(raw, 0).is_empty());
+        assert!(generate_join_refinements_raw(raw, "sku", 0).is_empty());
+
+        let raw = rawstmt_from_sql("SELECT total FROM mf_orders").unwrap();
+        assert!(!generate_one_hop_join_refinements_for_all_tables_raw(raw, 0).is_empty());
+    }
+
+    #[pg_test]
+    fn test_identifier_folding_modes() {
+        // 기본값 lower: ASCII만 접는다
+        assert!(identifiers_match("UserName", "username"));
+        assert!(!identifiers_match("ÜRÜN", "ürün"));
+        assert_eq!(fold_identifier("ÜRÜN"), "ÜrÜn");
+
+        Spi::run("SET safeql.identifier_folding = 'preserve'").unwrap();
+        assert!(!identifiers_match("UserName", "username"));
+        assert!(identifiers_match("UserName", "UserName"));
+        assert!(!identifiers_match("ÜRÜN", "ürün"));
+
+        Spi::run("SET safeql.identifier_folding = 'unicode_casefold'").unwrap();
+        assert!(identifiers_match("UserName", "username"));
+        assert!(identifiers_match("ÜRÜN", "ürün"));
+        assert_eq!(fold_identifier("ÜRÜN"), "ürün");
+    }
+
+    #[pg_test]
+    fn test_identifier_folding_in_walkers() {
+        let sql = r#"SELECT "ÜRÜN", "UserName" FROM items"#;
+
+        let lower = apply_walker(sql, |raw| unsafe { replace_column_ref_inplace(raw, "ürün", "product", None) });
+        assert_eq!(lower, r#"SELECT "ÜRÜN", "UserName" FROM items"#);
+        let lower = apply_walker(sql, |raw| unsafe { replace_column_ref_inplace(raw, "username", "user_name", None) });
+        assert_eq!(lower, r#"SELECT "ÜRÜN", user_name FROM items"#);
+
+        Spi::run("SET safeql.identifier_folding = 'unicode_casefold'").unwrap();
+        let casefold = apply_walker(sql, |raw| unsafe { replace_column_ref_inplace(raw, "ürün", "product", None) });
+        assert_eq!(casefold, r#"SELECT product, "UserName" FROM items"#);
+
+        Spi::run("SET safeql.identifier_folding = 'preserve'").unwrap();
+        let preserve = apply_walker(sql, |raw| unsafe { replace_column_ref_inplace(raw, "username", "user_name", None) });
+        assert_eq!(preserve, sql);
+    }
+
+    #[pg_test]
+    fn test_default_folding_matches_table_filter_and_column() {
+        // 별도 설정 없이도 컬럼명과 table filter 비교 모두 safeql.identifier_folding(lower)을 따른다
+        let sql = apply_walker(r#"SELECT "Items"."UserName" FROM "Items""#, |raw| unsafe {
+            replace_column_ref_inplace(raw, "username", "user_name", Some("items"))
+        });
+        assert_eq!(sql, r#"SELECT "Items".user_name FROM "Items""#);
+    }
+
+    #[pg_test]
+    fn test_from_function_column_refinement() {
+        let raw = rawstmt_from_sql("SELECT nn FROM generate_series(1, 10) g(n) WHERE n > 5").unwrap();
+        assert!(extract_all_tables_from_raw(raw).is_empty());
+        let functions = extract_all_function_ranges_from_raw(raw);
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].function_name, "generate_series");
+        assert_eq!(functions[0].get_reference_name(), "g");
+        assert_eq!(functions[0].columns, vec!["n".to_string()]);
+
+        // 함수 컬럼 이름과 가까우면 그 이름으로 교체
+        let refinements = generate_column_refinements_raw(raw, None, "nn", 0);
+        assert_eq!(refinements.len(), 1);
+        let sql = deparse_raw_stmt(refinements[0].1);
+        assert!(sql.starts_with("SELECT n FROM generate_series(1, 10)"), "{}", sql);
+
+        // 가까운 컬럼이 없으면 후보 없이 물러나고, FROM절을 테이블로 덮어쓰지도 않는다
+        let raw = rawstmt_from_sql("SELECT n FROM generate_series(1, 10) g(n) WHERE n = bad").unwrap();
+        assert!(generate_column_refinements_raw(raw, None, "bad", 0).is_empty());
+        assert!(generate_join_refinements_raw(raw, "bad", 0).is_empty());
+
+        // column alias가 없으면 alias가 컬럼 이름
+        let raw = rawstmt_from_sql("SELECT u FROM unnest(ARRAY[1, 2]) u").unwrap();
+        assert_eq!(extract_all_function_ranges_from_raw(raw)[0].columns, vec!["u".to_string()]);
+    }
+
+    #[pg_test]
+    fn test_value_refinement_operator_set() {
+        use crate::safeql::cache::generate_cache_key;
+
+        Spi::run("CREATE TABLE vo_reviews (title text, grade text)").unwrap();
+        // 임베딩 없이 'silvr'에 가장 가까운 값을 캐시에 넣어둔다
+        let key = generate_cache_key("similar_values", &["vo_reviews", "grade", "silvr"]);
+        let similar = vec![("silver".to_string(), "vo_reviews".to_string(), "grade".to_string(), 0.1f32)];
+        seed_cached_result(&key, "similar_values", &similar);
+
+        let raw = rawstmt_from_sql("SELECT title FROM vo_reviews WHERE grade >= 'silvr'").unwrap();
+        // 기본값은 = 만 대상
+        assert!(generate_value_refinements_raw(raw, 0).is_empty());
+
+        Spi::run("SET safeql.value_refinement_operators = '=, >=, !='").unwrap();
+        let refinements = generate_value_refinements_raw(raw, 0);
+        assert_eq!(refinements.len(), 1);
+        let sql = deparse_raw_stmt(refinements[0].1);
+        assert!(sql.contains("grade >= 'silver'"), "{}", sql);
+
+        // 지원하지 않는 연산자는 무시
+        Spi::run("SET safeql.value_refinement_operators = 'LIKE, ~~'").unwrap();
+        assert!(generate_value_refinements_raw(raw, 0).is_empty());
+    }
+
+    #[pg_test]
+    fn test_value_refinement_literal_on_left() {
+        use crate::safeql::cache::generate_cache_key;
+
+        Spi::run("CREATE TABLE customers (name text, status text)").unwrap();
+        let key = generate_cache_key("similar_values", &["customers", "status", "val"]);
+        let similar = vec![("valid".to_string(), "customers".to_string(), "status".to_string(), 0.1f32)];
+        seed_cached_result(&key, "similar_values", &similar);
+
+        // literal이 왼쪽에 있어도 추출 단계에서 찾은 그 operand만 교체하고 column은 그대로 둔다
+        let raw = rawstmt_from_sql("SELECT name FROM customers WHERE 'val' = customers.status").unwrap();
+        let refinements = generate_value_refinements_raw(raw, 0);
+        assert_eq!(refinements.len(), 1);
+        let sql = deparse_raw_stmt(refinements[0].1);
+        assert!(sql.contains("'valid' = customers.status"), "{}", sql);
+        assert!(!sql.contains("'val'"), "{}", sql);
+    }
+
+    #[pg_test]
+    fn test_value_refinement_in_exists_subquery() {
+        use crate::safeql::cache::generate_cache_key;
+
+        Spi::run("CREATE TABLE vx_customers (id int, status text)").unwrap();
+        Spi::run("CREATE TABLE vx_orders (customer_id int, status text)").unwrap();
+        let key = generate_cache_key("similar_values", &["vx_orders", "status", "shiped"]);
+        let similar = vec![("shipped".to_string(), "vx_orders".to_string(), "status".to_string(), 0.1f32)];
+        seed_cached_result(&key, "similar_values", &similar);
+
+        // 서브쿼리 안의 unqualified 컬럼은 바깥 FROM절이 아니라 서브쿼리 FROM절의 테이블로 찾는다
+        let raw = rawstmt_from_sql(
+            "SELECT id FROM vx_customers c WHERE EXISTS (SELECT 1 FROM vx_orders WHERE customer_id = c.id AND status = 'shiped')",
+        )
+        .unwrap();
+        let refinements = generate_value_refinements_raw(raw, 0);
+        assert_eq!(refinements.len(), 1);
+        let sql = deparse_raw_stmt(refinements[0].1);
+        assert!(sql.contains("status = 'shipped'"), "{}", sql);
+    }
+
+    #[pg_test]
+    fn test_numeric_value_refinement_snaps_to_step() {
+        Spi::run("CREATE TABLE nv_products (id int, price numeric)").unwrap();
+        reset_vector_catalogs();
+        Spi::run(
+            "INSERT INTO pg_vector_values VALUES
+                ('public', 'nv_products', 'price', '100', 'm', NULL),
+                ('public', 'nv_products', 'price', '101.8', 'm', NULL)",
+        )
+        .unwrap();
+
+        let raw = rawstmt_from_sql("SELECT id FROM nv_products WHERE price = 101").unwrap();
+        // 기본값 0이면 numeric literal은 건드리지 않는다
+        assert!(generate_value_refinements_raw(raw, 0).is_empty());
+
+        // 정수 단위: 101 그대로 두고 가장 가까운 101.8을 고른다
+        Spi::run("SET safeql.value_refinement_numeric_step = 1").unwrap();
+        let refinements = generate_value_refinements_raw(raw, 0);
+        assert_eq!(refinements.len(), 1);
+        let sql = deparse_raw_stmt(refinements[0].1);
+        assert!(sql.contains("price = 101.8"), "{}", sql);
+
+        // 10 단위: 101 -> 100으로 맞춘 뒤 저장된 100을 고른다
+        Spi::run("SET safeql.value_refinement_numeric_step = 10").unwrap();
+        let refinements = generate_value_refinements_raw(raw, 0);
+        assert_eq!(refinements.len(), 1);
+        let sql = deparse_raw_stmt(refinements[0].1);
+        assert!(sql.contains("price = 100"), "{}", sql);
+        assert!(!sql.contains("101"), "{}", sql);
+    }
+
+    #[pg_test]
+    fn test_replace_quoted_mixed_case_column() {
+        // preserve면 "UserName"만 교체하고 소문자 username은 그대로 둔다
+        Spi::run("SET safeql.identifier_folding = 'preserve'").unwrap();
+        let sql = apply_walker(r#"SELECT "UserName", username FROM users"#, |raw| unsafe {
+            replace_column_ref_inplace(raw, "UserName", "user_name", None)
+        });
+        assert_eq!(sql, "SELECT user_name, username FROM users");
+
+        let sql = apply_walker(r#"SELECT u."UserName" FROM users u"#, |raw| unsafe {
+            replace_column_ref_inplace(raw, "UserName", "DisplayName", Some("u"))
+        });
+        assert_eq!(sql, r#"SELECT u."DisplayName" FROM users u"#);
+
+        // 기본값 lower면 대소문자를 무시
+        Spi::run("RESET safeql.identifier_folding").unwrap();
+        let sql = apply_walker(r#"SELECT "UserName", username FROM users"#, |raw| unsafe {
+            replace_column_ref_inplace(raw, "UserName", "user_name", None)
+        });
+        assert_eq!(sql, "SELECT user_name, user_name FROM users");
+    }
+
+    #[pg_test]
+    fn test_replace_column_ref_unqualified() {
+        // table_filter가 없으면 unqualified/qualified reference 모두 교체
+        let sql = apply_walker("SELECT nme FROM customers WHERE nme = 'kim'", |raw| unsafe {
+            replace_column_ref_inplace(raw, "nme", "name", None)
+        });
+        assert_eq!(sql, "SELECT name FROM customers WHERE name = 'kim'");
+
+        let sql = apply_walker("SELECT c.nme FROM customers c", |raw| unsafe {
+            replace_column_ref_inplace(raw, "nme", "name", None)
+        });
+        assert_eq!(sql, "SELECT c.name FROM customers c");
+
+        // 대소문자 무시, 다른 컬럼은 그대로
+        let sql = apply_walker("SELECT NME, city FROM customers", |raw| unsafe {
+            replace_column_ref_inplace(raw, "nme", "name", None)
+        });
+        assert_eq!(sql, "SELECT name, city FROM customers");
+    }
+
+    #[pg_test]
+    fn test_replace_column_ref_qualified() {
+        // table_filter와 일치하는 qualifier만 교체
+        let sql = apply_walker("SELECT c.nme, o.nme FROM customers c, orders o", |raw| unsafe {
+            replace_column_ref_inplace(raw, "nme", "name", Some("c"))
+        });
+        assert_eq!(sql, "SELECT c.name, o.nme FROM customers c, orders o");
+
+        // table_filter가 있으면 target list의 unqualified reference는 건드리지 않는다
+        let sql = apply_walker("SELECT nme FROM customers c WHERE c.nme = 'kim'", |raw| unsafe {
+            replace_column_ref_inplace(raw, "nme", "name", Some("c"))
+        });
+        assert_eq!(sql, "SELECT nme FROM customers c WHERE c.name = 'kim'");
+    }
+
+    #[pg_test]
+    fn test_replace_table_intelligently() {
+        // alias로 참조된 경우 relname만 바뀌고 alias qualifier는 유지
+        let sql = apply_walker("SELECT w.name FROM wrld w WHERE w.code = 'KR'", |raw| unsafe {
+            replace_table_intelligently(raw, "w", "world")
+        });
+        assert_eq!(sql, "SELECT w.name FROM world w WHERE w.code = 'KR'");
+
+        // 테이블명으로 참조된 경우 qualifier도 새 테이블명으로
+        let sql = apply_walker("SELECT wrld.name FROM wrld WHERE wrld.code = 'KR'", |raw| unsafe {
+            replace_table_intelligently(raw, "wrld", "world")
+        });
+        assert_eq!(sql, "SELECT world.name FROM world WHERE world.code = 'KR'");
+
+        // unqualified column은 그대로 두고 다른 테이블은 건드리지 않음
+        let sql = apply_walker("SELECT name FROM wrld, city", |raw| unsafe {
+            replace_table_intelligently(raw, "wrld", "world")
+        });
+        assert_eq!(sql, "SELECT name FROM world, city");
+    }
+
+    #[pg_test]
+    fn test_column_refinement_updates_group_by() {
+        let raw = rawstmt_from_sql("SELECT c.nme, count(*) FROM grp_customers c GROUP BY nme").unwrap();
+        unsafe { replace_column_ref_inplace(raw, "nme", "name", Some("c")) };
+
+        let sql = deparse_raw_stmt(raw);
+        assert!(sql.contains("c.name"), "{}", sql);
+        assert!(sql.contains("GROUP BY name"), "{}", sql);
+
+        // 다른 테이블로 qualified된 GROUP BY reference는 건드리지 않는다
+        let raw = rawstmt_from_sql("SELECT c.nme FROM grp_customers c, grp_orders o GROUP BY c.nme, o.nme").unwrap();
+        unsafe { replace_column_ref_inplace(raw, "nme", "name", Some("c")) };
+
+        let sql = deparse_raw_stmt(raw);
+        assert!(sql.contains("GROUP BY c.name, o.nme"), "{}", sql);
+    }
+
+    #[pg_test]
+    fn test_same_table_column_bonus_prefers_from_table() {
+        use crate::safeql::cache::generate_cache_key;
+
+        Spi::run("CREATE TABLE st_orders (id int, total_amount int)").unwrap();
+        Spi::run("CREATE TABLE st_invoices (id int, amount int)").unwrap();
+        reset_vector_catalogs();
+
+        // FROM절에 없는 st_invoices.amount가 이름은 더 가깝다
+        let cache_key = generate_cache_key(
+            "column_similarity",
+            &["NULL", "amont", "false", &crate::gucs::parser::TOP_K_EXPANSION.get().to_string()],
+        );
+        let similar = vec![
+            ("amount".to_string(), "st_invoices".to_string(), 0.05f32),
+            ("total_amount".to_string(), "st_orders".to_string(), 0.2f32),
+        ];
+        seed_cached_result(&cache_key, "column_similarity", &similar);
+
+        let raw = rawstmt_from_sql("SELECT id, amont FROM st_orders").unwrap();
+        let best = |refinements: Vec<(i32, *mut pg_sys::RawStmt)>| {
+            let (_, raw) = refinements.into_iter().min_by_key(|(priority, _)| *priority).unwrap();
+            deparse_raw_stmt(raw)
+        };
+
+        // 기본값 0이면 distance 순서 그대로
+        assert_eq!(best(generate_column_refinements_raw(raw, None, "amont", 0)), "SELECT id, amount FROM st_orders");
+
+        // bonus를 주면 FROM절 테이블의 컬럼이 먼저
+        Spi::run("SET safeql.same_table_column_bonus = 0.2").unwrap();
+        assert_eq!(best(generate_column_refinements_raw(raw, None, "amont", 0)), "SELECT id, total_amount FROM st_orders");
+    }
+
+    #[pg_test]
+    fn test_unqualified_column_refinement_updates_group_and_order_by() {
+        // table_filter가 없으면 SELECT와 GROUP BY/ORDER BY의 bare ColumnRef를 함께 바꾼다
+        let sql = apply_walker(
+            "SELECT nme, count(*) FROM grp_customers GROUP BY nme ORDER BY nme",
+            |raw| unsafe { replace_column_ref_inplace(raw, "nme", "name", None) },
+        );
+        assert_eq!(sql, "SELECT name, count(*) FROM grp_customers GROUP BY name ORDER BY name");
+
+        // grouping set 안의 reference도, 위치 번호는 그대로
+        let sql = apply_walker(
+            "SELECT nme, city, count(*) FROM grp_customers GROUP BY ROLLUP (nme), 2",
+            |raw| unsafe { replace_column_ref_inplace(raw, "nme", "name", None) },
+        );
+        assert_eq!(sql, "SELECT name, city, count(*) FROM grp_customers GROUP BY ROLLUP (name), 2");
+    }
+
+    #[pg_test]
+    fn test_column_refinement_keeps_output_alias_refs() {
+        // project(customers.amont AS amt).order(amt) - alias 참조는 그대로
+        let raw = rawstmt_from_sql("SELECT customers.amont AS amt FROM customers ORDER BY amt").unwrap();
+        unsafe { replace_column_ref_inplace(raw, "amont", "amount", Some("customers")) };
+        let sql = deparse_raw_stmt(raw);
+        assert!(sql.contains("customers.amount AS amt"), "{}", sql);
+        assert!(sql.contains("ORDER BY amt"), "{}", sql);
+
+        // alias 없이 출력 컬럼 이름으로 정렬하면 새 이름을 따라간다
+        let raw = rawstmt_from_sql(
+            "SELECT c.city, sum(c.amont) FROM customers c GROUP BY c.city HAVING sum(amont) > 10 ORDER BY c.city, amont",
+        )
+        .unwrap();
+        unsafe { replace_column_ref_inplace(raw, "amont", "amount", Some("c")) };
+        let sql = deparse_raw_stmt(raw);
+        assert!(sql.contains("HAVING sum(amount) > 10"), "{}", sql);
+        assert!(sql.contains("ORDER BY c.city, amount"), "{}", sql);
+
+        // 출력 alias가 옛 컬럼 이름과 같으면 ORDER BY는 alias를 가리키므로 바꾸지 않는다
+        let raw = rawstmt_from_sql("SELECT amont AS amont FROM customers ORDER BY amont").unwrap();
+        unsafe { replace_column_ref_inplace(raw, "amont", "amount", None) };
+        let sql = deparse_raw_stmt(raw);
+        assert!(sql.contains("SELECT amount AS amont"), "{}", sql);
+        assert!(sql.contains("ORDER BY amont"), "{}", sql);
+    }
+
+    #[pg_test]
+    fn test_column_refinement_renames_insert_target() {
+        let raw = rawstmt_from_sql("INSERT INTO ins_orders (id, amont) VALUES (1, 10)").unwrap();
+        unsafe { replace_column_ref_inplace(raw, "amont", "amount", Some("ins_orders")) };
+
+        let sql = deparse_raw_stmt(raw);
+        assert!(sql.contains("(id, amount)"), "{}", sql);
+
+        // 다른 테이블의 컬럼 에러는 INSERT 대상 컬럼을 바꾸지 않는다
+        let raw = rawstmt_from_sql("INSERT INTO ins_orders (id, amont) VALUES (1, 10)").unwrap();
+        unsafe { replace_column_ref_inplace(raw, "amont", "amount", Some("other_table")) };
+        assert!(deparse_raw_stmt(raw).contains("(id, amont)"));
+    }
+
+    #[pg_test]
+    fn test_ambiguity_prefers_table_matching_operand_type() {
+        reset_vector_catalogs();
+        Spi::run(
+            "INSERT INTO pg_vector_fields VALUES
+                ('public', 'amb_items', 'code', 'integer', 'm', NULL),
+                ('public', 'amb_tags', 'code', 'text', 'm', NULL)",
+        )
+        .unwrap();
+
+        // FROM 순서로는 amb_items가 먼저지만, 문자열 literal과 비교하므로 text인 amb_tags.code가 먼저
+        let raw = rawstmt_from_sql("SELECT amb_items.id FROM amb_items, amb_tags WHERE code = 'X1'").unwrap();
+        let refinements = generate_column_ambiguity_refinements_raw(raw, "code", 0);
+        assert_eq!(refinements.len(), 2);
+        assert!(deparse_raw_stmt(refinements[0].1).contains("amb_tags.code = 'X1'"));
+        assert!(deparse_raw_stmt(refinements[1].1).contains("amb_items.code = 'X1'"));
+        // 같은 priority면 SQL 사전순(amb_items 먼저)으로 꺼내므로 순위가 priority에 반영되어야 한다
+        assert!(refinements[0].0 < refinements[1].0);
+
+        // 정수 literal이면 integer인 amb_items.code가 먼저
+        let raw = rawstmt_from_sql("SELECT amb_items.id FROM amb_items, amb_tags WHERE code = 7").unwrap();
+        let refinements = generate_column_ambiguity_refinements_raw(raw, "code", 0);
+        assert!(deparse_raw_stmt(refinements[0].1).contains("amb_items.code = 7"));
+    }
+
+    #[pg_test]
+    fn test_null_handling_uses_not_distinct_for_null_and_columns() {
+        let raw = rawstmt_from_sql("SELECT id FROM t WHERE a = NULL AND b = c AND d > 1").unwrap();
+        let refinements = generate_null_handling_refinements_raw(raw, 0);
+        assert_eq!(refinements.len(), 2);
+        assert!(deparse_raw_stmt(refinements[0].1).contains("a IS NOT DISTINCT FROM NULL"));
+        assert!(deparse_raw_stmt(refinements[1].1).contains("b IS NOT DISTINCT FROM c"));
+    }
+
+    #[pg_test]
+    fn test_nullif_guard_wraps_divisor() {
+        let raw = rawstmt_from_sql("SELECT total / cnt FROM stats").unwrap();
+        let refinements = generate_nullif_guard_refinements_raw(raw, 0);
+        assert_eq!(refinements.len(), 1);
+
+        let sql = deparse_raw_stmt(refinements[0].1);
+        assert!(sql.contains("NULLIF(cnt, 0)"), "{}", sql);
+
+        // 이미 NULLIF로 보호된 divisor는 다시 감싸지 않음
+        let guarded = rawstmt_from_sql("SELECT total / NULLIF(cnt, 0) FROM stats").unwrap();
+        assert!(generate_nullif_guard_refinements_raw(guarded, 0).is_empty());
+    }
+}
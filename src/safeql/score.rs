@@ -1,15 +1,30 @@
 use pgrx::pg_sys;
 use pgrx::{IntoDatum, PgOid, Spi};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
 use regex::Regex;
 
 use super::cache::{
+    constraint_generation_token,
     generate_cache_key,
     get_cached_result,
     store_cached_result
 };
-use crate::gucs::parser::{ENABLE_TYPE_BASED_REFINEMENT, TOP_K_EXPANSION};
+use crate::gucs::parser::{
+    refinement_schemas, COLUMN_STATISTICS_WEIGHT, ENABLE_TYPE_BASED_REFINEMENT, JOIN_REQUIRE_FK,
+    MAX_CANDIDATE_DISTANCE, MAX_EMBEDDING_CALLS, TOP_K_EXPANSION, VALUE_REFINEMENT_SAMPLES,
+};
+
+/// similarity 쿼리의 스키마 범위 인자를 마지막 SPI 인자로 붙인다
+/// safeql.refinement_schemas가 설정되면 그 스키마 배열을, 아니면 NULL을 바인딩하고
+/// 쿼리는 이 인자를 COALESCE($n::name[], current_schemas(false))로 받는다
+fn scope_schemas(mut args: Vec<(PgOid, Option<pg_sys::Datum>)>) -> Vec<(PgOid, Option<pg_sys::Datum>)> {
+    let schemas = refinement_schemas();
+    let schemas = if schemas.is_empty() { None } else { schemas.into_datum() };
+    args.push((PgOid::from(pg_sys::TEXTARRAYOID), schemas));
+    args
+}
 
 /// 문자열을 언더스코어 기준으로 쪼개서 검색 가능한 형태로 변환
 fn prepare_search_terms(input: &str) -> Vec<String> {
@@ -23,19 +38,148 @@ fn escape_sql_literal(s: &str) -> String {
     s.replace("'", "''")
 }
 
-fn create_combined_vector_query(terms: &[String]) -> String {
-    if terms.len() == 1 {
-        let term_escaped = escape_sql_literal(&terms[0]);
-        format!("SELECT _vectors_text2vec(quote_literal('{}')) AS v", term_escaped)
-    } else {
-        let combined_term = terms.join(" ");
-        let term_escaped = escape_sql_literal(&combined_term);
-        format!("SELECT _vectors_text2vec(quote_literal('{}')) AS v", term_escaped)
+thread_local! {
+    /// 한 번의 refinement search 동안 검색어 -> 임베딩 벡터(text) 캐시
+    /// 같은 토큰을 여러 similarity 쿼리에서 반복해서 임베딩하지 않도록 한다
+    static SEARCH_VECTOR_CACHE: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+
+    /// pg_vector_values에 실제로 보낸 similarity 쿼리 수 (batch 효과 확인용)
+    static VALUE_LOOKUP_QUERIES: Cell<u64> = const { Cell::new(0) };
+
+    /// 이번 refinement search에서 검색어를 임베딩한 횟수 (safeql.max_embedding_calls)
+    static EMBEDDING_CALLS: Cell<i32> = const { Cell::new(0) };
+}
+
+fn count_value_lookup_query() {
+    VALUE_LOOKUP_QUERIES.with(|count| count.set(count.get() + 1));
+}
+
+/// search 시작 시 호출 - 이전 search에서 계산한 벡터를 비우고 임베딩 횟수를 0으로 되돌린다
+pub fn reset_search_vector_cache() {
+    SEARCH_VECTOR_CACHE.with(|cache| cache.borrow_mut().clear());
+    EMBEDDING_CALLS.with(|count| count.set(0));
+}
+
+/// safeql.max_embedding_calls에 도달하지 않았으면 임베딩 횟수를 하나 늘리고 true
+fn try_count_embedding_call() -> bool {
+    let max_calls = MAX_EMBEDDING_CALLS.get();
+    EMBEDDING_CALLS.with(|count| {
+        if max_calls > 0 && count.get() >= max_calls {
+            return false;
+        }
+        count.set(count.get() + 1);
+        true
+    })
+}
+
+/// 공백을 정규화한 검색어로 캐시를 조회하고, 없으면 embed()로 계산해 저장
+fn cached_search_vector(search_text: &str, embed: impl FnOnce() -> Option<String>) -> Option<String> {
+    let key = search_text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if let Some(vector) = SEARCH_VECTOR_CACHE.with(|cache| cache.borrow().get(&key).cloned()) {
+        return Some(vector);
     }
+
+    let vector = embed()?;
+    SEARCH_VECTOR_CACHE.with(|cache| cache.borrow_mut().insert(key, vector.clone()));
+    Some(vector)
 }
 
+/// 검색 벡터 쿼리 생성
+/// safeql.max_embedding_calls에 도달했고 search 캐시에도 없는 검색어면 None (호출하는 쪽은 빈 후보를 캐시하지 않고 반환)
+fn create_combined_vector_query(terms: &[String]) -> Option<String> {
+    let combined_term = terms.join(" ");
+    let term_escaped = escape_sql_literal(&combined_term);
+
+    let mut capped = false;
+    let vector = cached_search_vector(&combined_term, || {
+        if !try_count_embedding_call() {
+            capped = true;
+            return None;
+        }
+        Spi::get_one::<String>(&format!("SELECT _vectors_text2vec(quote_literal('{}'))::text", term_escaped))
+            .ok()
+            .flatten()
+    });
+
+    match vector {
+        Some(vector) => Some(format!("SELECT '{}'::vector AS v", escape_sql_literal(&vector))),
+        None if capped => {
+            pgrx::notice!(
+                "SafeQL: Reached maximum embedding calls ({}), skipping similarity lookup for '{}'",
+                MAX_EMBEDDING_CALLS.get(), combined_term
+            );
+            None
+        }
+        None => Some(format!("SELECT _vectors_text2vec(quote_literal('{}')) AS v", term_escaped)),
+    }
+}
 
+
+/// 검색어마다 table/column similarity를 미리 계산해 캐시에 채운다 (스키마 로드 후 첫 refinement 지연을 줄이기 위함)
+/// 반환값: (검색어, 테이블 후보 수, 컬럼 후보 수)
+pub fn warm_similarity_cache(terms: &[String]) -> Vec<(String, i32, i32)> {
+    let warmed = terms
+        .iter()
+        .filter(|term| !term.trim().is_empty())
+        .map(|term| {
+            let tables = list_tables_by_similarity(term).len() as i32;
+            let columns = list_columns_by_similarity(None, term, false).len() as i32;
+            (term.clone(), tables, columns)
+        })
+        .collect();
+
+    reset_search_vector_cache();
+    warmed
+}
+
+/// safeql.max_candidate_distance 이내의 후보인지 확인
+fn within_max_candidate_distance(distance: f32) -> bool {
+    distance as f64 <= MAX_CANDIDATE_DISTANCE.get()
+}
+
+/// 테이블 후보 (대소문자만 다른 정확한 이름은 distance 0으로 벡터 결과보다 앞에 둔다)
 pub fn list_tables_by_similarity(missing_rel: &str) -> Vec<(String, f32)> {
+    let exact_matches = find_exact_table_matches(missing_rel);
+
+    let mut result: Vec<(String, f32)> = exact_matches.iter().map(|t| (t.clone(), 0.0)).collect();
+    result.extend(
+        list_tables_by_vector_similarity(missing_rel)
+            .into_iter()
+            .filter(|(fqname, _)| !exact_matches.contains(fqname))
+            .filter(|(_, distance)| within_max_candidate_distance(*distance))
+    );
+    result.truncate(TOP_K_EXPANSION.get().max(exact_matches.len() as i32) as usize);
+    result
+}
+
+/// 대소문자를 무시하고 이름이 정확히 같은 테이블 찾기 (schema.table)
+fn find_exact_table_matches(missing_rel: &str) -> Vec<String> {
+    let sql = r#"
+        SELECT schemaname || '.' || tablename AS fqname
+        FROM pg_vector_tables
+        WHERE schemaname = ANY (COALESCE($2::name[], current_schemas(false)))
+          AND LOWER(tablename) = LOWER($1)
+        ORDER BY fqname
+    "#;
+
+    Spi::connect(|client| -> Result<Vec<String>, pgrx::spi::Error> {
+        let args = vec![(PgOid::from(pg_sys::TEXTOID), missing_rel.into_datum())];
+
+        let rows = client.select(sql, None, Some(scope_schemas(args)))?;
+        let mut matches = Vec::new();
+
+        for row in rows {
+            if let Ok(Some(fqname)) = row["fqname"].value::<String>() {
+                matches.push(fqname);
+            }
+        }
+
+        Ok(matches)
+    }).unwrap_or_default()
+}
+
+fn list_tables_by_vector_similarity(missing_rel: &str) -> Vec<(String, f32)> {
     // 검색어 준비
     let search_terms = prepare_search_terms(missing_rel);
     let search_key = search_terms.join("_"); // 캐시 키용
@@ -53,7 +197,9 @@ pub fn list_tables_by_similarity(missing_rel: &str) -> Vec<(String, f32)> {
     }
     
     // 검색 벡터 쿼리 생성
-    let vector_query = create_combined_vector_query(&search_terms);
+    let Some(vector_query) = create_combined_vector_query(&search_terms) else {
+        return Vec::new();
+    };
     
     // 캐시 미스 - 실제 쿼리 실행
     let sql = format!(r#"
@@ -64,7 +210,7 @@ pub fn list_tables_by_similarity(missing_rel: &str) -> Vec<(String, f32)> {
             schemaname || '.' || tablename AS fqname,
             (embedding <=> q.v) AS distance
         FROM pg_vector_tables, q
-        WHERE schemaname = ANY (current_schemas(false))
+        WHERE schemaname = ANY (COALESCE($2::name[], current_schemas(false)))
         ORDER BY distance
         LIMIT $1
     "#, vector_query);
@@ -74,7 +220,7 @@ pub fn list_tables_by_similarity(missing_rel: &str) -> Vec<(String, f32)> {
             (PgOid::from(pg_sys::INT4OID), top_k.into_datum())
         ];
         
-        let rows = client.select(&sql, None, Some(args))?;
+        let rows = client.select(&sql, None, Some(scope_schemas(args)))?;
         let mut result = Vec::new();
         
         for row in rows {
@@ -96,11 +242,80 @@ pub fn list_tables_by_similarity(missing_rel: &str) -> Vec<(String, f32)> {
     result
 }
 
-/// Column 후보들을 가져오는 함수 (캐시 적용)
+/// Column 후보들을 가져오는 함수 (대소문자만 다른 정확한 이름은 distance 0으로 벡터 결과보다 앞에 둔다)
 pub fn list_columns_by_similarity(
     table_name: Option<&str>,
     missing_col: &str,
     exclude_same_name: bool,
+) -> Vec<(String, String, f32)> {
+    let exact_matches = find_exact_column_matches(table_name, missing_col, exclude_same_name);
+
+    let mut result: Vec<(String, String, f32)> = exact_matches
+        .iter()
+        .map(|(col, tbl)| (col.clone(), tbl.clone(), 0.0))
+        .collect();
+    result.extend(
+        list_columns_by_vector_similarity(table_name, missing_col, exclude_same_name)
+            .into_iter()
+            .filter(|(col, tbl, _)| !exact_matches.contains(&(col.clone(), tbl.clone())))
+            .filter(|(_, _, distance)| within_max_candidate_distance(*distance))
+    );
+    result.truncate(TOP_K_EXPANSION.get().max(exact_matches.len() as i32) as usize);
+    result
+}
+
+/// 대소문자를 무시하고 이름이 정확히 같은 컬럼 찾기 ((fieldname, tablename))
+/// table_name이 카탈로그에 있으면 그 테이블로 한정한다
+fn find_exact_column_matches(
+    table_name: Option<&str>,
+    missing_col: &str,
+    exclude_same_name: bool,
+) -> Vec<(String, String)> {
+    let sql = r#"
+        WITH check_table AS (
+            SELECT EXISTS (
+                SELECT 1
+                FROM pg_vector_fields
+                WHERE schemaname = ANY (COALESCE($4::name[], current_schemas(false)))
+                  AND tablename = $1
+            ) AS has_table
+        )
+        SELECT f.fieldname, f.tablename
+        FROM pg_vector_fields f, check_table c
+        WHERE f.schemaname = ANY (COALESCE($4::name[], current_schemas(false)))
+          AND LOWER(f.fieldname) = LOWER($2)
+          AND ( $3::bool = false OR f.fieldname <> $2 )
+          AND ( NOT c.has_table OR f.tablename = $1 )
+        ORDER BY f.tablename, f.fieldname
+    "#;
+
+    Spi::connect(|client| -> Result<Vec<(String, String)>, pgrx::spi::Error> {
+        let args = vec![
+            (PgOid::from(pg_sys::TEXTOID), table_name.into_datum()),
+            (PgOid::from(pg_sys::TEXTOID), missing_col.into_datum()),
+            (PgOid::from(pg_sys::BOOLOID), exclude_same_name.into_datum()),
+        ];
+
+        let rows = client.select(sql, None, Some(scope_schemas(args)))?;
+        let mut matches = Vec::new();
+
+        for row in rows {
+            if let (Ok(Some(col_name)), Ok(Some(tbl_name))) = (
+                row["fieldname"].value::<String>(),
+                row["tablename"].value::<String>()
+            ) {
+                matches.push((col_name, tbl_name));
+            }
+        }
+
+        Ok(matches)
+    }).unwrap_or_default()
+}
+
+fn list_columns_by_vector_similarity(
+    table_name: Option<&str>,
+    missing_col: &str,
+    exclude_same_name: bool,
 ) -> Vec<(String, String, f32)> {
     // 검색어 준비
     let search_terms = prepare_search_terms(missing_col);
@@ -124,7 +339,9 @@ pub fn list_columns_by_similarity(
     }
 
     // 검색 벡터 쿼리 생성
-    let vector_query = create_combined_vector_query(&search_terms);
+    let Some(vector_query) = create_combined_vector_query(&search_terms) else {
+        return Vec::new();
+    };
 
     // 캐시 미스 - 실제 쿼리 실행
     let sql = if table_name.is_some() {
@@ -136,7 +353,7 @@ pub fn list_columns_by_similarity(
             SELECT EXISTS (
                 SELECT 1
                 FROM pg_vector_fields
-                WHERE schemaname = ANY (current_schemas(false))
+                WHERE schemaname = ANY (COALESCE($5::name[], current_schemas(false)))
                   AND tablename = $1
             ) AS has_table
         )
@@ -145,7 +362,7 @@ pub fn list_columns_by_similarity(
             f.tablename,
             (f.embedding <=> q.v) AS distance
         FROM pg_vector_fields f, q, check_table c
-        WHERE f.schemaname = ANY (current_schemas(false))
+        WHERE f.schemaname = ANY (COALESCE($5::name[], current_schemas(false)))
           AND ( $3::bool = false OR f.fieldname <> $2 )
           AND (
                 (c.has_table AND f.tablename = $1)
@@ -164,7 +381,7 @@ pub fn list_columns_by_similarity(
             f.tablename,
             (f.embedding <=> q.v) AS distance
         FROM pg_vector_fields f, q
-        WHERE f.schemaname = ANY (current_schemas(false))
+        WHERE f.schemaname = ANY (COALESCE($4::name[], current_schemas(false)))
           AND ( $2::bool = false OR f.fieldname <> $1 )
         ORDER BY distance
         LIMIT $3
@@ -187,7 +404,7 @@ pub fn list_columns_by_similarity(
             ]
         };
 
-        let rows = client.select(&sql, None, Some(args))?;
+        let rows = client.select(&sql, None, Some(scope_schemas(args)))?;
         let mut result = Vec::new();
 
         for row in rows {
@@ -212,6 +429,213 @@ pub fn list_columns_by_similarity(
     result
 }
 
+/// 타입 친화도 보정값 (cosine distance 단위)
+const TYPE_AFFINITY_ADJUSTMENT: f32 = 0.15;
+
+/// list_columns_by_similarity에 타입 친화도를 섞은 버전
+/// 원래 컬럼의 타입을 알면 같은 타입 계열 후보는 distance를 줄이고, 다른 계열은 늘린다
+pub fn list_columns_by_similarity_with_type(
+    table_name: Option<&str>,
+    missing_col: &str,
+    exclude_same_name: bool,
+    expected_type: Option<&str>,
+) -> Vec<(String, String, f32)> {
+    let candidates = list_columns_by_similarity(table_name, missing_col, exclude_same_name);
+
+    let Some(expected_type) = expected_type else {
+        return candidates;
+    };
+    if !ENABLE_TYPE_BASED_REFINEMENT.get() || candidates.is_empty() {
+        return candidates;
+    }
+
+    let field_types = lookup_field_types(&candidates);
+    apply_type_affinity(candidates, expected_type, &field_types)
+}
+
+/// 후보 컬럼들의 fieldtype ((schemaname, tablename, fieldname) -> fieldtype)
+/// 후보 테이블 이름에는 스키마가 없으므로 스키마 범위에서 먼저 나오는 스키마의 테이블로 본다
+#[derive(Default)]
+struct FieldTypes {
+    types: HashMap<(String, String, String), String>,
+    table_schemas: HashMap<String, String>,
+}
+
+impl FieldTypes {
+    fn get(&self, table: &str, field: &str) -> Option<&String> {
+        let schema = self.table_schemas.get(table)?;
+        self.types.get(&(schema.clone(), table.to_string(), field.to_string()))
+    }
+}
+
+/// 후보 컬럼들의 fieldtype 조회
+fn lookup_field_types(candidates: &[(String, String, f32)]) -> FieldTypes {
+    let sql = r#"
+        WITH scope AS (
+            SELECT s.schemaname, s.pos
+            FROM unnest(COALESCE($2::name[], current_schemas(false))) WITH ORDINALITY AS s(schemaname, pos)
+        ),
+        resolved AS (
+            SELECT DISTINCT ON (f.tablename) f.tablename, f.schemaname
+            FROM pg_vector_fields f
+            JOIN scope s ON s.schemaname = f.schemaname
+            WHERE f.tablename = ANY($1)
+            ORDER BY f.tablename, s.pos
+        )
+        SELECT f.schemaname, f.tablename, f.fieldname, f.fieldtype
+        FROM pg_vector_fields f
+        JOIN resolved r ON r.schemaname = f.schemaname AND r.tablename = f.tablename
+    "#;
+
+    let mut tables: Vec<Option<String>> = candidates.iter().map(|(_, t, _)| Some(t.clone())).collect();
+    tables.sort();
+    tables.dedup();
+
+    Spi::connect(|client| -> Result<FieldTypes, pgrx::spi::Error> {
+        let args = vec![(PgOid::from(pg_sys::TEXTARRAYOID), tables.into_datum())];
+
+        let rows = client.select(sql, None, Some(scope_schemas(args)))?;
+        let mut field_types = FieldTypes::default();
+
+        for row in rows {
+            if let (Ok(Some(schema)), Ok(Some(table)), Ok(Some(field)), Ok(Some(field_type))) = (
+                row["schemaname"].value::<String>(),
+                row["tablename"].value::<String>(),
+                row["fieldname"].value::<String>(),
+                row["fieldtype"].value::<String>()
+            ) {
+                field_types.table_schemas.insert(table.clone(), schema.clone());
+                field_types.types.insert((schema, table, field), field_type);
+            }
+        }
+
+        Ok(field_types)
+    }).unwrap_or_default()
+}
+
+/// 후보 distance에 타입 친화도를 반영하고 다시 정렬
+fn apply_type_affinity(
+    mut candidates: Vec<(String, String, f32)>,
+    expected_type: &str,
+    field_types: &FieldTypes,
+) -> Vec<(String, String, f32)> {
+    let expected_family = type_family(expected_type);
+
+    for (col_name, tbl_name, distance) in candidates.iter_mut() {
+        // 타입을 모르는 후보는 그대로 둔다
+        if let Some(field_type) = field_types.get(tbl_name, col_name) {
+            if type_family(field_type) == expected_family {
+                *distance = (*distance - TYPE_AFFINITY_ADJUSTMENT).max(0.0);
+            } else {
+                *distance += TYPE_AFFINITY_ADJUSTMENT;
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal));
+    candidates
+}
+
+/// pg_stats로 본 컬럼의 필터 부적합도 (0.0 ~ 1.0)
+/// NULL 비율과 낮은 cardinality를 반씩 반영하고, 통계가 없으면 0.0
+fn column_statistics_penalty(table_name: &str, column_name: &str) -> f32 {
+    let sql = r#"
+        SELECT
+            s.null_frac::float8 AS null_frac,
+            (CASE WHEN s.n_distinct < 0 THEN -s.n_distinct
+                  ELSE s.n_distinct / greatest(c.reltuples, 1) END)::float8 AS distinct_ratio
+        FROM pg_stats s
+        JOIN pg_namespace n ON n.nspname = s.schemaname
+        JOIN pg_class c ON c.relnamespace = n.oid AND c.relname = s.tablename
+        WHERE s.schemaname = ANY (COALESCE($3::name[], current_schemas(false)))
+          AND s.tablename = $1
+          AND s.attname = $2
+        LIMIT 1
+    "#;
+
+    Spi::connect(|client| -> Result<f32, pgrx::spi::Error> {
+        let args = vec![
+            (PgOid::from(pg_sys::TEXTOID), table_name.into_datum()),
+            (PgOid::from(pg_sys::TEXTOID), column_name.into_datum()),
+        ];
+
+        let rows = client.select(sql, Some(1), Some(scope_schemas(args)))?;
+        for row in rows {
+            if let (Ok(Some(null_frac)), Ok(Some(distinct_ratio))) =
+                (row["null_frac"].value::<f64>(), row["distinct_ratio"].value::<f64>()) {
+                let low_cardinality = 1.0 - distinct_ratio.clamp(0.0, 1.0);
+                return Ok(((null_frac.clamp(0.0, 1.0) + low_cardinality) / 2.0) as f32);
+            }
+        }
+        Ok(0.0)
+    }).unwrap_or(0.0)
+}
+
+/// safeql.column_statistics_weight를 곱한 통계 penalty (가중치가 0이면 조회하지 않는다)
+pub fn weighted_column_statistics_penalty(table_name: &str, column_name: &str) -> f32 {
+    let weight = COLUMN_STATISTICS_WEIGHT.get();
+    if weight <= 0.0 {
+        return 0.0;
+    }
+    (weight * column_statistics_penalty(table_name, column_name) as f64) as f32
+}
+
+/// 후보 distance에 컬럼 통계 penalty를 반영하고 다시 정렬
+fn apply_column_statistics(mut candidates: Vec<(String, String, f32)>) -> Vec<(String, String, f32)> {
+    if COLUMN_STATISTICS_WEIGHT.get() <= 0.0 {
+        return candidates;
+    }
+
+    for (col_name, tbl_name, distance) in candidates.iter_mut() {
+        *distance += weighted_column_statistics_penalty(tbl_name, col_name);
+    }
+
+    candidates.sort_by(|a, b| a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal));
+    candidates
+}
+
+/// 타입 이름을 비교용 계열로 정규화 (int4, bigint -> numeric 등)
+fn type_family(type_name: &str) -> String {
+    let normalized = type_name.trim().to_ascii_lowercase();
+    let base = normalized.split('(').next().unwrap_or("").trim();
+    let base = base.strip_prefix("pg_catalog.").unwrap_or(base);
+
+    match base {
+        "smallint" | "integer" | "int" | "bigint" | "int2" | "int4" | "int8"
+        | "numeric" | "decimal" | "real" | "double precision" | "float4" | "float8" => "numeric",
+        "text" | "character varying" | "varchar" | "character" | "char" | "bpchar" | "name" => "text",
+        "boolean" | "bool" => "boolean",
+        b if b == "date" || b.starts_with("time") => "datetime",
+        other => other,
+    }
+    .to_string()
+}
+
+
+
+/// 같은 이름의 컬럼을 가진 테이블들을 컬럼 타입이 주변 문맥(expected_type)과 맞는 순서로 정렬
+/// 타입 계열이 같은 테이블을 앞에 두고, 나머지는 원래 순서를 유지한다
+pub fn rank_tables_by_column_type(
+    table_names: &[String],
+    column_name: &str,
+    expected_type: &str,
+) -> Vec<String> {
+    let candidates: Vec<(String, String, f32)> = table_names.iter()
+        .map(|t| (column_name.to_string(), t.clone(), 0.0))
+        .collect();
+    let field_types = lookup_field_types(&candidates);
+    let expected_family = type_family(expected_type);
+
+    let mut ranked = table_names.to_vec();
+    ranked.sort_by_key(|t| {
+        match field_types.get(t, column_name) {
+            Some(field_type) if type_family(field_type) == expected_family => 0,
+            Some(_) => 2,
+            None => 1,
+        }
+    });
+    ranked
+}
 
 /// FROM절의 모든 테이블에서 해당 컬럼을 가진 테이블들 찾기 (캐시 적용)
 /// Column Ambiguity Refinement 전용 함수 - 효율적으로 한 번에 조회
@@ -239,7 +663,7 @@ pub fn find_tables_with_exact_column(table_names: &[String], column_name: &str)
     let sql = r#"
         SELECT DISTINCT f.tablename
         FROM pg_vector_fields f
-        WHERE f.schemaname = ANY (current_schemas(false))
+        WHERE f.schemaname = ANY (COALESCE($3::name[], current_schemas(false)))
           AND f.tablename = ANY($1)
           AND LOWER(f.fieldname) = LOWER($2)
         ORDER BY f.tablename
@@ -252,7 +676,7 @@ pub fn find_tables_with_exact_column(table_names: &[String], column_name: &str)
             (PgOid::from(pg_sys::TEXTOID), column_name.into_datum()),
         ];
 
-        let rows = client.select(sql, None, Some(args))?;
+        let rows = client.select(sql, None, Some(scope_schemas(args)))?;
         let mut matching_tables = Vec::new();
 
         for row in rows {
@@ -276,6 +700,65 @@ pub fn find_tables_with_exact_column(table_names: &[String], column_name: &str)
 }
 
 
+/// 카탈로그(pg_attribute) 기준 테이블의 실제 컬럼 목록
+/// 캐시된 similarity/JOIN 결과가 컬럼 rename 이후에도 남아 있을 수 있으므로 캐시하지 않는다
+fn list_catalog_columns(table_name: &str) -> Vec<String> {
+    let sql = r#"
+        SELECT a.attname::text AS attname
+        FROM pg_attribute a
+        JOIN pg_class c ON c.oid = a.attrelid
+        JOIN pg_namespace n ON n.oid = c.relnamespace
+        WHERE n.nspname = ANY (COALESCE($2::name[], current_schemas(false)))
+          AND LOWER(c.relname) = LOWER($1)
+          AND a.attnum > 0
+          AND NOT a.attisdropped
+        ORDER BY a.attnum
+    "#;
+
+    Spi::connect(|client| -> Result<Vec<String>, pgrx::spi::Error> {
+        let args = vec![(PgOid::from(pg_sys::TEXTOID), table_name.into_datum())];
+
+        let rows = client.select(sql, None, Some(scope_schemas(args)))?;
+        let mut columns = Vec::new();
+
+        for row in rows {
+            if let Ok(Some(attname)) = row["attname"].value::<String>() {
+                columns.push(attname);
+            }
+        }
+
+        Ok(columns)
+    }).unwrap_or_default()
+}
+
+/// JOIN 조건의 오른쪽(새로 추가할 테이블) 컬럼이 실제로 있는지 확인
+/// 없으면 similarity 후보 중 실제로 존재하는 컬럼, 그것도 없으면 이름이 충분히 가까운 컬럼으로 바꾼다
+/// 마땅한 컬럼이 없으면 None (이 조건으로는 JOIN하지 않음)
+pub fn verify_join_right_column(condition: &JoinCondition) -> Option<JoinCondition> {
+    let columns = list_catalog_columns(&condition.right_table);
+    // 카탈로그에서 테이블을 찾지 못하면 검증할 수 없으므로 그대로 둔다
+    if columns.is_empty() || columns.iter().any(|c| c.eq_ignore_ascii_case(&condition.right_column)) {
+        return Some(condition.clone());
+    }
+
+    let by_similarity = list_columns_by_similarity(Some(&condition.right_table), &condition.right_column, true)
+        .into_iter()
+        .filter_map(|(col, _, _)| columns.iter().find(|c| c.eq_ignore_ascii_case(&col)).cloned())
+        .next();
+
+    let replacement = by_similarity.or_else(|| {
+        let missing = condition.right_column.to_ascii_lowercase();
+        columns
+            .iter()
+            .map(|c| (c, edit_distance(&missing, &c.to_ascii_lowercase())))
+            .filter(|(_, dist)| *dist <= (missing.len() / 3).max(1))
+            .min_by_key(|(_, dist)| *dist)
+            .map(|(c, _)| c.clone())
+    })?;
+
+    Some(JoinCondition { right_column: replacement, ..condition.clone() })
+}
+
 /// FROM절의 테이블들과 PK-FK 관계로 연결 가능한 모든 테이블 찾기 (컬럼 검색 없이)
 pub fn find_all_joinable_tables(
     existing_tables: &[String],
@@ -288,7 +771,12 @@ pub fn find_all_joinable_tables(
     let mut sorted_tables = existing_tables.to_vec();
     sorted_tables.sort();
     let tables_str = sorted_tables.join(",");
-    let cache_key = generate_cache_key("all_joinable_tables", &[&tables_str]);
+    let require_fk = JOIN_REQUIRE_FK.get();
+    let constraint_generation = constraint_generation_token();
+    let cache_key = generate_cache_key(
+        "all_joinable_tables",
+        &[&tables_str, &require_fk.to_string(), &constraint_generation]
+    );
     
     // 캐시에서 결과 조회
     if let Some(cached_result) = get_cached_result(&cache_key) {
@@ -297,8 +785,44 @@ pub fn find_all_joinable_tables(
         }
     }
 
+    // 이름만 같은 컬럼끼리 잇는 후보 (FK 선언 없음)
+    let natural_joinables_cte = r#",
+        natural_joinables AS (
+            SELECT DISTINCT
+                t2.relname AS join_table,
+                t1.relname AS existing_table,
+                a1.attname AS fk_column,
+                a2.attname AS pk_column,
+                3 AS direction,
+                NULL::name AS constraint_name
+            FROM pg_class t1
+            JOIN pg_namespace n1 ON t1.relnamespace = n1.oid
+            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum > 0 AND NOT a1.attisdropped
+            JOIN existing_tables et ON LOWER(t1.relname) = LOWER(et.table_name)
+            JOIN pg_class t2 ON t2.oid <> t1.oid
+            JOIN pg_namespace n2 ON t2.relnamespace = n2.oid
+            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum > 0 AND NOT a2.attisdropped
+            LEFT JOIN pk_columns pk1 ON pk1.table_oid = t1.oid AND LOWER(pk1.pk_column) = LOWER(a1.attname)
+            LEFT JOIN pk_columns pk2 ON pk2.table_oid = t2.oid AND LOWER(pk2.pk_column) = LOWER(a2.attname)
+            WHERE n1.nspname = ANY(COALESCE($2::name[], current_schemas(false)))
+            AND n2.nspname = ANY(COALESCE($2::name[], current_schemas(false)))
+            AND t1.relkind = 'r'
+            AND t2.relkind = 'r'
+            AND LOWER(a1.attname) = LOWER(a2.attname)
+            AND pk1.pk_column IS NULL
+            AND pk2.pk_column IS NULL
+            AND LOWER(t2.relname) NOT IN (SELECT LOWER(table_name) FROM existing_tables)
+        )"#;
+    let natural_joinables_union = "            UNION ALL\n            SELECT * FROM natural_joinables\n";
+
     // 캐시 미스 - 실제 쿼리 실행
-    let sql = r#"
+    // JOIN_REQUIRE_FK가 켜져 있으면 natural_joinables를 SQL에서 아예 뺀다
+    let (natural_cte, natural_union) = if require_fk {
+        ("", "")
+    } else {
+        (natural_joinables_cte, natural_joinables_union)
+    };
+    let sql = format!(r#"
         WITH existing_tables AS (
             SELECT unnest($1::text[]) AS table_name
         ),
@@ -308,18 +832,20 @@ pub fn find_all_joinable_tables(
                 t1.relname AS existing_table,
                 a1.attname AS fk_column,
                 a2.attname AS pk_column,
-                1 AS direction
+                1 AS direction,
+                c.conname AS constraint_name
             FROM pg_constraint c
             JOIN pg_class t1 ON c.conrelid = t1.oid
             JOIN pg_class t2 ON c.confrelid = t2.oid
             JOIN pg_namespace n1 ON t1.relnamespace = n1.oid
             JOIN pg_namespace n2 ON t2.relnamespace = n2.oid
-            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum = ANY(c.conkey)
-            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum = ANY(c.confkey)
+            CROSS JOIN LATERAL unnest(c.conkey, c.confkey) AS k(fk_attnum, pk_attnum)
+            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum = k.fk_attnum
+            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum = k.pk_attnum
             JOIN existing_tables et ON LOWER(t1.relname) = LOWER(et.table_name)
             WHERE c.contype = 'f'
-            AND n1.nspname = ANY(current_schemas(false))
-            AND n2.nspname = ANY(current_schemas(false))
+            AND n1.nspname = ANY(COALESCE($2::name[], current_schemas(false)))
+            AND n2.nspname = ANY(COALESCE($2::name[], current_schemas(false)))
             AND t1.relkind = 'r'
             AND t2.relkind = 'r'
             AND LOWER(t2.relname) NOT IN (SELECT LOWER(table_name) FROM existing_tables)
@@ -331,18 +857,20 @@ pub fn find_all_joinable_tables(
                 t2.relname AS existing_table,
                 a1.attname AS fk_column,
                 a2.attname AS pk_column,
-                2 AS direction
+                2 AS direction,
+                c.conname AS constraint_name
             FROM pg_constraint c
             JOIN pg_class t1 ON c.conrelid = t1.oid
             JOIN pg_class t2 ON c.confrelid = t2.oid
             JOIN pg_namespace n1 ON t1.relnamespace = n1.oid
             JOIN pg_namespace n2 ON t2.relnamespace = n2.oid
-            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum = ANY(c.conkey)
-            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum = ANY(c.confkey)
+            CROSS JOIN LATERAL unnest(c.conkey, c.confkey) AS k(fk_attnum, pk_attnum)
+            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum = k.fk_attnum
+            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum = k.pk_attnum
             JOIN existing_tables et ON LOWER(t2.relname) = LOWER(et.table_name)
             WHERE c.contype = 'f'
-            AND n1.nspname = ANY(current_schemas(false))
-            AND n2.nspname = ANY(current_schemas(false))
+            AND n1.nspname = ANY(COALESCE($2::name[], current_schemas(false)))
+            AND n2.nspname = ANY(COALESCE($2::name[], current_schemas(false)))
             AND t1.relkind = 'r'
             AND t2.relkind = 'r'
             AND LOWER(t1.relname) NOT IN (SELECT LOWER(table_name) FROM existing_tables)
@@ -355,46 +883,19 @@ pub fn find_all_joinable_tables(
             JOIN pg_class t ON t.oid = c.conrelid
             JOIN pg_attribute a ON a.attrelid = t.oid AND a.attnum = ANY(c.conkey)
             WHERE c.contype = 'p' -- primary key
-        ),
-        natural_joinables AS (
-            SELECT DISTINCT
-                t2.relname AS join_table,
-                t1.relname AS existing_table,
-                a1.attname AS fk_column,
-                a2.attname AS pk_column,
-                3 AS direction
-            FROM pg_class t1
-            JOIN pg_namespace n1 ON t1.relnamespace = n1.oid
-            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum > 0 AND NOT a1.attisdropped
-            JOIN existing_tables et ON LOWER(t1.relname) = LOWER(et.table_name)
-            JOIN pg_class t2 ON t2.oid <> t1.oid
-            JOIN pg_namespace n2 ON t2.relnamespace = n2.oid
-            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum > 0 AND NOT a2.attisdropped
-            LEFT JOIN pk_columns pk1 ON pk1.table_oid = t1.oid AND LOWER(pk1.pk_column) = LOWER(a1.attname)
-            LEFT JOIN pk_columns pk2 ON pk2.table_oid = t2.oid AND LOWER(pk2.pk_column) = LOWER(a2.attname)
-            WHERE n1.nspname = ANY(current_schemas(false))
-            AND n2.nspname = ANY(current_schemas(false))
-            AND t1.relkind = 'r'
-            AND t2.relkind = 'r'
-            AND LOWER(a1.attname) = LOWER(a2.attname)
-            AND pk1.pk_column IS NULL
-            AND pk2.pk_column IS NULL
-            AND LOWER(t2.relname) NOT IN (SELECT LOWER(table_name) FROM existing_tables)
-        )
+        ){}
         SELECT 
             jt.join_table::text,
             jt.existing_table::text,
             jt.fk_column::text,
             jt.pk_column::text,
+            jt.constraint_name::text,
             1.0::float4 AS distance
         FROM (
             SELECT * FROM pk_fk_joinables
-            UNION ALL
-            SELECT * FROM natural_joinables
-        ) jt
+{}        ) jt
         ORDER BY jt.join_table, jt.existing_table;
-        "#
-        .to_string();
+        "#, natural_cte, natural_union);
 
     let result = Spi::connect(|client| -> Result<Vec<(String, f32, Vec<JoinCondition>)>, pgrx::spi::Error> {
         let table_array: Vec<Option<String>> = existing_tables.iter().map(|t| Some(t.clone())).collect();
@@ -402,7 +903,7 @@ pub fn find_all_joinable_tables(
             (PgOid::from(pg_sys::TEXTARRAYOID), table_array.into_datum()),
         ];
         
-        let rows = client.select(&sql, None, Some(args))?;
+        let rows = client.select(&sql, None, Some(scope_schemas(args)))?;
         let mut result_map: HashMap<String, (f32, Vec<JoinCondition>)> = HashMap::new();
         
         for row in rows {
@@ -424,6 +925,7 @@ pub fn find_all_joinable_tables(
                     left_column: pk_column,
                     right_table: join_table.clone(),
                     right_column: fk_column,
+                    constraint_name: row["constraint_name"].value::<String>().ok().flatten(),
                 };
 
                 result_map.entry(join_table)
@@ -473,7 +975,12 @@ pub fn find_joinable_tables_for_column(
     let mut sorted_tables = existing_tables.to_vec();
     sorted_tables.sort();
     let tables_str = sorted_tables.join(",");
-    let cache_key = generate_cache_key("joinable_tables", &[&tables_str, &search_key]);
+    let require_fk = JOIN_REQUIRE_FK.get();
+    let constraint_generation = constraint_generation_token();
+    let cache_key = generate_cache_key(
+        "joinable_tables",
+        &[&tables_str, &search_key, &require_fk.to_string(), &constraint_generation]
+    );
     
     // 캐시에서 결과 조회
     if let Some(cached_result) = get_cached_result(&cache_key) {
@@ -485,9 +992,40 @@ pub fn find_joinable_tables_for_column(
     }
 
     // 검색 벡터 쿼리 생성
-    let vector_query = create_combined_vector_query(&search_terms);
+    let Some(vector_query) = create_combined_vector_query(&search_terms) else {
+        return Vec::new();
+    };
+
+    // 이름만 같은 컬럼끼리 잇는 후보 (FK 선언 없음)
+    let natural_joinables_cte = r#"        natural_joinables AS (
+            SELECT DISTINCT
+                t2.relname AS join_table,
+                t1.relname AS existing_table,
+                a1.attname AS fk_column,
+                a2.attname AS pk_column,
+                NULL::name AS constraint_name
+            FROM pg_class t1
+            JOIN pg_namespace n1 ON t1.relnamespace = n1.oid
+            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum > 0 AND NOT a1.attisdropped
+            JOIN existing_tables et ON LOWER(t1.relname) = LOWER(et.table_name)
+            JOIN pg_class t2 ON t2.oid <> t1.oid
+            JOIN pg_namespace n2 ON t2.relnamespace = n2.oid
+            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum > 0 AND NOT a2.attisdropped
+            WHERE n1.nspname = ANY(COALESCE($3::name[], current_schemas(false)))
+            AND n2.nspname = ANY(COALESCE($3::name[], current_schemas(false)))
+            AND LOWER(a1.attname) = LOWER(a2.attname)
+            AND LOWER(t2.relname) NOT IN (SELECT LOWER(table_name) FROM existing_tables)
+        ),
+"#;
+    let natural_joinables_union = "            UNION ALL\n            SELECT * FROM natural_joinables\n";
 
     // 캐시 미스 - 실제 쿼리 실행
+    // JOIN_REQUIRE_FK가 켜져 있으면 natural_joinables를 SQL에서 아예 뺀다
+    let (natural_cte, natural_union) = if require_fk {
+        ("", "")
+    } else {
+        (natural_joinables_cte, natural_joinables_union)
+    };
     let sql = format!(r#"
         WITH q AS (
             {}
@@ -500,17 +1038,19 @@ pub fn find_joinable_tables_for_column(
                 t2.relname AS join_table,
                 t1.relname AS existing_table,
                 a1.attname AS fk_column,
-                a2.attname AS pk_column
+                a2.attname AS pk_column,
+                c.conname AS constraint_name
             FROM pg_constraint c
             JOIN pg_class t1 ON c.conrelid = t1.oid
             JOIN pg_class t2 ON c.confrelid = t2.oid
             JOIN pg_namespace n1 ON t1.relnamespace = n1.oid
             JOIN pg_namespace n2 ON t2.relnamespace = n2.oid
-            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum = ANY(c.conkey)
-            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum = ANY(c.confkey)
+            CROSS JOIN LATERAL unnest(c.conkey, c.confkey) AS k(fk_attnum, pk_attnum)
+            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum = k.fk_attnum
+            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum = k.pk_attnum
             JOIN existing_tables et ON LOWER(t1.relname) = LOWER(et.table_name)
             WHERE c.contype = 'f'
-            AND n1.nspname = ANY(current_schemas(false))
+            AND n1.nspname = ANY(COALESCE($3::name[], current_schemas(false)))
             AND LOWER(t2.relname) NOT IN (SELECT LOWER(table_name) FROM existing_tables)
 
             UNION
@@ -519,55 +1059,38 @@ pub fn find_joinable_tables_for_column(
                 t1.relname AS join_table,
                 t2.relname AS existing_table,
                 a1.attname AS fk_column,
-                a2.attname AS pk_column
+                a2.attname AS pk_column,
+                c.conname AS constraint_name
             FROM pg_constraint c
             JOIN pg_class t1 ON c.conrelid = t1.oid
             JOIN pg_class t2 ON c.confrelid = t2.oid
             JOIN pg_namespace n1 ON t1.relnamespace = n1.oid
             JOIN pg_namespace n2 ON t2.relnamespace = n2.oid
-            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum = ANY(c.conkey)
-            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum = ANY(c.confkey)
+            CROSS JOIN LATERAL unnest(c.conkey, c.confkey) AS k(fk_attnum, pk_attnum)
+            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum = k.fk_attnum
+            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum = k.pk_attnum
             JOIN existing_tables et ON LOWER(t2.relname) = LOWER(et.table_name)
             WHERE c.contype = 'f'
-            AND n1.nspname = ANY(current_schemas(false))
+            AND n1.nspname = ANY(COALESCE($3::name[], current_schemas(false)))
             AND LOWER(t1.relname) NOT IN (SELECT LOWER(table_name) FROM existing_tables)
         ),
-        natural_joinables AS (
-            SELECT DISTINCT
-                t2.relname AS join_table,
-                t1.relname AS existing_table,
-                a1.attname AS fk_column,
-                a2.attname AS pk_column
-            FROM pg_class t1
-            JOIN pg_namespace n1 ON t1.relnamespace = n1.oid
-            JOIN pg_attribute a1 ON a1.attrelid = t1.oid AND a1.attnum > 0 AND NOT a1.attisdropped
-            JOIN existing_tables et ON LOWER(t1.relname) = LOWER(et.table_name)
-            JOIN pg_class t2 ON t2.oid <> t1.oid
-            JOIN pg_namespace n2 ON t2.relnamespace = n2.oid
-            JOIN pg_attribute a2 ON a2.attrelid = t2.oid AND a2.attnum > 0 AND NOT a2.attisdropped
-            WHERE n1.nspname = ANY(current_schemas(false))
-            AND n2.nspname = ANY(current_schemas(false))
-            AND LOWER(a1.attname) = LOWER(a2.attname)
-            AND LOWER(t2.relname) NOT IN (SELECT LOWER(table_name) FROM existing_tables)
-        ),
-        all_joinables AS (
+{}        all_joinables AS (
             SELECT * FROM pk_fk_joinables
-            UNION ALL
-            SELECT * FROM natural_joinables
-        )
+{}        )
         SELECT 
             jt.join_table::text,
             jt.existing_table::text,
             jt.fk_column::text,
             jt.pk_column::text,
+            jt.constraint_name::text,
             (f.embedding <=> q.v) AS column_distance
         FROM all_joinables jt
         JOIN pg_vector_fields f ON f.tablename = jt.join_table
-            AND f.schemaname = ANY(current_schemas(false))
+            AND f.schemaname = ANY(COALESCE($3::name[], current_schemas(false)))
             AND f.fieldname = $1
         CROSS JOIN q
         ORDER BY column_distance, jt.join_table;
-    "#, vector_query);
+    "#, vector_query, natural_cte, natural_union);
 
     let result = Spi::connect(|client| -> Result<Vec<(String, f32, Vec<JoinCondition>)>, pgrx::spi::Error> {
         let table_array: Vec<Option<String>> = existing_tables.iter().map(|t| Some(t.clone())).collect();
@@ -576,7 +1099,7 @@ pub fn find_joinable_tables_for_column(
             (PgOid::from(pg_sys::TEXTARRAYOID), table_array.into_datum()),
         ];
         
-        let rows = client.select(&sql, None, Some(args))?;
+        let rows = client.select(&sql, None, Some(scope_schemas(args)))?;
         let mut result_map: HashMap<String, (f32, Vec<JoinCondition>)> = HashMap::new();
         
         for row in rows {
@@ -598,6 +1121,7 @@ pub fn find_joinable_tables_for_column(
                     left_column: pk_column,
                     right_table: join_table.clone(),
                     right_column: fk_column,
+                    constraint_name: row["constraint_name"].value::<String>().ok().flatten(),
                 };
 
                 result_map.entry(join_table)
@@ -668,12 +1192,14 @@ pub fn find_compatible_columns_for_operator(
         if let Ok(result) = serde_json::from_str::<Vec<(String, String, f32)>>(&cached_result) {
             // pgrx::notice!("Cache hit for operator compatible columns query: table={:?}, col={}, position={:?} (terms: {:?})", 
             //              table_name, missing_col, operand_position, search_terms);
-            return result;
+            return apply_column_statistics(result);
         }
     }
 
     // 검색 벡터 쿼리 생성
-    let vector_query = create_combined_vector_query(&search_terms);
+    let Some(vector_query) = create_combined_vector_query(&search_terms) else {
+        return Vec::new();
+    };
 
     // 타입 기반 최적화가 활성화되어 있으면 호환 가능한 타입만 검색
     let type_filter = if ENABLE_TYPE_BASED_REFINEMENT.get() {
@@ -703,7 +1229,7 @@ pub fn find_compatible_columns_for_operator(
             SELECT EXISTS (
                 SELECT 1
                 FROM pg_vector_fields
-                WHERE schemaname = ANY (current_schemas(false))
+                WHERE schemaname = ANY (COALESCE($3::name[], current_schemas(false)))
                   AND tablename = $1
             ) AS has_table
         )
@@ -712,7 +1238,7 @@ pub fn find_compatible_columns_for_operator(
             f.tablename,
             (f.embedding <=> q.v) AS distance
         FROM pg_vector_fields f, q, check_table c
-        WHERE f.schemaname = ANY (current_schemas(false))
+        WHERE f.schemaname = ANY (COALESCE($3::name[], current_schemas(false)))
           AND f.fieldname <> $2
           AND (
                 (c.has_table AND f.tablename = $1)
@@ -732,7 +1258,7 @@ pub fn find_compatible_columns_for_operator(
             f.tablename,
             (f.embedding <=> q.v) AS distance
         FROM pg_vector_fields f, q
-        WHERE f.schemaname = ANY (current_schemas(false))
+        WHERE f.schemaname = ANY (COALESCE($2::name[], current_schemas(false)))
           AND f.fieldname <> $1
           {}
         ORDER BY distance
@@ -752,7 +1278,7 @@ pub fn find_compatible_columns_for_operator(
             ]
         };
 
-        let rows = client.select(&sql, None, Some(args))?;
+        let rows = client.select(&sql, None, Some(scope_schemas(args)))?;
         let mut result = Vec::new();
 
         for row in rows {
@@ -776,7 +1302,7 @@ pub fn find_compatible_columns_for_operator(
     // pgrx::notice!("SafeQL: Found {} compatible columns for operator {} (position: {:?}, original_col: {})", 
     //              result.len(), operator_info.operator_name, operand_position, missing_col);
 
-    result
+    apply_column_statistics(result)
 }
 
 /// 오퍼레이터와 현재 타입에 대해 가능한 타입 캐스팅 옵션들을 찾는 함수
@@ -916,7 +1442,9 @@ pub fn find_compatible_arguments_for_function(
     }
 
     // 검색 벡터 쿼리 생성
-    let vector_query = create_combined_vector_query(&search_terms);
+    let Some(vector_query) = create_combined_vector_query(&search_terms) else {
+        return Vec::new();
+    };
 
     // 타입 기반 최적화가 활성화되어 있으면 호환 가능한 타입만 검색
     let type_filter = if ENABLE_TYPE_BASED_REFINEMENT.get() {
@@ -946,7 +1474,7 @@ pub fn find_compatible_arguments_for_function(
             SELECT EXISTS (
                 SELECT 1
                 FROM pg_vector_fields
-                WHERE schemaname = ANY (current_schemas(false))
+                WHERE schemaname = ANY (COALESCE($3::name[], current_schemas(false)))
                   AND tablename = $1
             ) AS has_table
         )
@@ -955,7 +1483,7 @@ pub fn find_compatible_arguments_for_function(
             f.tablename,
             (f.embedding <=> q.v) AS distance
         FROM pg_vector_fields f, q, check_table c
-        WHERE f.schemaname = ANY (current_schemas(false))
+        WHERE f.schemaname = ANY (COALESCE($3::name[], current_schemas(false)))
           AND f.fieldname <> $2
           AND (
                 (c.has_table AND f.tablename = $1)
@@ -975,7 +1503,7 @@ pub fn find_compatible_arguments_for_function(
             f.tablename,
             (f.embedding <=> q.v) AS distance
         FROM pg_vector_fields f, q
-        WHERE f.schemaname = ANY (current_schemas(false))
+        WHERE f.schemaname = ANY (COALESCE($2::name[], current_schemas(false)))
           AND f.fieldname <> $1
           {}
         ORDER BY distance
@@ -995,7 +1523,7 @@ pub fn find_compatible_arguments_for_function(
             ]
         };
 
-        let rows = client.select(&sql, None, Some(args))?;
+        let rows = client.select(&sql, None, Some(scope_schemas(args)))?;
         let mut result = Vec::new();
 
         for row in rows {
@@ -1127,18 +1655,23 @@ pub fn get_typecast_refinements_for_function(
 
 
 /// 함수명과 유사한 함수들을 찾는 함수 (캐시 적용)
+/// schema가 주어지면 search_path 대신 그 스키마의 함수만 찾는다 (myschema.funk(...) 형태의 호출)
 pub fn find_similar_functions(
     function_name: &str,
     arg_count: usize,
+    schema: Option<&str>,
 ) -> Vec<(String, Vec<String>, String, f32)> {
     let search_terms = prepare_search_terms(function_name);
     let search_key = search_terms.join("_");
     
     let top_k = TOP_K_EXPANSION.get();
-    let cache_key = generate_cache_key(
-        "similar_functions", 
-        &[&search_key, &arg_count.to_string(), &top_k.to_string()]
-    );
+    let arg_count_key = arg_count.to_string();
+    let top_k_key = top_k.to_string();
+    let mut key_parts = vec![search_key.as_str(), arg_count_key.as_str(), top_k_key.as_str()];
+    if let Some(schema) = schema {
+        key_parts.push(schema);
+    }
+    let cache_key = generate_cache_key("similar_functions", &key_parts);
     
     if let Some(cached_result) = get_cached_result(&cache_key) {
         if let Ok(result) = serde_json::from_str::<Vec<(String, Vec<String>, String, f32)>>(&cached_result) {
@@ -1148,7 +1681,9 @@ pub fn find_similar_functions(
         }
     }
 
-    let vector_query = create_combined_vector_query(&search_terms);
+    let Some(vector_query) = create_combined_vector_query(&search_terms) else {
+        return Vec::new();
+    };
     
     let sql = format!(r#"
         WITH q AS (
@@ -1163,21 +1698,24 @@ pub fn find_similar_functions(
                 (f.embedding <=> q.v) AS distance,
                 ROW_NUMBER() OVER (PARTITION BY f.function_name ORDER BY (f.embedding <=> q.v)) AS rn
             FROM pg_vector_functions f, q
-            WHERE f.schemaname = ANY (current_schemas(true))
+            WHERE {}
             AND array_length(string_to_array(f.arg_types, ','), 1) = $1
             AND f.function_name <> $2
         ) sub
         WHERE rn = 1
         ORDER BY distance
         LIMIT $3;
-    "#, vector_query);
+    "#, vector_query, if schema.is_some() { "f.schemaname = $4" } else { "f.schemaname = ANY (current_schemas(true))" });
 
     let result = Spi::connect(|client| -> Result<Vec<(String, Vec<String>, String, f32)>, pgrx::spi::Error> {
-        let args = vec![
+        let mut args = vec![
             (PgOid::from(pg_sys::INT4OID), (arg_count as i32).into_datum()),
             (PgOid::from(pg_sys::TEXTOID), function_name.into_datum()),
             (PgOid::from(pg_sys::INT4OID), top_k.into_datum())
         ];
+        if let Some(schema) = schema {
+            args.push((PgOid::from(pg_sys::TEXTOID), schema.into_datum()));
+        }
         
         let rows = client.select(&sql, None, Some(args))?;
         let mut result = Vec::new();
@@ -1217,6 +1755,86 @@ pub fn find_similar_functions(
 }
 
 
+/// 엔진이 기본으로 아는 집계 함수들
+const KNOWN_AGGREGATES: &[&str] = &["sum", "count", "avg", "min", "max"];
+
+/// 집계 친화도 보정값 (cosine distance 단위)
+const AGGREGATE_AFFINITY_ADJUSTMENT: f32 = 0.15;
+
+pub fn is_known_aggregate(function_name: &str) -> bool {
+    KNOWN_AGGREGATES.iter().any(|agg| agg.eq_ignore_ascii_case(function_name))
+}
+
+/// 오타 난 함수명이 가리키는 집계 함수 추정 (편집 거리 2 이내에서 가장 가까운 것)
+/// 세 글자 이하 집계(sum, avg, min, max)는 mod/abs 같은 다른 함수와 2글자 차이이므로 1 이내만 본다
+fn intended_aggregate(function_name: &str, arg_count: usize) -> Option<&'static str> {
+    if arg_count > 1 {
+        return None;
+    }
+    let lowered = function_name.to_ascii_lowercase();
+
+    KNOWN_AGGREGATES.iter()
+        .map(|agg| (*agg, edit_distance(&lowered, agg)))
+        .filter(|(agg, dist)| *dist <= max_aggregate_typo_distance(agg))
+        .min_by_key(|(_, dist)| *dist)
+        .map(|(agg, _)| agg)
+}
+
+/// 집계 이름 길이에 따른 오타 허용 편집 거리
+fn max_aggregate_typo_distance(aggregate: &str) -> usize {
+    if aggregate.len() <= 3 { 1 } else { 2 }
+}
+
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b_chars.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b_chars.len() + 1];
+        for (j, cb) in b_chars.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b_chars.len()]
+}
+
+/// find_similar_functions에 집계 친화도를 섞은 버전
+/// 잘못된 함수명이 집계 함수의 오타로 보이면 그 집계를 맨 앞에 두고, 다른 집계는 distance를 줄이고 일반 함수는 늘린다
+/// 집계 함수는 pg_catalog에 있으므로 다른 스키마로 한정된 호출에는 적용하지 않는다
+pub fn find_similar_functions_with_aggregates(
+    function_name: &str,
+    arg_count: usize,
+    schema: Option<&str>,
+) -> Vec<(String, Vec<String>, String, f32)> {
+    let mut candidates = find_similar_functions(function_name, arg_count, schema);
+    if schema.is_some_and(|schema| !schema.eq_ignore_ascii_case("pg_catalog")) {
+        return candidates;
+    }
+    let Some(intended) = intended_aggregate(function_name, arg_count) else {
+        return candidates;
+    };
+
+    // 벡터 검색 결과에 빠져 있어도 의도한 집계는 후보에 넣는다
+    if !candidates.iter().any(|(name, _, _, _)| name.eq_ignore_ascii_case(intended)) {
+        candidates.push((intended.to_string(), Vec::new(), String::new(), AGGREGATE_AFFINITY_ADJUSTMENT));
+    }
+
+    for (name, _, _, distance) in candidates.iter_mut() {
+        if name.eq_ignore_ascii_case(intended) {
+            *distance = 0.0;
+        } else if is_known_aggregate(name) {
+            *distance = (*distance - AGGREGATE_AFFINITY_ADJUSTMENT).max(0.0);
+        } else {
+            *distance += AGGREGATE_AFFINITY_ADJUSTMENT;
+        }
+    }
+    candidates.sort_by(|a, b| a.3.partial_cmp(&b.3).unwrap_or(std::cmp::Ordering::Equal));
+    candidates
+}
+
+
 /// 함수명이 정확히 일치하는 함수가 존재하는지 확인 (캐시 적용)
 pub fn check_function_exists(function_name: &str) -> bool {
     let cache_key = generate_cache_key(
@@ -1267,6 +1885,105 @@ pub fn check_function_exists(function_name: &str) -> bool {
     result
 }
 
+/// 함수명이 set-returning 함수(proretset)인지 확인
+pub fn is_set_returning_function(function_name: &str) -> bool {
+    let sql = r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM pg_proc p
+            JOIN pg_namespace n ON p.pronamespace = n.oid
+            WHERE p.proname = LOWER($1)
+                AND p.proretset
+                AND n.nspname = ANY (current_schemas(true))
+        )
+    "#;
+
+    Spi::connect(|client| -> Result<bool, pgrx::spi::Error> {
+        let args = vec![
+            (PgOid::from(pg_sys::TEXTOID), function_name.into_datum())
+        ];
+
+        let rows = client.select(sql, None, Some(args))?;
+
+        for row in rows {
+            if let Ok(Some(is_srf)) = row[1].value::<bool>() {
+                return Ok(is_srf);
+            }
+        }
+
+        Ok(false)
+    }).unwrap_or(false)
+}
+
+/// 함수명이 집계 함수(prokind = 'a')인지 확인
+pub fn is_aggregate_function(function_name: &str) -> bool {
+    let sql = r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM pg_proc p
+            JOIN pg_namespace n ON p.pronamespace = n.oid
+            WHERE p.proname = LOWER($1)
+                AND p.prokind = 'a'
+                AND n.nspname = ANY (current_schemas(true))
+        )
+    "#;
+
+    Spi::connect(|client| -> Result<bool, pgrx::spi::Error> {
+        let args = vec![
+            (PgOid::from(pg_sys::TEXTOID), function_name.into_datum())
+        ];
+
+        let rows = client.select(sql, None, Some(args))?;
+
+        for row in rows {
+            if let Ok(Some(is_aggregate)) = row[1].value::<bool>() {
+                return Ok(is_aggregate);
+            }
+        }
+
+        Ok(false)
+    }).unwrap_or(false)
+}
+
+/// 같은 이름의 함수 overload들의 인자 타입(pg_type.typname) 목록 (variadic 함수 제외)
+pub fn find_function_overload_arg_types(function_name: &str) -> Vec<Vec<String>> {
+    let sql = r#"
+        SELECT DISTINCT array_to_string(ARRAY(
+            SELECT t.typname
+            FROM unnest(p.proargtypes::oid[]) WITH ORDINALITY AS a(typid, ord)
+            JOIN pg_type t ON t.oid = a.typid
+            ORDER BY a.ord
+        ), ',')
+        FROM pg_proc p
+        JOIN pg_namespace n ON p.pronamespace = n.oid
+        WHERE p.proname = LOWER($1)
+            AND p.provariadic = 0
+            AND n.nspname = ANY (current_schemas(true))
+    "#;
+
+    Spi::connect(|client| -> Result<Vec<Vec<String>>, pgrx::spi::Error> {
+        let args = vec![
+            (PgOid::from(pg_sys::TEXTOID), function_name.into_datum())
+        ];
+
+        let rows = client.select(sql, None, Some(args))?;
+        let mut overloads = Vec::new();
+
+        for row in rows {
+            if let Ok(Some(types)) = row[1].value::<String>() {
+                let arg_types: Vec<String> = types
+                    .split(',')
+                    .filter(|t| !t.is_empty())
+                    .map(|t| t.to_string())
+                    .collect();
+                overloads.push(arg_types);
+            }
+        }
+
+        Ok(overloads)
+    }).unwrap_or_default()
+}
+
 /// literal 값과 가장 유사한 실제 DB 값들을 찾는 함수 (캐시 적용)
 /// 가장 가까운 값 하나만 반환 (nearest neighbor)
 pub fn find_similar_values_for_literal(
@@ -1295,7 +2012,9 @@ pub fn find_similar_values_for_literal(
     }
     
     // 1. 먼저 검색 벡터를 가져옴
-    let vector_query = create_combined_vector_query(&search_terms);
+    let Some(vector_query) = create_combined_vector_query(&search_terms) else {
+        return Vec::new();
+    };
     let vector_query_as_text = format!("SELECT ({})::text", vector_query.trim_start_matches("SELECT ").trim_end_matches(" AS v"));
 
     let search_vector: Option<String> = Spi::get_one(&vector_query_as_text)
@@ -1314,7 +2033,7 @@ pub fn find_similar_values_for_literal(
             v.value,
             (v.embedding <=> '{}'::vector) AS distance
         FROM pg_vector_values v
-        WHERE v.schemaname = ANY (current_schemas(false))
+        WHERE v.schemaname = ANY (COALESCE($3::name[], current_schemas(false)))
           AND v.tablename = $1
           AND v.columnname = $2
           AND v.value IS NOT NULL
@@ -1323,13 +2042,14 @@ pub fn find_similar_values_for_literal(
         LIMIT {}
     "#, search_vector, search_vector, top_k);
 
+    count_value_lookup_query();
     let result = Spi::connect(|client| -> Result<Vec<(String, String, String, f32)>, pgrx::spi::Error> {
         let args = vec![
             (PgOid::from(pg_sys::TEXTOID), table_name.into_datum()),
             (PgOid::from(pg_sys::TEXTOID), column_name.into_datum()),
         ];
         
-        let rows = client.select(&sql, None, Some(args))?;
+        let rows = client.select(&sql, None, Some(scope_schemas(args)))?;
         let mut result = Vec::new();
         
         for row in rows {
@@ -1357,6 +2077,219 @@ pub fn find_similar_values_for_literal(
     result
 }
 
+/// find_similar_values_for_literal의 batch 버전 - 입력 순서대로 각 (table, column, literal)의 후보 목록을 반환
+/// 캐시에 없는 tuple들은 VALUES 목록으로 묶어 한 번의 쿼리로 가장 가까운 값을 찾는다 (캐시 키는 단건 버전과 공유)
+pub fn find_similar_values_for_literals(
+    targets: &[(String, String, String)],
+) -> Vec<Vec<(String, String, String, f32)>> {
+    let top_k = TOP_K_EXPANSION.get();
+    let mut results: Vec<Option<Vec<(String, String, String, f32)>>> = vec![None; targets.len()];
+    let mut cache_keys = Vec::with_capacity(targets.len());
+    let mut request_rows = Vec::new();
+
+    for (idx, (table_name, column_name, literal_value)) in targets.iter().enumerate() {
+        let search_terms = prepare_search_terms(literal_value);
+        let cache_key = generate_cache_key(
+            "similar_values",
+            &[table_name, column_name, &search_terms.join("_")]
+        );
+
+        if let Some(cached_result) = get_cached_result(&cache_key) {
+            if let Ok(result) = serde_json::from_str::<Vec<(String, String, String, f32)>>(&cached_result) {
+                results[idx] = Some(result);
+            }
+        }
+
+        if results[idx].is_none() {
+            // 임베딩 한도에 걸리면 캐시에 남기지 않고 이번에는 후보 없음
+            let Some(vector_query) = create_combined_vector_query(&search_terms) else {
+                results[idx] = Some(Vec::new());
+                cache_keys.push(cache_key);
+                continue;
+            };
+            let vector_expr = vector_query.trim_start_matches("SELECT ").trim_end_matches(" AS v");
+            request_rows.push(format!(
+                "({}, '{}', '{}', ({}))",
+                idx,
+                escape_sql_literal(table_name),
+                escape_sql_literal(column_name),
+                vector_expr
+            ));
+        }
+        cache_keys.push(cache_key);
+    }
+
+    if !request_rows.is_empty() {
+        let sql = format!(r#"
+            WITH req (idx, tablename, columnname, qv) AS (
+                VALUES {}
+            )
+            SELECT r.idx, s.value, s.distance
+            FROM req r
+            CROSS JOIN LATERAL (
+                SELECT
+                    v.value,
+                    (v.embedding <=> r.qv) AS distance
+                FROM pg_vector_values v
+                WHERE v.schemaname = ANY (COALESCE($1::name[], current_schemas(false)))
+                  AND v.tablename = r.tablename
+                  AND v.columnname = r.columnname
+                  AND v.value IS NOT NULL
+                  AND v.value <> ''
+                ORDER BY v.embedding <=> r.qv
+                LIMIT {}
+            ) s
+            ORDER BY r.idx, s.distance
+        "#, request_rows.join(", "), top_k);
+
+        count_value_lookup_query();
+        let fetched = Spi::connect(|client| -> Result<Vec<(usize, String, f32)>, pgrx::spi::Error> {
+            let rows = client.select(&sql, None, Some(scope_schemas(Vec::new())))?;
+            let mut fetched = Vec::new();
+
+            for row in rows {
+                if let (Ok(Some(idx)), Ok(Some(value)), Ok(Some(distance))) =
+                    (row["idx"].value::<i32>(), row["value"].value::<String>(), row["distance"].value::<f32>()) {
+                    fetched.push((idx as usize, value, distance));
+                }
+            }
+
+            Ok(fetched)
+        }).unwrap_or_default();
+
+        // 캐시 miss였던 tuple마다 결과를 모아 캐시에 저장 (후보가 없는 tuple도 빈 목록으로 저장)
+        for (idx, slot) in results.iter_mut().enumerate() {
+            if slot.is_some() {
+                continue;
+            }
+            let (table_name, column_name, _) = &targets[idx];
+            let values: Vec<(String, String, String, f32)> = fetched.iter()
+                .filter(|(row_idx, _, _)| *row_idx == idx)
+                .map(|(_, value, distance)| (value.clone(), table_name.clone(), column_name.clone(), *distance))
+                .collect();
+
+            if let Ok(result_json) = serde_json::to_string(&values) {
+                let _ = store_cached_result(&cache_keys[idx], "similar_values", &result_json);
+            }
+            *slot = Some(values);
+        }
+    }
+
+    results.into_iter().map(|result| result.unwrap_or_default()).collect()
+}
+
+/// numeric literal을 step 단위로 맞춘다 (step이 0 이하이면 그대로)
+pub fn snap_to_step(value: f64, step: f64) -> f64 {
+    if step <= 0.0 {
+        return value;
+    }
+    (value / step).round() * step
+}
+
+/// 컬럼에 저장된 숫자 값 중 target에 가장 가까운 값 (값 자체가 숫자로 읽히는 것만 대상)
+pub fn find_nearest_numeric_value(table_name: &str, column_name: &str, target: f64) -> Option<String> {
+    let sql = r#"
+        SELECT v.value
+        FROM pg_vector_values v
+        WHERE v.schemaname = ANY (COALESCE($4::name[], current_schemas(false)))
+          AND v.tablename = $1
+          AND v.columnname = $2
+          AND v.value ~ '^-?[0-9]+(\.[0-9]+)?$'
+        ORDER BY abs(v.value::numeric - $3::numeric), v.value
+        LIMIT 1
+    "#;
+
+    count_value_lookup_query();
+    Spi::connect(|client| -> Result<Option<String>, pgrx::spi::Error> {
+        let args = vec![
+            (PgOid::from(pg_sys::TEXTOID), table_name.into_datum()),
+            (PgOid::from(pg_sys::TEXTOID), column_name.into_datum()),
+            (PgOid::from(pg_sys::FLOAT8OID), target.into_datum()),
+        ];
+
+        let rows = client.select(sql, Some(1), Some(scope_schemas(args)))?;
+        for row in rows {
+            if let Ok(Some(value)) = row["value"].value::<String>() {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }).unwrap_or(None)
+}
+
+/// jsonb 컬럼에 실제로 존재하는 top-level key 목록 (캐시 적용)
+fn list_jsonb_keys(table_name: &str, column_name: &str) -> Vec<String> {
+    let cache_key = generate_cache_key("jsonb_keys", &[table_name, column_name]);
+
+    if let Some(cached_result) = get_cached_result(&cache_key) {
+        if let Ok(result) = serde_json::from_str::<Vec<String>>(&cached_result) {
+            return result;
+        }
+    }
+
+    let sql = format!(
+        "SELECT DISTINCT k FROM (SELECT jsonb_object_keys({col}) AS k FROM {table} WHERE jsonb_typeof({col}) = 'object' LIMIT {limit}) s ORDER BY k",
+        col = pgrx::spi::quote_identifier(column_name),
+        table = pgrx::spi::quote_identifier(table_name),
+        limit = VALUE_REFINEMENT_SAMPLES.get(),
+    );
+
+    let result = Spi::connect(|client| -> Result<Vec<String>, pgrx::spi::Error> {
+        let rows = client.select(&sql, None, None)?;
+        let mut keys = Vec::new();
+
+        for row in rows {
+            if let Ok(Some(key)) = row["k"].value::<String>() {
+                keys.push(key);
+            }
+        }
+
+        Ok(keys)
+    }).unwrap_or_default();
+
+    if let Ok(result_json) = serde_json::to_string(&result) {
+        let _ = store_cached_result(&cache_key, "jsonb_keys", &result_json);
+    }
+
+    result
+}
+
+/// jsonb path 연산자의 key literal과 비슷한 실제 key 찾기
+/// find_similar_values_for_literal 결과 중 실제 key인 것을 우선 쓰고, 나머지 key는 편집 거리로 distance를 매긴다
+/// key가 이미 존재하면 빈 목록 반환
+pub fn find_similar_jsonb_keys(
+    table_name: &str,
+    column_name: &str,
+    key: &str,
+) -> Vec<(String, f32)> {
+    let keys = list_jsonb_keys(table_name, column_name);
+    if keys.is_empty() || keys.iter().any(|k| k == key) {
+        return Vec::new();
+    }
+
+    let mut candidates: Vec<(String, f32)> = find_similar_values_for_literal(table_name, column_name, key)
+        .into_iter()
+        .filter(|(value, _, _, _)| keys.contains(value))
+        .map(|(value, _, _, distance)| (value, distance))
+        .collect();
+
+    for k in &keys {
+        if candidates.iter().any(|(value, _)| value == k) {
+            continue;
+        }
+        let max_len = key.chars().count().max(k.chars().count()).max(1);
+        let distance = edit_distance(&key.to_lowercase(), &k.to_lowercase()) as f32 / max_len as f32;
+        // 절반 이상 바뀌어야 하는 key는 다른 key로 본다
+        if distance <= 0.5 {
+            candidates.push((k.clone(), distance));
+        }
+    }
+
+    candidates.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+    candidates.truncate(TOP_K_EXPANSION.get().max(0) as usize);
+    candidates
+}
+
 /// 오퍼레이터 위치를 나타내는 enum
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum OperandPosition {
@@ -1605,4 +2538,455 @@ pub struct JoinCondition {
     pub left_column: String,  // 기존 테이블의 컬럼 (보통 PK)
     pub right_table: String,  // 새로 JOIN할 테이블
     pub right_column: String, // 새 테이블의 컬럼 (보통 FK)
-}
\ No newline at end of file
+    #[serde(default)]
+    pub constraint_name: Option<String>, // FK 제약조건 이름 (복합 FK의 컬럼들은 같은 이름을 가짐)
+}
+
+/// JOIN 조건들을 FK 제약조건 단위로 묶음
+/// 복합 FK는 모든 컬럼 쌍이 한 그룹이 되고, 제약조건이 없는 조건(이름만 같은 컬럼)은 각각 따로 그룹이 된다
+pub fn group_join_conditions(conditions: &[JoinCondition]) -> Vec<Vec<JoinCondition>> {
+    let mut groups: Vec<Vec<JoinCondition>> = Vec::new();
+    for condition in conditions {
+        let existing = condition.constraint_name.as_ref().and_then(|name| {
+            groups.iter_mut().find(|group| {
+                let first = &group[0];
+                first.constraint_name.as_ref() == Some(name)
+                    && first.left_table.eq_ignore_ascii_case(&condition.left_table)
+                    && first.right_table.eq_ignore_ascii_case(&condition.right_table)
+            })
+        });
+        match existing {
+            Some(group) => group.push(condition.clone()),
+            None => groups.push(vec![condition.clone()]),
+        }
+    }
+    groups
+}
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use super::*;
+    use pgrx::prelude::*;
+    use crate::safeql::fixtures::{reset_vector_catalogs, seed_cached_result};
+
+    #[test]
+    fn test_search_vector_cache_embeds_identical_terms_once() {
+        reset_search_vector_cache();
+        let mut calls = 0;
+
+        let first = cached_search_vector("customer name", || {
+            calls += 1;
+            Some("[0.1,0.2]".to_string())
+        });
+        let second = cached_search_vector("customer  name", || {
+            calls += 1;
+            Some("[0.3,0.4]".to_string())
+        });
+
+        assert_eq!(calls, 1);
+        assert_eq!(first, second);
+
+        // 새 search에서는 다시 임베딩한다
+        reset_search_vector_cache();
+        cached_search_vector("customer name", || {
+            calls += 1;
+            Some("[0.1,0.2]".to_string())
+        });
+        assert_eq!(calls, 2);
+    }
+
+    #[pg_test]
+    fn test_refinement_schemas_scope() {
+        reset_vector_catalogs();
+        Spi::run(
+            "INSERT INTO pg_vector_fields VALUES
+                ('public', 'scope_orders_a', 'amount', 'numeric', 'm', NULL),
+                ('scope_sales', 'scope_orders_b', 'amount', 'numeric', 'm', NULL)",
+        )
+        .unwrap();
+
+        let tables = vec!["scope_orders_a".to_string(), "scope_orders_b".to_string()];
+
+        // 기본값: search_path 기준
+        assert_eq!(find_tables_with_exact_column(&tables, "amount"), vec!["scope_orders_a".to_string()]);
+
+        // 명시적 스키마 목록
+        Spi::run("SET safeql.refinement_schemas = ' scope_sales '").unwrap();
+        assert_eq!(find_tables_with_exact_column(&tables, "amount"), vec!["scope_orders_b".to_string()]);
+
+        Spi::run("SET safeql.refinement_schemas = 'public, scope_sales'").unwrap();
+        assert_eq!(find_tables_with_exact_column(&tables, "amount").len(), 2);
+
+        // 스키마 이름은 SQL에 이어 붙이지 않고 인자로 바인딩한다
+        Spi::run("SET safeql.refinement_schemas = 'o''brien'").unwrap();
+        assert!(find_tables_with_exact_column(&tables, "amount").is_empty());
+    }
+
+    #[pg_test]
+    fn test_exact_match_short_circuit() {
+        reset_vector_catalogs();
+        Spi::run(
+            "INSERT INTO pg_vector_tables VALUES
+                ('public', 'customers', 'm', NULL),
+                ('public', 'customer_notes', 'm', NULL)",
+        )
+        .unwrap();
+
+        // 벡터 결과는 캐시로 대신한다: 이름이 다른 테이블이 더 가깝게 나온다고 가정
+        let cache_key = generate_cache_key(
+            "table_similarity",
+            &["Customers", &TOP_K_EXPANSION.get().to_string()],
+        );
+        let by_vector = vec![
+            ("public.customer_notes".to_string(), 0.05f32),
+            ("public.customers".to_string(), 0.08f32),
+        ];
+        seed_cached_result(&cache_key, "table_similarity", &by_vector);
+
+        let ranked = list_tables_by_similarity("Customers");
+        assert_eq!(ranked[0], ("public.customers".to_string(), 0.0));
+        assert_eq!(ranked[1].0, "public.customer_notes");
+        assert_eq!(ranked.len(), 2, "{:?}", ranked);
+    }
+
+    #[pg_test]
+    fn test_batched_value_lookup_uses_single_query() {
+        reset_vector_catalogs();
+        Spi::run(
+            "INSERT INTO pg_vector_values (schemaname, tablename, columnname, value, embedding) VALUES
+                ('public', 'bv_customers', 'city', 'Seoul', '[1,0]'),
+                ('public', 'bv_customers', 'city', 'Busan', '[0,1]'),
+                ('public', 'bv_orders', 'status', 'shipped', '[1,1]'),
+                ('public', 'bv_orders', 'status', 'pending', '[-1,0]')",
+        )
+        .unwrap();
+
+        // 임베딩 모델 호출 대신 검색 벡터를 미리 넣어둔다
+        SEARCH_VECTOR_CACHE.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            for (term, vector) in [
+                ("seol", "[0.9,0.1]"), ("shiped", "[1,0.9]"), ("nowhere", "[0,1]"),
+                ("busn", "[0.1,0.9]"), ("pendng", "[-1,0.1]"), ("elsewhere", "[1,0]"),
+            ] {
+                cache.insert(term.to_string(), vector.to_string());
+            }
+        });
+        let query_count = || VALUE_LOOKUP_QUERIES.with(|count| count.get());
+        let target = |t: &str, c: &str, l: &str| (t.to_string(), c.to_string(), l.to_string());
+
+        let before = query_count();
+        let batched = find_similar_values_for_literals(&[
+            target("bv_customers", "city", "seol"),
+            target("bv_orders", "status", "shiped"),
+            target("bv_missing", "col", "nowhere"),
+        ]);
+        assert_eq!(query_count() - before, 1);
+
+        assert_eq!(batched.len(), 3);
+        assert_eq!(batched[0][0].0, "Seoul");
+        assert_eq!((batched[0][0].1.as_str(), batched[0][0].2.as_str()), ("bv_customers", "city"));
+        assert_eq!(batched[1][0].0, "shipped");
+        assert!(batched[2].is_empty());
+
+        // 단건 버전은 같은 캐시 키를 쓰므로 추가 쿼리 없이 같은 결과
+        assert_eq!(find_similar_values_for_literal("bv_customers", "city", "seol"), batched[0]);
+        assert_eq!(query_count() - before, 1);
+
+        // 캐시에 없는 literal을 단건으로 찾으면 tuple마다 쿼리한다
+        let before = query_count();
+        assert_eq!(find_similar_values_for_literal("bv_customers", "city", "busn")[0].0, "Busan");
+        assert_eq!(find_similar_values_for_literal("bv_orders", "status", "pendng")[0].0, "pending");
+        assert!(find_similar_values_for_literal("bv_missing", "col", "elsewhere").is_empty());
+        assert_eq!(query_count() - before, 3);
+    }
+
+    #[pg_test]
+    fn test_zero_candidate_lookup_is_negatively_cached() {
+        reset_vector_catalogs();
+        SEARCH_VECTOR_CACHE.with(|cache| cache.borrow_mut().insert("negterm".to_string(), "[1,0]".to_string()));
+        let query_count = || VALUE_LOOKUP_QUERIES.with(|count| count.get());
+
+        // 두 번째 호출은 빈 결과를 캐시에서 받는다
+        let before = query_count();
+        assert!(find_similar_values_for_literal("nc_missing", "col", "negterm").is_empty());
+        assert!(find_similar_values_for_literal("nc_missing", "col", "negterm").is_empty());
+        assert_eq!(query_count() - before, 1);
+
+        // TTL이 지난 negative 엔트리는 다시 조회한다
+        Spi::run("SET vectors.search_cache_negative_ttl = 0").unwrap();
+        assert!(find_similar_values_for_literal("nc_missing", "col", "negterm").is_empty());
+        assert_eq!(query_count() - before, 2);
+    }
+
+    #[pg_test]
+    fn test_max_embedding_calls_caps_new_embeddings() {
+        Spi::run("SET safeql.max_embedding_calls = 2").unwrap();
+        reset_search_vector_cache();
+        let calls = || EMBEDDING_CALLS.with(|count| count.get());
+
+        // 한도 안에서는 임베딩 횟수를 센다
+        assert!(try_count_embedding_call());
+        assert!(try_count_embedding_call());
+        assert_eq!(calls(), 2);
+
+        // 한도에 도달하면 새 검색어는 임베딩하지 않는다
+        assert!(create_combined_vector_query(&["capped".to_string(), "term".to_string()]).is_none());
+        assert!(!try_count_embedding_call());
+        assert_eq!(calls(), 2);
+
+        // search 캐시에 있는 검색어는 계속 쓸 수 있다
+        SEARCH_VECTOR_CACHE.with(|cache| cache.borrow_mut().insert("cached term".to_string(), "[1,0]".to_string()));
+        assert_eq!(
+            create_combined_vector_query(&["cached".to_string(), "term".to_string()]).as_deref(),
+            Some("SELECT '[1,0]'::vector AS v")
+        );
+
+        // similarity lookup은 빈 후보를 돌려주고 캐시에 남기지 않는다
+        let table_key = generate_cache_key("table_similarity", &["capped_table", &TOP_K_EXPANSION.get().to_string()]);
+        assert!(list_tables_by_vector_similarity("capped_table").is_empty());
+        assert!(get_cached_result(&table_key).is_none());
+        assert_eq!(calls(), 2);
+
+        // 다음 search는 다시 0부터
+        reset_search_vector_cache();
+        assert_eq!(calls(), 0);
+        assert!(try_count_embedding_call());
+    }
+
+    #[pg_test]
+    fn test_warm_cache_populates_similarity_entries() {
+        reset_vector_catalogs();
+
+        // 임베딩 모델 호출 대신 검색 벡터를 미리 넣어둔다
+        SEARCH_VECTOR_CACHE.with(|cache| cache.borrow_mut().insert("warm term".to_string(), "[1,0]".to_string()));
+
+        let top_k = TOP_K_EXPANSION.get().to_string();
+        let table_key = generate_cache_key("table_similarity", &["warm_term", &top_k]);
+        let column_key = generate_cache_key("column_similarity", &["NULL", "warm_term", "false", &top_k]);
+        assert!(get_cached_result(&table_key).is_none());
+        assert!(get_cached_result(&column_key).is_none());
+
+        let warmed = warm_similarity_cache(&["warm_term".to_string(), " ".to_string()]);
+        assert_eq!(warmed, vec![("warm_term".to_string(), 0, 0)]);
+
+        assert_eq!(get_cached_result(&table_key).as_deref(), Some("[]"));
+        assert_eq!(get_cached_result(&column_key).as_deref(), Some("[]"));
+    }
+
+    #[pg_test]
+    fn test_max_candidate_distance_drops_far_candidates() {
+        reset_vector_catalogs();
+
+        let top_k = TOP_K_EXPANSION.get().to_string();
+        let table_key = generate_cache_key("table_similarity", &["ordrs", &top_k]);
+        let by_table = vec![
+            ("public.orders".to_string(), 0.12f32),
+            ("public.zebra_sightings".to_string(), 0.85f32),
+        ];
+        seed_cached_result(&table_key, "table_similarity", &by_table);
+
+        let column_key = generate_cache_key("column_similarity", &["orders", "amont", "false", &top_k]);
+        let by_column = vec![
+            ("amount".to_string(), "orders".to_string(), 0.10f32),
+            ("created_at".to_string(), "orders".to_string(), 0.90f32),
+        ];
+        seed_cached_result(&column_key, "column_similarity", &by_column);
+
+        // 기본값은 거리와 상관없이 모두 남긴다
+        assert_eq!(list_tables_by_similarity("ordrs").len(), 2);
+        assert_eq!(list_columns_by_similarity(Some("orders"), "amont", false).len(), 2);
+
+        Spi::run("SET safeql.max_candidate_distance = 0.5").unwrap();
+        assert_eq!(list_tables_by_similarity("ordrs"), vec![("public.orders".to_string(), 0.12f32)]);
+        let columns = list_columns_by_similarity(Some("orders"), "amont", false);
+        assert_eq!(columns, vec![("amount".to_string(), "orders".to_string(), 0.10f32)]);
+    }
+
+    #[pg_test]
+    fn test_column_statistics_rank_selective_candidate_first() {
+        Spi::run("CREATE TABLE stat_sparse (status text)").unwrap();
+        Spi::run("CREATE TABLE stat_dense (status text)").unwrap();
+        // stat_sparse.status는 90%가 NULL이고 값도 하나뿐, stat_dense.status는 모두 다른 값
+        Spi::run("INSERT INTO stat_sparse SELECT CASE WHEN g % 10 = 0 THEN 'x' END FROM generate_series(1, 1000) g")
+            .unwrap();
+        Spi::run("INSERT INTO stat_dense SELECT 'status_' || g FROM generate_series(1, 1000) g").unwrap();
+        Spi::run("ANALYZE stat_sparse, stat_dense").unwrap();
+
+        // 이름이 같은 두 후보가 같은 distance로 나온다고 가정
+        let operator_key = format!("=_ANY_ANY_LEFT_{}_stat", ENABLE_TYPE_BASED_REFINEMENT.get());
+        let cache_key = generate_cache_key("operator_compatible_columns", &["NULL", "stat", &operator_key]);
+        let by_name = vec![
+            ("status".to_string(), "stat_sparse".to_string(), 0.2f32),
+            ("status".to_string(), "stat_dense".to_string(), 0.2f32),
+        ];
+        seed_cached_result(&cache_key, "operator_compatible_columns", &by_name);
+
+        // 기본값은 통계를 보지 않는다
+        let ranked = find_compatible_columns_for_operator(None, "stat", "", OperandPosition::Left);
+        assert_eq!(ranked, by_name);
+
+        Spi::run("SET safeql.column_statistics_weight = 1").unwrap();
+        let ranked = find_compatible_columns_for_operator(None, "stat", "", OperandPosition::Left);
+        assert_eq!(ranked[0].1, "stat_dense", "{:?}", ranked);
+        assert_eq!(ranked[1].1, "stat_sparse");
+        assert!(ranked[1].2 > ranked[0].2);
+    }
+
+    #[pg_test]
+    fn test_type_affinity_outranks_closer_name() {
+        reset_vector_catalogs();
+        Spi::run(
+            "INSERT INTO pg_vector_fields VALUES
+                ('public', 'aff_orders', 'amount_note', 'text', 'm', NULL),
+                ('public', 'aff_orders', 'total', 'numeric', 'm', NULL)",
+        )
+        .unwrap();
+
+        // 임베딩 없이 이름 유사도 결과를 캐시에 넣어둔다: amount_note가 이름상 더 가깝다
+        let cache_key = generate_cache_key(
+            "column_similarity",
+            &["aff_orders", "amont", "false", &TOP_K_EXPANSION.get().to_string()],
+        );
+        let by_name = vec![
+            ("amount_note".to_string(), "aff_orders".to_string(), 0.10f32),
+            ("total".to_string(), "aff_orders".to_string(), 0.25f32),
+        ];
+        seed_cached_result(&cache_key, "column_similarity", &by_name);
+
+        let ranked = list_columns_by_similarity_with_type(Some("aff_orders"), "amont", false, None);
+        assert_eq!(ranked[0].0, "amount_note");
+
+        // 원래 컬럼이 정수와 비교되면 numeric 후보가 앞선다
+        let ranked = list_columns_by_similarity_with_type(Some("aff_orders"), "amont", false, Some("integer"));
+        assert_eq!(ranked[0].0, "total", "{:?}", ranked);
+        assert_eq!(ranked[1].0, "amount_note");
+
+        Spi::run("SET safeql.enable_type_based_refinement = off").unwrap();
+        let ranked = list_columns_by_similarity_with_type(Some("aff_orders"), "amont", false, Some("integer"));
+        assert_eq!(ranked[0].0, "amount_note");
+    }
+
+    #[pg_test]
+    fn test_field_types_follow_schema_scope_order() {
+        reset_vector_catalogs();
+        Spi::run(
+            "INSERT INTO pg_vector_fields VALUES
+                ('public', 'ft_orders', 'amount', 'numeric', 'm', NULL),
+                ('ft_archive', 'ft_orders', 'amount', 'text', 'm', NULL)",
+        )
+        .unwrap();
+        let candidates = vec![("amount".to_string(), "ft_orders".to_string(), 0.1f32)];
+
+        Spi::run("SET safeql.refinement_schemas = 'public, ft_archive'").unwrap();
+        let field_types = lookup_field_types(&candidates);
+        assert_eq!(field_types.get("ft_orders", "amount").map(String::as_str), Some("numeric"));
+
+        // 같은 이름의 테이블이 여러 스키마에 있으면 범위에서 먼저 나오는 스키마의 타입을 쓴다
+        Spi::run("SET safeql.refinement_schemas = 'ft_archive, public'").unwrap();
+        let field_types = lookup_field_types(&candidates);
+        assert_eq!(field_types.get("ft_orders", "amount").map(String::as_str), Some("text"));
+    }
+
+    #[pg_test]
+    fn test_misspelled_aggregate_prefers_aggregates() {
+        // 이름 유사도만 보면 abs가 가장 가깝다고 가정
+        let cache_key = generate_cache_key(
+            "similar_functions",
+            &["avgg", "1", &TOP_K_EXPANSION.get().to_string()],
+        );
+        let by_name = vec![
+            ("abs".to_string(), vec!["numeric".to_string()], "numeric".to_string(), 0.10f32),
+            ("sum".to_string(), vec!["numeric".to_string()], "numeric".to_string(), 0.30f32),
+            ("avg".to_string(), vec!["numeric".to_string()], "numeric".to_string(), 0.35f32),
+        ];
+        seed_cached_result(&cache_key, "similar_functions", &by_name);
+
+        let ranked: Vec<String> = find_similar_functions_with_aggregates("avgg", 1, None)
+            .into_iter()
+            .map(|(name, _, _, _)| name)
+            .collect();
+        assert_eq!(ranked, vec!["avg", "sum", "abs"]);
+
+        // 집계로 보이지 않는 이름은 그대로
+        assert_eq!(intended_aggregate("lenght", 1), None);
+        assert_eq!(intended_aggregate("maxx", 1), Some("max"));
+        // 세 글자 집계와 2글자 차이인 다른 함수는 집계로 바꾸지 않는다
+        assert_eq!(intended_aggregate("mod", 1), None);
+        assert_eq!(intended_aggregate("abs", 1), None);
+        assert_eq!(intended_aggregate("len", 1), None);
+        assert_eq!(intended_aggregate("coutn", 1), Some("count"));
+    }
+
+    #[pg_test]
+    fn test_join_require_fk_drops_natural_joinables() {
+        Spi::run("CREATE TABLE jf_customers (id INT PRIMARY KEY, name TEXT)").unwrap();
+        Spi::run(
+            "CREATE TABLE jf_orders (id INT PRIMARY KEY, customer_id INT REFERENCES jf_customers(id), region TEXT)",
+        )
+        .unwrap();
+        // FK 없이 region 이름만 같은 테이블
+        Spi::run("CREATE TABLE jf_shipments (id INT PRIMARY KEY, region TEXT)").unwrap();
+
+        let joinable_tables = || -> Vec<String> {
+            find_all_joinable_tables(&["jf_orders".to_string()])
+                .into_iter()
+                .map(|(table, _, _)| table)
+                .collect()
+        };
+
+        let tables = joinable_tables();
+        assert!(tables.contains(&"jf_customers".to_string()), "{:?}", tables);
+        assert!(tables.contains(&"jf_shipments".to_string()), "{:?}", tables);
+
+        Spi::run("SET safeql.join_require_fk = on").unwrap();
+        let tables = joinable_tables();
+        assert_eq!(tables, vec!["jf_customers".to_string()]);
+    }
+
+    #[pg_test]
+    fn test_adding_fk_invalidates_join_cache() {
+        Spi::run("CREATE TABLE jc_customers (id INT PRIMARY KEY, name TEXT)").unwrap();
+        Spi::run("CREATE TABLE jc_orders (id INT PRIMARY KEY, buyer INT)").unwrap();
+        Spi::run("SET safeql.join_require_fk = on").unwrap();
+
+        let joinable_tables = || -> Vec<String> {
+            find_all_joinable_tables(&["jc_orders".to_string()])
+                .into_iter()
+                .map(|(table, _, _)| table)
+                .collect()
+        };
+
+        // FK가 없으므로 빈 결과가 캐시된다
+        let token_before = constraint_generation_token();
+        assert!(joinable_tables().is_empty());
+
+        Spi::run("ALTER TABLE jc_orders ADD CONSTRAINT jc_orders_buyer_fk FOREIGN KEY (buyer) REFERENCES jc_customers(id)").unwrap();
+
+        // constraint 변경으로 token이 바뀌어 캐시된 빈 결과를 쓰지 않는다
+        assert_ne!(constraint_generation_token(), token_before);
+        assert_eq!(joinable_tables(), vec!["jc_customers".to_string()]);
+    }
+
+    #[pg_test]
+    fn test_table_ddl_without_vectors_search_path() {
+        let token_before = constraint_generation_token();
+
+        // vectors 스키마가 search_path에 없어도 event trigger 때문에 DDL이 실패하지 않는다
+        Spi::run("SET search_path = public").unwrap();
+        Spi::run("CREATE TABLE public.sp_items (id INT)").unwrap();
+        Spi::run("ALTER TABLE public.sp_items ADD COLUMN name TEXT").unwrap();
+
+        assert_ne!(constraint_generation_token(), token_before);
+    }
+
+    #[pg_test]
+    fn test_first_ddl_after_install_changes_generation_token() {
+        // 설치 직후처럼 sequence가 아직 한 번도 증가하지 않은 상태
+        Spi::run("ALTER SEQUENCE vectors.pg_vector_constraint_generation RESTART").unwrap();
+        let token_before = constraint_generation_token();
+
+        Spi::run("CREATE TABLE fg_items (id INT)").unwrap();
+
+        assert_ne!(constraint_generation_token(), token_before);
+    }
+}
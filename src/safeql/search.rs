@@ -1,10 +1,11 @@
+use std::cell::RefCell;
 use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashSet};
 use std::ffi::{CString, CStr};
 use std::os::raw::c_void;
 
 use pgrx::spi::Spi;
-use pgrx::pg_sys::panic::CaughtError;
+use pgrx::pg_sys::panic::{CaughtError, ErrorReport};
 use pgrx::{memcx, pg_sys, prelude::*};
 use pgrx::list::List;
 use pgrx::nodes::node_to_string;
@@ -12,11 +13,18 @@ use regex::Regex;
 
 use super::score::{
     extract_function_info_from_error,
+    extract_operator_info_from_error,
     check_function_exists,
+    find_function_overload_arg_types,
+    is_set_returning_function,
+    reset_search_vector_cache,
+    OperandPosition,
+    OperatorInfo,
 };
 
 use super::refine::{
     safe_raw_expression_tree_walker,
+    extract_all_tables_from_raw,
     generate_table_refinements_for_all_from_tables_raw,
     generate_table_refinements_raw,
     generate_column_refinements_raw,
@@ -25,19 +33,38 @@ use super::refine::{
     generate_column_table_reference_refinements_raw,
     generate_one_hop_join_refinements_for_all_tables_raw,
     generate_join_refinements_raw,
+    generate_missing_from_entry_refinements_raw,
     generate_function_argument_column_refinements_raw,
     generate_function_typecast_refinements_raw,
     generate_function_name_refinements_raw,
+    is_call_in_having_clause,
     generate_argument_format_refinements_raw,
     generate_operand_column_refinements_raw,
     generate_operand_typecast_refinements_raw,
     generate_column_ambiguity_refinements_raw,
     generate_value_refinements_raw,
+    generate_nullif_guard_refinements_raw,
+    generate_jsonb_operator_refinements_raw,
+    generate_jsonb_key_refinements_raw,
+    generate_null_handling_refinements_raw,
+    generate_srf_lateral_refinements_raw,
+    generate_window_over_refinements_raw,
+    generate_function_arity_refinements_raw,
+    generate_order_by_target_refinements_raw,
+    generate_ordinal_position_refinements_raw,
+    generate_case_branch_cast_refinements_raw,
+    is_aggregate_select,
+    literal_type_name,
     find_all_where_expressions,
     extract_operator_info_from_expr,
+    is_any_all_expr,
+    any_all_sublink,
     ColumnOperand,
+    LiteralOperand,
 };
 use super::utils::copy_node;
+use super::metrics::{record_execution_attempt, record_execution_success, record_search_result};
+use super::diff::{diff_raw_stmts, changes_to_json};
 use crate::softql::deparse_raw_stmt;
 use crate::gucs::parser::{
     ENABLE_SAFEQL_REFINEMENT,
@@ -55,12 +82,32 @@ use crate::gucs::parser::{
     ENABLE_FUNCTION_NAME_REFINEMENT,
     ENABLE_COLUMN_AMBIGUITY_REFINEMENT,
     ENABLE_VALUE_REFINEMENT,
+    ENABLE_NULLIF_GUARD_REFINEMENT,
+    ENABLE_JSONB_OPERATOR_REFINEMENT,
+    ENABLE_NULL_HANDLING_REFINEMENT,
+    ENABLE_SRF_LATERAL_REFINEMENT,
+    ENABLE_WINDOW_OVER_REFINEMENT,
+    ENABLE_FUNCTION_ARITY_REFINEMENT,
+    ENABLE_ORDER_BY_TARGET_REFINEMENT,
+    ENABLE_ORDINAL_POSITION_REFINEMENT,
+    ENABLE_CASE_BRANCH_CAST_REFINEMENT,
+    SAFEQL_CONSERVATIVE,
+    ENABLE_EXECUTION_VALIDATION,
     MAX_REFINEMENT_HOP,
     MAX_REFINEMENT_NUM,
+    MAX_CANDIDATE_SQL_LEN,
+    CANDIDATE_STATEMENT_TIMEOUT,
+    CARDINALITY_CHANGE_PENALTY,
+    ENABLE_SEARCH_SUMMARY_HINT,
+    ENABLE_DEPARSE_ROUND_TRIP_CHECK,
+    ENABLE_REFINEMENT_DIFF_NOTICE,
+    ENABLE_REFINED_PLAN_NOTICE,
+    refinement_case_order,
+    refinement_enabled_errors,
 };
 
 #[derive(Debug, Clone)]
-enum AnalyzeOutcome {
+pub(super) enum AnalyzeOutcome {
     Success(*mut pg_sys::Query),
     Failure { 
         code: Option<PgSqlErrorCode>, 
@@ -70,18 +117,119 @@ enum AnalyzeOutcome {
 }
 
 enum ExecutionOutcome {
-    Success,
+    /// row_count: SELECT 결과 행 수 (UPDATE/DELETE는 대상 행 수, SELECT로 옮길 수 없는 UPDATE/DELETE와 INSERT는 None)
+    Success { row_count: Option<i64> },
     ExecutionError(String),
     EmptyResult,
     ArgumentFormatError { message: String },
+    DivisionByZero { message: String },
+    SetReturningFunctionError { message: String },
+}
+
+/// search가 원본을 그대로 돌려준 이유
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchStopReason {
+    /// 모든 후보를 다 시도함
+    Exhausted,
+    /// MAX_REFINEMENT_NUM 도달
+    CandidateLimit,
+}
+
+/// refinement 실패 시 HINT로 보여줄 search 요약
+#[derive(Debug, Clone, Copy)]
+struct SearchSummary {
+    candidates_tried: i32,
+    hop_limited: i32,
+    stop_reason: SearchStopReason,
+}
+
+impl SearchSummary {
+    fn hint(&self) -> String {
+        let reason = match self.stop_reason {
+            SearchStopReason::Exhausted => "all candidates were exhausted".to_string(),
+            SearchStopReason::CandidateLimit => format!(
+                "the candidate limit was reached (safeql.max_refinement_num = {})",
+                MAX_REFINEMENT_NUM.get()
+            ),
+        };
+        let mut hint = format!(
+            "SafeQL tried {} refinement candidate(s) and stopped because {}.",
+            self.candidates_tried, reason
+        );
+        if self.hop_limited > 0 {
+            hint.push_str(&format!(
+                " {} candidate(s) were skipped for exceeding safeql.max_refinement_hop = {}.",
+                self.hop_limited,
+                MAX_REFINEMENT_HOP.get()
+            ));
+        }
+        hint
+    }
+}
+
+/// case 순서 한 칸마다 더하는 priority (같은 hop 안에서는 case 순서가 distance보다 앞선다)
+const CASE_ORDER_PRIORITY_STEP: i32 = 1000;
+
+thread_local! {
+    /// search 시작 시 파싱한 safeql.refinement_case_order (None이면 모든 case를 기본 순서로)
+    static CASE_ORDER: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// safeql.refinement_case_order를 읽어 이후 case_enabled/case_priority가 쓰도록 설정
+pub(super) fn load_case_order() {
+    CASE_ORDER.with(|order| *order.borrow_mut() = refinement_case_order());
+}
+
+/// case를 시도할지: 개별 enable 설정이 켜져 있고, case 순서가 설정돼 있으면 목록에 있어야 한다
+pub(super) fn case_enabled(case: &str, enabled: bool) -> bool {
+    enabled && CASE_ORDER.with(|order| {
+        order.borrow().as_ref().is_none_or(|cases| cases.iter().any(|c| c == case))
+    })
+}
+
+/// case 순서에서 뒤에 있는 case의 후보일수록 priority를 뒤로 민다 (순서 미설정이면 그대로)
+fn case_priority(case: &str, prio: i32) -> i32 {
+    let rank = CASE_ORDER.with(|order| {
+        order.borrow().as_ref().and_then(|cases| cases.iter().position(|c| c == case))
+    });
+    prio + rank.unwrap_or(0) as i32 * CASE_ORDER_PRIORITY_STEP
+}
+
+/// safeql.refinement_enabled_errors에서 쓰는 에러 이름
+fn refinement_error_name(code: PgSqlErrorCode) -> Option<&'static str> {
+    match code {
+        PgSqlErrorCode::ERRCODE_UNDEFINED_TABLE => Some("undefined_table"),
+        PgSqlErrorCode::ERRCODE_UNDEFINED_COLUMN => Some("undefined_column"),
+        PgSqlErrorCode::ERRCODE_UNDEFINED_FUNCTION => Some("undefined_function"),
+        PgSqlErrorCode::ERRCODE_AMBIGUOUS_COLUMN => Some("ambiguous_column"),
+        PgSqlErrorCode::ERRCODE_GROUPING_ERROR => Some("grouping"),
+        PgSqlErrorCode::ERRCODE_INVALID_COLUMN_REFERENCE => Some("invalid_column_reference"),
+        PgSqlErrorCode::ERRCODE_WRONG_OBJECT_TYPE => Some("wrong_object_type"),
+        PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED => Some("feature_not_supported"),
+        PgSqlErrorCode::ERRCODE_INVALID_TEXT_REPRESENTATION => Some("invalid_text_representation"),
+        PgSqlErrorCode::ERRCODE_DIVISION_BY_ZERO => Some("division_by_zero"),
+        PgSqlErrorCode::ERRCODE_DATATYPE_MISMATCH => Some("datatype_mismatch"),
+        _ => None,
+    }
+}
+
+/// 에러 코드에 대해 refinement를 시도할지 (목록 미설정이면 항상, 개별 case는 각자의 enable 설정을 따로 본다)
+pub(super) fn error_refinement_enabled(code: Option<PgSqlErrorCode>) -> bool {
+    match refinement_enabled_errors() {
+        None => true,
+        Some(errors) => code
+            .and_then(refinement_error_name)
+            .is_some_and(|name| errors.iter().any(|error| error == name)),
+    }
 }
 
 /// Expression들에 대해 operand refinement를 수행하는 공통 함수
 fn process_operand_refinements_for_expressions(
     cand_raw: *mut pg_sys::RawStmt,
     expressions: Vec<*mut pg_sys::Node>,
+    error_operator_info: Option<&OperatorInfo>,
     current_prio: i32,
-    pq: &mut BinaryHeap<Reverse<(i32, u64, *mut pg_sys::RawStmt, i32)>>,
+    pq: &mut BinaryHeap<Reverse<(i32, String, u64, *mut pg_sys::RawStmt, i32)>>,
     visited: &mut HashSet<String>,
     current_hop_count: i32,
     seq: &mut u64,
@@ -90,9 +238,9 @@ fn process_operand_refinements_for_expressions(
     
     for expr_node in expressions {
         unsafe {
-            if let Some((left_operand, right_operand)) = extract_column_operands_from_expr(expr_node) {
+            if let Some((left_operand, right_operand)) = extract_column_operands_from_expr(expr_node, error_operator_info) {
                 // 1. Column refinement
-                if ENABLE_OPERAND_COLUMN_REFINEMENT.get() {
+                if case_enabled("operand_column", ENABLE_OPERAND_COLUMN_REFINEMENT.get()) {
                     let operand_column_refinements = generate_operand_column_refinements_raw(
                         cand_raw,
                         &left_operand,
@@ -101,13 +249,13 @@ fn process_operand_refinements_for_expressions(
                     );
                     
                     for (new_prio, refined_raw) in operand_column_refinements {
-                        push_candidate(pq, visited, new_prio, current_hop_count + 1, seq, refined_raw);
+                        push_candidate(pq, visited, case_priority("operand_column", new_prio), current_hop_count + 1, seq, refined_raw);
                         refinements_added = true;
                     }
                 }
                 
                 // 2. Left operand에 대한 table/reference refinements
-                if let Some(ColumnOperand { table_name, column_name }) = &left_operand {
+                if let Some(ColumnOperand { table_name, column_name, .. }) = &left_operand {
                     refinements_added |= process_operand_table_refinements(
                         cand_raw, table_name, column_name, current_prio,
                         pq, visited, current_hop_count, seq
@@ -115,7 +263,7 @@ fn process_operand_refinements_for_expressions(
                 }
                 
                 // 3. Right operand에 대한 table/reference refinements
-                if let Some(ColumnOperand { table_name, column_name }) = &right_operand {
+                if let Some(ColumnOperand { table_name, column_name, .. }) = &right_operand {
                     refinements_added |= process_operand_table_refinements(
                         cand_raw, table_name, column_name, current_prio,
                         pq, visited, current_hop_count, seq
@@ -123,18 +271,21 @@ fn process_operand_refinements_for_expressions(
                 }
                 
                 // 4. Typecast refinement
-                if ENABLE_OPERAND_TYPECAST_REFINEMENT.get() {
+                if case_enabled("operand_typecast", ENABLE_OPERAND_TYPECAST_REFINEMENT.get()) {
                     let operator_info = extract_operator_info_from_expr(expr_node);
+                    let literal_operand = error_operator_info
+                        .and_then(|info| extract_literal_operand_from_expr(expr_node, info));
                     let operand_typecast_refinements = generate_operand_typecast_refinements_raw(
                         cand_raw,
                         &left_operand,
                         &right_operand,
+                        literal_operand.as_ref(),
                         &operator_info,
                         current_prio
                     );
                     
                     for (new_prio, refined_raw) in operand_typecast_refinements {
-                        push_candidate(pq, visited, new_prio, current_hop_count + 1, seq, refined_raw);
+                        push_candidate(pq, visited, case_priority("operand_typecast", new_prio), current_hop_count + 1, seq, refined_raw);
                         refinements_added = true;
                     }
                 }
@@ -151,7 +302,7 @@ fn process_operand_table_refinements(
     table_name: &Option<String>,
     column_name: &str,
     current_prio: i32,
-    pq: &mut BinaryHeap<Reverse<(i32, u64, *mut pg_sys::RawStmt, i32)>>,
+    pq: &mut BinaryHeap<Reverse<(i32, String, u64, *mut pg_sys::RawStmt, i32)>>,
     visited: &mut HashSet<String>,
     current_hop_count: i32,
     seq: &mut u64,
@@ -159,7 +310,7 @@ fn process_operand_table_refinements(
     let mut refinements_added = false;
     
     // Table-for-column refinement
-    if ENABLE_OPERAND_TABLE_FOR_COLUMN_REFINEMENT.get() {
+    if case_enabled("operand_table_for_column", ENABLE_OPERAND_TABLE_FOR_COLUMN_REFINEMENT.get()) {
         if let Some(ref table_name_str) = table_name {
             let refinements = generate_table_refinements_for_column_error_raw(
                 cand_raw,
@@ -168,7 +319,10 @@ fn process_operand_table_refinements(
             );
             
             for (new_prio, refined_raw) in refinements {
-                push_candidate(pq, visited, new_prio, current_hop_count + 1, seq, refined_raw);
+                if conservative_rejects_from_growth(cand_raw, refined_raw) {
+                    continue;
+                }
+                push_candidate(pq, visited, case_priority("operand_table_for_column", new_prio), current_hop_count + 1, seq, refined_raw);
                 refinements_added = true;
             }
         } else {
@@ -178,14 +332,17 @@ fn process_operand_table_refinements(
             );
             
             for (new_prio, refined_raw) in refinements {
-                push_candidate(pq, visited, new_prio, current_hop_count + 1, seq, refined_raw);
+                if conservative_rejects_from_growth(cand_raw, refined_raw) {
+                    continue;
+                }
+                push_candidate(pq, visited, case_priority("operand_table_for_column", new_prio), current_hop_count + 1, seq, refined_raw);
                 refinements_added = true;
             }
         }
     }
     
     // Column table reference refinement
-    if ENABLE_OPERAND_COLUMN_TABLE_REFERENCE_REFINEMENT.get() {
+    if case_enabled("operand_column_table_reference", ENABLE_OPERAND_COLUMN_TABLE_REFERENCE_REFINEMENT.get()) {
         if table_name.is_some() {
             let refinements = generate_column_table_reference_refinements_raw(
                 cand_raw,
@@ -195,7 +352,7 @@ fn process_operand_table_refinements(
             );
             
             for (new_prio, refined_raw) in refinements {
-                push_candidate(pq, visited, new_prio, current_hop_count + 1, seq, refined_raw);
+                push_candidate(pq, visited, case_priority("operand_column_table_reference", new_prio), current_hop_count + 1, seq, refined_raw);
                 refinements_added = true;
             }
         }
@@ -204,19 +361,35 @@ fn process_operand_table_refinements(
     refinements_added
 }
 
+/// JOIN/테이블 추가 refinement 사용 여부 (safeql.conservative면 항상 false)
+pub(super) fn join_refinement_enabled() -> bool {
+    ENABLE_JOIN_REFINEMENT.get() && !SAFEQL_CONSERVATIVE.get()
+}
+
+/// set-returning 호출을 LATERAL FROM item으로 옮기는 refinement 사용 여부 (FROM을 늘리므로 safeql.conservative면 false)
+pub(super) fn srf_lateral_refinement_enabled() -> bool {
+    case_enabled("srf_lateral", ENABLE_SRF_LATERAL_REFINEMENT.get()) && !SAFEQL_CONSERVATIVE.get()
+}
+
+/// safeql.conservative에서 FROM 항목을 늘리는 후보인지 (rename만 허용)
+fn conservative_rejects_from_growth(orig: *mut pg_sys::RawStmt, refined: *mut pg_sys::RawStmt) -> bool {
+    SAFEQL_CONSERVATIVE.get()
+        && extract_all_tables_from_raw(refined).len() > extract_all_tables_from_raw(orig).len()
+}
+
 /// Column들에 대해 JOIN refinement를 수행하는 공통 함수
 fn process_join_refinements_for_columns(
     cand_raw: *mut pg_sys::RawStmt,
     columns: Vec<String>,
     current_prio: i32,
-    pq: &mut BinaryHeap<Reverse<(i32, u64, *mut pg_sys::RawStmt, i32)>>,
+    pq: &mut BinaryHeap<Reverse<(i32, String, u64, *mut pg_sys::RawStmt, i32)>>,
     visited: &mut HashSet<String>,
     current_hop_count: i32,
     seq: &mut u64,
 ) -> bool {
     let mut refinements_added = false;
     
-    if ENABLE_JOIN_REFINEMENT.get() {
+    if case_enabled("join", join_refinement_enabled()) {
         for column_name in columns {
             let join_refinements = generate_join_refinements_raw(
                 cand_raw,
@@ -225,7 +398,7 @@ fn process_join_refinements_for_columns(
             );
             
             for (new_prio, refined_raw) in join_refinements {
-                push_candidate(pq, visited, new_prio, current_hop_count + 1, seq, refined_raw);
+                push_candidate(pq, visited, case_priority("join", new_prio), current_hop_count + 1, seq, refined_raw);
                 refinements_added = true;
             }
         }
@@ -234,12 +407,551 @@ fn process_join_refinements_for_columns(
     refinements_added
 }
 
+/// jsonb path 연산자(`->`/`->>`)를 교체하는 후보를 넣는 공통 함수
+fn push_jsonb_operator_refinements(
+    cand_raw: *mut pg_sys::RawStmt,
+    current_prio: i32,
+    pq: &mut BinaryHeap<Reverse<(i32, String, u64, *mut pg_sys::RawStmt, i32)>>,
+    visited: &mut HashSet<String>,
+    current_hop_count: i32,
+    seq: &mut u64,
+) -> bool {
+    let mut refinements_added = false;
+
+    if case_enabled("jsonb_operator", ENABLE_JSONB_OPERATOR_REFINEMENT.get()) {
+        pgrx::notice!("SafeQL: Executing jsonb operator refinement");
+
+        let jsonb_refinements = generate_jsonb_operator_refinements_raw(cand_raw, current_prio);
+        for (new_prio, refined_raw) in jsonb_refinements {
+            push_candidate(pq, visited, case_priority("jsonb_operator", new_prio), current_hop_count + 1, seq, refined_raw);
+            refinements_added = true;
+        }
+    }
+
+    refinements_added
+}
+
+
+/// 허용되지 않는 위치의 set-returning 함수를 LATERAL FROM item으로 옮기는 후보를 넣는 공통 함수
+/// 에러 위치의 호출을 우선하고, 위치를 모르면 set-returning 함수인 호출 모두를 대상으로 한다
+fn push_srf_lateral_refinements(
+    cand_raw: *mut pg_sys::RawStmt,
+    cursor_pos: Option<i32>,
+    current_prio: i32,
+    pq: &mut BinaryHeap<Reverse<(i32, String, u64, *mut pg_sys::RawStmt, i32)>>,
+    visited: &mut HashSet<String>,
+    current_hop_count: i32,
+    seq: &mut u64,
+) -> bool {
+    if !srf_lateral_refinement_enabled() {
+        return false;
+    }
+    pgrx::notice!("SafeQL: Executing set-returning function refinement");
+
+    let mut call_positions: Vec<i32> = match cursor_pos {
+        Some(error_pos) => unsafe { find_function_calls_at_position(cand_raw, error_pos) }
+            .into_iter()
+            .take(1)
+            .map(|(_, call_pos)| call_pos)
+            .collect(),
+        None => Vec::new(),
+    };
+    if call_positions.is_empty() {
+        call_positions = unsafe { find_set_returning_function_calls(cand_raw) };
+    }
+
+    let mut refinements_added = false;
+    let srf_refinements = generate_srf_lateral_refinements_raw(cand_raw, &call_positions, current_prio);
+    for (new_prio, refined_raw) in srf_refinements {
+        push_candidate(pq, visited, case_priority("srf_lateral", new_prio), current_hop_count + 1, seq, refined_raw);
+        refinements_added = true;
+    }
+
+    refinements_added
+}
+
+
+/// analyze 에러 메시지에 맞는 refinement case들의 후보를 PQ에 넣는다
+/// (refinement case가 에러를 처리했으면 true)
+fn push_analyze_error_refinements(
+    cand_raw: *mut pg_sys::RawStmt,
+    code: Option<PgSqlErrorCode>,
+    message: &str,
+    cursor_pos: Option<i32>,
+    current_prio: i32,
+    pq: &mut BinaryHeap<Reverse<(i32, String, u64, *mut pg_sys::RawStmt, i32)>>,
+    visited: &mut HashSet<String>,
+    current_hop_count: i32,
+    seq: &mut u64,
+) -> bool {
+    if !error_refinement_enabled(code) {
+        pgrx::notice!("SafeQL: Refinement is disabled for this error by safeql.refinement_enabled_errors: {}", message);
+        return false;
+    }
+
+    match code {
+        Some(PgSqlErrorCode::ERRCODE_UNDEFINED_TABLE) => {
+            let mut refinements_added = false;
+
+            // CASE 1-1) missing FROM-clause entry - 참조된 테이블을 rename보다 먼저 JOIN으로 추가
+            if case_enabled("missing_from_join", join_refinement_enabled()) {
+                if let Some(missing_ref) = extract_missing_from_clause_entry(message) {
+                    let join_refinements = generate_missing_from_entry_refinements_raw(
+                        cand_raw,
+                        &missing_ref,
+                        current_prio
+                    );
+
+                    for (new_prio, refined_raw) in join_refinements {
+                        push_candidate(pq, visited, case_priority("missing_from_join", new_prio), current_hop_count + 1, seq, refined_raw);
+                        refinements_added = true;
+                    }
+                }
+            }
+
+            // CASE 1) FROM Rel -> FROM Rel' - 테이블 refinement 수행
+            if case_enabled("table", ENABLE_TABLE_REFINEMENT.get()) {
+                if let Some(missing_rel) = extract_missing_relation(message) {
+                    pgrx::notice!("SafeQL: Executing table refinement for missing table '{}'", missing_rel);
+                    // 후보 테이블들을 RawStmt 변형으로 생성해서 PQ에 넣는다
+                    let refinements = generate_table_refinements_raw(
+                        cand_raw, 
+                        &missing_rel,
+                        current_prio
+                    );
+
+                    for (new_prio, refined_raw) in refinements {
+                        push_candidate(pq, visited, case_priority("table", new_prio), current_hop_count + 1, seq, refined_raw);
+                    }
+                    refinements_added = true;
+                }
+            }
+
+            if refinements_added {
+                return true;
+            }
+        },
+        Some(PgSqlErrorCode::ERRCODE_UNDEFINED_COLUMN) => {
+            if let Some((table_name, missing_col)) = extract_missing_column(message) {
+                let mut refinements_added = false;
+
+                // CASE 2) SELECT Att -> SELECT Att' - 칼럼 refinement 수행
+                if case_enabled("column", ENABLE_COLUMN_REFINEMENT.get()) {
+                    pgrx::notice!("SafeQL: Executing column refinement for missing column '{}'", missing_col);
+                    let column_refinements = generate_column_refinements_raw(
+                        cand_raw,
+                        table_name.as_deref(),
+                        &missing_col,
+                        current_prio 
+                    );
+                    for (new_prio, refined_raw) in column_refinements {
+                        push_candidate(pq, visited, case_priority("column", new_prio), current_hop_count + 1, seq, refined_raw);
+                        refinements_added = true;
+                    }
+                }
+
+                // CASE 3) FROM Rel -> FROM Rel' - 칼럼이 참조하는 테이블 refinement 수행
+                if case_enabled("table_for_column", ENABLE_TABLE_FOR_COLUMN.get()) {
+                    if let Some(ref table_name_str) = table_name {
+                        // 1) 테이블 이름이 명확한 경우, 해당 테이블에 대해서만 refinement 시도
+                        let table_refinements = generate_table_refinements_for_column_error_raw(
+                            cand_raw,
+                            table_name_str,
+                            current_prio 
+                        );
+                        
+                        for (new_prio, refined_raw) in table_refinements {
+                            if conservative_rejects_from_growth(cand_raw, refined_raw) {
+                                continue;
+                            }
+                            push_candidate(pq, visited, case_priority("table_for_column", new_prio), current_hop_count + 1, seq, refined_raw);
+                            refinements_added = true;
+                        }
+                    } else {
+                        // 2) 테이블 이름이 특정되지 않은 경우, FROM절의 모든 테이블에 대해 refinement 시도
+                        let all_table_refinements = generate_table_refinements_for_all_tables_raw(
+                            cand_raw,
+                            current_prio
+                        );
+                        
+                        for (new_prio, refined_raw) in all_table_refinements {
+                            if conservative_rejects_from_growth(cand_raw, refined_raw) {
+                                continue;
+                            }
+                            push_candidate(pq, visited, case_priority("table_for_column", new_prio), current_hop_count + 1, seq, refined_raw);
+                            refinements_added = true;
+                        }
+                    }
+                }
+
+                // CASE 4) FROM Rel WHERE col ... -> FROM Rel Rel2 WHERE ... - column reference refinement
+                if case_enabled("column_table_reference", ENABLE_COLUMN_TABLE_REFERENCE.get()) {
+                    if table_name.is_some() {
+                        let reference_refinements = generate_column_table_reference_refinements_raw(
+                            cand_raw,
+                            table_name.as_deref(),
+                            &missing_col,
+                            current_prio
+                        );
+                        
+                        for (new_prio, refined_raw) in reference_refinements {
+                            push_candidate(pq, visited, case_priority("column_table_reference", new_prio), current_hop_count + 1, seq, refined_raw);
+                            refinements_added = true;
+                        }
+                    }
+                }
+
+                // CASE 5) JOIN refinement
+                refinements_added |= process_join_refinements_for_columns(
+                    cand_raw,
+                    vec![missing_col.clone()],
+                    current_prio,
+                    pq,
+                    visited,
+                    current_hop_count,
+                    seq
+                );
+
+                if refinements_added {
+                    return true;
+                }
+            }
+        },
+        Some(PgSqlErrorCode::ERRCODE_UNDEFINED_FUNCTION) => {
+            // CASE 6) Function does not exist - function argument refinement 수행
+            if let Some((function_name, arg_types)) = extract_missing_function(message) {
+                if let Some(error_pos) = cursor_pos {
+                    let mut refinements_added = false;
+
+                    // 에러 위치에서 문제가 된 함수 호출 찾기
+                    let problematic_function_calls = unsafe { find_function_calls_at_position(cand_raw, error_pos) };
+                    // 함수 정보 추출
+                    let function_info = extract_function_info_from_error(message);
+
+                    for (call_index, (func_call_node, call_pos)) in problematic_function_calls.into_iter().enumerate() {
+                        // 첫 번째 호출은 에러 메시지의 함수, 나머지는 그 인자 안에 중첩된 호출
+                        let is_error_call = call_index == 0;
+                        let (call_name, call_arg_types) = if is_error_call {
+                            (function_name.clone(), arg_types.clone())
+                        } else {
+                            match unsafe { function_call_name(func_call_node) } {
+                                Some(name) => (name, unsafe { function_call_arg_types(func_call_node) }),
+                                None => continue,
+                            }
+                        };
+
+                        unsafe {
+                            if check_function_exists(&call_name) {
+                                if let Some(argument_operands) = extract_argument_operands_from_function_call(func_call_node) {
+                                    if case_enabled("argument_column", ENABLE_ARGUMENT_COLUMN_REFINEMENT.get()) {
+                                        let argument_column_refinements = generate_function_argument_column_refinements_raw(
+                                            cand_raw,
+                                            &argument_operands,
+                                            call_pos,
+                                            &call_name,
+                                            &call_arg_types,
+                                            current_prio
+                                        );
+                                        
+                                        for (new_prio, refined_raw) in argument_column_refinements {
+                                            push_candidate(pq, visited, case_priority("argument_column", new_prio), current_hop_count + 1, seq, refined_raw);
+                                            refinements_added = true;
+                                        }
+                                    }
+                                }
+
+                                if is_error_call && case_enabled("argument_typecast", ENABLE_ARGUMENT_TYPECAST_REFINEMENT.get()) {
+                                    let argument_typecast_refinements = generate_function_typecast_refinements_raw(
+                                        cand_raw,
+                                        &function_info,
+                                        call_pos,
+                                        &function_name,
+                                        &arg_types,
+                                        message,
+                                        current_prio
+                                    );
+
+                                    for (new_prio, refined_raw) in argument_typecast_refinements {
+                                        push_candidate(pq, visited, case_priority("argument_typecast", new_prio), current_hop_count + 1, seq, refined_raw);
+                                        refinements_added = true;
+                                    }
+                                }
+                            }
+
+                            if is_error_call && case_enabled("function_arity", ENABLE_FUNCTION_ARITY_REFINEMENT.get()) {
+                                // CASE 17) 같은 이름의 overload가 있으면 인자 개수 맞추기
+                                let overloads = find_function_overload_arg_types(&function_name);
+                                let function_arity_refinements = generate_function_arity_refinements_raw(
+                                    cand_raw,
+                                    call_pos,
+                                    &overloads,
+                                    current_prio
+                                );
+
+                                for (new_prio, refined_raw) in function_arity_refinements {
+                                    push_candidate(pq, visited, case_priority("function_arity", new_prio), current_hop_count + 1, seq, refined_raw);
+                                    refinements_added = true;
+                                }
+                            }
+
+                            if is_error_call && case_enabled("function_name", ENABLE_FUNCTION_NAME_REFINEMENT.get()) {
+                                let function_schema = extract_missing_function_schema(message);
+                                // HAVING절 안의 호출이면 집계 함수로만 바꾼다
+                                let in_having = is_call_in_having_clause(cand_raw, call_pos);
+                                let function_name_refinements = generate_function_name_refinements_raw(
+                                    cand_raw,
+                                    call_pos,
+                                    &function_name,
+                                    &arg_types,
+                                    function_schema.as_deref(),
+                                    in_having,
+                                    current_prio
+                                );
+                                
+                                for (new_prio, refined_raw) in function_name_refinements {
+                                    push_candidate(pq, visited, case_priority("function_name", new_prio), current_hop_count + 1, seq, refined_raw);
+                                    refinements_added = true;
+                                }
+                            }
+                        }
+                    }
+                    
+                    if refinements_added {
+                        return true;
+                    }
+                }
+
+                // CASE 13) jsonb 값을 text 함수에 넘긴 경우 - `->`/`->>` 교체
+                if arg_types.iter().any(|t| t == "jsonb")
+                    && push_jsonb_operator_refinements(cand_raw, current_prio, pq, visited, current_hop_count, seq)
+                {
+                    return true;
+                }
+            } else if is_operator_type_error(message) {
+                let mut refinements_added = false;
+
+                // CASE 6-4) Operand Type Mismatch
+                if let Some(error_pos) = cursor_pos {
+                    pgrx::notice!("SafeQL: Executing operand refinement for operator error at position {}", error_pos);
+                    
+                    let problematic_exprs = unsafe { find_expressions_at_position(cand_raw, error_pos) };
+                    let error_operator_info = extract_operator_info_from_error(message);
+                    
+                    refinements_added |= process_operand_refinements_for_expressions(
+                        cand_raw,
+                        problematic_exprs,
+                        Some(&error_operator_info),
+                        current_prio,
+                        pq,
+                        visited,
+                        current_hop_count,
+                        seq
+                    );
+                }
+
+                // CASE 13) jsonb 연산자 mismatch - `->`/`->>` 교체
+                if message.contains("jsonb") {
+                    refinements_added |= push_jsonb_operator_refinements(cand_raw, current_prio, pq, visited, current_hop_count, seq);
+                }
+
+                if refinements_added {
+                    return true;
+                }
+            }
+        },
+        Some(PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED) if is_set_returning_context_error(message) => {
+            // CASE 15) set-returning 함수를 허용되지 않는 위치에서 호출 - LATERAL FROM item으로 이동
+            if push_srf_lateral_refinements(cand_raw, cursor_pos, current_prio, pq, visited, current_hop_count, seq) {
+                return true;
+            }
+        },
+        Some(PgSqlErrorCode::ERRCODE_WRONG_OBJECT_TYPE) if is_missing_over_error(message) => {
+            // CASE 16) window 함수를 OVER 없이 호출 - 에러 위치의 호출에 `OVER ()` 추가
+            if case_enabled("window_over", ENABLE_WINDOW_OVER_REFINEMENT.get()) {
+                if let Some(error_pos) = cursor_pos {
+                    pgrx::notice!("SafeQL: Executing missing OVER refinement at position {}", error_pos);
+
+                    let mut refinements_added = false;
+                    let over_refinements = generate_window_over_refinements_raw(cand_raw, error_pos, current_prio);
+                    for (new_prio, refined_raw) in over_refinements {
+                        push_candidate(pq, visited, case_priority("window_over", new_prio), current_hop_count + 1, seq, refined_raw);
+                        refinements_added = true;
+                    }
+                    if refinements_added {
+                        return true;
+                    }
+                }
+            }
+        },
+        Some(PgSqlErrorCode::ERRCODE_INVALID_COLUMN_REFERENCE) if extract_ordinal_position(message).is_some() => {
+            // CASE 19) ORDER BY/GROUP BY 위치 번호가 select list 밖 - 유효한 위치로 보정
+            if case_enabled("ordinal_position", ENABLE_ORDINAL_POSITION_REFINEMENT.get()) {
+                if let Some((position, in_group_by)) = extract_ordinal_position(message) {
+                    pgrx::notice!("SafeQL: Executing ordinal position refinement for position {}", position);
+
+                    let mut refinements_added = false;
+                    let ordinal_refinements = generate_ordinal_position_refinements_raw(cand_raw, position, in_group_by, current_prio);
+                    for (new_prio, refined_raw) in ordinal_refinements {
+                        push_candidate(pq, visited, case_priority("ordinal_position", new_prio), current_hop_count + 1, seq, refined_raw);
+                        refinements_added = true;
+                    }
+                    if refinements_added {
+                        return true;
+                    }
+                }
+            }
+        },
+        Some(PgSqlErrorCode::ERRCODE_DATATYPE_MISMATCH) if extract_case_branch_types(message).is_some() => {
+            // CASE 20) CASE 분기 결과 타입 불일치 - 에러 위치의 분기 또는 나머지 분기에 cast 추가
+            if case_enabled("case_branch_cast", ENABLE_CASE_BRANCH_CAST_REFINEMENT.get()) {
+                if let (Some(error_pos), Some((common_type, branch_type))) = (cursor_pos, extract_case_branch_types(message)) {
+                    pgrx::notice!("SafeQL: Executing CASE branch cast refinement for types {} and {}", common_type, branch_type);
+
+                    let mut refinements_added = false;
+                    let cast_refinements = generate_case_branch_cast_refinements_raw(cand_raw, error_pos, &common_type, &branch_type, current_prio);
+                    for (new_prio, refined_raw) in cast_refinements {
+                        push_candidate(pq, visited, case_priority("case_branch_cast", new_prio), current_hop_count + 1, seq, refined_raw);
+                        refinements_added = true;
+                    }
+                    if refinements_added {
+                        return true;
+                    }
+                }
+            }
+        },
+        Some(PgSqlErrorCode::ERRCODE_INVALID_COLUMN_REFERENCE | PgSqlErrorCode::ERRCODE_GROUPING_ERROR)
+            if is_order_by_target_error(message) =>
+        {
+            // CASE 18) ORDER BY 식이 select list와 맞지 않음 - select list/GROUP BY 추가 또는 컬럼으로 대체
+            if case_enabled("order_by_target", ENABLE_ORDER_BY_TARGET_REFINEMENT.get()) {
+                pgrx::notice!("SafeQL: Executing ORDER BY target refinement for: {}", message);
+
+                let mut refinements_added = false;
+                let order_by_refinements = generate_order_by_target_refinements_raw(cand_raw, current_prio);
+                for (new_prio, refined_raw) in order_by_refinements {
+                    push_candidate(pq, visited, case_priority("order_by_target", new_prio), current_hop_count + 1, seq, refined_raw);
+                    refinements_added = true;
+                }
+                if refinements_added {
+                    return true;
+                }
+            }
+        },
+        Some(PgSqlErrorCode::ERRCODE_AMBIGUOUS_COLUMN) => {
+            // CASE 7) Column reference ambiguous - qualified reference로 변경
+            if case_enabled("column_ambiguity", ENABLE_COLUMN_AMBIGUITY_REFINEMENT.get()) {
+                if let Some(ambiguous_col) = extract_ambiguous_column(message) {
+                    pgrx::notice!("SafeQL: Executing column ambiguity refinement for ambiguous column '{}'", ambiguous_col);
+                    
+                    let ambiguity_refinements = generate_column_ambiguity_refinements_raw(
+                        cand_raw,
+                        &ambiguous_col,
+                        current_prio
+                    );
+                    
+                    for (new_prio, refined_raw) in ambiguity_refinements {
+                        push_candidate(pq, visited, case_priority("column_ambiguity", new_prio), current_hop_count + 1, seq, refined_raw);
+                    }
+                    return true;
+                }
+            }
+        },
+        _ => {
+            pgrx::notice!("SafeQL: Analysis error message: {}", message);
+        }  // 다른 에러는 무시하고 PQ에 저장된 RawStmt들로 계속 진행
+    }
+    false
+}
+
+/// safeql.last_refined_sql 설정 (SHOW로 클라이언트가 읽을 수 있게 세션 값으로)
+fn set_last_refined_sql(sql: &str) {
+    let name = CString::new("safeql.last_refined_sql").unwrap();
+    let value = CString::new(sql).unwrap_or_default();
+    unsafe {
+        pg_sys::SetConfigOption(
+            name.as_ptr(),
+            value.as_ptr(),
+            pg_sys::GucContext::PGC_USERSET,
+            pg_sys::GucSource::PGC_S_SESSION,
+        );
+    }
+}
+
+/// 채택된 후보가 원본과 다르면 refined SQL을 safeql.last_refined_sql에 기록
+/// (safeql.enable_refined_plan_notice면 실행 계획도 NOTICE로 보여준다)
+fn publish_refined_sql(cand_raw: *mut pg_sys::RawStmt, hop_count: i32) {
+    if hop_count > 0 {
+        set_last_refined_sql(&deparse_raw_stmt(cand_raw));
+        if let Some(plan_notice) = refined_plan_notice(cand_raw) {
+            pgrx::notice!("{}", plan_notice);
+        }
+    }
+}
+
+/// refined SQL의 EXPLAIN 결과로 만든 NOTICE 메시지 (설정이 꺼져 있거나 EXPLAIN이 실패하면 None)
+/// ANALYZE 없이 계획만 세우고, subtransaction은 항상 롤백한다
+fn refined_plan_notice(cand_raw: *mut pg_sys::RawStmt) -> Option<String> {
+    if !ENABLE_REFINED_PLAN_NOTICE.get() {
+        return None;
+    }
+    let explain_sql = format!("EXPLAIN {}", deparse_raw_stmt(cand_raw));
+
+    unsafe {
+        let subxact = SubTransactionGuard::begin();
+        let old_context = subxact.old_context;
+
+        let plan_lines = PgTryBuilder::new(|| {
+            pg_sys::MemoryContextSwitchTo(old_context);
+            Spi::connect(|client| -> Result<Vec<String>, pgrx::spi::Error> {
+                let rows = client.select(&explain_sql, None, None)?;
+                let mut lines = Vec::new();
+                for row in rows {
+                    if let Ok(Some(line)) = row[1].value::<String>() {
+                        lines.push(line);
+                    }
+                }
+                Ok(lines)
+            })
+            .ok()
+        })
+        .catch_others(|_| {
+            pg_sys::MemoryContextSwitchTo(old_context);
+            None
+        })
+        .execute();
+
+        subxact.rollback();
+
+        let plan_lines = plan_lines.filter(|lines| !lines.is_empty())?;
+        Some(format!("SafeQL refined query plan:\n{}", plan_lines.join("\n")))
+    }
+}
 
 /// 공통 refinement search 로직 - 성공한 RawStmt*를 반환
 pub fn perform_refinement_search(
     sql: &str, 
     pstate: *mut pg_sys::ParseState
 ) -> *mut pg_sys::RawStmt {
+    refinement_search_with_summary(sql, pstate).0
+}
+
+/// refinement search를 수행하고 채택된 후보와 그 후보까지의 refinement hop 수를 반환 (원본이면 0)
+pub fn perform_refinement_search_with_steps(
+    sql: &str,
+    pstate: *mut pg_sys::ParseState
+) -> (*mut pg_sys::RawStmt, i32) {
+    let (raw, steps, _) = refinement_search_with_summary(sql, pstate);
+    (raw, steps)
+}
+
+/// refinement search를 수행하고 (채택된 후보, hop 수, 원본으로 돌아간 경우 search 요약)을 반환
+fn refinement_search_with_summary(
+    sql: &str,
+    pstate: *mut pg_sys::ParseState
+) -> (*mut pg_sys::RawStmt, i32, Option<SearchSummary>) {
+    // 이전 쿼리의 refined SQL이 남지 않도록 비운다
+    set_last_refined_sql("");
+
     // SafeQL refinement가 전체적으로 비활성화되어 있으면 원본 반환
     if !ENABLE_SAFEQL_REFINEMENT.get() {
         pgrx::notice!("SafeQL: Refinement is globally disabled, performing standard analysis");
@@ -248,17 +960,24 @@ pub fn perform_refinement_search(
         });
         
         match try_analyze_raw_once(sql, init_raw, pstate) {
-            AnalyzeOutcome::Success(_) => return init_raw,
+            AnalyzeOutcome::Success(_) => return (init_raw, 0, None),
             AnalyzeOutcome::Failure { code, message, .. } => {
-                raise_saved_error(code, message);
+                raise_saved_error(code, message, None);
             }
         }
     }
 
+    // 검색어 임베딩 캐시는 search 단위로 유지
+    reset_search_vector_cache();
+    // case 순서도 search 시작 시 한 번만 파싱
+    load_case_order();
+
     // 제한값 설정
     let max_hops = MAX_REFINEMENT_HOP.get();
     let max_searches = MAX_REFINEMENT_NUM.get();
     let mut search_count = 0;
+    let mut hop_limited = 0;
+    let mut stop_reason = SearchStopReason::Exhausted;
 
     // 1) 초기 RawStmt*
     let init_raw = rawstmt_from_sql(sql).unwrap_or_else(|e| {
@@ -266,19 +985,24 @@ pub fn perform_refinement_search(
     });
     
     // 2) PQ: (prio, seq, RawStmt*, hop_count)
-    let mut pq: BinaryHeap<Reverse<(i32, u64, *mut pg_sys::RawStmt, i32)>> = BinaryHeap::new();
+    let mut pq: BinaryHeap<Reverse<(i32, String, u64, *mut pg_sys::RawStmt, i32)>> = BinaryHeap::new();
     let mut visited: HashSet<String> = HashSet::new();
     let mut seq: u64 = 0;
+    // cardinality penalty를 이미 적용해 다시 넣은 후보 (다시 꺼내면 그대로 반환)
+    let mut cardinality_checked: HashSet<String> = HashSet::new();
+    // 원본 FROM절 행 수 (처음 필요할 때 한 번만 계산)
+    let mut baseline_row_count: Option<Option<i64>> = None;
 
     let init_analyze_raw = unsafe { copy_node(init_raw) };
     push_candidate(&mut pq, &mut visited, 0, 0, &mut seq, init_analyze_raw);
 
-    while let Some(Reverse((current_prio, _seq, cand_raw, current_hop_count))) = pq.pop() {
+    while let Some(Reverse((current_prio, cand_sql, _seq, cand_raw, current_hop_count))) = pq.pop() {
         search_count += 1;
         
         // 최대 탐색 횟수 체크
         if search_count > max_searches {
             pgrx::notice!("SafeQL: Reached maximum search limit ({}), stopping refinement", max_searches);
+            stop_reason = SearchStopReason::CandidateLimit;
             break;
         }
         
@@ -286,6 +1010,7 @@ pub fn perform_refinement_search(
         if current_hop_count > max_hops {
             pgrx::notice!("SafeQL: Candidate with hop count {} exceeds maximum ({}) - skipping", 
                          current_hop_count, max_hops);
+            hop_limited += 1;
             continue;
         }
 
@@ -293,22 +1018,70 @@ pub fn perform_refinement_search(
         let analyze_raw = unsafe { copy_node(cand_raw) };
         match try_analyze_raw_once(sql, analyze_raw, pstate) {
             AnalyzeOutcome::Success(_q) => {
+                // 실행 검증을 끈 경우 analyze만 통과하면 바로 반환
+                if !ENABLE_EXECUTION_VALIDATION.get() {
+                    record_search_result(current_hop_count > 0, search_count);
+                    unsafe {
+                        let refined_sql = node_to_string(cand_raw as *mut pg_sys::Node)
+                            .unwrap_or("<failed-to-serialize>").to_string();
+                        pgrx::notice!("SafeQL refined SQL passed analysis (execution validation disabled): {}", refined_sql);
+                    }
+                    if ENABLE_REFINEMENT_DIFF_NOTICE.get() && current_hop_count > 0 {
+                        let changes = diff_raw_stmts(init_raw, cand_raw);
+                        pgrx::notice!("SafeQL refinement diff: {}", changes_to_json(&changes));
+                    }
+                    publish_refined_sql(cand_raw, current_hop_count);
+                    return (cand_raw, current_hop_count, None);
+                }
+
                 // 성공한 쿼리에 대해 실행 테스트 수행
                 let execute_raw = unsafe { copy_node(cand_raw) };
                 // print current priority
                 // pgrx::notice!("SafeQL: Analyzed candidate with priority {}", current_prio);
+                record_execution_attempt();
                 match try_execute_query(execute_raw, sql) {
-                    ExecutionOutcome::Success => {
+                    ExecutionOutcome::Success { row_count } => {
+                        // 결과 행 수가 원본 FROM절과 크게 다르면 penalty만큼 뒤로 미뤄 다시 넣는다 (safeql.cardinality_change_penalty)
+                        // 집계/GROUP BY 결과 행 수는 원본 FROM/WHERE 행 수와 비교할 수 없으므로 제외한다
+                        let penalty_weight = CARDINALITY_CHANGE_PENALTY.get();
+                        if penalty_weight > 0.0
+                            && current_hop_count > 0
+                            && !cardinality_checked.contains(&cand_sql)
+                            && !is_aggregate_select(cand_raw)
+                        {
+                            let baseline = *baseline_row_count.get_or_insert_with(|| from_clause_row_count(init_raw));
+                            let penalty = cardinality_change_penalty(baseline, row_count, penalty_weight);
+                            if penalty > 0 {
+                                pgrx::notice!(
+                                    "SafeQL: Candidate changes result cardinality ({:?} -> {:?} rows), deferring by {}",
+                                    baseline, row_count, penalty
+                                );
+                                cardinality_checked.insert(cand_sql.clone());
+                                pq.push(Reverse((current_prio + penalty, cand_sql, seq, cand_raw, current_hop_count)));
+                                seq += 1;
+                                continue;
+                            }
+                        }
+
                         // 실행도 성공 - refined RawStmt 반환
+                        record_execution_success();
+                        record_search_result(current_hop_count > 0, search_count);
                         unsafe {
                             let refined_sql = node_to_string(cand_raw as *mut pg_sys::Node)
                                 .unwrap_or("<failed-to-serialize>").to_string();
                             pgrx::notice!("SafeQL successfully refined and validated SQL: {}", refined_sql);
                         }
-                        return cand_raw;
+                        if ENABLE_REFINEMENT_DIFF_NOTICE.get() && current_hop_count > 0 {
+                            let changes = diff_raw_stmts(init_raw, cand_raw);
+                            pgrx::notice!("SafeQL refinement diff: {}", changes_to_json(&changes));
+                        }
+                        publish_refined_sql(cand_raw, current_hop_count);
+                        return (cand_raw, current_hop_count, None);
                     },
                     ExecutionOutcome::ArgumentFormatError { message } => {
-                        if ENABLE_FUNCTION_NAME_REFINEMENT.get() {
+                        if case_enabled("argument_format", ENABLE_FUNCTION_NAME_REFINEMENT.get())
+                            && error_refinement_enabled(Some(PgSqlErrorCode::ERRCODE_INVALID_TEXT_REPRESENTATION))
+                        {
                             pgrx::notice!("SafeQL: Detected argument format error during execution: {}", message);
                             
                             let format_refinements = generate_argument_format_refinements_raw(
@@ -318,19 +1091,41 @@ pub fn perform_refinement_search(
                             );
                             
                             for (new_prio, refined_raw) in format_refinements {
-                                push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
+                                push_candidate(&mut pq, &mut visited, case_priority("argument_format", new_prio), current_hop_count + 1, &mut seq, refined_raw);
                             }
                             continue;
                         }
                     },
-                    ExecutionOutcome::ExecutionError(exec_error) => {
-                        // 실행 에러가 발생한 경우 다음 후보 시도
-                        unsafe {
-                            let refined_sql = node_to_string(cand_raw as *mut pg_sys::Node)
-                                .unwrap_or("<failed-to-serialize>").to_string();
-                            pgrx::notice!("SafeQL refined SQL failed execution test: {} - Error: {}", 
-                                         refined_sql, exec_error);
-                        }
+                    ExecutionOutcome::DivisionByZero { message } => {
+                        if case_enabled("nullif_guard", ENABLE_NULLIF_GUARD_REFINEMENT.get())
+                            && error_refinement_enabled(Some(PgSqlErrorCode::ERRCODE_DIVISION_BY_ZERO))
+                        {
+                            pgrx::notice!("SafeQL: Detected division by zero during execution: {}", message);
+
+                            let nullif_refinements = generate_nullif_guard_refinements_raw(cand_raw, current_prio);
+
+                            for (new_prio, refined_raw) in nullif_refinements {
+                                push_candidate(&mut pq, &mut visited, case_priority("nullif_guard", new_prio), current_hop_count + 1, &mut seq, refined_raw);
+                            }
+                        }
+                        continue;
+                    },
+                    ExecutionOutcome::SetReturningFunctionError { message } => {
+                        pgrx::notice!("SafeQL: Detected set-returning function error during execution: {}", message);
+                        if !error_refinement_enabled(Some(PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED)) {
+                            continue;
+                        }
+                        push_srf_lateral_refinements(cand_raw, None, current_prio, &mut pq, &mut visited, current_hop_count, &mut seq);
+                        continue;
+                    },
+                    ExecutionOutcome::ExecutionError(exec_error) => {
+                        // 실행 에러가 발생한 경우 다음 후보 시도
+                        unsafe {
+                            let refined_sql = node_to_string(cand_raw as *mut pg_sys::Node)
+                                .unwrap_or("<failed-to-serialize>").to_string();
+                            pgrx::notice!("SafeQL refined SQL failed execution test: {} - Error: {}", 
+                                         refined_sql, exec_error);
+                        }
                         continue;
                     },
                     ExecutionOutcome::EmptyResult => {
@@ -338,23 +1133,23 @@ pub fn perform_refinement_search(
                         let mut refinements_added = false;
                         
                         // 1. Value refinement
-                        if ENABLE_VALUE_REFINEMENT.get() {
+                        if case_enabled("value", ENABLE_VALUE_REFINEMENT.get()) {
                             let value_refinements = generate_value_refinements_raw(cand_raw, current_prio);
                             for (new_prio, refined_raw) in value_refinements {
-                                push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
+                                push_candidate(&mut pq, &mut visited, case_priority("value", new_prio), current_hop_count + 1, &mut seq, refined_raw);
                                 refinements_added = true;
                             }
                         }
 
                         // 2. FROM절의 모든 테이블에 대해 table refinement
-                        if ENABLE_TABLE_REFINEMENT.get() {
+                        if case_enabled("table", ENABLE_TABLE_REFINEMENT.get()) {
                             let table_refinements = generate_table_refinements_for_all_from_tables_raw(
                                 cand_raw,
                                 current_prio
                             );
                             
                             for (new_prio, refined_raw) in table_refinements {
-                                push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
+                                push_candidate(&mut pq, &mut visited, case_priority("table", new_prio), current_hop_count + 1, &mut seq, refined_raw);
                                 refinements_added = true;
                             }
                         }
@@ -365,6 +1160,7 @@ pub fn perform_refinement_search(
                             refinements_added |= process_operand_refinements_for_expressions(
                                 cand_raw,
                                 where_exprs,
+                                None,
                                 current_prio,
                                 &mut pq,
                                 &mut visited,
@@ -373,15 +1169,33 @@ pub fn perform_refinement_search(
                             );
                         }
                         
-                        // 4. FROM절의 모든 테이블에 대해 1-hop JOIN refinement
-                        if ENABLE_JOIN_REFINEMENT.get() {
+                        // 4. jsonb path 연산자의 key literal refinement
+                        if case_enabled("jsonb_operator", ENABLE_JSONB_OPERATOR_REFINEMENT.get()) {
+                            let jsonb_key_refinements = generate_jsonb_key_refinements_raw(cand_raw, current_prio);
+                            for (new_prio, refined_raw) in jsonb_key_refinements {
+                                push_candidate(&mut pq, &mut visited, case_priority("jsonb_operator", new_prio), current_hop_count + 1, &mut seq, refined_raw);
+                                refinements_added = true;
+                            }
+                        }
+
+                        // 5. FROM절의 모든 테이블에 대해 1-hop JOIN refinement
+                        if case_enabled("join_add", join_refinement_enabled()) {
                             let join_add_refinements = generate_one_hop_join_refinements_for_all_tables_raw(
                                 cand_raw,
                                 current_prio
                             );
                             
                             for (new_prio, refined_raw) in join_add_refinements {
-                                push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
+                                push_candidate(&mut pq, &mut visited, case_priority("join_add", new_prio), current_hop_count + 1, &mut seq, refined_raw);
+                                refinements_added = true;
+                            }
+                        }
+
+                        // 6. nullable 컬럼 비교를 NULL도 허용하도록 완화 (opt-in)
+                        if case_enabled("null_handling", ENABLE_NULL_HANDLING_REFINEMENT.get()) {
+                            let null_handling_refinements = generate_null_handling_refinements_raw(cand_raw, current_prio);
+                            for (new_prio, refined_raw) in null_handling_refinements {
+                                push_candidate(&mut pq, &mut visited, case_priority("null_handling", new_prio), current_hop_count + 1, &mut seq, refined_raw);
                                 refinements_added = true;
                             }
                         }
@@ -396,229 +1210,29 @@ pub fn perform_refinement_search(
                 }
             },
             AnalyzeOutcome::Failure { code, message, cursor_pos } => {
-                match code {
-                    Some(PgSqlErrorCode::ERRCODE_UNDEFINED_TABLE) => {
-                        // CASE 1) FROM Rel -> FROM Rel' - 테이블 refinement 수행
-                        if ENABLE_TABLE_REFINEMENT.get() {
-                            if let Some(missing_rel) = extract_missing_relation(&message) {
-                                pgrx::notice!("SafeQL: Executing table refinement for missing table '{}'", missing_rel);
-                                // 후보 테이블들을 RawStmt 변형으로 생성해서 PQ에 넣는다
-                                let refinements = generate_table_refinements_raw(
-                                    cand_raw, 
-                                    &missing_rel,
-                                    current_prio
-                                );
-        
-                                for (new_prio, refined_raw) in refinements {
-                                    push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
-                                }
-                                continue;
-                            }
-                        }
-                    },
-                    Some(PgSqlErrorCode::ERRCODE_UNDEFINED_COLUMN) => {
-                        if let Some((table_name, missing_col)) = extract_missing_column(&message) {
-                            let mut refinements_added = false;
-
-                            // CASE 2) SELECT Att -> SELECT Att' - 칼럼 refinement 수행
-                            if ENABLE_COLUMN_REFINEMENT.get() {
-                                pgrx::notice!("SafeQL: Executing column refinement for missing column '{}'", missing_col);
-                                let column_refinements = generate_column_refinements_raw(
-                                    cand_raw,
-                                    table_name.as_deref(),
-                                    &missing_col,
-                                    current_prio 
-                                );
-                                for (new_prio, refined_raw) in column_refinements {
-                                    push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
-                                    refinements_added = true;
-                                }
-                            }
-
-                            // CASE 3) FROM Rel -> FROM Rel' - 칼럼이 참조하는 테이블 refinement 수행
-                            if ENABLE_TABLE_FOR_COLUMN.get() {
-                                if let Some(ref table_name_str) = table_name {
-                                    // 1) 테이블 이름이 명확한 경우, 해당 테이블에 대해서만 refinement 시도
-                                    let table_refinements = generate_table_refinements_for_column_error_raw(
-                                        cand_raw,
-                                        table_name_str,
-                                        current_prio 
-                                    );
-                                    
-                                    for (new_prio, refined_raw) in table_refinements {
-                                        push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
-                                        refinements_added = true;
-                                    }
-                                } else {
-                                    // 2) 테이블 이름이 특정되지 않은 경우, FROM절의 모든 테이블에 대해 refinement 시도
-                                    let all_table_refinements = generate_table_refinements_for_all_tables_raw(
-                                        cand_raw,
-                                        current_prio
-                                    );
-                                    
-                                    for (new_prio, refined_raw) in all_table_refinements {
-                                        push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
-                                        refinements_added = true;
-                                    }
-                                }
-                            }
-
-                            // CASE 4) FROM Rel WHERE col ... -> FROM Rel Rel2 WHERE ... - column reference refinement
-                            if ENABLE_COLUMN_TABLE_REFERENCE.get() {
-                                if table_name.is_some() {
-                                    let reference_refinements = generate_column_table_reference_refinements_raw(
-                                        cand_raw,
-                                        table_name.as_deref(),
-                                        &missing_col,
-                                        current_prio
-                                    );
-                                    
-                                    for (new_prio, refined_raw) in reference_refinements {
-                                        push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
-                                        refinements_added = true;
-                                    }
-                                }
-                            }
-
-                            // CASE 5) JOIN refinement
-                            refinements_added |= process_join_refinements_for_columns(
-                                cand_raw,
-                                vec![missing_col.clone()],
-                                current_prio,
-                                &mut pq,
-                                &mut visited,
-                                current_hop_count,
-                                &mut seq
-                            );
-
-                            if refinements_added {
-                                continue;
-                            }
-                        }
-                    },
-                    Some(PgSqlErrorCode::ERRCODE_UNDEFINED_FUNCTION) => {
-                        // CASE 6) Function does not exist - function argument refinement 수행
-                        if let Some((function_name, arg_types)) = extract_missing_function(&message) {
-                            if let Some(error_pos) = cursor_pos {
-                                let mut refinements_added = false;
-
-                                // 에러 위치에서 문제가 된 함수 호출 찾기
-                                let problematic_function_calls = unsafe { find_function_calls_at_position(cand_raw, error_pos) };
-                                // 함수 정보 추출
-                                let function_info = extract_function_info_from_error(&message);
-
-                                for func_call_node in problematic_function_calls {
-                                    unsafe {
-                                        if check_function_exists(&function_name) {
-                                            if let Some(argument_operands) = extract_argument_operands_from_function_call(func_call_node) {
-                                                if ENABLE_ARGUMENT_COLUMN_REFINEMENT.get() {
-                                                    let argument_column_refinements = generate_function_argument_column_refinements_raw(
-                                                        cand_raw,
-                                                        &argument_operands,
-                                                        error_pos,
-                                                        &function_name,
-                                                        &arg_types,
-                                                        current_prio
-                                                    );
-                                                    
-                                                    for (new_prio, refined_raw) in argument_column_refinements {
-                                                        push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
-                                                        refinements_added = true;
-                                                    }
-                                                }
-                                            }
-    
-                                            if ENABLE_ARGUMENT_TYPECAST_REFINEMENT.get() {
-                                                let argument_typecast_refinements = generate_function_typecast_refinements_raw(
-                                                    cand_raw,
-                                                    &function_info,
-                                                    error_pos,
-                                                    &function_name,
-                                                    &arg_types,
-                                                    &message,
-                                                    current_prio
-                                                );
-    
-                                                for (new_prio, refined_raw) in argument_typecast_refinements {
-                                                    push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
-                                                    refinements_added = true;
-                                                }
-                                            }
-                                        }
-
-                                        if ENABLE_FUNCTION_NAME_REFINEMENT.get() {
-                                            let function_name_refinements = generate_function_name_refinements_raw(
-                                                cand_raw,
-                                                error_pos,
-                                                &function_name,
-                                                &arg_types,
-                                                current_prio
-                                            );
-                                            
-                                            for (new_prio, refined_raw) in function_name_refinements {
-                                                push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
-                                                refinements_added = true;
-                                            }
-                                        }
-                                    }
-                                }
-                                
-                                if refinements_added {
-                                    continue;
-                                }
-                            }
-                        } else if is_operator_type_error(&message) {
-                            // CASE 6-4) Operand Type Mismatch
-                            if let Some(error_pos) = cursor_pos {
-                                pgrx::notice!("SafeQL: Executing operand refinement for operator error at position {}", error_pos);
-                                
-                                let problematic_exprs = unsafe { find_expressions_at_position(cand_raw, error_pos) };
-                                
-                                let refinements_added = process_operand_refinements_for_expressions(
-                                    cand_raw,
-                                    problematic_exprs,
-                                    current_prio,
-                                    &mut pq,
-                                    &mut visited,
-                                    current_hop_count,
-                                    &mut seq
-                                );
-                                
-                                if refinements_added {
-                                    continue;
-                                }
-                            }
-                        }
-                    },
-                    Some(PgSqlErrorCode::ERRCODE_AMBIGUOUS_COLUMN) => {
-                        // CASE 7) Column reference ambiguous - qualified reference로 변경
-                        if ENABLE_COLUMN_AMBIGUITY_REFINEMENT.get() {
-                            if let Some(ambiguous_col) = extract_ambiguous_column(&message) {
-                                pgrx::notice!("SafeQL: Executing column ambiguity refinement for ambiguous column '{}'", ambiguous_col);
-                                
-                                let ambiguity_refinements = generate_column_ambiguity_refinements_raw(
-                                    cand_raw,
-                                    &ambiguous_col,
-                                    current_prio
-                                );
-                                
-                                for (new_prio, refined_raw) in ambiguity_refinements {
-                                    push_candidate(&mut pq, &mut visited, new_prio, current_hop_count + 1, &mut seq, refined_raw);
-                                }
-                                continue;
-                            }
-                        }
-                    },
-                    _ => {
-                        pgrx::notice!("SafeQL: Analysis error message: {}", message);
-                    }  // 다른 에러는 무시하고 PQ에 저장된 RawStmt들로 계속 진행
-                }
+                push_analyze_error_refinements(
+                    cand_raw,
+                    code,
+                    &message,
+                    cursor_pos,
+                    current_prio,
+                    &mut pq,
+                    &mut visited,
+                    current_hop_count,
+                    &mut seq
+                );
             }
         }
     }
 
     // 모든 refinement 시도 실패 - 원본으로 리턴
-    return init_raw;
+    let summary = SearchSummary {
+        candidates_tried: search_count.min(max_searches),
+        hop_limited,
+        stop_reason,
+    };
+    record_search_result(false, summary.candidates_tried);
+    return (init_raw, 0, Some(summary));
     // match try_analyze_raw_once(sql, init_raw, pstate) {
     //     AnalyzeOutcome::Success(_) => return init_raw,
     //     AnalyzeOutcome::Failure { code, message, .. } => {
@@ -633,128 +1247,506 @@ pub fn perform_refinement_search(
 
 /// SafeQL refinement를 수행하고 refined Query를 반환
 pub fn analyze_with_refinement(sql: &str, pstate: *mut pg_sys::ParseState) -> *mut pg_sys::Query {
-    let refined_raw = perform_refinement_search(sql, pstate);
+    let (refined_raw, _, summary) = refinement_search_with_summary(sql, pstate);
     
     // refined RawStmt를 다시 analyze해서 Query 반환
     match try_analyze_raw_once(sql, refined_raw, pstate) {
         AnalyzeOutcome::Success(q) => q,
         AnalyzeOutcome::Failure { code, message, .. } => {
-            raise_saved_error(code, message);
+            let hint = summary
+                .filter(|_| ENABLE_SEARCH_SUMMARY_HINT.get())
+                .map(|summary| summary.hint());
+            raise_saved_error(code, message, hint);
+        }
+    }
+}
+
+/// 이미 발생한 에러 메시지로 refinement 후보를 만들고 (deparsed SQL, priority)를 priority 순으로 반환
+/// 에러 코드는 호출자가 받은 SQLSTATE를 쓰고, 없으면 쿼리를 한 번 analyze해 잡은 에러의 코드를 쓴다
+pub fn rank_refinement_candidates(
+    sql: &str,
+    error_message: &str,
+    cursor_pos: Option<i32>,
+    sqlstate: Option<&str>
+) -> Vec<(String, i32)> {
+    if !ENABLE_SAFEQL_REFINEMENT.get() {
+        return Vec::new();
+    }
+
+    reset_search_vector_cache();
+    load_case_order();
+    let raw = rawstmt_from_sql(sql).unwrap_or_else(|e| {
+        pgrx::error!("SQL parse error: {}", e);
+    });
+
+    let code = match sqlstate {
+        Some(sqlstate) => Some(error_code_from_sqlstate(sqlstate).unwrap_or_else(|| {
+            pgrx::error!("invalid SQLSTATE: {}", sqlstate);
+        })),
+        None => {
+            let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+            let outcome = try_analyze_raw_once(sql, raw, pstate);
+            unsafe { pg_sys::free_parsestate(pstate) };
+            match outcome {
+                AnalyzeOutcome::Failure { code, .. } => code,
+                AnalyzeOutcome::Success(_) => None,
+            }
         }
+    };
+
+    let mut pq: BinaryHeap<Reverse<(i32, String, u64, *mut pg_sys::RawStmt, i32)>> = BinaryHeap::new();
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut seq: u64 = 0;
+
+    push_analyze_error_refinements(
+        raw,
+        code,
+        error_message,
+        cursor_pos,
+        0,
+        &mut pq,
+        &mut visited,
+        0,
+        &mut seq
+    );
+
+    let mut ranked = Vec::with_capacity(pq.len());
+    while let Some(Reverse((prio, sql, _seq, _cand_raw, _hop))) = pq.pop() {
+        ranked.push((sql, prio));
     }
+    ranked
 }
 
+/// 5자리 SQLSTATE 문자열을 에러 코드로 변환 (MAKE_SQLSTATE와 같은 6비트 인코딩)
+fn error_code_from_sqlstate(sqlstate: &str) -> Option<PgSqlErrorCode> {
+    let bytes = sqlstate.as_bytes();
+    if bytes.len() != 5 || !bytes.iter().all(|b| b.is_ascii_digit() || b.is_ascii_uppercase()) {
+        return None;
+    }
+    let code = bytes
+        .iter()
+        .enumerate()
+        .fold(0i32, |acc, (i, b)| acc | (((*b as i32 - b'0' as i32) & 0x3F) << (6 * i)));
+    Some(PgSqlErrorCode::from(code))
+}
+
+
+/// 후보 실행이 Success가 아닌 이유
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExecutionErrorKind {
+    /// 실행은 됐지만 결과가 0행 (UPDATE/DELETE는 count(*)가 0)
+    EmptyResult,
+    /// SPI 호출이 ereport 없이 Err를 돌려줌
+    SpiFailure,
+    /// Postgres ereport (code에 실제 SQLSTATE)
+    PostgresError,
+    /// SPI 실행 중 Rust panic
+    RustPanic,
+}
 
 struct ExecutionErrorInfo {
+    kind: ExecutionErrorKind,
     code: Option<PgSqlErrorCode>,
     message: String,
 }
 
+impl ExecutionErrorInfo {
+    fn empty_result() -> Self {
+        ExecutionErrorInfo { kind: ExecutionErrorKind::EmptyResult, code: None, message: String::new() }
+    }
+
+    fn spi_failure(err: pgrx::spi::Error) -> Self {
+        ExecutionErrorInfo { kind: ExecutionErrorKind::SpiFailure, code: None, message: err.to_string() }
+    }
+
+    /// SQLSTATE와 메시지로 ExecutionOutcome을 결정
+    fn into_outcome(self) -> ExecutionOutcome {
+        match (self.kind, self.code) {
+            (ExecutionErrorKind::EmptyResult, _) => ExecutionOutcome::EmptyResult,
+            (_, Some(PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE)) => {
+                ExecutionOutcome::ArgumentFormatError { message: self.message }
+            }
+            (_, Some(PgSqlErrorCode::ERRCODE_DIVISION_BY_ZERO)) => {
+                ExecutionOutcome::DivisionByZero { message: self.message }
+            }
+            (_, Some(PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED))
+                if is_set_returning_context_error(&self.message) =>
+            {
+                ExecutionOutcome::SetReturningFunctionError { message: self.message }
+            }
+            _ => ExecutionOutcome::ExecutionError(self.message),
+        }
+    }
+}
+
+/// BeginInternalSubTransaction ~ Release/Rollback 쌍을 묶는 RAII guard
+/// commit/rollback 전에 panic 등으로 drop되면 subtransaction을 롤백해 누수를 막는다
+pub(crate) struct SubTransactionGuard {
+    pub(crate) old_context: pg_sys::MemoryContext,
+    old_owner: pg_sys::ResourceOwner,
+    finished: bool,
+}
+
+impl SubTransactionGuard {
+    /// 현재 컨텍스트와 owner를 저장하고 subtransaction 시작
+    pub(crate) unsafe fn begin() -> Self {
+        unsafe {
+            let old_context = pg_sys::CurrentMemoryContext;
+            let old_owner = pg_sys::CurrentResourceOwner;
+
+            pg_sys::BeginInternalSubTransaction(std::ptr::null());
+            pg_sys::MemoryContextSwitchTo(old_context);
+
+            SubTransactionGuard { old_context, old_owner, finished: false }
+        }
+    }
+
+    pub(crate) unsafe fn commit(mut self) {
+        unsafe {
+            pg_sys::ReleaseCurrentSubTransaction();
+            self.restore();
+        }
+        self.finished = true;
+    }
+
+    pub(crate) unsafe fn rollback(mut self) {
+        unsafe {
+            pg_sys::RollbackAndReleaseCurrentSubTransaction();
+            self.restore();
+        }
+        self.finished = true;
+    }
+
+    unsafe fn restore(&self) {
+        unsafe {
+            pg_sys::MemoryContextSwitchTo(self.old_context);
+            pg_sys::CurrentResourceOwner = self.old_owner;
+        }
+    }
+}
+
+impl Drop for SubTransactionGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            unsafe {
+                pg_sys::RollbackAndReleaseCurrentSubTransaction();
+                self.restore();
+            }
+        }
+    }
+}
+
+/// 후보 실행 동안만 STATEMENT_TIMEOUT 타이머를 candidate_statement_timeout으로 건다
+/// drop 시 바깥 statement_timeout 타이머를 원래대로 되돌린다
+struct CandidateTimeoutGuard {
+    prior_finish: Option<pg_sys::TimestampTz>,
+}
+
+impl CandidateTimeoutGuard {
+    /// timeout_ms가 0이면 아무것도 하지 않음 (바깥 타이머가 더 먼저 끝나면 그대로 둔다)
+    unsafe fn arm(timeout_ms: i32) -> Option<Self> {
+        if timeout_ms <= 0 {
+            return None;
+        }
+
+        unsafe {
+            let prior_finish = if pg_sys::get_timeout_active(pg_sys::TimeoutId::STATEMENT_TIMEOUT) {
+                Some(pg_sys::get_timeout_finish_time(pg_sys::TimeoutId::STATEMENT_TIMEOUT))
+            } else {
+                None
+            };
+
+            let candidate_finish = pg_sys::GetCurrentTimestamp() + timeout_ms as i64 * 1000;
+            if prior_finish.is_some_and(|finish| finish <= candidate_finish) {
+                return None;
+            }
+
+            pg_sys::enable_timeout_after(pg_sys::TimeoutId::STATEMENT_TIMEOUT, timeout_ms);
+            Some(CandidateTimeoutGuard { prior_finish })
+        }
+    }
+}
+
+impl Drop for CandidateTimeoutGuard {
+    fn drop(&mut self) {
+        unsafe {
+            match self.prior_finish {
+                Some(finish) => pg_sys::enable_timeout_at(pg_sys::TimeoutId::STATEMENT_TIMEOUT, finish),
+                None => pg_sys::disable_timeout(pg_sys::TimeoutId::STATEMENT_TIMEOUT, false),
+            }
+        }
+    }
+}
+
 fn try_execute_query(rawstmt: *mut pg_sys::RawStmt, _source_sql: &str) -> ExecutionOutcome {
     let refined_sql = deparse_raw_stmt(rawstmt);
     pgrx::notice!("Executing refined SQL: {}", refined_sql);
+
+    if ENABLE_DEPARSE_ROUND_TRIP_CHECK.get() && !deparse_round_trips(rawstmt, &refined_sql) {
+        pgrx::warning!("SafeQL: deparsed candidate does not match its RawStmt: {}", refined_sql);
+    }
+
+    // INSERT는 실행하지 않고 (trigger, sequence 등 롤백되지 않는 부작용 방지) 입력 SELECT/VALUES만 실행해 검증한다
+    let is_insert = unsafe {
+        !(*rawstmt).stmt.is_null() && (*(*rawstmt).stmt).type_ == pg_sys::NodeTag::T_InsertStmt
+    };
+    let insert_source_sql = unsafe { insert_source_count_query(rawstmt) };
+    // UPDATE/DELETE는 실행하지 않고 같은 FROM/WHERE에 대한 행 수 세기로 검증한다
+    let is_update_or_delete = unsafe {
+        !(*rawstmt).stmt.is_null()
+            && matches!((*(*rawstmt).stmt).type_, pg_sys::NodeTag::T_UpdateStmt | pg_sys::NodeTag::T_DeleteStmt)
+    };
+    let count_sql = unsafe { dml_count_query(rawstmt) }.map(deparse_raw_stmt);
     
     unsafe {
-        // 현재 컨텍스트와 owner 저장
-        let old_context = pg_sys::CurrentMemoryContext;
-        let old_owner = pg_sys::CurrentResourceOwner;
-        
-        // Subtransaction 시작 - 리소스 격리를 위해
-        pg_sys::BeginInternalSubTransaction(std::ptr::null());
-        pg_sys::MemoryContextSwitchTo(old_context);
+        // Subtransaction 시작 - 리소스 격리를 위해 (guard가 drop되면 롤백)
+        let subxact = SubTransactionGuard::begin();
+        let old_context = subxact.old_context;
         
         let result = PgTryBuilder::new(|| {
             pg_sys::MemoryContextSwitchTo(old_context);
+
+            // 느린 후보가 search 전체를 붙잡지 않도록 실행 시간을 제한 (timeout은 실행 에러로 처리)
+            let _timeout = CandidateTimeoutGuard::arm(CANDIDATE_STATEMENT_TIMEOUT.get());
             
-            Spi::connect(|client| {
+            Spi::connect(|mut client| {
+                if is_insert {
+                    // DEFAULT VALUES처럼 입력이 없으면 analyze 성공으로 충분하다
+                    if let Some(insert_source_sql) = &insert_source_sql {
+                        client.select(insert_source_sql, Some(1), None)
+                            .map_err(ExecutionErrorInfo::spi_failure)?;
+                    }
+                    return Ok(None);
+                }
+
+                if let Some(count_sql) = &count_sql {
+                    let matched = client.select(count_sql, Some(1), None)
+                        .and_then(|rows| rows.first().get_one::<i64>())
+                        .map_err(ExecutionErrorInfo::spi_failure)?;
+                    return if matched.unwrap_or(0) > 0 {
+                        Ok(matched)
+                    } else {
+                        Err(ExecutionErrorInfo::empty_result())
+                    };
+                }
+                // SELECT로 옮길 수 없는 UPDATE/DELETE(WHERE CURRENT OF 등)는 analyze 성공으로 충분하다
+                if is_update_or_delete {
+                    return Ok(None);
+                }
+
                 // read-only 모드로 쿼리 실행
                 match client.select(&refined_sql, None, None) {
                     Ok(tuple_table) => {
                         // 결과 행 수 확인
                         if tuple_table.is_empty() {
-                            Err(ExecutionErrorInfo {
-                                code: None,
-                                message: "EMPTY_RESULT".to_string(),
-                            })
+                            Err(ExecutionErrorInfo::empty_result())
                         } else {
-                            Ok(())
+                            Ok(Some(tuple_table.len() as i64))
                         }
                     }
-                    Err(_) => {
-                        Err(ExecutionErrorInfo {
-                            code: None,
-                            message: "EXECUTION_FAILED".to_string(),
-                        })
-                    }
+                    Err(err) => Err(ExecutionErrorInfo::spi_failure(err)),
                 }
-            })?;
-            
-            // 성공 시 subtransaction 커밋
-            pg_sys::ReleaseCurrentSubTransaction();
-            pg_sys::MemoryContextSwitchTo(old_context);
-            pg_sys::CurrentResourceOwner = old_owner;
-            
-            Ok(())
+            })
         })
         .catch_others(|e| {
-            // 에러 발생 시 명시적으로 subtransaction 롤백
             pg_sys::MemoryContextSwitchTo(old_context);
             
-            let (code, message) = match e {
+            let (kind, code, message) = match e {
                 CaughtError::PostgresError(ref err_report) | 
                 CaughtError::ErrorReport(ref err_report) => {
-                    (Some(err_report.sql_error_code()), err_report.message().to_string())
+                    (ExecutionErrorKind::PostgresError, Some(err_report.sql_error_code()), err_report.message().to_string())
                 },
                 CaughtError::RustPanic { ref ereport, .. } => {
-                    (Some(ereport.sql_error_code()), 
+                    (ExecutionErrorKind::RustPanic, Some(ereport.sql_error_code()), 
                      format!("Rust panic during SPI execution: {}", ereport.message()))
                 }
             };
             
-            // 롤백 및 리소스 정리
-            pg_sys::RollbackAndReleaseCurrentSubTransaction();
-            pg_sys::MemoryContextSwitchTo(old_context);
-            pg_sys::CurrentResourceOwner = old_owner;
-            
-            Err(ExecutionErrorInfo { code, message })
+            Err(ExecutionErrorInfo { kind, code, message })
         })
         .execute();
         
-        // 최종 컨텍스트 복원
-        pg_sys::MemoryContextSwitchTo(old_context);
-        pg_sys::CurrentResourceOwner = old_owner;
+        // 성공 시 subtransaction 커밋, 에러 시 롤백
+        if result.is_ok() {
+            subxact.commit();
+        } else {
+            subxact.rollback();
+        }
         
         // ExecutionOutcome으로 변환
         match result {
-            Ok(_) => ExecutionOutcome::Success,
-            Err(error_info) => {
-                if error_info.message == "EMPTY_RESULT" {
-                    ExecutionOutcome::EmptyResult
-                } else if let Some(PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE) = error_info.code {
-                    ExecutionOutcome::ArgumentFormatError {
-                        message: error_info.message.clone()
-                    }
-                } else {
-                    ExecutionOutcome::ExecutionError(error_info.message.clone())
+            Ok(row_count) => ExecutionOutcome::Success { row_count },
+            Err(error_info) => error_info.into_outcome(),
+        }
+    }
+}
+
+/// 원본 SELECT의 FROM/WHERE로 센 행 수 (`SELECT count(*) FROM <원본 FROM절> WHERE <원본 WHERE절>`)
+/// 원본 WHERE절이 실행되지 않으면 (refinement 대상이 WHERE에 있는 경우) FROM절만으로 센다
+/// FROM절이 없거나 FROM절 자체가 실행되지 않으면 None
+fn from_clause_row_count(init_raw: *mut pg_sys::RawStmt) -> Option<i64> {
+    let count_sql = |with_where: bool| unsafe {
+        let stmt = (*init_raw).stmt;
+        if stmt.is_null() || (*stmt).type_ != pg_sys::NodeTag::T_SelectStmt {
+            return None;
+        }
+        let select_stmt = copy_node(stmt as *mut pg_sys::SelectStmt);
+        if (*select_stmt).fromClause.is_null() || (with_where && (*select_stmt).whereClause.is_null()) {
+            return None;
+        }
+
+        let count_raw = rawstmt_from_sql("SELECT count(*)").ok()?;
+        let count_stmt = (*count_raw).stmt as *mut pg_sys::SelectStmt;
+        (*count_stmt).fromClause = (*select_stmt).fromClause;
+        (*count_stmt).withClause = (*select_stmt).withClause;
+        if with_where {
+            (*count_stmt).whereClause = (*select_stmt).whereClause;
+        }
+        Some(deparse_raw_stmt(count_raw))
+    };
+
+    count_sql(true)
+        .and_then(|sql| run_row_count(&sql))
+        .or_else(|| count_sql(false).and_then(|sql| run_row_count(&sql)))
+}
+
+/// `SELECT count(*) ...` 를 subtransaction 안에서 실행 (에러나 timeout이면 None)
+fn run_row_count(count_sql: &str) -> Option<i64> {
+    unsafe {
+        let subxact = SubTransactionGuard::begin();
+        let old_context = subxact.old_context;
+
+        let result = PgTryBuilder::new(|| {
+            pg_sys::MemoryContextSwitchTo(old_context);
+            let _timeout = CandidateTimeoutGuard::arm(CANDIDATE_STATEMENT_TIMEOUT.get());
+            Spi::get_one::<i64>(count_sql).ok().flatten()
+        })
+        .catch_others(|_| {
+            pg_sys::MemoryContextSwitchTo(old_context);
+            None
+        })
+        .execute();
+
+        subxact.rollback();
+        result
+    }
+}
+
+/// 원본 FROM절 행 수 대비 refined 결과 행 수의 log 비율에 가중치를 곱한 priority penalty
+/// 어느 한쪽을 모르면 0
+fn cardinality_change_penalty(baseline: Option<i64>, row_count: Option<i64>, weight: f64) -> i32 {
+    let (Some(baseline), Some(row_count)) = (baseline, row_count) else {
+        return 0;
+    };
+    let (larger, smaller) = (baseline.max(row_count) as f64 + 1.0, baseline.min(row_count) as f64 + 1.0);
+    ((larger / smaller).ln() * 100.0 * weight) as i32
+}
+
+/// UPDATE/DELETE 후보를 같은 대상 테이블, FROM/USING, WHERE에 대한 행 수 세기 SELECT로 바꾼다
+/// SET 값과 RETURNING 표현식은 안쪽 SELECT에서 projection해서 함께 검증하고,
+/// 바깥은 whole-row를 세어 planner가 쓰이지 않는 projection을 버리지 못하게 한다
+/// 데이터를 건드리지 않고 문장이 유효하고 대상 행이 비어 있지 않은지 확인하는 용도
+/// WHERE CURRENT OF나 다중 컬럼 SET처럼 SELECT로 옮길 수 없는 문장, 다른 문장은 None
+unsafe fn dml_count_query(rawstmt: *mut pg_sys::RawStmt) -> Option<*mut pg_sys::RawStmt> {
+    unsafe {
+        let stmt = (*rawstmt).stmt;
+        if stmt.is_null() {
+            return None;
+        }
+
+        let stmt = match (*stmt).type_ {
+            pg_sys::NodeTag::T_UpdateStmt | pg_sys::NodeTag::T_DeleteStmt => copy_node(stmt),
+            _ => return None,
+        };
+        let (relation, extra_from, where_clause, with_clause, set_list, returning_list) = if (*stmt).type_ == pg_sys::NodeTag::T_UpdateStmt {
+            let update_stmt = stmt as *mut pg_sys::UpdateStmt;
+            (
+                (*update_stmt).relation, (*update_stmt).fromClause, (*update_stmt).whereClause,
+                (*update_stmt).withClause, (*update_stmt).targetList, (*update_stmt).returningList,
+            )
+        } else {
+            let delete_stmt = stmt as *mut pg_sys::DeleteStmt;
+            (
+                (*delete_stmt).relation, (*delete_stmt).usingClause, (*delete_stmt).whereClause,
+                (*delete_stmt).withClause, std::ptr::null_mut(), (*delete_stmt).returningList,
+            )
+        };
+        if !where_clause.is_null() && (*where_clause).type_ == pg_sys::NodeTag::T_CurrentOfExpr {
+            return None;
+        }
+
+        let rows_raw = rawstmt_from_sql("SELECT 1").ok()?;
+        let rows_select = (*rows_raw).stmt as *mut pg_sys::SelectStmt;
+        (*rows_select).fromClause = pg_sys::lcons(relation as *mut c_void, extra_from);
+        (*rows_select).whereClause = where_clause;
+
+        // DELETE는 SET 목록이 없다
+        let projected = set_list.is_null() || memcx::current_context(|mcx| {
+            let Some(set_targets) = List::<*mut c_void>::downcast_ptr_in_memcx(set_list, mcx) else {
+                return false;
+            };
+            for slot in set_targets.iter() {
+                let res_target = *slot as *mut pg_sys::ResTarget;
+                match (*(*res_target).val).type_ {
+                    pg_sys::NodeTag::T_MultiAssignRef => return false,
+                    // DEFAULT는 항상 유효하다
+                    pg_sys::NodeTag::T_SetToDefault => continue,
+                    _ => {}
                 }
+                // SET 대상 컬럼 이름/첨자는 SELECT 출력 이름이 아니다
+                (*res_target).name = std::ptr::null_mut();
+                (*res_target).indirection = std::ptr::null_mut();
+                (*rows_select).targetList = pg_sys::lappend((*rows_select).targetList, res_target as *mut c_void);
             }
+            true
+        });
+        if !projected {
+            return None;
         }
+        (*rows_select).targetList = pg_sys::list_concat((*rows_select).targetList, returning_list);
+
+        let count_raw = rawstmt_from_sql("SELECT count(dml_rows) FROM (SELECT 1) AS dml_rows").ok()?;
+        let count_select = (*count_raw).stmt as *mut pg_sys::SelectStmt;
+        let subselect = pg_sys::list_nth((*count_select).fromClause, 0) as *mut pg_sys::RangeSubselect;
+        (*subselect).subquery = rows_select as *mut pg_sys::Node;
+        (*count_select).withClause = with_clause;
+        Some(count_raw)
     }
 }
 
+/// INSERT 후보의 입력(SELECT/VALUES)을 `SELECT count(*) FROM (...) AS insert_source`로 감싼 검증 SQL
+/// INSERT 자체는 실행하지 않는다 (INSERT가 아니거나 DEFAULT VALUES면 None)
+unsafe fn insert_source_count_query(rawstmt: *mut pg_sys::RawStmt) -> Option<String> {
+    unsafe {
+        let stmt = (*rawstmt).stmt;
+        if stmt.is_null() || (*stmt).type_ != pg_sys::NodeTag::T_InsertStmt {
+            return None;
+        }
+
+        let insert_stmt = copy_node(stmt as *mut pg_sys::InsertStmt);
+        let source = (*insert_stmt).selectStmt as *mut pg_sys::SelectStmt;
+        if source.is_null() {
+            return None;
+        }
+        // INSERT의 WITH절은 입력 SELECT가 참조할 수 있으므로 함께 옮긴다
+        if (*source).withClause.is_null() {
+            (*source).withClause = (*insert_stmt).withClause;
+        }
+
+        let source_raw = rawstmt_from_sql("SELECT 1").ok()?;
+        (*source_raw).stmt = source as *mut pg_sys::Node;
+        Some(format!("SELECT count(*) FROM ({}) AS insert_source", deparse_raw_stmt(source_raw)))
+    }
+}
 
 /// RawStmt* 로 단 한 번 analyze하고, 에러를 캡처해 돌려준다.
-fn try_analyze_raw_once(source_sql: &str, rawstmt: *mut pg_sys::RawStmt, pstate: *mut pg_sys::ParseState) -> AnalyzeOutcome {
+pub(super) fn try_analyze_raw_once(source_sql: &str, rawstmt: *mut pg_sys::RawStmt, pstate: *mut pg_sys::ParseState) -> AnalyzeOutcome {
     use pgrx::pg_sys::panic::CaughtError;
     
     unsafe {
-        // 현재 컨텍스트와 owner 저장
-        let old_context = pg_sys::CurrentMemoryContext;
-        let old_owner = pg_sys::CurrentResourceOwner;
-        
-        // Subtransaction 시작
-        pg_sys::BeginInternalSubTransaction(std::ptr::null());
-        pg_sys::MemoryContextSwitchTo(old_context);
+        // Subtransaction 시작 (guard가 drop되면 롤백)
+        let subxact = SubTransactionGuard::begin();
+        let old_context = subxact.old_context;
         
         let csql = CString::new(source_sql).unwrap_or_else(|_| CString::new("").unwrap());
         
@@ -769,9 +1761,6 @@ fn try_analyze_raw_once(source_sql: &str, rawstmt: *mut pg_sys::RawStmt, pstate:
                 std::ptr::null_mut(),
             );
             set_subselect_mode(pstate, false);
-            pg_sys::ReleaseCurrentSubTransaction();
-            pg_sys::MemoryContextSwitchTo(old_context);
-            pg_sys::CurrentResourceOwner = old_owner;
             Ok(q)
         })
         .catch_others(|e| {
@@ -796,17 +1785,16 @@ fn try_analyze_raw_once(source_sql: &str, rawstmt: *mut pg_sys::RawStmt, pstate:
                     }
                 }
             };
-
-            pg_sys::RollbackAndReleaseCurrentSubTransaction();
-            pg_sys::MemoryContextSwitchTo(old_context);
-            pg_sys::CurrentResourceOwner = old_owner;
             
             Err(outcome)
         })
         .execute();
         
-        pg_sys::MemoryContextSwitchTo(old_context);
-        pg_sys::CurrentResourceOwner = old_owner;
+        if result.is_ok() {
+            subxact.commit();
+        } else {
+            subxact.rollback();
+        }
         match result {
             Ok(q) => {
                 AnalyzeOutcome::Success(q)
@@ -819,6 +1807,29 @@ fn try_analyze_raw_once(source_sql: &str, rawstmt: *mut pg_sys::RawStmt, pstate:
 }
 
 
+/// deparse한 SQL을 다시 parse해서 candidate RawStmt와 구조가 같은지 확인
+/// (equal()은 location을 비교하지 않는다)
+/// raw_parser는 문법 에러를 ERROR로 던지므로 subtransaction 안에서 비교하고 항상 롤백한다
+fn deparse_round_trips(rawstmt: *mut pg_sys::RawStmt, deparsed_sql: &str) -> bool {
+    unsafe {
+        let subxact = SubTransactionGuard::begin();
+        let old_context = subxact.old_context;
+
+        let round_trips = PgTryBuilder::new(|| match rawstmt_from_sql(deparsed_sql) {
+            Ok(reparsed) => pg_sys::equal((*rawstmt).stmt as *const c_void, (*reparsed).stmt as *const c_void),
+            Err(_) => false,
+        })
+        .catch_others(|_| {
+            pg_sys::MemoryContextSwitchTo(old_context);
+            false
+        })
+        .execute();
+
+        subxact.rollback();
+        round_trips
+    }
+}
+
 /// PostgreSQL의 reportErrorPosition과 동일하게 동작하는 함수
 /// cursor_pos는 1-based byte offset
 fn format_error_position(message: &str, query: &str, cursor_pos: i32, encoding: i32) -> String {
@@ -1081,8 +2092,38 @@ fn get_error_cursor_pos() -> Option<i32> {
 }
 
 
+/// window 함수를 OVER 없이 호출한 에러인지 확인
+pub(super) fn is_missing_over_error(message: &str) -> bool {
+    Regex::new(r"^window function \S+ requires an OVER clause").unwrap().is_match(message)
+}
+
+/// "ORDER BY position N is not in select list" 에러에서 (N, GROUP BY 여부) 추출
+pub(super) fn extract_ordinal_position(message: &str) -> Option<(i32, bool)> {
+    let caps = Regex::new(r"^(ORDER|GROUP) BY position (-?\d+) is not in select list").unwrap().captures(message)?;
+    let position = caps.get(2)?.as_str().parse::<i32>().ok()?;
+    Some((position, &caps[1] == "GROUP"))
+}
+
+/// "CASE types X and Y cannot be matched" 에러에서 (먼저 본 분기 타입 X, 에러 위치 분기 타입 Y) 추출
+pub(super) fn extract_case_branch_types(message: &str) -> Option<(String, String)> {
+    let caps = Regex::new(r"^CASE types (.+?) and (.+?) cannot be matched").unwrap().captures(message)?;
+    Some((caps[1].to_string(), caps[2].to_string()))
+}
+
+/// ORDER BY 식이 select list와 맞지 않는 에러인지 확인 (DISTINCT 정렬, GROUP BY 없는 컬럼)
+pub(super) fn is_order_by_target_error(message: &str) -> bool {
+    message.starts_with("for SELECT DISTINCT, ORDER BY expressions must appear in select list")
+        || message.contains("must appear in the GROUP BY clause or be used in an aggregate function")
+}
+
+/// set-returning 함수를 set을 받을 수 없는 위치에서 호출한 에러인지 확인 (analyze/실행 시점 모두)
+pub(super) fn is_set_returning_context_error(message: &str) -> bool {
+    message.starts_with("set-returning functions are not allowed in")
+        || message.contains("set-returning function called in context that cannot accept a set")
+}
+
 /// 오퍼레이터 타입 에러인지 확인
-fn is_operator_type_error(message: &str) -> bool {
+pub(super) fn is_operator_type_error(message: &str) -> bool {
     let operator_patterns = [
         r"operator does not exist:",
         r"could not identify an equality operator for type",
@@ -1094,7 +2135,7 @@ fn is_operator_type_error(message: &str) -> bool {
     })
 }
 
-fn rawstmt_from_sql(sql: &str) -> Result<*mut pg_sys::RawStmt, String> {
+pub(super) fn rawstmt_from_sql(sql: &str) -> Result<*mut pg_sys::RawStmt, String> {
     let csql = CString::new(sql).map_err(|_| "CString::new failed (interior NUL)".to_string())?;
     unsafe {
         let rawtree = pg_sys::raw_parser(csql.as_ptr(), 0);
@@ -1114,9 +2155,9 @@ fn rawstmt_from_sql(sql: &str) -> Result<*mut pg_sys::RawStmt, String> {
 }
 
 /// PQ 후보 삽입: RawStmt* 를 node_to_string 으로 직렬화해 중복 제거
-/// hop_count가 max_hops를 넘으면 추가하지 않음
+/// hop_count가 max_hops를 넘거나, refined SQL이 max_candidate_sql_len보다 길면 추가하지 않음
 fn push_candidate(
-    pq: &mut BinaryHeap<Reverse<(i32, u64, *mut pg_sys::RawStmt, i32)>>,
+    pq: &mut BinaryHeap<Reverse<(i32, String, u64, *mut pg_sys::RawStmt, i32)>>,
     visited: &mut HashSet<String>,
     prio: i32,
     hop_count: i32,
@@ -1127,24 +2168,48 @@ fn push_candidate(
     if hop_count > MAX_REFINEMENT_HOP.get() {
         return;
     }
-    
+
     let key = unsafe {
         node_to_string(raw as *mut pg_sys::Node).unwrap_or("<ser-failed>")
     };
-    if visited.insert(key.to_string()) {
-        pq.push(Reverse((prio, *seq, raw, hop_count)));
-        *seq += 1;
+    // 이미 본 후보는 deparse하지 않는다
+    if visited.contains(key) {
+        return;
     }
+
+    // 너무 긴 후보(깊은 JOIN 체인 등)는 의도한 수정일 가능성이 낮으므로 버린다 (원본은 제외)
+    let sql = deparse_raw_stmt(raw);
+    let max_sql_len = MAX_CANDIDATE_SQL_LEN.get();
+    if max_sql_len > 0 && hop_count > 0 && sql.len() > max_sql_len as usize {
+        return;
+    }
+
+    visited.insert(key.to_string());
+    // priority가 같으면 생성 순서(seq)가 아니라 deparse된 SQL의 사전순으로 꺼낸다
+    // (생성 순서를 지켜야 하는 generator는 순위를 priority에 반영한다)
+    pq.push(Reverse((prio, sql, *seq, raw, hop_count)));
+    *seq += 1;
 }
 
 
-/// 저장된 에러를 그대로 ereport!
-fn raise_saved_error(_code: Option<PgSqlErrorCode>, message: String) -> ! {
-    pgrx::error!("{}", message);
+/// 저장된 에러를 그대로 ereport! (hint가 있으면 원래 에러 코드와 함께 HINT로 붙인다)
+fn raise_saved_error(code: Option<PgSqlErrorCode>, message: String, hint: Option<String>) -> ! {
+    let Some(hint) = hint else {
+        pgrx::error!("{}", message);
+    };
+
+    ErrorReport::new(
+        code.unwrap_or(PgSqlErrorCode::ERRCODE_INTERNAL_ERROR),
+        message,
+        pgrx::function_name!(),
+    )
+    .set_hint(hint)
+    .report(PgLogLevel::ERROR);
+    unreachable!("ERROR level report does not return")
 }
 
 /// 에러 메시지에서 relation "X" 추출
-fn extract_missing_relation(errmsg: &str) -> Option<String> {
+pub(super) fn extract_missing_relation(errmsg: &str) -> Option<String> {
     // 기존 패턴: relation "hello" does not exist
     let re1 = Regex::new(r#"(?i)relation\s+"([^"]+)"\s+does\s+not\s+exist"#).unwrap();
     if let Some(cap) = re1.captures(errmsg) {
@@ -1160,7 +2225,15 @@ fn extract_missing_relation(errmsg: &str) -> Option<String> {
     None
 }
 
-fn extract_missing_column(errmsg: &str) -> Option<(Option<String>, String)> {
+/// missing FROM-clause entry for table "p" - FROM절에 없는 테이블(또는 alias)을 참조한 경우
+pub(super) fn extract_missing_from_clause_entry(errmsg: &str) -> Option<String> {
+    let re = Regex::new(r#"(?i)missing\s+FROM-clause\s+entry\s+for\s+table\s+"([^"]+)""#).unwrap();
+    re.captures(errmsg)
+        .and_then(|cap| cap.get(1))
+        .map(|m| m.as_str().to_string())
+}
+
+pub(super) fn extract_missing_column(errmsg: &str) -> Option<(Option<String>, String)> {
     // 여러 패턴 시도
     // 1. column "X" does not exist
     // 2. column "X" of relation "Y" does not exist  
@@ -1174,6 +2247,14 @@ fn extract_missing_column(errmsg: &str) -> Option<(Option<String>, String)> {
         return Some((rel, col));
     }
     
+    // Pattern 2-1: column "table"."column" does not exist (quoted 식별자, 대소문자/공백 그대로 유지)
+    let re_quoted = Regex::new(r#"(?i)column\s+"([^"]+)"\."([^"]+)"\s+does\s+not\s+exist"#).unwrap();
+    if let Some(cap) = re_quoted.captures(errmsg) {
+        let rel = cap.get(1).map(|m| m.as_str().to_string());
+        let col = cap.get(2).map(|m| m.as_str().to_string())?;
+        return Some((rel, col));
+    }
+
     // Pattern 2: column table.column does not exist
     let re2 = Regex::new(r#"(?i)column\s+([^.\s]+)\.([^.\s]+)\s+does\s+not\s+exist"#).unwrap();
     if let Some(cap) = re2.captures(errmsg) {
@@ -1221,7 +2302,7 @@ fn extract_missing_column(errmsg: &str) -> Option<(Option<String>, String)> {
 
 /// 에러 메시지에서 함수 정보 추출
 /// 예: "function pg_catalog.extract(unknown, bigint) does not exist"
-fn extract_missing_function(errmsg: &str) -> Option<(String, Vec<String>)> {
+pub(super) fn extract_missing_function(errmsg: &str) -> Option<(String, Vec<String>)> {
     // Pattern 1: function schema.function_name(arg1, arg2, ...) does not exist
     let re1 = Regex::new(r#"(?i)function\s+(?:[^.]+\.)?([^(]+)\(([^)]*)\)\s+does\s+not\s+exist"#).unwrap();
     if let Some(cap) = re1.captures(errmsg) {
@@ -1261,8 +2342,15 @@ fn extract_missing_function(errmsg: &str) -> Option<(String, Vec<String>)> {
     None
 }
 
+/// "function schema.name(...) does not exist" 에러에서 schema 한정자 추출 (한정되지 않았으면 None)
+pub(super) fn extract_missing_function_schema(errmsg: &str) -> Option<String> {
+    let re = Regex::new(r#"(?i)function\s+("[^"]+"|[^.\s(]+)\.[^(]+\([^)]*\)\s+does\s+not\s+exist"#).unwrap();
+    let schema = re.captures(errmsg)?.get(1)?.as_str();
+    Some(schema.trim_matches('"').to_string())
+}
+
 /// 에러 메시지에서 ambiguous column name 추출
-fn extract_ambiguous_column(errmsg: &str) -> Option<String> {
+pub(super) fn extract_ambiguous_column(errmsg: &str) -> Option<String> {
     // Pattern 1: column reference "column_name" is ambiguous
     let re1 = Regex::new(r#"(?i)column\s+reference\s+"([^"]+)"\s+is\s+ambiguous"#).unwrap();
     if let Some(cap) = re1.captures(errmsg) {
@@ -1281,53 +2369,179 @@ fn extract_ambiguous_column(errmsg: &str) -> Option<String> {
         return cap.get(1).map(|m| m.as_str().to_string());
     }
     
-    None
+    None
+}
+
+/// 에러 cursor와 노드 location 사이에 허용하는 최대 오차 (문자 수)
+const POSITION_MATCH_WINDOW: i32 = 10;
+
+/// 에러 위치에서 함수 호출들 찾기
+///
+/// 반환값은 (FuncCall 노드, 해당 호출의 cursor 위치 = location + 1) 목록이다.
+/// 첫 번째 항목이 에러 위치에 대응하는 호출이고, 나머지는 그 인자 안에 중첩된 호출들이다.
+/// 정확히 일치하는 호출이 없으면 에러 위치를 범위 안에 포함하는 가장 안쪽 호출을 고른다.
+unsafe fn find_function_calls_at_position(
+    raw: *mut pg_sys::RawStmt,
+    error_pos: i32
+) -> Vec<(*mut pg_sys::Node, i32)> {
+    let mut all_calls: Vec<*mut pg_sys::Node> = Vec::new();
+    let ctx_ptr = &mut all_calls as *mut Vec<*mut pg_sys::Node> as *mut c_void;
+    
+    unsafe {
+        safe_raw_expression_tree_walker(
+            (*raw).stmt,
+            Some(collect_function_calls_walker),
+            ctx_ptr
+        );
+    }
+
+    let call_pos = |node: *mut pg_sys::Node| unsafe { (*(node as *mut pg_sys::FuncCall)).location + 1 };
+
+    // 1) 정확히 일치하는 호출
+    // 2) 에러 위치가 호출 범위(이름 ~ 마지막 하위 노드) 안에 있는 가장 안쪽 호출 (enclosing)
+    // 3) 에러 위치 이후에 시작하는 가장 가까운 호출
+    let exact = all_calls.iter().copied().find(|&n| call_pos(n) == error_pos);
+    let enclosing = all_calls.iter().copied()
+        .filter(|&n| call_pos(n) < error_pos && error_pos <= unsafe { function_call_span_end(n) })
+        .max_by_key(|&n| call_pos(n));
+    let following = all_calls.iter().copied()
+        .filter(|&n| call_pos(n) > error_pos && call_pos(n) - error_pos <= POSITION_MATCH_WINDOW)
+        .min_by_key(|&n| call_pos(n));
+
+    let target = match exact.or(enclosing).or(following) {
+        Some(node) => node,
+        None => return Vec::new(),
+    };
+
+    let mut function_calls = vec![(target, call_pos(target))];
+
+    // 인자 안의 중첩 호출도 함께 반환 (calcSum(convertToNumber(col)) 의 convertToNumber)
+    let mut nested: Vec<*mut pg_sys::Node> = Vec::new();
+    let nested_ptr = &mut nested as *mut Vec<*mut pg_sys::Node> as *mut c_void;
+    unsafe {
+        safe_raw_expression_tree_walker(target, Some(collect_function_calls_walker), nested_ptr);
+    }
+    for node in nested {
+        function_calls.push((node, call_pos(node)));
+    }
+
+    function_calls
+}
+
+/// FuncCall이 차지하는 cursor 범위의 끝 (하위 노드 중 가장 뒤에서 시작하는 노드의 위치 + 1)
+unsafe fn function_call_span_end(func_call_node: *mut pg_sys::Node) -> i32 {
+    let mut last = unsafe { (*(func_call_node as *mut pg_sys::FuncCall)).location + 1 };
+    let ctx_ptr = &mut last as *mut i32 as *mut c_void;
+
+    unsafe {
+        safe_raw_expression_tree_walker(func_call_node, Some(last_location_walker), ctx_ptr);
+    }
+    last
+}
+
+unsafe extern "C" fn last_location_walker(
+    node: *mut pg_sys::Node,
+    ctx: *mut c_void
+) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        let last = &mut *(ctx as *mut i32);
+        let location = pg_sys::exprLocation(node);
+        if location >= 0 {
+            *last = (*last).max(location + 1);
+        }
+
+        safe_raw_expression_tree_walker(node, Some(last_location_walker), ctx)
+    }
+}
+
+/// FuncCall 인자들의 타입 이름 (에러 메시지에는 바깥 호출의 인자 타입만 나오므로 중첩 호출용)
+/// 리터럴과 명시적 캐스트만 타입을 알 수 있고, 나머지는 "unknown"
+unsafe fn function_call_arg_types(func_call_node: *mut pg_sys::Node) -> Vec<String> {
+    unsafe {
+        let func_call = func_call_node as *mut pg_sys::FuncCall;
+        if (*func_call).args.is_null() {
+            return Vec::new();
+        }
+
+        memcx::current_context(|mcx| {
+            let Some(args) = List::<*mut c_void>::downcast_ptr_in_memcx((*func_call).args, mcx) else {
+                return Vec::new();
+            };
+            (0..args.len())
+                .filter_map(|i| args.get(i))
+                .map(|arg| literal_type_name(*arg as *mut pg_sys::Node).unwrap_or_else(|| "unknown".to_string()))
+                .collect()
+        })
+    }
+}
+
+unsafe extern "C" fn collect_function_calls_walker(
+    node: *mut pg_sys::Node,
+    ctx: *mut c_void
+) -> bool {
+    if node.is_null() {
+        return false;
+    }
+    
+    unsafe {
+        let function_calls = &mut *(ctx as *mut Vec<*mut pg_sys::Node>);
+        
+        if (*node).type_ == pg_sys::NodeTag::T_FuncCall && (*(node as *mut pg_sys::FuncCall)).location >= 0 {
+            function_calls.push(node);
+        }
+        
+        safe_raw_expression_tree_walker(node, Some(collect_function_calls_walker), ctx)
+    }
 }
 
-/// 에러 위치에서 함수 호출들 찾기
-unsafe fn find_function_calls_at_position(
-    raw: *mut pg_sys::RawStmt,
-    error_pos: i32
-) -> Vec<*mut pg_sys::Node> {
-    let mut function_calls = Vec::new();
-    let ctx = (&mut function_calls, error_pos);
-    let ctx_ptr = &ctx as *const _ as *mut c_void;
-    
+/// set-returning 함수인 FuncCall들의 위치(location + 1) 목록
+unsafe fn find_set_returning_function_calls(raw: *mut pg_sys::RawStmt) -> Vec<i32> {
+    let mut all_calls: Vec<*mut pg_sys::Node> = Vec::new();
+    let ctx_ptr = &mut all_calls as *mut Vec<*mut pg_sys::Node> as *mut c_void;
+
     unsafe {
         safe_raw_expression_tree_walker(
             (*raw).stmt,
-            Some(find_function_call_at_position_walker),
+            Some(collect_function_calls_walker),
             ctx_ptr
         );
+
+        all_calls
+            .into_iter()
+            .filter(|&node| function_call_name(node).is_some_and(|name| is_set_returning_function(&name)))
+            .map(|node| (*(node as *mut pg_sys::FuncCall)).location + 1)
+            .collect()
     }
-    
-    function_calls
 }
 
-unsafe extern "C" fn find_function_call_at_position_walker(
-    node: *mut pg_sys::Node,
-    ctx: *mut c_void
-) -> bool {
-    if node.is_null() {
-        return false;
+/// FuncCall 노드의 함수명 (스키마 제외 마지막 이름)
+unsafe fn function_call_name(func_call_node: *mut pg_sys::Node) -> Option<String> {
+    if func_call_node.is_null() {
+        return None;
     }
-    
+
     unsafe {
-        let (function_calls, target_pos) = &mut *(ctx as *mut (&mut Vec<*mut pg_sys::Node>, i32));
-        
-        // FuncCall 노드에서 location 확인
-        if (*node).type_ == pg_sys::NodeTag::T_FuncCall {
-            let func_call = node as *mut pg_sys::FuncCall;
-            let location = (*func_call).location;
-            
-            // location이 에러 위치 근처에 있으면 추가
-            if location >= 0 && location + 1 == *target_pos {
-                function_calls.push(node);
-                return false; // 찾았으므로 중단
-            }
+        if (*func_call_node).type_ != pg_sys::NodeTag::T_FuncCall {
+            return None;
         }
-        
-        safe_raw_expression_tree_walker(node, Some(find_function_call_at_position_walker), ctx)
+        let func_call = func_call_node as *mut pg_sys::FuncCall;
+        if (*func_call).funcname.is_null() {
+            return None;
+        }
+
+        memcx::current_context(|mcx| {
+            let names = List::<*mut c_void>::downcast_ptr_in_memcx((*func_call).funcname, mcx)?;
+            let last = *names.get(names.len().checked_sub(1)?)? as *mut pg_sys::Node;
+            if (*last).type_ != pg_sys::NodeTag::T_String {
+                return None;
+            }
+            let str_node = last as *mut pg_sys::String;
+            Some(CStr::from_ptr((*str_node).sval).to_string_lossy().into_owned())
+        })
     }
 }
 
@@ -1368,14 +2582,27 @@ unsafe fn extract_argument_operands_from_function_call(
 }
 
 
+/// 에러 cursor가 연산자 토큰이 아닌 곳(피연산자 사이 공백 등)을 가리킬 때 허용하는 거리
+const EXPRESSION_POSITION_WINDOW: i32 = 8;
+
+/// 위치 정보와 함께 모은 A_Expr / ANY·ALL SubLink (location은 0-based)
+struct PositionedExpr {
+    node: *mut pg_sys::Node,
+    op_location: i32,
+    start: i32, // 왼쪽 피연산자 시작
+    end: i32,   // 오른쪽 피연산자 시작
+}
+
 /// 에러 위치에서 표현식들 찾기
+/// 1) 연산자 위치가 cursor와 정확히 같은 A_Expr
+/// 2) 없으면 cursor를 감싸는 가장 안쪽 A_Expr
+/// 3) 그것도 없으면 cursor에서 EXPRESSION_POSITION_WINDOW 안의 가장 가까운 연산자
 unsafe fn find_expressions_at_position(
     raw: *mut pg_sys::RawStmt,
     error_pos: i32
 ) -> Vec<*mut pg_sys::Node> {
-    let mut expressions = Vec::new();
-    let ctx = (&mut expressions, error_pos);
-    let ctx_ptr = &ctx as *const _ as *mut c_void;
+    let mut positioned: Vec<PositionedExpr> = Vec::new();
+    let ctx_ptr = &mut positioned as *mut Vec<PositionedExpr> as *mut c_void;
     
     unsafe {
         safe_raw_expression_tree_walker(
@@ -1384,8 +2611,31 @@ unsafe fn find_expressions_at_position(
             ctx_ptr
         );
     }
-    
-    expressions
+
+    // cursor는 1-based
+    let target = error_pos - 1;
+
+    let exact: Vec<*mut pg_sys::Node> = positioned.iter()
+        .filter(|e| e.op_location == target)
+        .map(|e| e.node)
+        .collect();
+    if !exact.is_empty() {
+        return exact;
+    }
+
+    if let Some(enclosing) = positioned.iter()
+        .filter(|e| e.start <= target && target <= e.end + EXPRESSION_POSITION_WINDOW)
+        .min_by_key(|e| e.end - e.start)
+    {
+        return vec![enclosing.node];
+    }
+
+    positioned.iter()
+        .map(|e| (e, (e.op_location - target).abs()))
+        .filter(|(_, dist)| *dist <= EXPRESSION_POSITION_WINDOW)
+        .min_by_key(|(_, dist)| *dist)
+        .map(|(e, _)| vec![e.node])
+        .unwrap_or_default()
 }
 
 unsafe extern "C" fn find_expr_at_position_walker(
@@ -1397,17 +2647,34 @@ unsafe extern "C" fn find_expr_at_position_walker(
     }
     
     unsafe {
-        let (expressions, target_pos) = &mut *(ctx as *mut (&mut Vec<*mut pg_sys::Node>, i32));
+        let positioned = &mut *(ctx as *mut Vec<PositionedExpr>);
         
-        // A_Expr 노드에서 location 확인
+        // A_Expr의 연산자/피연산자 위치 수집
         if (*node).type_ == pg_sys::NodeTag::T_A_Expr {
             let a_expr = node as *mut pg_sys::A_Expr;
-            let location = (*a_expr).location;
+            let op_location = (*a_expr).location;
             
-            // location이 에러 위치 근처에 있으면 추가 (±5 char 범위)
-            if location >= 0 && location + 1 == *target_pos {
-                expressions.push(node);
-                return false; // 찾았으므로 중단
+            if op_location >= 0 {
+                let start = match pg_sys::exprLocation((*a_expr).lexpr) {
+                    loc if loc >= 0 => loc.min(op_location),
+                    _ => op_location,
+                };
+                let end = match pg_sys::exprLocation((*a_expr).rexpr) {
+                    loc if loc >= 0 => loc.max(op_location),
+                    _ => op_location,
+                };
+                positioned.push(PositionedExpr { node, op_location, start, end });
+            }
+        } else if let Some(sublink) = any_all_sublink(node) {
+            // `x = ANY (SELECT ...)`: location은 연산자 위치, 오른쪽은 서브쿼리
+            let op_location = (*sublink).location;
+
+            if op_location >= 0 {
+                let start = match pg_sys::exprLocation((*sublink).testexpr) {
+                    loc if loc >= 0 => loc.min(op_location),
+                    _ => op_location,
+                };
+                positioned.push(PositionedExpr { node, op_location, start, end: op_location });
             }
         }
         
@@ -1417,8 +2684,10 @@ unsafe extern "C" fn find_expr_at_position_walker(
 
 
 /// 표현식에서 left/right ColumnRef 오퍼랜드들 추출
+/// (에러 메시지의 오퍼레이터 타입이 있으면 각 오퍼랜드의 resolved_type으로 채운다)
 unsafe fn extract_column_operands_from_expr(
-    expr_node: *mut pg_sys::Node
+    expr_node: *mut pg_sys::Node,
+    error_operator_info: Option<&OperatorInfo>,
 ) -> Option<(Option<ColumnOperand>, Option<ColumnOperand>)> {
     if expr_node.is_null() {
         return None;
@@ -1431,13 +2700,15 @@ unsafe fn extract_column_operands_from_expr(
             // 왼쪽 오퍼랜드 추출
             let left_operand = if !(*a_expr).lexpr.is_null() {
                 extract_column_ref_operand((*a_expr).lexpr)
+                    .map(|op| with_resolved_type(op, error_operator_info.and_then(|info| info.left_type.as_ref())))
             } else {
                 None
             };
             
-            // 오른쪽 오퍼랜드 추출
-            let right_operand = if !(*a_expr).rexpr.is_null() {
+            // 오른쪽 오퍼랜드 추출 (ANY/ALL이면 오른쪽은 배열이므로 제외)
+            let right_operand = if !(*a_expr).rexpr.is_null() && !is_any_all_expr(expr_node) {
                 extract_column_ref_operand((*a_expr).rexpr)
+                    .map(|op| with_resolved_type(op, error_operator_info.and_then(|info| info.right_type.as_ref())))
             } else {
                 None
             };
@@ -1448,11 +2719,78 @@ unsafe fn extract_column_operands_from_expr(
                 return Some((None, None));
             }
         }
+
+        // `x op ANY/ALL (SELECT ...)`는 스칼라 쪽만 오퍼랜드로 본다
+        if let Some(sublink) = any_all_sublink(expr_node) {
+            let left_operand = extract_column_ref_operand((*sublink).testexpr)
+                .map(|op| with_resolved_type(op, error_operator_info.and_then(|info| info.left_type.as_ref())));
+            return Some((left_operand, None));
+        }
     }
     
     None
 }
 
+/// 오퍼랜드에 추출 시점에 알아낸 컬럼 타입을 붙인다
+fn with_resolved_type(operand: ColumnOperand, resolved_type: Option<&String>) -> ColumnOperand {
+    ColumnOperand {
+        resolved_type: resolved_type.cloned(),
+        ..operand
+    }
+}
+
+/// 한쪽이 ColumnRef이고 다른 쪽이 리터럴인 표현식에서, 리터럴 쪽과 캐스팅할 컬럼 타입 추출
+/// (컬럼 타입은 "operator does not exist: date = text" 에러 메시지의 타입을 사용)
+unsafe fn extract_literal_operand_from_expr(
+    expr_node: *mut pg_sys::Node,
+    error_operator_info: &OperatorInfo,
+) -> Option<LiteralOperand> {
+    if expr_node.is_null() {
+        return None;
+    }
+
+    unsafe {
+        // ANY/ALL의 오른쪽은 배열이므로 스칼라 컬럼 타입으로 캐스팅할 수 없다
+        if (*expr_node).type_ != pg_sys::NodeTag::T_A_Expr || is_any_all_expr(expr_node) {
+            return None;
+        }
+        let a_expr = expr_node as *mut pg_sys::A_Expr;
+
+        let (position, column_type) = if is_literal_operand((*a_expr).lexpr)
+            && extract_column_ref_operand((*a_expr).rexpr).is_some()
+        {
+            (OperandPosition::Left, error_operator_info.right_type.clone()?)
+        } else if is_literal_operand((*a_expr).rexpr)
+            && extract_column_ref_operand((*a_expr).lexpr).is_some()
+        {
+            (OperandPosition::Right, error_operator_info.left_type.clone()?)
+        } else {
+            return None;
+        };
+
+        Some(LiteralOperand {
+            position,
+            expr_location: (*a_expr).location,
+            column_type,
+        })
+    }
+}
+
+/// A_Const 또는 A_Const에 대한 TypeCast인지 확인
+unsafe fn is_literal_operand(node: *mut pg_sys::Node) -> bool {
+    if node.is_null() {
+        return false;
+    }
+
+    unsafe {
+        match (*node).type_ {
+            pg_sys::NodeTag::T_A_Const => true,
+            pg_sys::NodeTag::T_TypeCast => is_literal_operand((*(node as *mut pg_sys::TypeCast)).arg),
+            _ => false,
+        }
+    }
+}
+
 /// 단일 노드에서 ColumnRef 추출
 unsafe fn extract_column_ref_operand(node: *mut pg_sys::Node) -> Option<ColumnOperand> {
     if node.is_null() {
@@ -1479,6 +2817,7 @@ unsafe fn extract_column_ref_operand(node: *mut pg_sys::Node) -> Option<ColumnOp
                                     return Some(ColumnOperand {
                                         table_name: None,
                                         column_name,
+                                        resolved_type: None,
                                     });
                                 }
                             }
@@ -1501,6 +2840,7 @@ unsafe fn extract_column_ref_operand(node: *mut pg_sys::Node) -> Option<ColumnOp
                                     return Some(ColumnOperand {
                                         table_name: Some(table_name),
                                         column_name,
+                                        resolved_type: None,
                                     });
                                 }
                             }
@@ -1528,4 +2868,1044 @@ unsafe fn set_subselect_mode(pstate: *mut pg_sys::ParseState, enable: bool) {
             (*pstate).p_expr_kind = pg_sys::ParseExprKind::EXPR_KIND_NONE;
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use super::*;
+    use pgrx::prelude::*;
+    use crate::safeql::cache::generate_cache_key;
+    use crate::safeql::fixtures::{reset_vector_catalogs, seed_cached_result};
+    use crate::safeql::score::TypecastRefinement;
+    use crate::gucs::parser::{ENABLE_TYPE_BASED_REFINEMENT, TOP_K_EXPANSION};
+
+    #[pg_test]
+    fn test_find_nested_function_call_arguments() {
+        let sql = "SELECT calcsum(converttonumber(amont)) FROM orders";
+        let raw = rawstmt_from_sql(sql).unwrap();
+        let outer_pos = sql.find("calcsum").unwrap() as i32 + 1;
+        let inner_pos = sql.find("converttonumber").unwrap() as i32 + 1;
+
+        // cursor가 바깥 함수 시작점과 정확히 일치하지 않아도 감싸는 호출을 찾는다
+        let calls = unsafe { find_function_calls_at_position(raw, outer_pos + 3) };
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].1, outer_pos);
+        assert_eq!(calls[1].1, inner_pos);
+        assert_eq!(unsafe { function_call_name(calls[1].0) }.as_deref(), Some("converttonumber"));
+
+        // 바깥 호출의 인자는 컬럼이 아니고, refinement 대상 컬럼은 중첩 호출의 인자이다
+        let outer_args = unsafe { extract_argument_operands_from_function_call(calls[0].0) }.unwrap();
+        assert!(outer_args[0].is_none());
+        let inner_args = unsafe { extract_argument_operands_from_function_call(calls[1].0) }.unwrap();
+        assert_eq!(inner_args[0].as_ref().unwrap().column_name, "amont");
+
+        // 중첩 호출의 인자 타입은 그 호출의 인자에서 구한다
+        let sql = "SELECT calcsum(converttonumber(amont, 2, '1'::date)) FROM orders";
+        let raw = rawstmt_from_sql(sql).unwrap();
+        let calls = unsafe { find_function_calls_at_position(raw, sql.find("calcsum").unwrap() as i32 + 1) };
+        assert_eq!(unsafe { function_call_arg_types(calls[0].0) }, vec!["unknown"]);
+        assert_eq!(unsafe { function_call_arg_types(calls[1].0) }, vec!["unknown", "integer", "date"]);
+    }
+
+    #[pg_test]
+    fn test_find_function_call_ignores_position_past_call() {
+        let sql = "SELECT lower(a), bb FROM orders";
+        let raw = rawstmt_from_sql(sql).unwrap();
+        let a_pos = sql.find("(a)").unwrap() as i32 + 2;
+        let bb_pos = sql.find("bb").unwrap() as i32 + 1;
+
+        // lower( ... ) 안의 위치는 lower 호출로 본다
+        assert_eq!(unsafe { find_function_calls_at_position(raw, a_pos) }.len(), 1);
+        // 호출이 끝난 뒤의 위치는 시작점이 가까워도 lower 호출이 아니다
+        assert!(unsafe { find_function_calls_at_position(raw, bb_pos) }.is_empty());
+    }
+
+    #[pg_test]
+    fn test_find_function_call_exact_position_preferred() {
+        let sql = "SELECT calcsum(converttonumber(amont)) FROM orders";
+        let raw = rawstmt_from_sql(sql).unwrap();
+        let inner_pos = sql.find("converttonumber").unwrap() as i32 + 1;
+
+        let calls = unsafe { find_function_calls_at_position(raw, inner_pos) };
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].1, inner_pos);
+    }
+
+    #[pg_test]
+    fn test_division_by_zero_refined_with_nullif() {
+        Spi::run("CREATE TABLE div_stats (total int, cnt int)").unwrap();
+        Spi::run("INSERT INTO div_stats VALUES (10, 0)").unwrap();
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search("SELECT total / cnt FROM div_stats", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let sql = deparse_raw_stmt(refined);
+        assert!(sql.contains("NULLIF(cnt, 0)"), "{}", sql);
+    }
+
+    #[pg_test]
+    fn test_srf_in_scalar_context_moved_to_lateral() {
+        Spi::run("CREATE TABLE srf_items (id int, n int)").unwrap();
+        Spi::run("INSERT INTO srf_items VALUES (1, 2)").unwrap();
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search("SELECT id, coalesce(generate_series(1, n), 0) FROM srf_items", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let sql = deparse_raw_stmt(refined);
+        assert!(sql.contains("LATERAL generate_series(1, n) srf"), "{}", sql);
+        assert!(sql.contains("COALESCE(srf, 0)"), "{}", sql);
+        assert_eq!(Spi::get_one::<i64>(&format!("SELECT count(*) FROM ({}) q", sql)).unwrap(), Some(2));
+    }
+
+    #[pg_test]
+    fn test_overlong_candidate_rejected() {
+        let short_sql = "SELECT a FROM t";
+        let long_sql = format!(
+            "SELECT {} FROM t",
+            (0..50).map(|i| format!("col_{}", i)).collect::<Vec<_>>().join(", ")
+        );
+        Spi::run("SET safeql.max_candidate_sql_len = 100").unwrap();
+
+        let mut pq = BinaryHeap::new();
+        let mut visited = HashSet::new();
+        let mut seq = 0;
+        push_candidate(&mut pq, &mut visited, 0, 1, &mut seq, rawstmt_from_sql(&long_sql).unwrap());
+        assert!(pq.is_empty());
+        push_candidate(&mut pq, &mut visited, 0, 1, &mut seq, rawstmt_from_sql(short_sql).unwrap());
+        assert_eq!(pq.len(), 1);
+
+        // 원본(hop 0)은 길이와 상관없이 넣는다
+        push_candidate(&mut pq, &mut visited, 0, 0, &mut seq, rawstmt_from_sql(&long_sql).unwrap());
+        assert_eq!(pq.len(), 2);
+    }
+
+    #[test]
+    fn test_extract_missing_column_preserves_quoted_case() {
+        assert_eq!(
+            extract_missing_column(r#"column "UserName" does not exist"#),
+            Some((None, "UserName".to_string()))
+        );
+        assert_eq!(
+            extract_missing_column("column u.UserName does not exist"),
+            Some((Some("u".to_string()), "UserName".to_string()))
+        );
+        assert_eq!(
+            extract_missing_column(r#"column "Users"."User Name" does not exist"#),
+            Some((Some("Users".to_string()), "User Name".to_string()))
+        );
+        assert_eq!(
+            extract_missing_column(r#"column "UserName" of relation "Accounts" does not exist"#),
+            Some((Some("Accounts".to_string()), "UserName".to_string()))
+        );
+    }
+
+    #[pg_test]
+    fn test_equal_priority_tie_break_is_deterministic() {
+        let pop_order = |first: &str, second: &str| {
+            let mut pq = BinaryHeap::new();
+            let mut visited = HashSet::new();
+            let mut seq = 0;
+            push_candidate(&mut pq, &mut visited, 10, 1, &mut seq, rawstmt_from_sql(first).unwrap());
+            push_candidate(&mut pq, &mut visited, 10, 1, &mut seq, rawstmt_from_sql(second).unwrap());
+            let mut order = Vec::new();
+            while let Some(Reverse((_, sql, _, _, _))) = pq.pop() {
+                order.push(sql);
+            }
+            order
+        };
+
+        // 넣는 순서와 상관없이 같은 후보가 먼저 나온다
+        let expected = vec!["SELECT a FROM t".to_string(), "SELECT b FROM t".to_string()];
+        assert_eq!(pop_order("SELECT b FROM t", "SELECT a FROM t"), expected);
+        assert_eq!(pop_order("SELECT a FROM t", "SELECT b FROM t"), expected);
+    }
+
+    #[pg_test]
+    fn test_slow_candidate_times_out() {
+        Spi::run("SET safeql.candidate_statement_timeout = 50").unwrap();
+
+        let started = std::time::Instant::now();
+        let raw = rawstmt_from_sql("SELECT pg_sleep(5)").unwrap();
+        assert!(matches!(try_execute_query(raw, ""), ExecutionOutcome::ExecutionError(_)));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        // 타이머는 해제되고 다음 후보는 정상 실행된다
+        assert!(!unsafe { pg_sys::get_timeout_active(pg_sys::TimeoutId::STATEMENT_TIMEOUT) });
+        let raw = rawstmt_from_sql("SELECT 1").unwrap();
+        assert!(matches!(try_execute_query(raw, ""), ExecutionOutcome::Success { .. }));
+    }
+
+    #[pg_test]
+    fn test_execution_outcome_variants() {
+        Spi::run("CREATE TABLE outcome_items (id int)").unwrap();
+        let run = |sql: &str| try_execute_query(rawstmt_from_sql(sql).unwrap(), "");
+
+        assert!(matches!(run("SELECT 1"), ExecutionOutcome::Success { row_count: Some(1) }));
+        assert!(matches!(run("SELECT id FROM outcome_items"), ExecutionOutcome::EmptyResult));
+        assert!(matches!(run("DELETE FROM outcome_items WHERE id = 1"), ExecutionOutcome::EmptyResult));
+        assert!(matches!(run("SELECT 1 / 0"), ExecutionOutcome::DivisionByZero { .. }));
+        assert!(matches!(
+            run("SELECT * FROM generate_series(1, 10, 0)"),
+            ExecutionOutcome::ArgumentFormatError { .. }
+        ));
+        match run("SELECT 'abc'::int") {
+            ExecutionOutcome::ExecutionError(message) => assert!(message.contains("invalid input syntax"), "{}", message),
+            _ => panic!("expected an execution error"),
+        }
+
+        // SQLSTATE와 메시지가 모두 맞아야 SetReturningFunctionError
+        let srf_message = "set-valued function called in context that cannot accept a set".to_string();
+        let outcome = ExecutionErrorInfo {
+            kind: ExecutionErrorKind::PostgresError,
+            code: Some(PgSqlErrorCode::ERRCODE_FEATURE_NOT_SUPPORTED),
+            message: srf_message.clone(),
+        }
+        .into_outcome();
+        assert!(matches!(outcome, ExecutionOutcome::SetReturningFunctionError { .. }));
+        let outcome = ExecutionErrorInfo {
+            kind: ExecutionErrorKind::PostgresError,
+            code: Some(PgSqlErrorCode::ERRCODE_INTERNAL_ERROR),
+            message: srf_message,
+        }
+        .into_outcome();
+        assert!(matches!(outcome, ExecutionOutcome::ExecutionError(_)));
+
+        // 빈 결과는 메시지 내용과 상관없이 EmptyResult
+        let mut empty = ExecutionErrorInfo::empty_result();
+        empty.message = "EXECUTION_FAILED".to_string();
+        assert!(matches!(empty.into_outcome(), ExecutionOutcome::EmptyResult));
+    }
+
+    #[pg_test]
+    fn test_refinement_case_order_picks_first_listed_case() {
+        Spi::run("CREATE TABLE co_items (id int, color text)").unwrap();
+        Spi::run("INSERT INTO co_items VALUES (1, 'blue'), (2, NULL)").unwrap();
+        Spi::run("SET safeql.enable_null_handling_refinement = on").unwrap();
+
+        // 임베딩 없이 'blu'에 가장 가까운 값을 캐시에 넣어둔다
+        let key = generate_cache_key("similar_values", &["co_items", "color", "blu"]);
+        let similar = vec![("blue".to_string(), "co_items".to_string(), "color".to_string(), 0.1f32)];
+        seed_cached_result(&key, "similar_values", &similar);
+
+        let refine = || {
+            let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+            let refined = perform_refinement_search("SELECT id FROM co_items WHERE color = 'blu'", pstate);
+            unsafe { pg_sys::free_parsestate(pstate) };
+            deparse_raw_stmt(refined)
+        };
+
+        // 모르는 case 이름은 무시한다
+        Spi::run("SET safeql.refinement_case_order = 'value, no_such_case, null_handling'").unwrap();
+        assert_eq!(refinement_case_order(), Some(vec!["value".to_string(), "null_handling".to_string()]));
+        let sql = refine();
+        assert!(sql.contains("color = 'blue'"), "{}", sql);
+
+        // 순서를 바꾸면 NULL 허용 후보가 먼저 채택된다
+        Spi::run("SET safeql.refinement_case_order = 'null_handling, value'").unwrap();
+        let sql = refine();
+        assert!(sql.contains("IS NULL"), "{}", sql);
+        assert!(!sql.contains("'blue'"), "{}", sql);
+
+        // 목록에 없는 case는 꺼진다
+        Spi::run("SET safeql.refinement_case_order = 'column'").unwrap();
+        assert_eq!(refine(), "SELECT id FROM co_items WHERE color = 'blu'");
+    }
+
+    #[pg_test]
+    fn test_refined_plan_notice() {
+        Spi::run("CREATE TABLE plan_items (id int, name text)").unwrap();
+        let raw = rawstmt_from_sql("SELECT name FROM plan_items WHERE id = 1").unwrap();
+
+        // 기본값은 EXPLAIN을 돌리지 않는다
+        assert_eq!(refined_plan_notice(raw), None);
+
+        Spi::run("SET safeql.enable_refined_plan_notice = on").unwrap();
+        let before = unsafe { pg_sys::GetCurrentTransactionNestLevel() };
+        let notice = refined_plan_notice(raw).expect("plan notice should be emitted when enabled");
+        assert!(notice.starts_with("SafeQL refined query plan:\n"), "{}", notice);
+        assert!(notice.contains("Seq Scan on plan_items"), "{}", notice);
+        assert!(notice.contains("cost="), "{}", notice);
+        assert_eq!(unsafe { pg_sys::GetCurrentTransactionNestLevel() }, before);
+
+        // DML은 실행하지 않고 계획만 본다
+        let raw = rawstmt_from_sql("DELETE FROM plan_items").unwrap();
+        assert!(refined_plan_notice(raw).is_some_and(|notice| notice.contains("Delete on plan_items")));
+    }
+
+    #[pg_test]
+    fn test_missing_over_refined() {
+        Spi::run("CREATE TABLE over_items (id int, name text)").unwrap();
+        Spi::run("INSERT INTO over_items VALUES (1, 'a'), (2, 'b')").unwrap();
+
+        assert!(is_missing_over_error("window function row_number requires an OVER clause"));
+        assert!(!is_missing_over_error("function row_number() does not exist"));
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search("SELECT name, row_number() FROM over_items", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        assert_eq!(deparse_raw_stmt(refined), "SELECT name, row_number() OVER () FROM over_items");
+    }
+
+    #[pg_test]
+    fn test_function_arity_refined() {
+        Spi::run("CREATE TABLE arity_items (id int, name text)").unwrap();
+        Spi::run("INSERT INTO arity_items VALUES (1, 'abc'), (2, 'def')").unwrap();
+        Spi::run("SET safeql.refinement_case_order = 'function_arity'").unwrap();
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search("SELECT substr(name) FROM arity_items", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        // 인자를 하나 채운 overload가 두 개를 채운 것보다 먼저 선택된다
+        assert_eq!(deparse_raw_stmt(refined), "SELECT substr(name, 1) FROM arity_items");
+    }
+
+    #[pg_test]
+    fn test_order_by_unprojected_aggregate_refined() {
+        Spi::run("CREATE TABLE ob_items (id int, name text, amount int)").unwrap();
+        Spi::run("INSERT INTO ob_items VALUES (1, 'a', 10), (2, 'b', 5), (3, 'a', 1)").unwrap();
+
+        assert!(is_order_by_target_error(
+            "column \"ob_items.name\" must appear in the GROUP BY clause or be used in an aggregate function"
+        ));
+        assert!(is_order_by_target_error("for SELECT DISTINCT, ORDER BY expressions must appear in select list"));
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search("SELECT name FROM ob_items ORDER BY sum(amount)", pstate);
+        assert_eq!(deparse_raw_stmt(refined), "SELECT name FROM ob_items GROUP BY name ORDER BY sum(amount)");
+
+        // DISTINCT는 ORDER BY 식을 select list에 추가한다
+        let refined = perform_refinement_search("SELECT DISTINCT name FROM ob_items ORDER BY amount", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+        assert_eq!(deparse_raw_stmt(refined), "SELECT DISTINCT name, amount FROM ob_items ORDER BY amount");
+    }
+
+    #[pg_test]
+    fn test_ordinal_position_refined() {
+        Spi::run("CREATE TABLE op_items (id int, name text)").unwrap();
+        Spi::run("INSERT INTO op_items VALUES (1, 'a'), (2, 'b')").unwrap();
+
+        assert_eq!(extract_ordinal_position("ORDER BY position 3 is not in select list"), Some((3, false)));
+        assert_eq!(extract_ordinal_position("GROUP BY position 5 is not in select list"), Some((5, true)));
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let order_by = deparse_raw_stmt(perform_refinement_search("SELECT id, name FROM op_items ORDER BY 3", pstate));
+        let group_by = deparse_raw_stmt(perform_refinement_search("SELECT name, count(*) FROM op_items GROUP BY 3", pstate));
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        assert_eq!(order_by, "SELECT id, name FROM op_items ORDER BY 2");
+        // 2번째 위치는 집계라 GROUP BY할 수 없으므로 1로 보정된다
+        assert_eq!(group_by, "SELECT name, count(*) FROM op_items GROUP BY 1");
+    }
+
+    #[pg_test]
+    fn test_rank_candidates_uses_sqlstate() {
+        Spi::run("CREATE TABLE rs_items (id int, name text)").unwrap();
+        let sql = "SELECT id, name FROM rs_items ORDER BY 3";
+        let message = "ORDER BY position 3 is not in select list";
+
+        // 호출자가 받은 SQLSTATE(invalid_column_reference)로 분류
+        let ranked = rank_refinement_candidates(sql, message, None, Some("42P10"));
+        assert!(ranked.iter().any(|(c, _)| c.ends_with("ORDER BY 2")), "{:?}", ranked);
+
+        // SQLSTATE가 없으면 analyze에서 잡은 에러 코드를 쓴다
+        let ranked = rank_refinement_candidates(sql, message, None, None);
+        assert!(ranked.iter().any(|(c, _)| c.ends_with("ORDER BY 2")), "{:?}", ranked);
+    }
+
+    #[pg_test]
+    fn test_case_branch_cast_refined() {
+        Spi::run("CREATE TABLE cb_items (id int, name text, label text)").unwrap();
+        Spi::run("INSERT INTO cb_items VALUES (1, 'a', 'x'), (2, 'b', 'y'), (3, 'c', 'z')").unwrap();
+
+        assert_eq!(
+            extract_case_branch_types("CASE types text and integer cannot be matched"),
+            Some(("text".to_string(), "integer".to_string()))
+        );
+
+        // 세 분기 중 integer인 분기 하나만 text로 cast하는 쪽이 먼저 시도된다
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search(
+            "SELECT CASE WHEN id = 1 THEN id WHEN id = 2 THEN name ELSE label END FROM cb_items",
+            pstate,
+        );
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let refined_sql = deparse_raw_stmt(refined);
+        assert!(refined_sql.contains("THEN id::text"), "unexpected refinement: {}", refined_sql);
+        assert!(!refined_sql.contains("::integer"), "unexpected refinement: {}", refined_sql);
+        assert_eq!(Spi::get_one::<i64>(&format!("SELECT count(*) FROM ({}) t", refined_sql)).unwrap(), Some(3));
+    }
+
+    #[pg_test]
+    fn test_subtransaction_guard_rolls_back_on_panic() {
+        Spi::run("CREATE TABLE subxact_items (id int)").unwrap();
+        let before = unsafe { pg_sys::GetCurrentTransactionNestLevel() };
+
+        // guard 안에서 실행 도중 panic을 주입
+        let caught = PgTryBuilder::new(|| -> bool {
+            let _subxact = unsafe { SubTransactionGuard::begin() };
+            assert_eq!(unsafe { pg_sys::GetCurrentTransactionNestLevel() }, before + 1);
+            Spi::run("INSERT INTO subxact_items VALUES (1)").unwrap();
+            panic!("injected panic mid-execution");
+        })
+        .catch_others(|_| true)
+        .execute();
+
+        assert!(caught);
+        assert_eq!(unsafe { pg_sys::GetCurrentTransactionNestLevel() }, before);
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM subxact_items").unwrap(), Some(0));
+
+        // 정상/에러 경로 모두 nesting level을 원래대로 돌려놓는다
+        let raw = rawstmt_from_sql("SELECT id FROM subxact_items").unwrap();
+        assert!(matches!(try_execute_query(raw, ""), ExecutionOutcome::EmptyResult));
+        let raw = rawstmt_from_sql("SELECT 1 / 0").unwrap();
+        assert!(matches!(try_execute_query(raw, ""), ExecutionOutcome::DivisionByZero { .. }));
+        assert_eq!(unsafe { pg_sys::GetCurrentTransactionNestLevel() }, before);
+    }
+
+    #[pg_test]
+    fn test_jsonb_arrow_swapped_for_text_context() {
+        Spi::run("CREATE TABLE js_orders (id int, data jsonb)").unwrap();
+        Spi::run(r#"INSERT INTO js_orders VALUES (1, '{"amount": "100"}'), (2, '{"amount": "250"}')"#).unwrap();
+        // operand 후보는 임베딩이 필요하므로 끈다
+        Spi::run("SET safeql.enable_operand_column_refinement = off").unwrap();
+        Spi::run("SET safeql.enable_operand_table_for_column_refinement = off").unwrap();
+        Spi::run("SET safeql.enable_operand_column_table_reference_refinement = off").unwrap();
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search("SELECT id FROM js_orders WHERE data->'amount' LIKE '1%'", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let sql = deparse_raw_stmt(refined);
+        assert!(sql.contains("data ->> 'amount'"), "{}", sql);
+        assert_eq!(Spi::get_one::<i32>(&sql).unwrap(), Some(1));
+    }
+
+    #[pg_test]
+    fn test_null_handling_loosens_empty_predicate() {
+        Spi::run("CREATE TABLE nh_customers (name text, city text)").unwrap();
+        Spi::run("INSERT INTO nh_customers VALUES ('kim', NULL), ('lee', 'Busan')").unwrap();
+        // 임베딩이 필요한 empty result refinement는 끈다
+        Spi::run("SET safeql.enable_value_refinement = off").unwrap();
+        Spi::run("SET safeql.enable_table_refinement = off").unwrap();
+        Spi::run("SET safeql.enable_join_refinement = off").unwrap();
+        Spi::run("SET safeql.enable_operand_column_refinement = off").unwrap();
+        Spi::run("SET safeql.enable_operand_table_for_column_refinement = off").unwrap();
+        Spi::run("SET safeql.enable_operand_column_table_reference_refinement = off").unwrap();
+
+        let sql = "SELECT name FROM nh_customers WHERE city = 'Seoul'";
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search(sql, pstate);
+        assert_eq!(deparse_raw_stmt(refined), sql);
+
+        Spi::run("SET safeql.enable_null_handling_refinement = on").unwrap();
+        let refined = perform_refinement_search(sql, pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let refined_sql = deparse_raw_stmt(refined);
+        assert!(refined_sql.contains("COALESCE(city, 'Seoul') = 'Seoul'"), "{}", refined_sql);
+        assert_eq!(Spi::get_one::<String>(&refined_sql).unwrap().as_deref(), Some("kim"));
+    }
+
+    #[pg_test]
+    fn test_conservative_mode_skips_join_add() {
+        Spi::run("CREATE TABLE cv_x (id int PRIMARY KEY, name text)").unwrap();
+        Spi::run("CREATE TABLE cv_y (yid int PRIMARY KEY, x_id int REFERENCES cv_x(id), v int)").unwrap();
+        // rename 후보는 임베딩이 필요하므로 끈다
+        Spi::run("SET safeql.enable_table_refinement = off").unwrap();
+
+        let sql = "SELECT cv_y.v FROM cv_x";
+        let message = r#"missing FROM-clause entry for table "cv_y""#;
+        let ranked = rank_refinement_candidates(sql, message, None, None);
+        assert!(ranked.iter().any(|(c, _)| c.contains("cv_x, cv_y")), "{:?}", ranked);
+
+        Spi::run("SET safeql.conservative = on").unwrap();
+        let ranked = rank_refinement_candidates(sql, message, None, None);
+        assert!(ranked.is_empty(), "{:?}", ranked);
+    }
+
+    #[pg_test]
+    fn test_conservative_mode_skips_table_add_for_alias() {
+        Spi::run("CREATE TABLE cz_orders (id int)").unwrap();
+        Spi::run("CREATE TABLE cz_order_items (id int)").unwrap();
+        reset_vector_catalogs();
+
+        let cache_key = generate_cache_key("table_similarity", &["cz_orders", &TOP_K_EXPANSION.get().to_string()]);
+        let similar = vec![("public.cz_order_items".to_string(), 0.1f32)];
+        seed_cached_result(&cache_key, "table_similarity", &similar);
+
+        // x는 cz_orders의 alias이므로 후보는 FROM에 cz_order_items x를 추가한다
+        let raw = rawstmt_from_sql("SELECT x.id FROM cz_orders x").unwrap();
+        assert_eq!(generate_table_refinements_raw(raw, "x", 0).len(), 1);
+
+        Spi::run("SET safeql.conservative = on").unwrap();
+        assert!(generate_table_refinements_raw(raw, "x", 0).is_empty());
+    }
+
+    #[pg_test]
+    fn test_execution_validation_disabled_returns_analyzed_candidate() {
+        Spi::run("CREATE TABLE ev_t (id int, v int)").unwrap();
+        Spi::run("SET safeql.enable_execution_validation = off").unwrap();
+
+        let before = super::metrics::executions_attempted();
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        // 빈 결과와 0 나누기 모두 실행해야만 드러나므로 analyze 통과 즉시 반환된다
+        let refined = perform_refinement_search("SELECT v / 0 FROM ev_t WHERE id = 5", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        assert_eq!(super::metrics::executions_attempted(), before);
+        let sql = deparse_raw_stmt(refined);
+        assert!(sql.contains("v / 0") && sql.contains("id = 5"), "{}", sql);
+    }
+
+    #[pg_test]
+    fn test_last_refined_sql_is_published() {
+        Spi::run("CREATE TABLE lr_x (id int PRIMARY KEY, name text)").unwrap();
+        Spi::run("CREATE TABLE lr_y (yid int PRIMARY KEY, x_id int REFERENCES lr_x(id), v int)").unwrap();
+        Spi::run("INSERT INTO lr_x VALUES (1, 'a')").unwrap();
+        Spi::run("INSERT INTO lr_y VALUES (10, 1, 100)").unwrap();
+        // rename 후보는 임베딩이 필요하므로 끈다
+        Spi::run("SET safeql.enable_table_refinement = off").unwrap();
+        let show = || Spi::get_one::<String>("SHOW safeql.last_refined_sql").unwrap().unwrap_or_default();
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search("SELECT lr_y.v FROM lr_x", pstate);
+        let refined_sql = deparse_raw_stmt(refined);
+        assert!(refined_sql.contains("lr_x, lr_y"), "{}", refined_sql);
+        assert_eq!(show(), refined_sql);
+
+        // 그대로 쓰인 쿼리는 값을 비운다
+        perform_refinement_search("SELECT v FROM lr_y", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+        assert_eq!(show(), "");
+    }
+
+    #[pg_test]
+    fn test_find_expression_near_operator_cursor() {
+        let sql = "SELECT id FROM ops WHERE price = 'abc' AND qty > 1";
+        let raw = rawstmt_from_sql(sql).unwrap();
+        let left_column = |exprs: &[*mut pg_sys::Node]| unsafe {
+            extract_column_operands_from_expr(exprs[0], None).and_then(|(left, _)| left).map(|op| op.column_name)
+        };
+
+        // cursor가 연산자 토큰을 가리키는 경우
+        let op_pos = sql.find(" = ").unwrap() as i32 + 2;
+        let exprs = unsafe { find_expressions_at_position(raw, op_pos) };
+        assert_eq!(exprs.len(), 1);
+        assert_eq!(left_column(&exprs).as_deref(), Some("price"));
+
+        // cursor가 피연산자 사이 공백을 가리켜도 감싸는 A_Expr을 찾는다
+        let between_pos = sql.find(" = ").unwrap() as i32 + 1;
+        let exprs = unsafe { find_expressions_at_position(raw, between_pos) };
+        assert_eq!(exprs.len(), 1);
+        assert_eq!(left_column(&exprs).as_deref(), Some("price"));
+
+        let exprs = unsafe { find_expressions_at_position(raw, sql.find(" > ").unwrap() as i32 + 1) };
+        assert_eq!(left_column(&exprs).as_deref(), Some("qty"));
+
+        // 어떤 연산자와도 멀면 찾지 않는다
+        assert!(unsafe { find_expressions_at_position(raw, 1) }.is_empty());
+    }
+
+    #[pg_test]
+    fn test_any_all_operand_refines_scalar_side() {
+        Spi::run("CREATE TABLE aa_labels (label text)").unwrap();
+        Spi::run("INSERT INTO aa_labels VALUES ('b')").unwrap();
+        Spi::run("CREATE TABLE aa_items (id int, name text, name_id int, tags text[])").unwrap();
+        Spi::run("INSERT INTO aa_items VALUES (1, 'a', 10, '{}'), (2, 'b', 20, '{}')").unwrap();
+        Spi::run("SET safeql.enable_operand_typecast_refinement = off").unwrap();
+        Spi::run("SET safeql.enable_operand_table_for_column_refinement = off").unwrap();
+        Spi::run("SET safeql.enable_operand_column_table_reference_refinement = off").unwrap();
+
+        // 배열 쪽 컬럼은 오퍼랜드로 잡지 않는다
+        let raw = rawstmt_from_sql("SELECT id FROM aa_items WHERE name = ALL(tags)").unwrap();
+        let exprs = unsafe { find_all_where_expressions(raw) };
+        let (left, right) = unsafe { extract_column_operands_from_expr(exprs[0], None) }.unwrap();
+        assert_eq!(left.map(|op| op.column_name).as_deref(), Some("name"));
+        assert!(right.is_none());
+
+        // 임베딩 없이 호환 컬럼 결과를 캐시에 미리 넣어둔다
+        let operator_key = format!("=_ANY_ANY_LEFT_{}_name_id", ENABLE_TYPE_BASED_REFINEMENT.get());
+        let cache_key = generate_cache_key("operator_compatible_columns", &["NULL", "name_id", &operator_key]);
+        let compatible = vec![("name".to_string(), "aa_items".to_string(), 0.1f32)];
+        seed_cached_result(&cache_key, "operator_compatible_columns", &compatible);
+
+        // integer = text 에러의 cursor는 ANY 서브쿼리의 연산자를 가리킨다
+        let sql = "SELECT id FROM aa_items WHERE name_id = ANY(SELECT label FROM aa_labels)";
+        let raw = rawstmt_from_sql(sql).unwrap();
+        let exprs = unsafe { find_expressions_at_position(raw, sql.find(" = ").unwrap() as i32 + 2) };
+        assert_eq!(exprs.len(), 1);
+        let (left, right) = unsafe { extract_column_operands_from_expr(exprs[0], None) }.unwrap();
+        assert_eq!(left.map(|op| op.column_name).as_deref(), Some("name_id"));
+        assert!(right.is_none());
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search(sql, pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let refined_sql = deparse_raw_stmt(refined);
+        assert!(refined_sql.contains("WHERE name = ANY"), "{}", refined_sql);
+        assert_eq!(Spi::get_one::<i32>(&refined_sql).unwrap(), Some(2));
+    }
+
+    #[pg_test]
+    fn test_undefined_table_refined_to_view() {
+        Spi::run("CREATE TABLE vw_orders (id int, status text)").unwrap();
+        Spi::run("INSERT INTO vw_orders VALUES (1, 'open'), (2, 'closed')").unwrap();
+        Spi::run("CREATE VIEW vw_open_orders AS SELECT id FROM vw_orders WHERE status = 'open'").unwrap();
+        Spi::run("CREATE MATERIALIZED VIEW vw_order_counts AS SELECT status, count(*) AS n FROM vw_orders GROUP BY status").unwrap();
+        Spi::run("SET safeql.enable_join_refinement = off").unwrap();
+        reset_vector_catalogs();
+
+        // 임베딩 없이 similarity 결과를 캐시에 미리 넣어둔다
+        let top_k = TOP_K_EXPANSION.get().to_string();
+        for (typo, fqname) in [("vw_opn_orders", "public.vw_open_orders"), ("vw_ordr_counts", "public.vw_order_counts")] {
+            let cache_key = generate_cache_key("table_similarity", &[typo, &top_k]);
+            let similar = vec![(fqname.to_string(), 0.1f32)];
+            seed_cached_result(&cache_key, "table_similarity", &similar);
+        }
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let view = deparse_raw_stmt(perform_refinement_search("SELECT id FROM vw_opn_orders", pstate));
+        let matview = deparse_raw_stmt(perform_refinement_search("SELECT n FROM vw_ordr_counts WHERE status = 'open'", pstate));
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        assert_eq!(view, "SELECT id FROM vw_open_orders");
+        assert_eq!(matview, "SELECT n FROM vw_order_counts WHERE status = 'open'");
+        assert_eq!(Spi::get_one::<i64>(&matview).unwrap(), Some(1));
+    }
+
+    #[pg_test]
+    fn test_refinement_enabled_errors_disables_undefined_table() {
+        Spi::run("CREATE TABLE re_orders (id int, status text)").unwrap();
+        Spi::run("INSERT INTO re_orders VALUES (1, 'open')").unwrap();
+        Spi::run("SET safeql.enable_join_refinement = off").unwrap();
+        reset_vector_catalogs();
+
+        // 임베딩 없이 similarity 결과를 캐시에 미리 넣어둔다
+        let top_k = TOP_K_EXPANSION.get().to_string();
+        let cache_key = generate_cache_key("table_similarity", &["re_ordrs", &top_k]);
+        let similar = vec![("public.re_orders".to_string(), 0.1f32)];
+        seed_cached_result(&cache_key, "table_similarity", &similar);
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = deparse_raw_stmt(perform_refinement_search("SELECT id FROM re_ordrs", pstate));
+        assert_eq!(refined, "SELECT id FROM re_orders");
+
+        // 목록에서 undefined_table을 빼면 시도하지 않고 원본으로 돌아온다
+        Spi::run("SET safeql.refinement_enabled_errors = 'undefined_column, ambiguous_column, bogus'").unwrap();
+        assert_eq!(
+            refinement_enabled_errors(),
+            Some(vec!["undefined_column".to_string(), "ambiguous_column".to_string()])
+        );
+        let unchanged = deparse_raw_stmt(perform_refinement_search("SELECT id FROM re_ordrs", pstate));
+        unsafe { pg_sys::free_parsestate(pstate) };
+        assert_eq!(unchanged, "SELECT id FROM re_ordrs");
+    }
+
+    #[pg_test]
+    fn test_missing_from_entry_joins_referenced_table() {
+        Spi::run("CREATE TABLE mf_x (id int PRIMARY KEY, name text)").unwrap();
+        Spi::run("CREATE TABLE mf_y (yid int PRIMARY KEY, x_id int REFERENCES mf_x(id), v int)").unwrap();
+        Spi::run("INSERT INTO mf_x VALUES (1, 'a'), (2, 'b')").unwrap();
+        Spi::run("INSERT INTO mf_y VALUES (10, 1, 100), (20, 2, 200)").unwrap();
+        // rename 후보는 임베딩이 필요하므로 끈다
+        Spi::run("SET safeql.enable_table_refinement = off").unwrap();
+
+        assert_eq!(
+            extract_missing_from_clause_entry(r#"missing FROM-clause entry for table "mf_y""#).as_deref(),
+            Some("mf_y")
+        );
+        assert!(extract_missing_from_clause_entry(r#"relation "mf_y" does not exist"#).is_none());
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search("SELECT mf_y.v FROM mf_x WHERE mf_x.name = 'b'", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let sql = deparse_raw_stmt(refined);
+        assert!(sql.contains("mf_x, mf_y"), "{}", sql);
+        assert!(sql.contains("mf_x.id = mf_y.x_id"), "{}", sql);
+        assert_eq!(Spi::get_one::<i32>(&sql).unwrap(), Some(200));
+    }
+
+    #[pg_test]
+    fn test_metrics_count_refined_query() {
+        Spi::run("CREATE TABLE metric_stats (total int, cnt int)").unwrap();
+        Spi::run("INSERT INTO metric_stats VALUES (10, 0)").unwrap();
+        let before = refinement_metrics();
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        perform_refinement_search("SELECT total / cnt FROM metric_stats", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let after = refinement_metrics();
+        assert_eq!(after.queries_refined, before.queries_refined + 1);
+        assert_eq!(after.queries_unchanged, before.queries_unchanged);
+        assert!(after.avg_candidates_tried >= 1.0);
+        assert!(after.execution_success_rate > 0.0);
+    }
+
+    #[pg_test]
+    fn test_operand_typecast_casts_literal_side() {
+        Spi::run("SELECT load_vector_operators()").unwrap();
+        Spi::run("CREATE TABLE lit_events (id int, happened_on date)").unwrap();
+        Spi::run("INSERT INTO lit_events VALUES (1, '2020-01-01')").unwrap();
+        Spi::run("SET safeql.enable_operand_typecast_refinement = on").unwrap();
+        Spi::run("SET safeql.enable_operand_column_refinement = off").unwrap();
+        Spi::run("SET safeql.enable_operand_table_for_column_refinement = off").unwrap();
+        Spi::run("SET safeql.enable_operand_column_table_reference_refinement = off").unwrap();
+
+        let sql = "SELECT id FROM lit_events WHERE happened_on = '2020-01-01'::text";
+        let raw = rawstmt_from_sql(sql).unwrap();
+        let exprs = unsafe { find_all_where_expressions(raw) };
+        let info = extract_operator_info_from_error("operator does not exist: date = text");
+        let literal = unsafe { extract_literal_operand_from_expr(exprs[0], &info) }.unwrap();
+        assert!(matches!(literal.position, OperandPosition::Right));
+        assert_eq!(literal.column_type, "date");
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search(sql, pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let refined_sql = deparse_raw_stmt(refined);
+        assert!(refined_sql.contains("::text::date"), "{}", refined_sql);
+        assert!(!refined_sql.contains("happened_on::"), "{}", refined_sql);
+    }
+
+    #[pg_test]
+    fn test_operand_resolved_type_reused_for_typecast() {
+        Spi::run("CREATE TABLE rt_items (id int, code text)").unwrap();
+
+        let raw = rawstmt_from_sql("SELECT id FROM rt_items WHERE id = code").unwrap();
+        let exprs = unsafe { find_all_where_expressions(raw) };
+        let info = extract_operator_info_from_error("operator does not exist: integer = text");
+        let (left, right) = unsafe { extract_column_operands_from_expr(exprs[0], Some(&info)) }.unwrap();
+        assert_eq!(left.as_ref().and_then(|op| op.resolved_type.as_deref()), Some("integer"));
+        assert_eq!(right.as_ref().and_then(|op| op.resolved_type.as_deref()), Some("text"));
+
+        // 해석된 타입으로 만든 캐시 키에만 결과를 넣어둔다 (타입 없이 조회하면 캐시를 못 찾는다)
+        let cache_key = generate_cache_key("typecast_refinements", &["=", "integer", "text"]);
+        let refinements = vec![TypecastRefinement {
+            cast_position: OperandPosition::Right,
+            target_type: "int4".to_string(),
+            fixed_type: Some("int4".to_string()),
+        }];
+        seed_cached_result(&cache_key, "typecast_refinements", &refinements);
+
+        let operator_info = unsafe { extract_operator_info_from_expr(exprs[0]) };
+        assert!(operator_info.left_type.is_none() && operator_info.right_type.is_none());
+        let candidates = generate_operand_typecast_refinements_raw(raw, &left, &right, None, &operator_info, 0);
+
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(deparse_raw_stmt(candidates[0].1), "SELECT id FROM rt_items WHERE id = code::int4");
+    }
+
+    #[pg_test]
+    fn test_rank_candidates_for_undefined_column() {
+        Spi::run("CREATE TABLE rank_customers (id int, name text)").unwrap();
+        reset_vector_catalogs();
+        Spi::run("SET safeql.enable_table_for_column = off").unwrap();
+        Spi::run("SET safeql.enable_column_table_reference = off").unwrap();
+        Spi::run("SET safeql.enable_join_refinement = off").unwrap();
+
+        // 임베딩 없이 similarity 결과를 캐시에 미리 넣어둔다
+        let cache_key = generate_cache_key(
+            "column_similarity",
+            &["rank_customers", "nme", "false", &TOP_K_EXPANSION.get().to_string()],
+        );
+        let similar = vec![
+            ("name".to_string(), "rank_customers".to_string(), 0.1f32),
+            ("id".to_string(), "rank_customers".to_string(), 0.9f32),
+        ];
+        seed_cached_result(&cache_key, "column_similarity", &similar);
+
+        let sql = "SELECT c.nme FROM rank_customers c";
+        let ranked = rank_refinement_candidates(sql, "column c.nme does not exist", Some(8), None);
+        assert_eq!(ranked.len(), 2, "{:?}", ranked);
+        assert!(ranked[0].0.contains("c.name"), "{:?}", ranked);
+        assert!(ranked[1].0.contains("c.id"), "{:?}", ranked);
+        assert!(ranked[0].1 < ranked[1].1);
+
+        // 분류되지 않는 에러는 후보가 없다
+        assert!(rank_refinement_candidates(sql, "permission denied for table rank_customers", None, Some("42501")).is_empty());
+    }
+
+    #[pg_test]
+    fn test_correlated_exists_column_refined() {
+        Spi::run("CREATE TABLE ex_customers (id int, name text)").unwrap();
+        Spi::run("CREATE TABLE ex_orders (id int, customer_id int)").unwrap();
+        Spi::run("INSERT INTO ex_customers VALUES (1, 'kim'), (2, 'lee')").unwrap();
+        Spi::run("INSERT INTO ex_orders VALUES (10, 1)").unwrap();
+        reset_vector_catalogs();
+        Spi::run("SET safeql.enable_table_for_column = off").unwrap();
+        Spi::run("SET safeql.enable_column_table_reference = off").unwrap();
+        Spi::run("SET safeql.enable_join_refinement = off").unwrap();
+
+        let cache_key = generate_cache_key(
+            "column_similarity",
+            &["ex_orders", "customr_id", "false", &TOP_K_EXPANSION.get().to_string()],
+        );
+        let similar = vec![("customer_id".to_string(), "ex_orders".to_string(), 0.1f32)];
+        seed_cached_result(&cache_key, "column_similarity", &similar);
+
+        // WHERE절 표현식 수집은 EXISTS 서브쿼리 안의 비교까지 내려간다
+        let sql = "SELECT c.name FROM ex_customers c WHERE EXISTS (SELECT 1 FROM ex_orders o WHERE o.customr_id = c.id)";
+        let raw = rawstmt_from_sql(sql).unwrap();
+        assert_eq!(unsafe { find_all_where_expressions(raw) }.len(), 1);
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search(sql, pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let sql = deparse_raw_stmt(refined);
+        assert!(sql.contains("WHERE o.customer_id = c.id"), "{}", sql);
+        assert_eq!(Spi::get_one::<String>(&sql).unwrap().as_deref(), Some("kim"));
+    }
+
+    #[pg_test]
+    fn test_cardinality_change_penalty_prefers_smaller_change() {
+        Spi::run("CREATE TABLE cp_orders (id int, state text, status text)").unwrap();
+        // state = 'open'은 1행, status = 'open'은 9행 (FROM절 전체는 10행)
+        Spi::run(
+            "INSERT INTO cp_orders
+             SELECT g, CASE WHEN g = 1 THEN 'open' ELSE 'closed' END, CASE WHEN g = 1 THEN 'closed' ELSE 'open' END
+             FROM generate_series(1, 10) g",
+        )
+        .unwrap();
+        reset_vector_catalogs();
+        Spi::run("SET safeql.enable_table_for_column = off").unwrap();
+        Spi::run("SET safeql.enable_column_table_reference = off").unwrap();
+        Spi::run("SET safeql.enable_join_refinement = off").unwrap();
+
+        // 이름은 state가 더 가깝다
+        let cache_key = generate_cache_key(
+            "column_similarity",
+            &["cp_orders", "statu", "false", &TOP_K_EXPANSION.get().to_string()],
+        );
+        let similar = vec![
+            ("state".to_string(), "cp_orders".to_string(), 0.1f32),
+            ("status".to_string(), "cp_orders".to_string(), 0.2f32),
+        ];
+        seed_cached_result(&cache_key, "column_similarity", &similar);
+
+        let refine = || {
+            let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+            let refined = perform_refinement_search("SELECT o.id FROM cp_orders o WHERE o.statu = 'open'", pstate);
+            unsafe { pg_sys::free_parsestate(pstate) };
+            deparse_raw_stmt(refined)
+        };
+
+        // 기본값 0이면 가장 가까운 이름
+        let sql = refine();
+        assert!(sql.contains("o.state = 'open'"), "{}", sql);
+
+        // penalty를 켜면 결과 행 수가 원본 FROM절(10행)에 더 가까운 쪽
+        Spi::run("SET safeql.cardinality_change_penalty = 1").unwrap();
+        let sql = refine();
+        assert!(sql.contains("o.status = 'open'"), "{}", sql);
+
+        // 집계 쿼리는 항상 1행이므로 penalty 없이 가장 가까운 이름
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search("SELECT count(*) FROM cp_orders o WHERE o.statu = 'open'", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+        let sql = deparse_raw_stmt(refined);
+        assert!(sql.contains("o.state = 'open'"), "{}", sql);
+
+        assert_eq!(cardinality_change_penalty(Some(10), Some(10), 1.0), 0);
+        assert_eq!(cardinality_change_penalty(None, Some(1), 1.0), 0);
+        assert!(cardinality_change_penalty(Some(10), Some(1), 1.0) > cardinality_change_penalty(Some(10), Some(9), 1.0));
+    }
+
+    #[pg_test]
+    fn test_window_partition_column_refined() {
+        Spi::run("CREATE TABLE wd_sales (region text, amount int)").unwrap();
+        Spi::run("INSERT INTO wd_sales VALUES ('east', 10), ('east', 20), ('west', 5)").unwrap();
+        reset_vector_catalogs();
+        Spi::run("SET safeql.enable_table_for_column = off").unwrap();
+        Spi::run("SET safeql.enable_column_table_reference = off").unwrap();
+        Spi::run("SET safeql.enable_join_refinement = off").unwrap();
+
+        let cache_key = generate_cache_key(
+            "column_similarity",
+            &["wd_sales", "regon", "false", &TOP_K_EXPANSION.get().to_string()],
+        );
+        let similar = vec![("region".to_string(), "wd_sales".to_string(), 0.1f32)];
+        seed_cached_result(&cache_key, "column_similarity", &similar);
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search(
+            "SELECT s.amount, rank() OVER (PARTITION BY s.regon ORDER BY s.amount) FROM wd_sales s",
+            pstate,
+        );
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let sql = deparse_raw_stmt(refined);
+        assert!(sql.contains("PARTITION BY s.region ORDER BY s.amount"), "{}", sql);
+        assert_eq!(Spi::get_one::<i64>(&format!("SELECT count(*) FROM ({}) q", sql)).unwrap(), Some(3));
+    }
+
+    #[pg_test]
+    fn test_insert_target_column_refined() {
+        Spi::run("CREATE TABLE ins_orders (id int, amount int)").unwrap();
+        reset_vector_catalogs();
+        Spi::run("SET safeql.enable_table_for_column = off").unwrap();
+        Spi::run("SET safeql.enable_column_table_reference = off").unwrap();
+        Spi::run("SET safeql.enable_join_refinement = off").unwrap();
+
+        let cache_key = generate_cache_key(
+            "column_similarity",
+            &["ins_orders", "amont", "false", &TOP_K_EXPANSION.get().to_string()],
+        );
+        let similar = vec![("amount".to_string(), "ins_orders".to_string(), 0.1f32)];
+        seed_cached_result(&cache_key, "column_similarity", &similar);
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search("INSERT INTO ins_orders (id, amont) VALUES (1, 10)", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let sql = deparse_raw_stmt(refined);
+        assert!(sql.contains("(id, amount)"), "{}", sql);
+
+        // INSERT는 실행하지 않으므로 데이터도 sequence 증가도 남지 않는다
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM ins_orders").unwrap(), Some(0));
+    }
+
+    #[pg_test]
+    fn test_insert_candidate_not_executed() {
+        Spi::run("CREATE TABLE ine_orders (id serial, amount int)").unwrap();
+        Spi::run("CREATE TABLE ine_audit (note text)").unwrap();
+        Spi::run(
+            "CREATE FUNCTION ine_audit_fn() RETURNS trigger LANGUAGE plpgsql AS $$
+             BEGIN INSERT INTO ine_audit VALUES ('fired'); RETURN NEW; END $$",
+        )
+        .unwrap();
+        Spi::run("CREATE TRIGGER ine_audit_trg BEFORE INSERT ON ine_orders FOR EACH ROW EXECUTE FUNCTION ine_audit_fn()").unwrap();
+
+        let raw = rawstmt_from_sql("INSERT INTO ine_orders (amount) VALUES (10)").unwrap();
+        let source_sql = unsafe { insert_source_count_query(raw) }.unwrap();
+        assert!(source_sql.starts_with("SELECT count(*) FROM (VALUES (10))"), "{}", source_sql);
+        assert!(matches!(try_execute_query(raw, ""), ExecutionOutcome::Success { row_count: None }));
+
+        // trigger도 sequence도 실행되지 않는다
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM ine_audit").unwrap(), Some(0));
+        assert_eq!(Spi::get_one::<i64>("SELECT nextval('ine_orders_id_seq')").unwrap(), Some(1));
+
+        // 입력 VALUES의 실행 에러는 그대로 실행 에러로 잡힌다
+        let raw = rawstmt_from_sql("INSERT INTO ine_orders (amount) VALUES (1 / 0)").unwrap();
+        assert!(!matches!(try_execute_query(raw, ""), ExecutionOutcome::Success { .. }));
+    }
+
+    #[pg_test]
+    fn test_delete_where_refined_via_count_rewrite() {
+        Spi::run("CREATE TABLE dml_items (id int, item_name text)").unwrap();
+        Spi::run("INSERT INTO dml_items VALUES (1, 'a'), (2, 'b')").unwrap();
+        reset_vector_catalogs();
+        Spi::run("SET safeql.enable_table_for_column = off").unwrap();
+        Spi::run("SET safeql.enable_column_table_reference = off").unwrap();
+        Spi::run("SET safeql.enable_join_refinement = off").unwrap();
+
+        let cache_key = generate_cache_key(
+            "column_similarity",
+            &["NULL", "item_nme", "false", &TOP_K_EXPANSION.get().to_string()],
+        );
+        let similar = vec![("item_name".to_string(), "dml_items".to_string(), 0.1f32)];
+        seed_cached_result(&cache_key, "column_similarity", &similar);
+
+        let raw = rawstmt_from_sql("DELETE FROM dml_items d WHERE d.id > 1").unwrap();
+        let count_sql = deparse_raw_stmt(unsafe { dml_count_query(raw) }.unwrap());
+        assert!(count_sql.starts_with("SELECT count(dml_rows) FROM (SELECT 1 FROM dml_items d WHERE d.id > 1)"), "{}", count_sql);
+        assert_eq!(Spi::get_one::<i64>(&count_sql).unwrap(), Some(1));
+        assert!(unsafe { dml_count_query(rawstmt_from_sql("SELECT 1").unwrap()) }.is_none());
+
+        let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+        let refined = perform_refinement_search("DELETE FROM dml_items WHERE item_nme = 'b'", pstate);
+        unsafe { pg_sys::free_parsestate(pstate) };
+
+        let sql = deparse_raw_stmt(refined);
+        assert!(sql.starts_with("DELETE FROM dml_items WHERE item_name = 'b'"), "{}", sql);
+
+        // 검증은 count(*)로만 했으므로 행이 그대로 남아 있다
+        assert_eq!(Spi::get_one::<i64>("SELECT count(*) FROM dml_items").unwrap(), Some(2));
+    }
+
+    #[pg_test]
+    fn test_dml_count_rewrite_validates_set_and_returning() {
+        Spi::run("CREATE TABLE dmlv_items (id int, qty int, note text)").unwrap();
+        Spi::run("INSERT INTO dmlv_items VALUES (1, 5, 'a'), (2, 0, 'b')").unwrap();
+        let outcome = |sql: &str| try_execute_query(rawstmt_from_sql(sql).unwrap(), sql);
+
+        assert!(matches!(
+            outcome("UPDATE dmlv_items SET note = 'c', qty = DEFAULT WHERE id = 1 RETURNING id"),
+            ExecutionOutcome::Success { row_count: Some(1) }
+        ));
+
+        // SET 값과 RETURNING 표현식의 실행 에러도 잡힌다
+        assert!(!matches!(outcome("UPDATE dmlv_items SET qty = 10 / qty WHERE id = 2"), ExecutionOutcome::Success { .. }));
+        assert!(!matches!(outcome("DELETE FROM dmlv_items WHERE id = 2 RETURNING 10 / qty"), ExecutionOutcome::Success { .. }));
+
+        // SELECT로 옮길 수 없는 문장은 실행하지 않고 analyze 결과만 쓴다
+        for sql in [
+            "UPDATE dmlv_items SET (qty, note) = (SELECT 1, 'x') WHERE id = 1",
+            "DELETE FROM dmlv_items WHERE CURRENT OF dmlv_cursor",
+        ] {
+            assert!(unsafe { dml_count_query(rawstmt_from_sql(sql).unwrap()) }.is_none());
+            assert!(matches!(outcome(sql), ExecutionOutcome::Success { row_count: None }));
+        }
+        assert_eq!(
+            Spi::get_one::<String>("SELECT string_agg(qty || note, ',' ORDER BY id) FROM dmlv_items").unwrap().as_deref(),
+            Some("5a,0b")
+        );
+    }
+
+    #[pg_test]
+    fn test_deparse_round_trip() {
+        let raw = rawstmt_from_sql("SELECT id, name FROM rt_customers c WHERE c.id = 1 AND name LIKE 'a%'").unwrap();
+        assert!(deparse_round_trips(raw, &deparse_raw_stmt(raw)));
+
+        // lexpr이 빠진 A_Expr는 "WHERE = 1"처럼 deparse되어 다시 parse되지 않는다
+        let raw = rawstmt_from_sql("SELECT id FROM rt_customers WHERE id = 1").unwrap();
+        unsafe {
+            let select_stmt = (*raw).stmt as *mut pg_sys::SelectStmt;
+            let a_expr = (*select_stmt).whereClause as *mut pg_sys::A_Expr;
+            (*a_expr).lexpr = std::ptr::null_mut();
+        }
+        assert!(!deparse_round_trips(raw, &deparse_raw_stmt(raw)));
+        // 문법 에러를 잡은 뒤에도 트랜잭션은 그대로 쓸 수 있다
+        assert_eq!(Spi::get_one::<i32>("SELECT 1").unwrap(), Some(1));
+    }
+
+    fn analyze_error_hint(sql: &str) -> Option<String> {
+        PgTryBuilder::new(|| {
+            let pstate = unsafe { pg_sys::make_parsestate(std::ptr::null_mut()) };
+            analyze_with_refinement(sql, pstate);
+            None
+        })
+        .catch_others(|e| match e {
+            CaughtError::PostgresError(ref report) | CaughtError::ErrorReport(ref report) => {
+                Some(report.hint().unwrap_or_default().to_string())
+            }
+            CaughtError::RustPanic { ref ereport, .. } => {
+                Some(ereport.hint().unwrap_or_default().to_string())
+            }
+        })
+        .execute()
+    }
+
+    #[pg_test]
+    fn test_search_summary_hint() {
+        Spi::run("CREATE TABLE hint_customers (id int, city text, name text)").unwrap();
+        let sql = "SELECT city, count(*), name FROM hint_customers GROUP BY city";
+
+        let hint = analyze_error_hint(sql).expect("grouping error should not be refined");
+        assert!(hint.is_empty(), "{}", hint);
+
+        Spi::run("SET safeql.enable_search_summary_hint = on").unwrap();
+        let hint = analyze_error_hint(sql).expect("grouping error should not be refined");
+        assert!(hint.contains("SafeQL tried 1 refinement candidate(s)"), "{}", hint);
+        assert!(hint.contains("all candidates were exhausted"), "{}", hint);
+    }
+}
@@ -51,12 +51,14 @@ pub fn bind_softql(query: &SoftQLQuery) -> Result<protobuf::RawStmt, Error> {
                 let be = build_predicate_node(pred)?;
                 stmt.where_clause = Some(Box::new(be));
             }
-            Operator::Group(expr) => {
-                let node = match expr {
-                    Expression::FunctionCall(f) => build_func_call_node(f)?,
-                    _ => build_expr_node(expr)?,
-                };
-                stmt.group_clause.push(node);
+            Operator::Group(es) => {
+                // group key 하나당 GROUP BY 항목 하나
+                for e in es {
+                    stmt.group_clause.push(build_operand_node(e)?);
+                }
+            }
+            Operator::InferredGroup(expr) => {
+                stmt.group_clause.push(build_operand_node(expr)?);
             }
             Operator::Having(pred) => {
                 stmt.having_clause = Some(Box::new(build_predicate_node(pred)?));
@@ -93,11 +95,14 @@ pub fn bind_softql(query: &SoftQLQuery) -> Result<protobuf::RawStmt, Error> {
                     }                );
                 }
             }
-            Operator::Limit(nstr) => {
-                let num_expr = Expression::NumberLiteral(nstr.clone());
-                let limit_node = build_expr_node(&num_expr)?;
+            Operator::Limit(e) => {
+                let limit_node = build_expr_node(e)?;
                 stmt.limit_count = Some(Box::new(limit_node));
             }
+            Operator::Offset(e) => {
+                let offset_node = build_expr_node(e)?;
+                stmt.limit_offset = Some(Box::new(offset_node));
+            }
         }
     }
 
@@ -183,6 +188,29 @@ fn build_predicate_node(p: &PredicateExpr) -> Result<Node, Error> {
                 node: Some(NodeOneof::AConst(a)),
             })
         }
+        PredicateExpr::NotIn(in_list) => {
+            // expr NOT IN (...) 는 이름이 "<>"인 AEXPR_IN
+            let mut items = Vec::with_capacity(in_list.values.len());
+            for v in &in_list.values {
+                items.push(build_operand_node(v)?);
+            }
+            let ax = protobuf::AExpr {
+                kind: protobuf::AExprKind::AexprIn as i32,
+                name: vec![Node {
+                    node: Some(NodeOneof::String(protobuf::String {
+                        sval: "<>".to_string(),
+                    })),
+                }],
+                lexpr: Some(Box::new(build_operand_node(&in_list.expr)?)),
+                rexpr: Some(Box::new(Node {
+                    node: Some(NodeOneof::List(protobuf::List { items })),
+                })),
+                location: 0,
+            };
+            Ok(Node {
+                node: Some(NodeOneof::AExpr(Box::new(ax))),
+            })
+        }
     }
 }
 
@@ -279,12 +307,128 @@ fn build_expr_node(e: &Expression) -> Result<Node, Error> {
                 node: Some(NodeOneof::AConst(a)),
             })
         }
+        Expression::Placeholder(Placeholder::Positional(n)) => {
+            let pr = protobuf::ParamRef {
+                number: *n as i32,
+                location: 0,
+            };
+            Ok(Node {
+                node: Some(NodeOneof::ParamRef(pr)),
+            })
+        }
+        Expression::Placeholder(Placeholder::Named(name)) => Err(SerdeDeErrorTrait::custom(format!(
+            "Named placeholder :{} must be substituted before binding",
+            name
+        ))),
+        Expression::BinaryOp(l, op, r) => {
+            let ax = protobuf::AExpr {
+                kind: protobuf::AExprKind::AexprOp as i32,
+                name: vec![Node {
+                    node: Some(NodeOneof::String(protobuf::String {
+                        sval: op.symbol().to_string(),
+                    })),
+                }],
+                lexpr: Some(Box::new(build_operand_node(l)?)),
+                rexpr: Some(Box::new(build_operand_node(r)?)),
+                location: 0,
+            };
+            Ok(Node {
+                node: Some(NodeOneof::AExpr(Box::new(ax))),
+            })
+        }
+        Expression::CaseWhen(c) => {
+            let mut args = Vec::with_capacity(c.branches.len());
+            for b in &c.branches {
+                let cw = protobuf::CaseWhen {
+                    xpr: None,
+                    expr: Some(Box::new(build_predicate_node(&b.condition)?)),
+                    result: Some(Box::new(build_operand_node(&b.result)?)),
+                    location: 0,
+                };
+                args.push(Node {
+                    node: Some(NodeOneof::CaseWhen(Box::new(cw))),
+                });
+            }
+            let defresult = match &c.else_result {
+                Some(e) => Some(Box::new(build_operand_node(e)?)),
+                None => None,
+            };
+            let ce = protobuf::CaseExpr {
+                xpr: None,
+                casetype: 0,
+                casecollid: 0,
+                arg: None,
+                args,
+                defresult,
+                location: 0,
+            };
+            Ok(Node {
+                node: Some(NodeOneof::CaseExpr(Box::new(ce))),
+            })
+        }
+        Expression::Wildcard => {
+            let cref = protobuf::ColumnRef {
+                fields: vec![Node {
+                    node: Some(NodeOneof::AStar(protobuf::AStar {})),
+                }],
+                location: 0,
+            };
+            Ok(Node {
+                node: Some(NodeOneof::ColumnRef(cref)),
+            })
+        }
+        Expression::TableWildcard(rel) => {
+            let (schema, table) = resolve_relname((*rel).clone()).ok_or_else(|| {
+                SerdeDeErrorTrait::custom(format!(
+                    "Unknown relation name in Expression::TableWildcard: {}",
+                    rel
+                ))
+            })?;
+            let cref = protobuf::ColumnRef {
+                fields: vec![
+                    Node {
+                        node: Some(NodeOneof::String(protobuf::String { sval: schema })),
+                    },
+                    Node {
+                        node: Some(NodeOneof::String(protobuf::String { sval: table })),
+                    },
+                    Node {
+                        node: Some(NodeOneof::AStar(protobuf::AStar {})),
+                    },
+                ],
+                location: 0,
+            };
+            Ok(Node {
+                node: Some(NodeOneof::ColumnRef(cref)),
+            })
+        }
+        Expression::WildcardExcept(w) => {
+            // 전개하려면 FROM 테이블의 컬럼 순서가 필요하므로 아직 바인딩하지 않는다
+            let wildcard = match &w.table {
+                Some(table) => format!("{}.*", table),
+                None => "*".to_string(),
+            };
+            let excluded: Vec<String> = w.excluded.iter().map(|(t, c)| format!("{}.{}", t, c)).collect();
+            Err(SerdeDeErrorTrait::custom(format!(
+                "Unsupported syntax in project(): `{} except({})` (wildcard except is not supported by the binder)",
+                wildcard,
+                excluded.join(", ")
+            )))
+        }
         Expression::FunctionCall(_) => {
             unreachable!("FunctionCall should be handled in build_res_target or build_predicate_node")
         }
     }
 }
 
+/// 산술 연산의 피연산자 → 노드 (함수 호출 포함)
+fn build_operand_node(e: &Expression) -> Result<Node, Error> {
+    match e {
+        Expression::FunctionCall(f) => build_func_call_node(f),
+        _ => build_expr_node(e),
+    }
+}
+
 fn build_func_call_node(f: &FunctionCall) -> Result<Node, Error> {
     // --- (1) build children first ------------------------------------------------
     let mut args = Vec::with_capacity(f.args.len());
@@ -368,6 +512,39 @@ fn infer_alias(e: &Expression) -> String {
         Expression::NumberLiteral(n) => n.clone(),
         Expression::BoolLiteral(b) => b.to_string(),
         Expression::NullLiteral => "null".into(),
+        Expression::Placeholder(_) => "?column?".into(),
         Expression::FunctionCall(f) => f.name.clone(),
+        Expression::BinaryOp(..) => "?column?".into(),
+        Expression::CaseWhen(_) => "case".into(),
+        // `*` / `table.*` 는 이름 없이 전개
+        Expression::Wildcard | Expression::TableWildcard(_) | Expression::WildcardExcept(_) => String::new(),
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pgrx::pg_schema]
+mod tests {
+    use super::*;
+    use pgrx::prelude::*;
+
+    #[pg_test]
+    fn test_wildcard_except_rejected() {
+        let query = softql::static_parse_softql(
+            "customers.project(customers.* except(customers.ssn, customers.password_hash), *except(orders.note))",
+        )
+        .unwrap();
+        let Operator::Project(exprs) = &query.operations[0] else {
+            panic!("expected project");
+        };
+
+        let errors: Vec<String> = exprs
+            .iter()
+            .map(|e| build_res_target(e).expect_err("wildcard except must not bind").to_string())
+            .collect();
+        assert!(errors[0].contains("`customers.* except(customers.ssn, customers.password_hash)`"), "{}", errors[0]);
+        assert!(errors[1].contains("`* except(orders.note)`"), "{}", errors[1]);
+
+        // except 없는 wildcard는 그대로 바인딩된다
+        assert!(build_res_target(&Expression::Wildcard).is_ok());
     }
 }
@@ -825,4 +825,43 @@ CREATE VIEW pg_vector_index_stat AS
 
 GRANT SELECT ON TABLE pg_vector_index_stat TO PUBLIC;
 
+CREATE UNLOGGED TABLE pg_vector_search_cache (
+    role_name NAME NOT NULL DEFAULT current_user,
+    cache_key TEXT NOT NULL,
+    query_type TEXT NOT NULL,
+    data TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_accessed TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL DEFAULT now() + interval '7 days',
+    PRIMARY KEY (role_name, cache_key)
+);
+
+CREATE INDEX pg_vector_search_cache_expires_at ON pg_vector_search_cache (expires_at);
+
+-- 캐시 결과는 refinement 방향을 바꾸므로 role마다 자기 행만 읽고 쓴다
+ALTER TABLE pg_vector_search_cache ENABLE ROW LEVEL SECURITY;
+
+CREATE POLICY pg_vector_search_cache_own_role ON pg_vector_search_cache
+    USING (role_name = current_user)
+    WITH CHECK (role_name = current_user);
+
+GRANT SELECT, INSERT, UPDATE, DELETE ON TABLE pg_vector_search_cache TO PUBLIC;
+
+-- FK 등 constraint가 바뀔 수 있는 DDL마다 증가, join 경로 캐시 키에 포함된다
+CREATE SEQUENCE pg_vector_constraint_generation;
+
+GRANT SELECT, USAGE ON SEQUENCE pg_vector_constraint_generation TO PUBLIC;
+
+-- search_path에 vectors가 없는 세션의 DDL도 실패하지 않도록 스키마를 고정한다
+CREATE FUNCTION _vectors_bump_constraint_generation() RETURNS event_trigger
+LANGUAGE plpgsql SET search_path = vectors, pg_catalog AS $$
+BEGIN
+    PERFORM nextval('vectors.pg_vector_constraint_generation');
+END;
+$$;
+
+CREATE EVENT TRIGGER _vectors_constraint_generation_ddl ON ddl_command_end
+    WHEN TAG IN ('CREATE TABLE', 'ALTER TABLE', 'DROP TABLE')
+    EXECUTE FUNCTION _vectors_bump_constraint_generation();
+
 -- finalize end
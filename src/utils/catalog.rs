@@ -32,12 +32,15 @@ fn load_vector_tables() -> SpiResult<()> {
     Spi::run(&format!(
         r#"
         WITH numbered AS (
-            SELECT tablename,
-                   schemaname,
+            SELECT c.relname AS tablename,
+                   n.nspname AS schemaname,
                    (row_number() OVER () - 1) / 256 AS batch_id
-            FROM pg_catalog.pg_tables
-            WHERE schemaname NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
-              AND tablename NOT LIKE 'pg_vector_%'
+            FROM pg_catalog.pg_class c
+            JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+            -- 일반/파티션 테이블뿐 아니라 view, materialized view도 table refinement 후보에 넣는다
+            WHERE c.relkind IN ('r', 'p', 'v', 'm')
+              AND n.nspname NOT IN ('pg_catalog', 'information_schema', 'pg_toast')
+              AND c.relname NOT LIKE 'pg_vector_%'
         ),
         batches AS (
             SELECT schemaname,